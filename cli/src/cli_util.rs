@@ -31,7 +31,9 @@ use git2::{Oid, Repository};
 use indexmap::IndexSet;
 use itertools::Itertools;
 use jj_lib::backend::{BackendError, ChangeId, CommitId, MergedTreeId, ObjectId};
+use jj_lib::bisect::BisectionError;
 use jj_lib::commit::Commit;
+use jj_lib::fsmonitor::FsmonitorKind;
 use jj_lib::git::{
     FailedRefExport, FailedRefExportReason, GitConfigParseError, GitExportError, GitImportError,
     GitRemoteManagementError,
@@ -55,12 +57,13 @@ use jj_lib::revset::{
     RevsetIteratorExt, RevsetParseContext, RevsetParseError, RevsetParseErrorKind,
     RevsetResolutionError, RevsetWorkspaceContext,
 };
-use jj_lib::settings::{ConfigResultExt as _, UserSettings};
+use jj_lib::settings::{ConfigResultExt as _, HumanByteSize, UserSettings};
+use jj_lib::sparse::{self, SparseMatcher, SparsePattern};
 use jj_lib::transaction::Transaction;
 use jj_lib::tree::TreeMergeError;
 use jj_lib::working_copy::{
-    CheckoutStats, LockedWorkingCopy, ResetError, SnapshotError, SnapshotOptions, TreeStateError,
-    WorkingCopy,
+    CheckoutOptions, CheckoutStats, LockedWorkingCopy, ResetError, SnapshotError, SnapshotOptions,
+    SnapshotStats, TreeStateError, UntrackedReason, WorkingCopy,
 };
 use jj_lib::workspace::{Workspace, WorkspaceInitError, WorkspaceLoadError, WorkspaceLoader};
 use jj_lib::{dag_walk, file_util, git, revset};
@@ -75,6 +78,7 @@ use crate::config::{
     new_config_path, AnnotatedValue, CommandNameAndArgs, ConfigSource, LayeredConfigs,
 };
 use crate::formatter::{FormatRecorder, Formatter, PlainTextFormatter};
+use crate::git_util::with_remote_callbacks;
 use crate::merge_tools::{ConflictResolveError, DiffEditError, DiffGenerateError};
 use crate::template_parser::{TemplateAliasesMap, TemplateParseError};
 use crate::templater::Template;
@@ -208,16 +212,7 @@ impl From<OpHeadResolutionError<CommandError>> for CommandError {
 
 impl From<SnapshotError> for CommandError {
     fn from(err: SnapshotError) -> Self {
-        match err {
-            SnapshotError::NewFileTooLarge { .. } => user_error_with_hint(
-                format!("Failed to snapshot the working copy: {err}"),
-                r#"Increase the value of the `snapshot.max-new-file-size` config option if you
-want this file to be snapshotted. Otherwise add it to your `.gitignore` file."#,
-            ),
-            err => {
-                CommandError::InternalError(format!("Failed to snapshot the working copy: {err}"))
-            }
-        }
+        CommandError::InternalError(format!("Failed to snapshot the working copy: {err}"))
     }
 }
 
@@ -259,7 +254,7 @@ impl From<DiffGenerateError> for CommandError {
 
 impl From<ConflictResolveError> for CommandError {
     fn from(err: ConflictResolveError) -> Self {
-        user_error(format!("Failed to use external tool to resolve: {err}"))
+        user_error(format!("Failed to resolve conflicts: {err}"))
     }
 }
 
@@ -307,6 +302,12 @@ impl From<GitRemoteManagementError> for CommandError {
     }
 }
 
+impl From<BisectionError> for CommandError {
+    fn from(err: BisectionError) -> Self {
+        user_error(format!("{err}"))
+    }
+}
+
 impl From<RevsetEvaluationError> for CommandError {
     fn from(err: RevsetEvaluationError) -> Self {
         user_error(format!("{err}"))
@@ -706,6 +707,7 @@ pub struct WorkspaceCommandHelper {
     template_aliases_map: TemplateAliasesMap,
     may_update_working_copy: bool,
     working_copy_shared_with_git: bool,
+    snapshot_stats: SnapshotStats,
 }
 
 impl WorkspaceCommandHelper {
@@ -743,6 +745,7 @@ impl WorkspaceCommandHelper {
             template_aliases_map,
             may_update_working_copy,
             working_copy_shared_with_git,
+            snapshot_stats: SnapshotStats::default(),
         })
     }
 
@@ -750,6 +753,12 @@ impl WorkspaceCommandHelper {
         self.user_repo.git_backend()
     }
 
+    /// Returns the stats of the last snapshot of the working copy taken by this
+    /// command, which are empty if none was taken.
+    pub fn snapshot_stats(&self) -> &SnapshotStats {
+        &self.snapshot_stats
+    }
+
     pub fn check_working_copy_writable(&self) -> Result<(), CommandError> {
         if self.may_update_working_copy {
             Ok(())
@@ -770,12 +779,24 @@ impl WorkspaceCommandHelper {
     /// copy is collocated with Git.
     #[instrument(skip_all)]
     pub fn snapshot(&mut self, ui: &mut Ui) -> Result<(), CommandError> {
+        self.snapshot_internal(ui, true)
+    }
+
+    /// Like [`Self::snapshot()`], but walks the whole working copy instead of
+    /// only the paths reported by the filesystem monitor, so all untracked
+    /// paths end up in the snapshot stats.
+    #[instrument(skip_all)]
+    pub fn snapshot_without_fsmonitor(&mut self, ui: &mut Ui) -> Result<(), CommandError> {
+        self.snapshot_internal(ui, false)
+    }
+
+    fn snapshot_internal(&mut self, ui: &mut Ui, use_fsmonitor: bool) -> Result<(), CommandError> {
         if self.may_update_working_copy {
             if self.working_copy_shared_with_git {
                 let git_repo = self.git_backend().unwrap().git_repo_clone();
                 self.import_git_refs_and_head(ui, &git_repo)?;
             }
-            self.snapshot_working_copy(ui)?;
+            self.snapshot_working_copy(ui, use_fsmonitor)?;
         }
         Ok(())
     }
@@ -872,6 +893,30 @@ impl WorkspaceCommandHelper {
         Ok(())
     }
 
+    /// Rewrites the Git index to match `HEAD`, with the stat info of the
+    /// working copy.
+    fn reset_git_index(&self) -> Result<(), CommandError> {
+        let git_repo = self.git_backend().unwrap().git_repo_clone();
+        let head_commit_id = git_repo
+            .head()
+            .ok()
+            .and_then(|head_ref| head_ref.target())
+            .map(|oid| CommitId::from_bytes(oid.as_bytes()));
+        let working_copy = self.working_copy();
+        let wc_tree = self
+            .repo()
+            .store()
+            .get_root_tree(working_copy.current_tree_id()?)?;
+        git::reset_head_index(
+            &git_repo,
+            self.workspace_root(),
+            head_commit_id.as_ref(),
+            &wc_tree,
+            working_copy.file_states()?,
+        )?;
+        Ok(())
+    }
+
     pub fn repo(&self) -> &Arc<ReadonlyRepo> {
         &self.user_repo.repo
     }
@@ -947,6 +992,51 @@ impl WorkspaceCommandHelper {
         }
     }
 
+    /// Returns the matcher for the new files to track when snapshotting the
+    /// working copy, as configured by `snapshot.auto-track`. The patterns use
+    /// the sparse pattern syntax, with paths relative to the workspace root.
+    pub fn auto_tracking_matcher(&self) -> Result<Box<dyn Matcher>, CommandError> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Patterns {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        let patterns = match self
+            .settings
+            .config()
+            .get::<Patterns>("snapshot.auto-track")
+        {
+            Ok(Patterns::One(pattern)) => vec![pattern],
+            Ok(Patterns::Many(patterns)) => patterns,
+            Err(config::ConfigError::NotFound(_)) => return Ok(Box::new(EverythingMatcher)),
+            Err(err) => return Err(err.into()),
+        };
+        let workspace_root = self.workspace_root();
+        let patterns: Vec<_> = patterns
+            .iter()
+            .map(|pattern| {
+                parse_sparse_pattern(pattern, |path| {
+                    Ok(RepoPath::parse_fs_path(
+                        workspace_root,
+                        workspace_root,
+                        path,
+                    )?)
+                })
+            })
+            .try_collect()?;
+        Ok(Box::new(SparseMatcher::new(&patterns)))
+    }
+
+    /// Returns the options for writing files to the working copy, such as the
+    /// configured `ui.conflict-marker-style`.
+    pub fn checkout_options(&self) -> Result<CheckoutOptions, CommandError> {
+        Ok(CheckoutOptions {
+            conflict_marker_style: self.settings.conflict_marker_style()?,
+        })
+    }
+
     pub fn git_config(&self) -> Result<git2::Config, git2::Error> {
         if let Some(git_backend) = self.git_backend() {
             git_backend.git_repo().config()
@@ -1245,8 +1335,57 @@ impl WorkspaceCommandHelper {
         Ok(())
     }
 
+    /// Starts the built-in filesystem monitor daemon in the background if
+    /// it isn't running yet, and waits briefly for it to become ready.
+    #[cfg(all(feature = "inotify", target_os = "linux"))]
+    fn start_inotify_daemon(&self) {
+        use std::process::{Command, Stdio};
+        use std::time::{Duration, Instant};
+
+        use jj_lib::fsmonitor::inotify;
+
+        let state_dir = inotify::state_dir(self.working_copy().state_path());
+        if inotify::is_daemon_running(&state_dir).unwrap_or(false) {
+            return;
+        }
+        let spawn_result = env::current_exe().and_then(|jj_path| {
+            use std::os::unix::process::CommandExt as _;
+            Command::new(jj_path)
+                .args(["debug", "inotify", "daemon"])
+                .current_dir(self.workspace_root())
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                // Don't receive signals such as Ctrl-C meant for this process
+                .process_group(0)
+                .spawn()
+        });
+        if let Err(err) = spawn_result {
+            tracing::warn!(?err, "Failed to start the filesystem monitor daemon");
+            return;
+        }
+        // If the daemon isn't ready in time, the snapshot crawls the working
+        // copy like it would without a filesystem monitor.
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let working_copy_path = self.working_copy().working_copy_path();
+        while matches!(
+            inotify::query_changed_files(working_copy_path, &state_dir, None),
+            Err(inotify::Error::NotRunning)
+        ) && Instant::now() < deadline
+        {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[cfg(not(all(feature = "inotify", target_os = "linux")))]
+    fn start_inotify_daemon(&self) {}
+
     #[instrument(skip_all)]
-    pub fn snapshot_working_copy(&mut self, ui: &mut Ui) -> Result<(), CommandError> {
+    fn snapshot_working_copy(
+        &mut self,
+        ui: &mut Ui,
+        use_fsmonitor: bool,
+    ) -> Result<(), CommandError> {
         let workspace_id = self.workspace_id().to_owned();
         let get_wc_commit = |repo: &ReadonlyRepo| -> Result<Option<_>, _> {
             repo.view()
@@ -1261,6 +1400,15 @@ impl WorkspaceCommandHelper {
             return Ok(());
         };
         let base_ignores = self.base_ignores();
+        let auto_tracking_matcher = self.auto_tracking_matcher()?;
+        let fsmonitor_kind = if use_fsmonitor {
+            self.settings.fsmonitor_kind()?
+        } else {
+            None
+        };
+        if matches!(fsmonitor_kind, Some(FsmonitorKind::Inotify)) {
+            self.start_inotify_daemon();
+        }
 
         // Compare working-copy tree and operation with repo's, and reload as needed.
         let mut locked_wc = self.workspace.working_copy_mut().start_mutation()?;
@@ -1306,13 +1454,16 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
         };
         self.user_repo = ReadonlyUserRepo::new(repo);
         let progress = crate::progress::snapshot_progress(ui);
-        let new_tree_id = locked_wc.snapshot(SnapshotOptions {
+        let (new_tree_id, stats) = locked_wc.snapshot(SnapshotOptions {
             base_ignores,
-            fsmonitor_kind: self.settings.fsmonitor_kind()?,
+            fsmonitor_kind,
             progress: progress.as_ref().map(|x| x as _),
+            start_tracking_matcher: auto_tracking_matcher.as_ref(),
             max_new_file_size: self.settings.max_new_file_size()?,
+            conflict_marker_style: self.settings.conflict_marker_style()?,
         })?;
         drop(progress);
+        self.snapshot_stats = stats;
         if new_tree_id != *wc_commit.tree_id() {
             let mut tx = start_repo_transaction(
                 &self.user_repo.repo,
@@ -1345,6 +1496,51 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
             self.user_repo = ReadonlyUserRepo::new(tx.commit());
         }
         locked_wc.finish(self.user_repo.repo.op_id().clone())?;
+        self.print_snapshot_stats(ui, &self.snapshot_stats)?;
+        Ok(())
+    }
+
+    /// Warns about the new files that were left untracked by a snapshot because
+    /// they're too large.
+    pub fn print_snapshot_stats(
+        &self,
+        ui: &Ui,
+        stats: &SnapshotStats,
+    ) -> Result<(), std::io::Error> {
+        let large_files = stats
+            .untracked_paths
+            .iter()
+            .filter_map(|(path, reason)| match reason {
+                UntrackedReason::FileTooLarge { size, max_size } => Some((path, *size, *max_size)),
+                UntrackedReason::FileNotAutoTracked | UntrackedReason::NestedGitRepository => None,
+            })
+            .collect_vec();
+        let Some(&(_, _, max_size)) = large_files.first() else {
+            return Ok(());
+        };
+        writeln!(
+            ui.warning(),
+            "Refused to snapshot some files larger than snapshot.max-new-file-size ({}):",
+            HumanByteSize(max_size)
+        )?;
+        let mut formatter = ui.stderr_formatter();
+        for (path, size, _) in &large_files {
+            writeln!(
+                formatter,
+                "  {}: {}",
+                self.format_file_path(path),
+                HumanByteSize(*size)
+            )?;
+        }
+        drop(formatter);
+        let largest_size = large_files.iter().map(|(_, size, _)| *size).max().unwrap();
+        writeln!(
+            ui.hint(),
+            "Hint: To track them, increase snapshot.max-new-file-size in your config, or for a \
+             single command:
+  jj --config-toml 'snapshot.max-new-file-size={largest_size}' status
+Otherwise add them to your `.gitignore` file."
+        )?;
         Ok(())
     }
 
@@ -1361,11 +1557,13 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
                 return Ok(());
             }
         };
+        let checkout_options = self.checkout_options()?;
         let stats = update_working_copy(
             &self.user_repo.repo,
             self.workspace.working_copy_mut(),
             maybe_old_commit,
             &new_commit,
+            &checkout_options,
         )?;
         if Some(&new_commit) != maybe_old_commit {
             ui.write("Working copy now at: ")?;
@@ -1382,6 +1580,52 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
         }
         if let Some(stats) = stats {
             print_checkout_stats(ui, stats)?;
+            self.check_out_submodules(ui, maybe_old_commit, &new_commit)?;
+        }
+        Ok(())
+    }
+
+    fn check_out_submodules(
+        &mut self,
+        ui: &mut Ui,
+        maybe_old_commit: Option<&Commit>,
+        new_commit: &Commit,
+    ) -> Result<(), CommandError> {
+        let repo = self.repo().clone();
+        let old_tree = match maybe_old_commit {
+            Some(commit) => commit.tree()?,
+            None => repo
+                .store()
+                .get_root_tree(&repo.store().empty_merged_tree_id())?,
+        };
+        let new_tree = new_commit.tree()?;
+        let sparse_patterns = self.workspace.working_copy().sparse_patterns()?.to_vec();
+        let matcher = SparseMatcher::new(&sparse_patterns);
+        let workspace_root = self.workspace_root().clone();
+        let result = with_remote_callbacks(ui, |callbacks| {
+            git::check_out_submodules(
+                repo.as_ref(),
+                &workspace_root,
+                &old_tree,
+                &new_tree,
+                &matcher,
+                callbacks,
+            )
+        });
+        match result {
+            Ok(stats) => {
+                for path in stats.unconfigured {
+                    writeln!(
+                        ui.warning(),
+                        "Not checking out Git submodule at {} because it's missing from \
+                         .gitmodules",
+                        self.format_file_path(&path)
+                    )?;
+                }
+            }
+            Err(err) => {
+                writeln!(ui.warning(), "Failed to check out Git submodules: {err}")?;
+            }
         }
         Ok(())
     }
@@ -1419,6 +1663,9 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
         if self.may_update_working_copy {
             self.update_working_copy(ui, maybe_old_commit.as_ref())?;
         }
+        if self.working_copy_shared_with_git {
+            self.reset_git_index()?;
+        }
         let settings = &self.settings;
         if settings.user_name().is_empty() || settings.user_email().is_empty() {
             writeln!(
@@ -1476,10 +1723,11 @@ impl WorkspaceCommandTransaction<'_> {
         ui: &Ui,
         tree: &MergedTree,
         repo_path: &RepoPath,
+        tool_name: Option<&str>,
     ) -> Result<MergedTreeId, CommandError> {
         let settings = &self.helper.settings;
         Ok(crate::merge_tools::run_mergetool(
-            ui, tree, repo_path, settings,
+            ui, tree, repo_path, tool_name, settings,
         )?)
     }
 
@@ -1609,6 +1857,9 @@ jj init --git-repo=.",
         ) => CommandError::InternalError(format!(
             "The repository appears broken or inaccessible: {err}"
         )),
+        WorkspaceLoadError::StoreLoadError(err @ StoreLoadError::MergeConfig(_)) => {
+            CommandError::ConfigError(err.to_string())
+        }
     }
 }
 
@@ -1720,6 +1971,36 @@ pub fn check_stale_working_copy(
     }
 }
 
+/// Parses a sparse pattern. A leading `!` makes it exclude the matching paths.
+/// Globs are kept as written, and other patterns are parsed as paths by
+/// `parse_path`.
+pub fn parse_sparse_pattern(
+    input: &str,
+    parse_path: impl FnOnce(&str) -> Result<RepoPath, CommandError>,
+) -> Result<SparsePattern, CommandError> {
+    let (exclude, pattern) = match input.strip_prefix('!') {
+        Some(pattern) => (true, pattern),
+        None => (false, input),
+    };
+    if sparse::is_glob(pattern) {
+        glob::Pattern::new(pattern)
+            .map_err(|err| user_error(format!(r#"Invalid glob "{input}": {err}"#)))?;
+        let glob = pattern.to_owned();
+        Ok(if exclude {
+            SparsePattern::ExcludeGlob(glob)
+        } else {
+            SparsePattern::Glob(glob)
+        })
+    } else {
+        let path = parse_path(pattern)?;
+        Ok(if exclude {
+            SparsePattern::ExcludePrefix(path)
+        } else {
+            SparsePattern::Prefix(path)
+        })
+    }
+}
+
 pub fn print_checkout_stats(ui: &mut Ui, stats: CheckoutStats) -> Result<(), std::io::Error> {
     if stats.added_files > 0 || stats.updated_files > 0 || stats.removed_files > 0 {
         writeln!(
@@ -1941,6 +2222,7 @@ pub fn update_working_copy(
     wc: &mut WorkingCopy,
     old_commit: Option<&Commit>,
     new_commit: &Commit,
+    options: &CheckoutOptions,
 ) -> Result<Option<CheckoutStats>, CommandError> {
     let old_tree_id = old_commit.map(|commit| commit.tree_id().clone());
     let stats = if Some(new_commit.tree_id()) != old_tree_id.as_ref() {
@@ -1948,7 +2230,12 @@ pub fn update_working_copy(
         // warning for most commands (but be an error for the checkout command)
         let new_tree = new_commit.tree()?;
         let stats = wc
-            .check_out(repo.op_id().clone(), old_tree_id.as_ref(), &new_tree)
+            .check_out(
+                repo.op_id().clone(),
+                old_tree_id.as_ref(),
+                &new_tree,
+                options,
+            )
             .map_err(|err| {
                 CommandError::InternalError(format!(
                     "Failed to check out commit {}: {}",
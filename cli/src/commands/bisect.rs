@@ -0,0 +1,408 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+
+use clap::Subcommand;
+use jj_lib::backend::{CommitId, ObjectId};
+use jj_lib::bisect::{self, BisectionStep};
+use jj_lib::repo::Repo;
+use tracing::instrument;
+
+use crate::cli_util::{
+    short_commit_hash, user_error, user_error_with_hint, CommandError, CommandHelper, RevisionArg,
+    WorkspaceCommandHelper,
+};
+use crate::commands::shell_command;
+use crate::ui::Ui;
+
+/// Find the revision that introduced a bug by binary search
+///
+/// Start a bisection with `jj bisect start --good <rev> --bad <rev>`. Each step
+/// checks out a revision about halfway between the good and the bad revisions
+/// in a new working-copy commit. Test it and mark it with `jj bisect good`, `jj
+/// bisect bad`, or `jj bisect skip`, or let `jj bisect run` test the revisions
+/// with a command. When done, end the bisection with `jj bisect reset`.
+#[derive(Subcommand, Clone, Debug)]
+pub enum BisectCommands {
+    Start(BisectStartArgs),
+    Good(BisectGoodArgs),
+    Bad(BisectBadArgs),
+    Skip(BisectSkipArgs),
+    Run(BisectRunArgs),
+    Reset(BisectResetArgs),
+}
+
+/// Start a bisection
+#[derive(clap::Args, Clone, Debug)]
+pub struct BisectStartArgs {
+    /// A revision without the bug (can be repeated)
+    #[arg(long, required = true)]
+    good: Vec<RevisionArg>,
+    /// A revision with the bug
+    #[arg(long)]
+    bad: RevisionArg,
+}
+
+/// Mark a revision as good
+#[derive(clap::Args, Clone, Debug)]
+pub struct BisectGoodArgs {
+    /// The revision to mark (default: the revision being tested)
+    revision: Option<RevisionArg>,
+}
+
+/// Mark a revision as bad
+#[derive(clap::Args, Clone, Debug)]
+pub struct BisectBadArgs {
+    /// The revision to mark (default: the revision being tested)
+    revision: Option<RevisionArg>,
+}
+
+/// Skip a revision that can't be tested
+#[derive(clap::Args, Clone, Debug)]
+pub struct BisectSkipArgs {
+    /// The revision to skip (default: the revision being tested)
+    revision: Option<RevisionArg>,
+}
+
+/// Test each revision with a command until the first bad one is found
+///
+/// The command is run by the shell in the workspace root. Exit status 0 marks
+/// the revision as good, 125 skips it, and any other status up to 127 marks it
+/// as bad. Any other status, or being killed by a signal, stops the bisection.
+#[derive(clap::Args, Clone, Debug)]
+pub struct BisectRunArgs {
+    /// The shell command to run
+    command: String,
+}
+
+/// End the bisection
+///
+/// The working-copy commit from before the bisection is checked out again.
+#[derive(clap::Args, Clone, Debug)]
+pub struct BisectResetArgs {}
+
+#[derive(Clone, Copy, Debug)]
+enum Mark {
+    Good,
+    Bad,
+    Skip,
+}
+
+impl fmt::Display for Mark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mark::Good => write!(f, "good"),
+            Mark::Bad => write!(f, "bad"),
+            Mark::Skip => write!(f, "skipped"),
+        }
+    }
+}
+
+/// The state of a bisection. It's kept in the repo directory so the bisection
+/// can continue over several commands.
+#[derive(Debug)]
+struct BisectState {
+    good: Vec<CommitId>,
+    bad: CommitId,
+    skipped: Vec<CommitId>,
+    /// The working-copy commit from before the bisection.
+    original_wc_commit: CommitId,
+    /// The revision being tested, or `None` once the bisection is done.
+    current: Option<CommitId>,
+}
+
+impl BisectState {
+    fn path(repo_path: &Path) -> PathBuf {
+        repo_path.join("bisect")
+    }
+
+    fn load(repo_path: &Path) -> Result<Option<Self>, CommandError> {
+        let content = match fs::read_to_string(Self::path(repo_path)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let corrupt = || CommandError::InternalError("The bisection state is corrupt".to_owned());
+        let mut good = vec![];
+        let mut bad = None;
+        let mut skipped = vec![];
+        let mut original_wc_commit = None;
+        let mut current = None;
+        for line in content.lines() {
+            let (key, hex) = line.split_once(' ').ok_or_else(corrupt)?;
+            let id = CommitId::new(hex::decode(hex).map_err(|_| corrupt())?);
+            match key {
+                "good" => good.push(id),
+                "bad" => bad = Some(id),
+                "skip" => skipped.push(id),
+                "original" => original_wc_commit = Some(id),
+                "current" => current = Some(id),
+                _ => return Err(corrupt()),
+            }
+        }
+        Ok(Some(BisectState {
+            good,
+            bad: bad.ok_or_else(corrupt)?,
+            skipped,
+            original_wc_commit: original_wc_commit.ok_or_else(corrupt)?,
+            current,
+        }))
+    }
+
+    fn save(&self, repo_path: &Path) -> Result<(), CommandError> {
+        let mut content = String::new();
+        let mut add_line = |key: &str, id: &CommitId| {
+            content.push_str(&format!("{key} {}\n", id.hex()));
+        };
+        for id in &self.good {
+            add_line("good", id);
+        }
+        add_line("bad", &self.bad);
+        for id in &self.skipped {
+            add_line("skip", id);
+        }
+        add_line("original", &self.original_wc_commit);
+        if let Some(id) = &self.current {
+            add_line("current", id);
+        }
+        fs::write(Self::path(repo_path), content)?;
+        Ok(())
+    }
+
+    fn mark(&mut self, commit_id: CommitId, mark: Mark) {
+        match mark {
+            Mark::Good => self.good.push(commit_id),
+            Mark::Bad => self.bad = commit_id,
+            Mark::Skip => self.skipped.push(commit_id),
+        }
+    }
+}
+
+fn load_state(workspace_command: &WorkspaceCommandHelper) -> Result<BisectState, CommandError> {
+    BisectState::load(workspace_command.repo().repo_path())?.ok_or_else(|| {
+        user_error_with_hint(
+            "No bisection in progress",
+            "Use `jj bisect start` to start one.",
+        )
+    })
+}
+
+#[instrument(skip_all)]
+pub fn cmd_bisect(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    subcommand: &BisectCommands,
+) -> Result<(), CommandError> {
+    match subcommand {
+        BisectCommands::Start(sub_args) => cmd_bisect_start(ui, command, sub_args),
+        BisectCommands::Good(sub_args) => {
+            cmd_bisect_mark(ui, command, sub_args.revision.as_ref(), Mark::Good)
+        }
+        BisectCommands::Bad(sub_args) => {
+            cmd_bisect_mark(ui, command, sub_args.revision.as_ref(), Mark::Bad)
+        }
+        BisectCommands::Skip(sub_args) => {
+            cmd_bisect_mark(ui, command, sub_args.revision.as_ref(), Mark::Skip)
+        }
+        BisectCommands::Run(sub_args) => cmd_bisect_run(ui, command, sub_args),
+        BisectCommands::Reset(sub_args) => cmd_bisect_reset(ui, command, sub_args),
+    }
+}
+
+fn cmd_bisect_start(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &BisectStartArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    if BisectState::load(workspace_command.repo().repo_path())?.is_some() {
+        return Err(user_error_with_hint(
+            "A bisection is already in progress",
+            "Use `jj bisect reset` to end it.",
+        ));
+    }
+    let original_wc_commit = workspace_command
+        .get_wc_commit_id()
+        .ok_or_else(|| user_error("This command requires a working copy"))?
+        .clone();
+    let bad = workspace_command.resolve_single_rev(&args.bad, ui)?;
+    let mut good = vec![];
+    for revision in &args.good {
+        good.push(
+            workspace_command
+                .resolve_single_rev(revision, ui)?
+                .id()
+                .clone(),
+        );
+    }
+    let state = BisectState {
+        good,
+        bad: bad.id().clone(),
+        skipped: vec![],
+        original_wc_commit,
+        current: None,
+    };
+    check_out_next(ui, &mut workspace_command, state)
+}
+
+fn cmd_bisect_mark(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    revision: Option<&RevisionArg>,
+    mark: Mark,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let mut state = load_state(&workspace_command)?;
+    let commit_id = match revision {
+        Some(revision) => workspace_command
+            .resolve_single_rev(revision, ui)?
+            .id()
+            .clone(),
+        None => state.current.clone().ok_or_else(|| {
+            user_error_with_hint(
+                "No revision is being tested",
+                "Specify the revision to mark.",
+            )
+        })?,
+    };
+    state.mark(commit_id, mark);
+    check_out_next(ui, &mut workspace_command, state)
+}
+
+fn cmd_bisect_run(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &BisectRunArgs,
+) -> Result<(), CommandError> {
+    let workspace_command = command.workspace_helper(ui)?;
+    if load_state(&workspace_command)?.current.is_none() {
+        return Err(user_error("No revision is being tested"));
+    }
+    drop(workspace_command);
+    loop {
+        let workspace_command = command.workspace_helper(ui)?;
+        let Some(commit_id) = load_state(&workspace_command)?.current else {
+            return Ok(());
+        };
+        let status = shell_command(&args.command)
+            .current_dir(workspace_command.workspace_root())
+            .status()
+            .map_err(|err| user_error(format!("Failed to run the command: {err}")))?;
+        let mark = match status.code() {
+            Some(0) => Mark::Good,
+            Some(125) => Mark::Skip,
+            Some(1..=127) => Mark::Bad,
+            _ => {
+                return Err(user_error(format!(
+                    "The command failed with {status}; stopping the bisection"
+                )));
+            }
+        };
+        writeln!(
+            ui,
+            "Marking {} as {mark} ({status})",
+            short_commit_hash(&commit_id)
+        )?;
+        drop(workspace_command);
+        // Reload to snapshot any changes the command made to the working copy.
+        let mut workspace_command = command.workspace_helper(ui)?;
+        let mut state = load_state(&workspace_command)?;
+        state.mark(commit_id, mark);
+        check_out_next(ui, &mut workspace_command, state)?;
+    }
+}
+
+fn cmd_bisect_reset(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &BisectResetArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let state = load_state(&workspace_command)?;
+    let repo_path = workspace_command.repo().repo_path().clone();
+    if workspace_command.get_wc_commit_id() != Some(&state.original_wc_commit) {
+        let original = workspace_command
+            .repo()
+            .store()
+            .get_commit(&state.original_wc_commit)?;
+        let mut tx = workspace_command.start_transaction("end bisection");
+        if original.is_discardable() {
+            // It was most likely abandoned when the first revision was checked
+            // out, so create an equivalent commit instead.
+            let new_wc_commit = tx
+                .mut_repo()
+                .new_commit(
+                    command.settings(),
+                    original.parent_ids().to_vec(),
+                    original.tree_id().clone(),
+                )
+                .write()?;
+            tx.edit(&new_wc_commit)?;
+        } else {
+            tx.edit(&original)?;
+        }
+        tx.finish(ui)?;
+    }
+    fs::remove_file(BisectState::path(&repo_path))?;
+    Ok(())
+}
+
+/// Saves the state and checks out the next revision to test, or reports the
+/// first bad revision if it has been found.
+fn check_out_next(
+    ui: &mut Ui,
+    workspace_command: &mut WorkspaceCommandHelper,
+    mut state: BisectState,
+) -> Result<(), CommandError> {
+    let repo = workspace_command.repo().clone();
+    let skipped: HashSet<CommitId> = state.skipped.iter().cloned().collect();
+    match bisect::next_step(&repo, &state.good, &state.bad, &skipped)? {
+        BisectionStep::Next {
+            commit_id,
+            num_candidates,
+        } => {
+            let commit = repo.store().get_commit(&commit_id)?;
+            state.current = Some(commit_id);
+            state.save(repo.repo_path())?;
+            writeln!(
+                ui,
+                "Bisecting: {num_candidates} revisions left to test (roughly {} steps)",
+                usize::BITS - num_candidates.leading_zeros()
+            )?;
+            let mut tx = workspace_command
+                .start_transaction(&format!("bisect: check out commit {}", commit.id().hex()));
+            tx.check_out(&commit)?;
+            tx.finish(ui)?;
+        }
+        BisectionStep::Found(commit_ids) => {
+            state.current = None;
+            state.save(repo.repo_path())?;
+            if commit_ids.len() == 1 {
+                ui.write("The first bad revision is:\n")?;
+            } else {
+                ui.write("The first bad revision could be any of:\n")?;
+            }
+            for commit_id in &commit_ids {
+                let commit = repo.store().get_commit(commit_id)?;
+                ui.write("  ")?;
+                workspace_command.write_commit_summary(ui.stdout_formatter().as_mut(), &commit)?;
+                ui.write("\n")?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -38,6 +38,8 @@ pub enum DebugCommands {
     Operation(DebugOperationArgs),
     #[command(subcommand)]
     Watchman(DebugWatchmanSubcommand),
+    #[command(subcommand)]
+    Inotify(DebugInotifySubcommand),
 }
 
 /// Evaluate revset to full commit IDs
@@ -92,6 +94,16 @@ pub enum DebugWatchmanSubcommand {
     ResetClock,
 }
 
+#[derive(Subcommand, Clone, Debug)]
+pub enum DebugInotifySubcommand {
+    /// Run the built-in filesystem monitor daemon in the foreground
+    Daemon,
+    /// Stop the built-in filesystem monitor daemon
+    Stop,
+    QueryClock,
+    QueryChangedFiles,
+}
+
 pub fn cmd_debug(
     ui: &mut Ui,
     command: &CommandHelper,
@@ -190,6 +202,9 @@ pub fn cmd_debug(
         DebugCommands::Watchman(watchman_subcommand) => {
             cmd_debug_watchman(ui, command, watchman_subcommand)?;
         }
+        DebugCommands::Inotify(inotify_subcommand) => {
+            cmd_debug_inotify(ui, command, inotify_subcommand)?;
+        }
     }
     Ok(())
 }
@@ -268,3 +283,51 @@ fn cmd_debug_watchman(
         "Cannot query Watchman because jj was not compiled with the `watchman` feature",
     ))
 }
+
+#[cfg(all(feature = "inotify", target_os = "linux"))]
+fn cmd_debug_inotify(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    subcommand: &DebugInotifySubcommand,
+) -> Result<(), CommandError> {
+    use jj_lib::fsmonitor::inotify;
+
+    let fsmonitor_error = |err: inotify::Error| user_error(err.to_string());
+    match subcommand {
+        DebugInotifySubcommand::Daemon => {
+            // Don't snapshot the working copy, which would start another daemon
+            let workspace = command.load_workspace()?;
+            let wc = workspace.working_copy();
+            let state_dir = inotify::state_dir(wc.state_path());
+            inotify::run_daemon(wc.working_copy_path(), &state_dir).map_err(fsmonitor_error)?;
+        }
+        DebugInotifySubcommand::Stop => {
+            let workspace = command.load_workspace()?;
+            let state_dir = inotify::state_dir(workspace.working_copy().state_path());
+            inotify::stop_daemon(&state_dir).map_err(fsmonitor_error)?;
+        }
+        DebugInotifySubcommand::QueryClock => {
+            let workspace_command = command.workspace_helper(ui)?;
+            let (clock, _changed_files) = workspace_command.working_copy().query_inotify()?;
+            ui.write(&format!("Clock: {clock:?}"))?;
+        }
+        DebugInotifySubcommand::QueryChangedFiles => {
+            let workspace_command = command.workspace_helper(ui)?;
+            let (_clock, changed_files) = workspace_command.working_copy().query_inotify()?;
+            ui.write(&format!("Changed files: {changed_files:?}"))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(all(feature = "inotify", target_os = "linux")))]
+fn cmd_debug_inotify(
+    _ui: &mut Ui,
+    _command: &CommandHelper,
+    _subcommand: &DebugInotifySubcommand,
+) -> Result<(), CommandError> {
+    Err(user_error(
+        "Cannot use the built-in filesystem monitor because it's only supported on Linux, and jj \
+         was not compiled with the `inotify` feature",
+    ))
+}
@@ -2,17 +2,17 @@ use std::collections::HashSet;
 use std::io::{Read, Seek as _, SeekFrom, Write};
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::sync::Mutex;
-use std::time::Instant;
 use std::{fs, io};
 
 use clap::{ArgGroup, Subcommand};
 use itertools::Itertools;
 use jj_lib::backend::{CommitId, ObjectId, TreeValue};
+use jj_lib::default_index_store::DefaultIndexStore;
 use jj_lib::git::{self, parse_gitmodules, GitFetchError, GitPushError, GitRefUpdate};
 use jj_lib::git_backend::GitBackend;
+use jj_lib::git_hooks;
 use jj_lib::op_store::{BranchTarget, RefTarget};
+use jj_lib::operation::Operation;
 use jj_lib::refs::{classify_branch_push_action, BranchPushAction, BranchPushUpdate};
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::RepoPath;
@@ -29,7 +29,7 @@ use crate::cli_util::{
     WorkspaceCommandHelper,
 };
 use crate::commands::make_branch_term;
-use crate::progress::Progress;
+use crate::git_util::{handle_git_hook_result, with_remote_callbacks};
 use crate::ui::Ui;
 
 /// Commands for working with the underlying Git repo
@@ -45,7 +45,7 @@ pub enum GitCommands {
     Push(GitPushArgs),
     Import(GitImportArgs),
     Export(GitExportArgs),
-    #[command(subcommand, hide = true)]
+    #[command(subcommand)]
     Submodule(GitSubmoduleCommands),
 }
 
@@ -105,6 +105,12 @@ pub struct GitFetchArgs {
     /// Fetch from all remotes
     #[arg(long, conflicts_with = "remotes")]
     all_remotes: bool,
+    /// Fetch only this many commits from the tip of each branch
+    ///
+    /// The oldest fetched commits are shown as children of the root commit
+    /// and marked as "shallow" in `jj log`.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    depth: Option<u32>,
 }
 
 /// Create a new repo backed by a clone of a Git repo
@@ -121,6 +127,12 @@ pub struct GitCloneArgs {
     /// Whether or not to colocate the Jujutsu repo with the git repo
     #[arg(long)]
     colocate: bool,
+    /// Fetch only this many commits from the tip of each branch
+    ///
+    /// The oldest fetched commits are shown as children of the root commit
+    /// and marked as "shallow" in `jj log`.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    depth: Option<u32>,
 }
 
 /// Push to a Git remote
@@ -165,17 +177,17 @@ pub struct GitImportArgs {}
 #[derive(clap::Args, Clone, Debug)]
 pub struct GitExportArgs {}
 
-/// FOR INTERNAL USE ONLY Interact with git submodules
+/// Interact with Git submodules
+///
+/// Submodules are checked out when the working-copy commit changes. The repo
+/// backing each submodule is kept in `.jj/repo/submodule_store/`.
 #[derive(Subcommand, Clone, Debug)]
 pub enum GitSubmoduleCommands {
-    /// Print the relevant contents from .gitmodules. For debugging purposes
-    /// only.
     PrintGitmodules(GitSubmodulePrintGitmodulesArgs),
 }
 
-/// Print debugging info about Git submodules
+/// Print the submodules described in .gitmodules
 #[derive(clap::Args, Clone, Debug)]
-#[command(hide = true)]
 pub struct GitSubmodulePrintGitmodulesArgs {
     /// Read .gitmodules from the given revision.
     #[arg(long, short = 'r', default_value = "@")]
@@ -329,6 +341,7 @@ fn cmd_git_fetch(
         remotes.iter().join(",")
     ));
     let branches = args.branch.iter().map(|b| b.as_str()).collect_vec();
+    let shallow_commits_before = get_shallow_commits(tx.base_repo().store())?;
     for remote in remotes {
         with_remote_callbacks(ui, |cb| {
             git::fetch(
@@ -336,6 +349,7 @@ fn cmd_git_fetch(
                 &git_repo,
                 &remote,
                 (!branches.is_empty()).then_some(&*branches),
+                args.depth,
                 cb,
                 &command.settings().git_settings(),
             )
@@ -346,10 +360,37 @@ fn cmd_git_fetch(
             _ => user_error(err.to_string()),
         })?;
     }
+    let shallow_commits_after = get_shallow_commits(tx.base_repo().store())?;
     tx.finish(ui)?;
+    if !shallow_commits_before.is_subset(&shallow_commits_after) {
+        reindex_deepened_commits(command, workspace_command.repo().operation())?;
+    }
     Ok(())
 }
 
+/// Rebuilds the index after fetching the parents of some shallow commits,
+/// which the index still records as children of the root commit.
+fn reindex_deepened_commits(command: &CommandHelper, op: &Operation) -> Result<(), CommandError> {
+    // Load the repo again so no commits are read from the store's cache with
+    // their old parents
+    let workspace = command.load_workspace()?;
+    let repo_loader = workspace.repo_loader();
+    let default_index_store: Option<&DefaultIndexStore> =
+        repo_loader.index_store().as_any().downcast_ref();
+    if let Some(default_index_store) = default_index_store {
+        default_index_store.reinit();
+        repo_loader.load_at(op)?;
+    }
+    Ok(())
+}
+
+fn get_shallow_commits(store: &Store) -> Result<HashSet<CommitId>, CommandError> {
+    match store.backend_impl().downcast_ref::<GitBackend>() {
+        None => Ok(HashSet::new()),
+        Some(git_backend) => Ok(git_backend.shallow_commits()?),
+    }
+}
+
 fn get_single_remote(git_repo: &git2::Repository) -> Result<Option<String>, CommandError> {
     let git_remotes = git_repo.remotes()?;
     Ok(match git_remotes.len() {
@@ -457,7 +498,14 @@ fn cmd_git_clone(
     let canonical_wc_path: PathBuf = wc_path
         .canonicalize()
         .map_err(|err| user_error(format!("Failed to create {wc_path_str}: {err}")))?;
-    let clone_result = do_git_clone(ui, command, args.colocate, &source, &canonical_wc_path);
+    let clone_result = do_git_clone(
+        ui,
+        command,
+        args.colocate,
+        args.depth,
+        &source,
+        &canonical_wc_path,
+    );
     if clone_result.is_err() {
         let clean_up_dirs = || -> io::Result<()> {
             fs::remove_dir_all(canonical_wc_path.join(".jj"))?;
@@ -507,6 +555,7 @@ fn do_git_clone(
     ui: &mut Ui,
     command: &CommandHelper,
     colocate: bool,
+    depth: Option<u32>,
     source: &str,
     wc_path: &Path,
 ) -> Result<(WorkspaceCommandHelper, git2::Repository, Option<String>), CommandError> {
@@ -530,6 +579,7 @@ fn do_git_clone(
             &git_repo,
             remote_name,
             None,
+            depth,
             cb,
             &command.settings().git_settings(),
         )
@@ -548,119 +598,6 @@ fn do_git_clone(
     Ok((workspace_command, git_repo, maybe_default_branch))
 }
 
-fn with_remote_callbacks<T>(ui: &mut Ui, f: impl FnOnce(git::RemoteCallbacks<'_>) -> T) -> T {
-    let mut ui = Mutex::new(ui);
-    let mut callback = None;
-    if let Some(mut output) = ui.get_mut().unwrap().progress_output() {
-        let mut progress = Progress::new(Instant::now());
-        callback = Some(move |x: &git::Progress| {
-            _ = progress.update(Instant::now(), x, &mut output);
-        });
-    }
-    let mut callbacks = git::RemoteCallbacks::default();
-    callbacks.progress = callback
-        .as_mut()
-        .map(|x| x as &mut dyn FnMut(&git::Progress));
-    let mut get_ssh_keys = get_ssh_keys; // Coerce to unit fn type
-    callbacks.get_ssh_keys = Some(&mut get_ssh_keys);
-    let mut get_pw = |url: &str, _username: &str| {
-        pinentry_get_pw(url).or_else(|| terminal_get_pw(*ui.lock().unwrap(), url))
-    };
-    callbacks.get_password = Some(&mut get_pw);
-    let mut get_user_pw = |url: &str| {
-        let ui = &mut *ui.lock().unwrap();
-        Some((terminal_get_username(ui, url)?, terminal_get_pw(ui, url)?))
-    };
-    callbacks.get_username_password = Some(&mut get_user_pw);
-    f(callbacks)
-}
-
-fn terminal_get_username(ui: &mut Ui, url: &str) -> Option<String> {
-    ui.prompt(&format!("Username for {url}")).ok()
-}
-
-fn terminal_get_pw(ui: &mut Ui, url: &str) -> Option<String> {
-    ui.prompt_password(&format!("Passphrase for {url}: ")).ok()
-}
-
-fn pinentry_get_pw(url: &str) -> Option<String> {
-    let mut pinentry = Command::new("pinentry")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-        .ok()?;
-    #[rustfmt::skip]
-    pinentry
-        .stdin
-        .take()
-        .unwrap()
-        .write_all(
-            format!(
-                "SETTITLE jj passphrase\n\
-                 SETDESC Enter passphrase for {url}\n\
-                 SETPROMPT Passphrase:\n\
-                 GETPIN\n"
-            )
-            .as_bytes(),
-        )
-        .ok()?;
-    let mut out = String::new();
-    pinentry
-        .stdout
-        .take()
-        .unwrap()
-        .read_to_string(&mut out)
-        .ok()?;
-    _ = pinentry.wait();
-    for line in out.split('\n') {
-        if !line.starts_with("D ") {
-            continue;
-        }
-        let (_, encoded) = line.split_at(2);
-        return decode_assuan_data(encoded);
-    }
-    None
-}
-
-// https://www.gnupg.org/documentation/manuals/assuan/Server-responses.html#Server-responses
-fn decode_assuan_data(encoded: &str) -> Option<String> {
-    let encoded = encoded.as_bytes();
-    let mut decoded = Vec::with_capacity(encoded.len());
-    let mut i = 0;
-    while i < encoded.len() {
-        if encoded[i] != b'%' {
-            decoded.push(encoded[i]);
-            i += 1;
-            continue;
-        }
-        i += 1;
-        let byte =
-            u8::from_str_radix(std::str::from_utf8(encoded.get(i..i + 2)?).ok()?, 16).ok()?;
-        decoded.push(byte);
-        i += 2;
-    }
-    String::from_utf8(decoded).ok()
-}
-
-#[tracing::instrument]
-fn get_ssh_keys(_username: &str) -> Vec<PathBuf> {
-    let mut paths = vec![];
-    if let Ok(home_dir) = std::env::var("HOME") {
-        let ssh_dir = Path::new(&home_dir).join(".ssh");
-        for filename in ["id_ed25519_sk", "id_ed25519", "id_rsa"] {
-            let key_path = ssh_dir.join(filename);
-            if key_path.is_file() {
-                tracing::info!(path = ?key_path, "found ssh key");
-                paths.push(key_path);
-            }
-        }
-    }
-    if paths.is_empty() {
-        tracing::info!("no ssh key found");
-    }
-    paths
-}
-
 fn cmd_git_push(
     ui: &mut Ui,
     command: &CommandHelper,
@@ -668,6 +605,7 @@ fn cmd_git_push(
 ) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
     let git_repo = get_git_repo(workspace_command.repo().store())?;
+    let workspace_root = workspace_command.workspace_root().clone();
 
     let remote = if let Some(name) = &args.remote {
         name.clone()
@@ -962,6 +900,16 @@ fn cmd_git_push(
         return Ok(());
     }
 
+    if command
+        .settings()
+        .git_settings()
+        .should_run_hook("pre-push")
+    {
+        let result =
+            git_hooks::run_pre_push_hook(&git_repo, &workspace_root, &remote, &ref_updates);
+        handle_git_hook_result(ui, result.map(|output| ((), output)))?;
+    }
+
     with_remote_callbacks(ui, |cb| {
         git::push_updates(&git_repo, &remote, &ref_updates, cb)
     })
@@ -14,16 +14,19 @@
 
 #[cfg(feature = "bench")]
 mod bench;
+mod bisect;
 mod branch;
 mod debug;
 mod git;
 mod operation;
 
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::Debug;
 use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::process::Stdio;
+use std::sync::{mpsc, Arc, Mutex};
 use std::{fs, io};
 
 use clap::builder::NonEmptyStringValueParser;
@@ -31,14 +34,17 @@ use clap::parser::ValueSource;
 use clap::{ArgGroup, Command, CommandFactory, FromArgMatches, Subcommand};
 use indexmap::{IndexMap, IndexSet};
 use itertools::Itertools;
-use jj_lib::backend::{CommitId, ObjectId, TreeValue};
+use jj_lib::absorb::split_hunks_to_trees;
+use jj_lib::backend::{CommitId, FileId, MergedTreeId, ObjectId, TreeValue};
 use jj_lib::commit::Commit;
+use jj_lib::conflicts::ConflictMarkerStyle;
 use jj_lib::dag_walk::topo_order_reverse;
 use jj_lib::git_backend::GitBackend;
-use jj_lib::matchers::EverythingMatcher;
+use jj_lib::gitignore::GitIgnoreFile;
+use jj_lib::matchers::{EverythingMatcher, Matcher, UnionMatcher};
 use jj_lib::merge::Merge;
 use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
-use jj_lib::op_store::WorkspaceId;
+use jj_lib::op_store::{OperationId, WorkspaceId};
 use jj_lib::repo::{ReadonlyRepo, Repo};
 use jj_lib::repo_path::RepoPath;
 use jj_lib::revset::{RevsetExpression, RevsetFilterPredicate, RevsetIteratorExt};
@@ -46,23 +52,28 @@ use jj_lib::revset_graph::{
     ReverseRevsetGraphIterator, RevsetGraphEdgeType, TopoGroupedRevsetGraphIterator,
 };
 use jj_lib::rewrite::{back_out_commit, merge_commit_trees, rebase_commit, DescendantRebaser};
-use jj_lib::settings::UserSettings;
-use jj_lib::working_copy::SnapshotOptions;
+use jj_lib::settings::{HumanByteSize, UserSettings};
+use jj_lib::sparse::{SparseMatcher, SparsePattern};
+use jj_lib::store::Store;
+use jj_lib::working_copy::{
+    CheckoutOptions, LockedWorkingCopy, SnapshotOptions, UntrackedReason, WorkingCopy,
+};
 use jj_lib::workspace::Workspace;
 use jj_lib::{conflicts, file_util, revset};
 use maplit::{hashmap, hashset};
 use tracing::instrument;
 
 use crate::cli_util::{
-    self, check_stale_working_copy, get_new_config_file_path, print_checkout_stats,
-    resolve_multiple_nonempty_revsets, resolve_multiple_nonempty_revsets_default_single,
-    run_ui_editor, serialize_config_value, short_commit_hash, user_error, user_error_with_hint,
-    write_config_value_to_file, Args, CommandError, CommandHelper, LogContentFormat, RevisionArg,
-    WorkspaceCommandHelper,
+    self, check_stale_working_copy, get_new_config_file_path, parse_sparse_pattern,
+    print_checkout_stats, resolve_multiple_nonempty_revsets,
+    resolve_multiple_nonempty_revsets_default_single, run_ui_editor, serialize_config_value,
+    short_commit_hash, user_error, user_error_with_hint, write_config_value_to_file, Args,
+    CommandError, CommandHelper, LogContentFormat, RevisionArg, WorkspaceCommandHelper,
 };
-use crate::config::{AnnotatedValue, ConfigSource};
+use crate::config::{AnnotatedValue, CommandNameAndArgs, ConfigSource};
 use crate::diff_util::{self, DiffFormat, DiffFormatArgs};
 use crate::formatter::{Formatter, PlainTextFormatter};
+use crate::git_util::run_commit_msg_hook;
 use crate::graphlog::{get_graphlog, Edge};
 use crate::text_util;
 use crate::ui::Ui;
@@ -70,11 +81,14 @@ use crate::ui::Ui;
 #[derive(clap::Parser, Clone, Debug)]
 enum Commands {
     Abandon(AbandonArgs),
+    Absorb(AbsorbArgs),
     Backout(BackoutArgs),
     #[cfg(feature = "bench")]
     #[command(subcommand)]
     Bench(bench::BenchCommands),
     #[command(subcommand)]
+    Bisect(bisect::BisectCommands),
+    #[command(subcommand)]
     Branch(branch::BranchSubcommand),
     #[command(alias = "print")]
     Cat(CatArgs),
@@ -91,8 +105,10 @@ enum Commands {
     Duplicate(DuplicateArgs),
     Edit(EditArgs),
     Files(FilesArgs),
+    Fix(FixArgs),
     #[command(subcommand)]
     Git(git::GitCommands),
+    Histedit(HisteditArgs),
     Init(InitArgs),
     Interdiff(InterdiffArgs),
     Log(LogArgs),
@@ -113,12 +129,11 @@ enum Commands {
     #[command(subcommand)]
     #[command(visible_alias = "op")]
     Operation(operation::OperationCommands),
+    Parallelize(ParallelizeArgs),
     Prev(PrevArgs),
     Rebase(RebaseArgs),
     Resolve(ResolveArgs),
     Restore(RestoreArgs),
-    #[command(hide = true)]
-    // TODO: Flesh out.
     Run(RunArgs),
     Show(ShowArgs),
     #[command(subcommand)]
@@ -126,6 +141,7 @@ enum Commands {
     Split(SplitArgs),
     Squash(SquashArgs),
     Status(StatusArgs),
+    Track(TrackArgs),
     #[command(subcommand)]
     Util(UtilCommands),
     /// Undo an operation (shortcut for `jj op undo`)
@@ -267,6 +283,18 @@ struct CheckoutArgs {
     message_paragraphs: Vec<String>,
 }
 
+/// Start tracking specified paths in the working copy
+///
+/// New files are tracked automatically when the working copy is snapshotted,
+/// unless they don't match the `snapshot.auto-track` patterns. Such files are
+/// listed by `jj status` as untracked until they're tracked with this command.
+#[derive(clap::Args, Clone, Debug)]
+struct TrackArgs {
+    /// Paths to track
+    #[arg(required = true, value_hint = clap::ValueHint::AnyPath)]
+    paths: Vec<String>,
+}
+
 /// Stop tracking specified paths in the working copy
 #[derive(clap::Args, Clone, Debug)]
 struct UntrackArgs {
@@ -622,6 +650,64 @@ struct PrevArgs {
     edit: bool,
 }
 
+/// Make revisions siblings of each other
+///
+/// Running `jj parallelize 1::2` transforms the history like this:
+///
+/// 3
+/// |         3
+/// 2        / \
+/// |   =>  1   2
+/// 1        \ /
+/// |         0
+/// 0
+///
+/// Each of the target revisions is rebased onto the parents of the root(s) of
+/// the target revisions. The children of the head(s) of the target revisions
+/// are rebased onto all of the target revisions, so they become merges of
+/// them. Other children of the target revisions stay on top of their parents.
+///
+/// The target revisions must be connected, e.g. a linear stack of changes.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+struct ParallelizeArgs {
+    /// Revisions to parallelize
+    revisions: Vec<RevisionArg>,
+}
+
+/// Reorder, combine, reword, or drop the revisions in a stack
+///
+/// Opens an editor with one line per revision, oldest first, e.g.:
+///
+///   pick 0123456789ab First change
+///   pick 456789abcdef Second change
+///
+/// Edit the history by reordering the lines or changing the action at the
+/// start of a line to one of these:
+///
+///   pick   = keep the revision
+///   reword = keep the revision, but edit its description
+///   edit   = keep the revision and make it the working-copy commit
+///   squash = squash the revision into the one above, combining their
+///            descriptions
+///   fixup  = squash the revision into the one above, keeping only the
+///            description above
+///   drop   = remove the revision and its changes
+///
+/// Actions can be abbreviated to their first letter. Removing all lines
+/// leaves the history unchanged.
+///
+/// The revisions must form a linear stack. Their other descendants follow
+/// them, except that the children of the last revision are rebased onto the
+/// new last revision. All the changes are made in a single operation.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+struct HisteditArgs {
+    /// The revisions to edit
+    #[arg(required = true)]
+    revisions: Vec<RevisionArg>,
+}
+
 /// Move changes from one revision into another
 ///
 /// Use `--interactive` to move only part of the source revision into the
@@ -651,21 +737,67 @@ struct MoveArgs {
     paths: Vec<String>,
 }
 
-/// Move changes from a revision into its parent
+/// Move changes from a revision into the ancestors that last modified the
+/// same lines
 ///
-/// After moving the changes into the parent, the child revision will have the
-/// same content state as before. If that means that the change is now empty
-/// compared to its parent, it will be abandoned.
-/// Without `--interactive`, the child change will always be empty.
+/// Each hunk of the changes in the source revision is moved into the closest
+/// ancestor that last modified the lines the hunk changes, as if by `jj squash
+/// -i --into` for each hunk. A hunk that only adds lines is moved into the
+/// ancestor that last modified the lines around it. Hunks that touch lines last
+/// modified by several ancestors, or by ancestors outside the destination
+/// revisions, stay in the source revision. So do changes to added, removed, or
+/// binary files and to conflicted files.
 ///
-/// If the source became empty and both the source and destination had a
-/// non-empty description, you will be asked for the combined description. If
-/// either was empty, then the other one will be used.
+/// Descendants of the rewritten ancestors are rebased, so the source revision
+/// keeps its content and loses the absorbed changes from its diff.
+#[derive(clap::Args, Clone, Debug)]
+struct AbsorbArgs {
+    /// Source revision to absorb changes from
+    #[arg(long, short, default_value = "@")]
+    from: RevisionArg,
+    /// Revisions to absorb changes into
+    ///
+    /// Only ancestors of the source revision are considered.
+    #[arg(long, short = 't', default_value = "all()")]
+    into: RevisionArg,
+    /// Absorb only changes to these paths (instead of all paths)
+    #[arg(value_hint = clap::ValueHint::AnyPath)]
+    paths: Vec<String>,
+}
+
+/// Move changes from a revision into another revision
+///
+/// With the `-r` option, moves the changes from the specified revision to the
+/// parent revision. Fails if there are several parent revisions (i.e., the
+/// given revision is a merge).
+///
+/// With the `--from` and/or `--into` options, moves changes from/to the given
+/// revisions. If either is left out, it defaults to the working-copy commit.
+/// For example, `jj squash --into @--` moves changes from the working-copy
+/// commit to the grandparent. Several `--from` revisions can be given, and
+/// their changes are all moved into the destination.
+///
+/// After moving the changes out of a source revision, it will have the same
+/// content state as before. If that means that the change is now empty compared
+/// to its parent, it will be abandoned. Without `--interactive`, the source
+/// changes will always be empty. Descendants of the rewritten revisions are
+/// rebased.
+///
+/// If a source became empty and both it and the destination had a non-empty
+/// description, you will be asked for the combined description. If either was
+/// empty, then the other one will be used.
 #[derive(clap::Args, Clone, Debug)]
 #[command(visible_alias = "amend")]
 struct SquashArgs {
-    #[arg(long, short, default_value = "@")]
+    /// Revision to squash into its parent
+    #[arg(long, short, default_value = "@", conflicts_with_all = ["from", "into"])]
     revision: RevisionArg,
+    /// Revision(s) to squash from (default: @)
+    #[arg(long)]
+    from: Vec<RevisionArg>,
+    /// Revision to squash into (default: @)
+    #[arg(long)]
+    into: Option<RevisionArg>,
     /// The description to use for squashed revision (don't open editor)
     #[arg(long = "message", short, value_name = "MESSAGE")]
     message_paragraphs: Vec<String>,
@@ -727,8 +859,13 @@ struct ChmodArgs {
 
 /// Resolve a conflicted file with an external merge tool
 ///
-/// Only conflicts that can be resolved with a 3-way merge are supported. See
-/// docs for merge tool configuration instructions.
+/// Only conflicts that can be resolved with a 3-way merge are supported by
+/// external merge tools. See docs for merge tool configuration instructions.
+///
+/// There are also builtin tools. `:ours` and `:theirs` resolve the conflict by
+/// picking the first or second side as it is, and `:side-N` picks the N-th
+/// side. `:builtin` lets you resolve conflicts with any number of sides hunk
+/// by hunk by selecting lines from the sides in a terminal UI.
 ///
 /// Note that conflicts can also be resolved without using this command. You may
 /// edit the conflict markers in the conflicted file directly with a text
@@ -753,6 +890,10 @@ struct ResolveArgs {
     /// conflict
     #[arg(long, short, conflicts_with = "list")]
     quiet: bool,
+    /// Specify the merge tool to use instead of `ui.merge-editor`, e.g.
+    /// `:ours`
+    #[arg(long, conflicts_with = "list", value_name = "NAME")]
+    tool: Option<String>,
     /// Restrict to these paths when searching for a conflict to resolve. We
     /// will attempt to resolve the first conflict we can find. You can use
     /// the `--list` argument to find paths to use here.
@@ -809,27 +950,73 @@ struct RestoreArgs {
     revision: Option<RevisionArg>,
 }
 
-/// Run a command across a set of revisions.
+/// Update files with formatting fixes or other changes
+///
+/// Runs the tools configured in the `fix.tools` table on the files changed in
+/// the given revisions and their descendants, and rewrites the revisions with
+/// the results. This is mainly useful for applying a code formatter to a stack
+/// of revisions without checking out each one. Each tool reads the content of a
+/// file from stdin and writes the new content to stdout:
+///
+///   [fix.tools.rustfmt]
+///   command = ["rustfmt", "--emit", "stdout"]
+///   patterns = ["**/*.rs"]
+///
+/// The patterns use the same syntax as `jj sparse set`, relative to the
+/// workspace root. An argument `$path` in the command is replaced with the path
+/// of the file. If several tools match a file, they run in the order of their
+/// names, each on the output of the previous one. If a tool exits with a
+/// non-zero status, its output is discarded.
+///
+/// A file that is fixed in a revision is also fixed in all its descendants, so
+/// descendants that don't modify the file don't undo the fixes. The tools are
+/// run once for each unique file content and path, and the working-copy
+/// directory isn't used except as the tools' current directory. Descendants of
+/// the fixed revisions are rebased.
+#[derive(clap::Args, Clone, Debug)]
+#[command(verbatim_doc_comment)]
+struct FixArgs {
+    /// Fix files in the specified revision(s) and their descendants
+    #[arg(long, short, default_value = "@")]
+    source: Vec<RevisionArg>,
+    /// Fix only these paths (instead of all paths)
+    #[arg(value_hint = clap::ValueHint::AnyPath)]
+    paths: Vec<String>,
+}
+
+/// Run a command on each of a set of revisions
 ///
+/// Each revision is checked out into a temporary working copy, where the
+/// command runs with `sh -c` (`cmd /c` on Windows). The working copy of the
+/// current workspace isn't touched. Up to `--jobs` revisions are processed in
+/// parallel. Each temporary working copy is reused for several revisions, so
+/// ignored files such as build outputs are kept between them.
 ///
-/// All recorded state will be persisted in the `.jj` directory, so occasionally
-/// a `jj run --clean` is needed to clean up disk space.
+/// The output of the command is printed for each revision once all of them
+/// are done, followed by the exit status. If the command fails on any revision,
+/// `jj run` fails too.
 ///
-/// # Example
+/// With `--commit-changes`, the changes the command makes to the files are
+/// recorded in the revisions, and their descendants are rebased.
 ///
-/// # Run pre-commit on your local work
-/// $ jj run 'pre-commit.py .github/pre-commit.yaml' -r (main..@) -j 4
+/// Example:
 ///
-/// This allows pre-commit integration and other funny stuff.
+/// $ jj run 'cargo test' -r 'main..@'
 #[derive(clap::Args, Clone, Debug)]
 #[command(verbatim_doc_comment)]
 struct RunArgs {
-    /// The command to run across all selected revisions.
-    #[arg(long, short, alias = "x")]
-    command: String,
-    /// The revisions to change.
+    /// The shell command to run on each revision
+    shell_command: String,
+    /// The revisions to run the command on
     #[arg(long, short, default_value = "@")]
     revisions: Vec<RevisionArg>,
+    /// How many revisions to process in parallel [default: the number of
+    /// CPUs]
+    #[arg(long, short)]
+    jobs: Option<NonZeroUsize>,
+    /// Record the changes the command makes to the files in the revisions
+    #[arg(long)]
+    commit_changes: bool,
 }
 
 /// Touch up the content changes in a revision with a diff editor
@@ -1064,6 +1251,8 @@ struct WorkspaceUpdateStaleArgs {}
 enum SparseArgs {
     List(SparseListArgs),
     Set(SparseSetArgs),
+    Edit(SparseEditArgs),
+    Reset(SparseResetArgs),
 }
 
 /// List the patterns that are currently present in the working copy
@@ -1079,6 +1268,13 @@ struct SparseListArgs {}
 /// For example, if all you need is the `README.md` and the `lib/`
 /// directory, use `jj sparse set --clear --add README.md --add lib`.
 /// If you no longer need the `lib` directory, use `jj sparse set --remove lib`.
+///
+/// A pattern containing `*`, `?` or `[` is a glob matched against paths
+/// relative to the workspace root, e.g. `services/*/src`. Other patterns are
+/// paths relative to the current directory. A pattern prefixed with `!`
+/// excludes the matching paths even if another pattern includes them, e.g.
+/// `jj sparse set --add services --add '!services/*/testdata'`. A pattern
+/// matching a directory matches everything in it.
 #[derive(clap::Args, Clone, Debug)]
 struct SparseSetArgs {
     /// Patterns to add to the working copy
@@ -1098,12 +1294,24 @@ struct SparseSetArgs {
     reset: bool,
 }
 
+/// Start an editor to update the patterns that are present in the working copy
+///
+/// The patterns are listed one per line, using the syntax of `jj sparse set`,
+/// except that paths are relative to the workspace root.
+#[derive(clap::Args, Clone, Debug)]
+struct SparseEditArgs {}
+
+/// Reset the patterns to include all files in the working copy
+#[derive(clap::Args, Clone, Debug)]
+struct SparseResetArgs {}
+
 /// Infrequently used commands such as for generating shell completions
 #[derive(Subcommand, Clone, Debug)]
 enum UtilCommands {
     Completion(UtilCompletionArgs),
     Mangen(UtilMangenArgs),
     ConfigSchema(UtilConfigSchemaArgs),
+    UpgradeRepo(UtilUpgradeRepoArgs),
 }
 
 /// Print a command-line-completion script
@@ -1143,6 +1351,17 @@ struct UtilMangenArgs {}
 #[derive(clap::Args, Clone, Debug)]
 struct UtilConfigSchemaArgs {}
 
+/// Upgrade the repo to tree-level conflicts
+///
+/// Repos created by older versions of jj record conflicts as conflict objects
+/// at individual paths. This sets `format.tree-level-conflicts` in the repo
+/// config and rewrites the visible commits that have conflicts in the old
+/// format. Their descendants and branches are updated accordingly; other
+/// commits are left alone. The rewrite can be reverted with `jj undo`, which
+/// doesn't reset the config.
+#[derive(clap::Args, Clone, Debug)]
+struct UtilUpgradeRepoArgs {}
+
 #[instrument(skip_all)]
 fn cmd_version(
     ui: &mut Ui,
@@ -1388,6 +1607,57 @@ fn cmd_checkout(
     Ok(())
 }
 
+#[instrument(skip_all)]
+fn cmd_track(ui: &mut Ui, command: &CommandHelper, args: &TrackArgs) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let matcher = workspace_command.matcher_from_values(&args.paths)?;
+    let auto_tracking_matcher = workspace_command.auto_tracking_matcher()?;
+    let initial_untracked_paths = workspace_command.snapshot_stats().untracked_paths.clone();
+
+    let mut tx = workspace_command
+        .start_transaction("track paths")
+        .into_inner();
+    let base_ignores = workspace_command.base_ignores();
+    let (mut locked_working_copy, wc_commit) = workspace_command.start_working_copy_mutation()?;
+    // Snapshot the working copy again, this time also tracking the new files
+    // matching the paths. The filesystem monitor wouldn't report untracked
+    // files that haven't changed since the last snapshot, so walk all files.
+    let (new_tree_id, mut stats) = locked_working_copy.snapshot(SnapshotOptions {
+        base_ignores,
+        fsmonitor_kind: None,
+        progress: None,
+        start_tracking_matcher: &UnionMatcher::new(
+            auto_tracking_matcher.as_ref(),
+            matcher.as_ref(),
+        ),
+        max_new_file_size: command.settings().max_new_file_size()?,
+        conflict_marker_style: command.settings().conflict_marker_style()?,
+    })?;
+    // Don't report the same paths as the initial snapshot again
+    stats.untracked_paths.retain(|path, reason| {
+        matcher.matches(path) && initial_untracked_paths.get(path) != Some(reason)
+    });
+    if new_tree_id == *wc_commit.tree_id() {
+        let operation_id = locked_working_copy.old_operation_id().clone();
+        locked_working_copy.finish(operation_id)?;
+        workspace_command.print_snapshot_stats(ui, &stats)?;
+        ui.write("Nothing changed.\n")?;
+        return Ok(());
+    }
+    tx.mut_repo()
+        .rewrite_commit(command.settings(), &wc_commit)
+        .set_tree_id(new_tree_id)
+        .write()?;
+    let num_rebased = tx.mut_repo().rebase_descendants(command.settings())?;
+    if num_rebased > 0 {
+        writeln!(ui, "Rebased {num_rebased} descendant commits")?;
+    }
+    let repo = tx.commit();
+    locked_working_copy.finish(repo.op_id().clone())?;
+    workspace_command.print_snapshot_stats(ui, &stats)?;
+    Ok(())
+}
+
 #[instrument(skip_all)]
 fn cmd_untrack(
     ui: &mut Ui,
@@ -1397,6 +1667,7 @@ fn cmd_untrack(
     let mut workspace_command = command.workspace_helper(ui)?;
     let store = workspace_command.repo().store().clone();
     let matcher = workspace_command.matcher_from_values(&args.paths)?;
+    let auto_tracking_matcher = workspace_command.auto_tracking_matcher()?;
 
     let mut tx = workspace_command
         .start_transaction("untrack paths")
@@ -1415,11 +1686,13 @@ fn cmd_untrack(
     locked_working_copy.reset(&new_tree)?;
     // Commit the working copy again so we can inform the user if paths couldn't be
     // untracked because they're not ignored.
-    let wc_tree_id = locked_working_copy.snapshot(SnapshotOptions {
+    let (wc_tree_id, _stats) = locked_working_copy.snapshot(SnapshotOptions {
         base_ignores,
         fsmonitor_kind: command.settings().fsmonitor_kind()?,
         progress: None,
+        start_tracking_matcher: auto_tracking_matcher.as_ref(),
         max_new_file_size: command.settings().max_new_file_size()?,
+        conflict_marker_style: command.settings().conflict_marker_style()?,
     })?;
     if wc_tree_id != new_tree_id {
         let wc_tree = store.get_root_tree(&wc_tree_id)?;
@@ -1492,7 +1765,14 @@ fn cmd_cat(ui: &mut Ui, command: &CommandHelper, args: &CatArgs) -> Result<(), C
         }
         Err(conflict) => {
             let mut contents = vec![];
-            conflicts::materialize(&conflict, repo.store(), &path, &mut contents).unwrap();
+            conflicts::materialize(
+                &conflict,
+                repo.store(),
+                &path,
+                command.settings().conflict_marker_style()?,
+                &mut contents,
+            )
+            .unwrap();
             ui.request_pager();
             ui.stdout_formatter().write_all(&contents)?;
         }
@@ -1563,7 +1843,10 @@ fn cmd_status(
     command: &CommandHelper,
     _args: &StatusArgs,
 ) -> Result<(), CommandError> {
-    let workspace_command = command.workspace_helper(ui)?;
+    let mut workspace_command = command.workspace_helper_no_snapshot(ui)?;
+    // The filesystem monitor only reports changed paths, so walk the whole
+    // working copy to find all the untracked paths
+    workspace_command.snapshot_without_fsmonitor(ui)?;
     let repo = workspace_command.repo();
     let maybe_wc_commit = workspace_command
         .get_wc_commit_id()
@@ -1596,6 +1879,27 @@ fn cmd_status(
             print_conflicted_paths(&conflicts, formatter, &workspace_command)?
         }
 
+        let untracked_paths = &workspace_command.snapshot_stats().untracked_paths;
+        if !untracked_paths.is_empty() {
+            formatter.write_str("Untracked paths:\n")?;
+            for (path, reason) in untracked_paths {
+                write!(formatter, "? {}", workspace_command.format_file_path(path))?;
+                match reason {
+                    UntrackedReason::FileNotAutoTracked => {}
+                    UntrackedReason::NestedGitRepository => {
+                        write!(formatter, " (Git repository)")?;
+                    }
+                    UntrackedReason::FileTooLarge { size, max_size } => write!(
+                        formatter,
+                        " ({}, larger than snapshot.max-new-file-size {})",
+                        HumanByteSize(*size),
+                        HumanByteSize(*max_size)
+                    )?,
+                }
+                writeln!(formatter)?;
+            }
+        }
+
         formatter.write_str("Working copy : ")?;
         formatter.with_label("working_copy", |fmt| {
             workspace_command.write_commit_summary(fmt, wc_commit)
@@ -1972,39 +2276,59 @@ fn rebase_to_dest_parent(
     }
 }
 
-fn edit_description(
+/// Lets the user edit `content` in a temporary file in the repo directory and
+/// returns the edited content. `name` describes the file in error messages.
+fn edit_temp_file(
     repo: &ReadonlyRepo,
-    description: &str,
+    name: &str,
+    suffix: &str,
+    content: &str,
     settings: &UserSettings,
 ) -> Result<String, CommandError> {
-    let description_file_path = (|| -> Result<_, io::Error> {
+    let file_path = (|| -> Result<_, io::Error> {
         let mut file = tempfile::Builder::new()
             .prefix("editor-")
-            .suffix(".jjdescription")
+            .suffix(suffix)
             .tempfile_in(repo.repo_path())?;
-        file.write_all(description.as_bytes())?;
-        file.write_all(b"\nJJ: Lines starting with \"JJ: \" (like this one) will be removed.\n")?;
+        file.write_all(content.as_bytes())?;
         let (_, path) = file.keep().map_err(|e| e.error)?;
         Ok(path)
     })()
     .map_err(|e| {
         user_error(format!(
-            r#"Failed to create description file in "{path}": {e}"#,
+            r#"Failed to create {name} file in "{path}": {e}"#,
             path = repo.repo_path().display()
         ))
     })?;
 
-    run_ui_editor(settings, &description_file_path)?;
+    run_ui_editor(settings, &file_path)?;
 
-    let description = fs::read_to_string(&description_file_path).map_err(|e| {
+    let content = fs::read_to_string(&file_path).map_err(|e| {
         user_error(format!(
-            r#"Failed to read description file "{path}": {e}"#,
-            path = description_file_path.display()
+            r#"Failed to read {name} file "{path}": {e}"#,
+            path = file_path.display()
         ))
     })?;
     // Delete the file only if everything went well.
     // TODO: Tell the user the name of the file we left behind.
-    std::fs::remove_file(description_file_path).ok();
+    std::fs::remove_file(file_path).ok();
+    Ok(content)
+}
+
+fn edit_description(
+    repo: &ReadonlyRepo,
+    description: &str,
+    settings: &UserSettings,
+) -> Result<String, CommandError> {
+    let description = edit_temp_file(
+        repo,
+        "description",
+        ".jjdescription",
+        &format!(
+            "{description}\nJJ: Lines starting with \"JJ: \" (like this one) will be removed.\n"
+        ),
+        settings,
+    )?;
     // Normalize line ending, remove leading and trailing blank lines.
     let description = description
         .lines()
@@ -2016,18 +2340,18 @@ fn edit_description(
 fn edit_sparse(
     workspace_root: &Path,
     repo_path: &Path,
-    sparse: &[RepoPath],
+    sparse: &[SparsePattern],
     settings: &UserSettings,
-) -> Result<Vec<RepoPath>, CommandError> {
+) -> Result<Vec<SparsePattern>, CommandError> {
     let file = (|| -> Result<_, io::Error> {
         let mut file = tempfile::Builder::new()
             .prefix("editor-")
             .suffix(".jjsparse")
             .tempfile_in(repo_path)?;
-        for sparse_path in sparse {
-            let workspace_relative_sparse_path =
-                file_util::relative_path(workspace_root, &sparse_path.to_fs_path(workspace_root));
-            file.write_all(
+        for pattern in sparse {
+            let line = format_sparse_pattern(pattern, |path| {
+                let workspace_relative_sparse_path =
+                    file_util::relative_path(workspace_root, &path.to_fs_path(workspace_root));
                 workspace_relative_sparse_path
                     .to_str()
                     .ok_or_else(|| {
@@ -2038,9 +2362,10 @@ fn edit_sparse(
                                 workspace_relative_sparse_path.display()
                             ),
                         )
-                    })?
-                    .as_bytes(),
-            )?;
+                    })
+                    .map(ToOwned::to_owned)
+            })?;
+            file.write_all(line.as_bytes())?;
             file.write_all(b"\n")?;
         }
         file.seek(SeekFrom::Start(0))?;
@@ -2071,15 +2396,30 @@ fn edit_sparse(
                     path = file_path.display()
                 ))
             })?;
-            Ok::<_, CommandError>(RepoPath::parse_fs_path(
-                workspace_root,
-                workspace_root,
-                line.trim(),
-            )?)
+            parse_sparse_pattern(line.trim(), |path| {
+                Ok(RepoPath::parse_fs_path(
+                    workspace_root,
+                    workspace_root,
+                    path,
+                )?)
+            })
         })
         .try_collect()
 }
 
+/// Formats a sparse pattern in the syntax accepted by `parse_sparse_pattern()`.
+fn format_sparse_pattern<E>(
+    pattern: &SparsePattern,
+    format_path: impl FnOnce(&RepoPath) -> Result<String, E>,
+) -> Result<String, E> {
+    Ok(match pattern {
+        SparsePattern::Prefix(path) => format_path(path)?,
+        SparsePattern::Glob(glob) => glob.clone(),
+        SparsePattern::ExcludePrefix(path) => format!("!{}", format_path(path)?),
+        SparsePattern::ExcludeGlob(glob) => format!("!{glob}"),
+    })
+}
+
 #[instrument(skip_all)]
 fn cmd_describe(
     ui: &mut Ui,
@@ -2102,6 +2442,7 @@ fn cmd_describe(
             description_template_for_commit(ui, command.settings(), &workspace_command, &commit)?;
         edit_description(workspace_command.repo(), &template, command.settings())?
     };
+    let description = run_commit_msg_hook(ui, command.settings(), &workspace_command, description)?;
     if description == *commit.description() && !args.reset_author {
         ui.write("Nothing changed.\n")?;
     } else {
@@ -2136,6 +2477,7 @@ fn cmd_commit(ui: &mut Ui, command: &CommandHelper, args: &CommitArgs) -> Result
             description_template_for_commit(ui, command.settings(), &workspace_command, &commit)?;
         edit_description(workspace_command.repo(), &template, command.settings())?
     };
+    let description = run_commit_msg_hook(ui, command.settings(), &workspace_command, description)?;
 
     let mut tx = workspace_command.start_transaction(&format!("commit {}", commit.id().hex()));
     let new_commit = tx
@@ -2559,141 +2901,529 @@ fn cmd_prev(ui: &mut Ui, command: &CommandHelper, args: &PrevArgs) -> Result<(),
     Ok(())
 }
 
+/// Returns the description for `destination` after squashing `sources` into
+/// it. Pass only the sources that become empty and will be abandoned, since the
+/// other ones keep their descriptions.
 fn combine_messages(
     repo: &ReadonlyRepo,
-    source: &Commit,
+    sources: &[&Commit],
     destination: &Commit,
     settings: &UserSettings,
-    abandon_source: bool,
 ) -> Result<String, CommandError> {
-    let description = if abandon_source {
-        if source.description().is_empty() {
-            destination.description().to_string()
-        } else if destination.description().is_empty() {
-            source.description().to_string()
-        } else {
+    let non_empty_sources = sources
+        .iter()
+        .filter(|source| !source.description().is_empty())
+        .collect_vec();
+    let description = match non_empty_sources.as_slice() {
+        [] => destination.description().to_string(),
+        [source] if destination.description().is_empty() => source.description().to_string(),
+        _ => {
+            let mut parts = vec![];
+            if !destination.description().is_empty() {
+                parts.push(
+                    "JJ: Description from the destination commit:\n".to_string()
+                        + destination.description(),
+                );
+            }
+            for source in non_empty_sources {
+                parts.push(
+                    "JJ: Description from the source commit:\n".to_string() + source.description(),
+                );
+            }
             let combined = "JJ: Enter a description for the combined commit.\n".to_string()
-                + "JJ: Description from the destination commit:\n"
-                + destination.description()
-                + "\nJJ: Description from the source commit:\n"
-                + source.description();
+                + &parts.join("\n");
             edit_description(repo, &combined, settings)?
         }
-    } else {
-        destination.description().to_string()
     };
     Ok(description)
 }
 
 #[instrument(skip_all)]
-fn cmd_move(ui: &mut Ui, command: &CommandHelper, args: &MoveArgs) -> Result<(), CommandError> {
+fn cmd_parallelize(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &ParallelizeArgs,
+) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
-    let source = workspace_command.resolve_single_rev(args.from.as_deref().unwrap_or("@"), ui)?;
-    let mut destination =
-        workspace_command.resolve_single_rev(args.to.as_deref().unwrap_or("@"), ui)?;
-    if source.id() == destination.id() {
-        return Err(user_error("Source and destination cannot be the same."));
+    let targets: IndexSet<Commit> =
+        resolve_multiple_nonempty_revsets(&args.revisions, &workspace_command, ui)?;
+    for commit in &targets {
+        workspace_command.check_rewritable(commit)?;
     }
-    workspace_command.check_rewritable(&source)?;
-    workspace_command.check_rewritable(&destination)?;
-    let matcher = workspace_command.matcher_from_values(&args.paths)?;
-    let mut tx = workspace_command.start_transaction(&format!(
-        "move changes from {} to {}",
-        source.id().hex(),
-        destination.id().hex()
-    ));
-    let parent_tree = merge_commit_trees(tx.repo(), &source.parents())?;
-    let source_tree = source.tree()?;
-    let instructions = format!(
-        "\
-You are moving changes from: {}
-into commit: {}
-
-The left side of the diff shows the contents of the parent commit. The
-right side initially shows the contents of the commit you're moving
-changes from.
-
-Adjust the right side until the diff shows the changes you want to move
-to the destination. If you don't make any changes, then all the changes
-from the source will be moved into the destination.
-",
-        tx.format_commit_summary(&source),
-        tx.format_commit_summary(&destination)
-    );
-    let new_parent_tree_id = tx.select_diff(
-        ui,
-        &parent_tree,
-        &source_tree,
-        &instructions,
-        args.interactive,
-        matcher.as_ref(),
-    )?;
-    if args.interactive && new_parent_tree_id == parent_tree.id() {
-        return Err(user_error("No changes to move"));
+    let target_ids = targets
+        .iter()
+        .map(|commit| commit.id().clone())
+        .collect_vec();
+    let target_expression = RevsetExpression::commits(target_ids.clone());
+    let repo = workspace_command.repo().clone();
+    let connected_len = target_expression
+        .connected()
+        .resolve(repo.as_ref())?
+        .evaluate(repo.as_ref())?
+        .iter()
+        .count();
+    if connected_len != targets.len() {
+        return Err(user_error(
+            "Cannot parallelize since the target revisions are not connected.",
+        ));
     }
-    let new_parent_tree = tx.repo().store().get_root_tree(&new_parent_tree_id)?;
-    // Apply the reverse of the selected changes onto the source
-    let new_source_tree = source_tree.merge(&new_parent_tree, &parent_tree)?;
-    let abandon_source = new_source_tree.id() == parent_tree.id();
-    if abandon_source {
-        tx.mut_repo().record_abandoned_commit(source.id().clone());
-    } else {
-        tx.mut_repo()
-            .rewrite_commit(command.settings(), &source)
-            .set_tree_id(new_source_tree.id().clone())
-            .write()?;
+    let new_parents: Vec<Commit> = target_expression
+        .roots()
+        .parents()
+        .resolve(repo.as_ref())?
+        .evaluate(repo.as_ref())?
+        .iter()
+        .commits(repo.store())
+        .try_collect()?;
+    let head_children: Vec<Commit> = target_expression
+        .heads()
+        .children()
+        .minus(&target_expression)
+        .resolve(repo.as_ref())?
+        .evaluate(repo.as_ref())?
+        .iter()
+        .commits(repo.store())
+        .try_collect()?;
+
+    let mut tx =
+        workspace_command.start_transaction(&format!("parallelize {} commits", targets.len()));
+    // Rebase the targets from the earliest to the latest, so they keep their
+    // order as parents of the merges created below.
+    let mut new_targets = vec![];
+    for target_id in repo.index().topo_order(&mut target_ids.iter()) {
+        let target = repo.store().get_commit(&target_id)?;
+        if target.parents() == new_parents {
+            new_targets.push(target);
+        } else {
+            new_targets.push(rebase_commit(
+                command.settings(),
+                tx.mut_repo(),
+                &target,
+                &new_parents,
+            )?);
+        }
     }
-    if tx.repo().index().is_ancestor(source.id(), destination.id()) {
-        // If we're moving changes to a descendant, first rebase descendants onto the
-        // rewritten source. Otherwise it will likely already have the content
-        // changes we're moving, so applying them will have no effect and the
-        // changes will disappear.
-        let mut rebaser = tx.mut_repo().create_descendant_rebaser(command.settings());
-        rebaser.rebase_all()?;
-        let rebased_destination_id = rebaser.rebased().get(destination.id()).unwrap().clone();
-        destination = tx.mut_repo().store().get_commit(&rebased_destination_id)?;
+    for child in &head_children {
+        let mut child_parents = child
+            .parents()
+            .into_iter()
+            .filter(|parent| !target_ids.contains(parent.id()))
+            .collect_vec();
+        child_parents.extend(new_targets.iter().cloned());
+        if child.parents() != child_parents {
+            rebase_commit(command.settings(), tx.mut_repo(), child, &child_parents)?;
+        }
     }
-    // Apply the selected changes onto the destination
-    let destination_tree = destination.tree()?;
-    let new_destination_tree = destination_tree.merge(&parent_tree, &new_parent_tree)?;
-    let description = combine_messages(
-        tx.base_repo(),
-        &source,
-        &destination,
-        command.settings(),
-        abandon_source,
-    )?;
-    tx.mut_repo()
-        .rewrite_commit(command.settings(), &destination)
-        .set_tree_id(new_destination_tree.id().clone())
-        .set_description(description)
-        .write()?;
     tx.finish(ui)?;
     Ok(())
 }
 
-#[instrument(skip_all)]
-fn cmd_squash(ui: &mut Ui, command: &CommandHelper, args: &SquashArgs) -> Result<(), CommandError> {
-    let mut workspace_command = command.workspace_helper(ui)?;
-    let commit = workspace_command.resolve_single_rev(&args.revision, ui)?;
-    workspace_command.check_rewritable(&commit)?;
-    let parents = commit.parents();
-    if parents.len() != 1 {
-        return Err(user_error("Cannot squash merge commits"));
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HisteditAction {
+    Pick,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Drop,
+}
+
+impl HisteditAction {
+    fn parse(action: &str) -> Option<Self> {
+        match action {
+            "pick" | "p" => Some(HisteditAction::Pick),
+            "reword" | "r" => Some(HisteditAction::Reword),
+            "edit" | "e" => Some(HisteditAction::Edit),
+            "squash" | "s" => Some(HisteditAction::Squash),
+            "fixup" | "f" => Some(HisteditAction::Fixup),
+            "drop" | "d" => Some(HisteditAction::Drop),
+            _ => None,
+        }
     }
-    let parent = &parents[0];
-    workspace_command.check_rewritable(parent)?;
-    let matcher = workspace_command.matcher_from_values(&args.paths)?;
-    let mut tx =
-        workspace_command.start_transaction(&format!("squash commit {}", commit.id().hex()));
-    let instructions = format!(
-        "\
-You are moving changes from: {}
-into its parent: {}
+}
 
-The left side of the diff shows the contents of the parent commit. The
-right side initially shows the contents of the commit you're moving
-changes from.
+/// A revision in the new history, along with the revisions squashed into it.
+struct HisteditGroup {
+    action: HisteditAction,
+    commits: Vec<Commit>,
+    /// The squashed revisions whose descriptions are combined.
+    squashed: Vec<Commit>,
+}
+
+const HISTEDIT_INSTRUCTIONS: &str = "\
+JJ: Reorder the lines or change the action at the start of a line:
+JJ:   pick   = keep the revision
+JJ:   reword = keep the revision, but edit its description
+JJ:   edit   = keep the revision and make it the working-copy commit
+JJ:   squash = squash the revision into the one above, combining their descriptions
+JJ:   fixup  = squash the revision into the one above, keeping only the description above
+JJ:   drop   = remove the revision and its changes
+JJ: Removing all lines leaves the history unchanged.
+JJ: Lines starting with \"JJ: \" (like this one) will be removed.
+";
+
+/// Parses the plan edited by the user into the actions for each of `commits`,
+/// in their new order.
+fn parse_histedit_plan(
+    plan: &str,
+    commits: &[Commit],
+) -> Result<Vec<(HisteditAction, Commit)>, CommandError> {
+    let mut actions = vec![];
+    let mut seen = HashSet::new();
+    for line in plan.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("JJ:") {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let action_str = words.next().unwrap();
+        let action = HisteditAction::parse(action_str).ok_or_else(|| {
+            user_error(format!(r#"Unknown action "{action_str}" in line "{line}""#))
+        })?;
+        let hash = words
+            .next()
+            .ok_or_else(|| user_error(format!(r#"Missing revision in line "{line}""#)))?;
+        let matches = commits
+            .iter()
+            .filter(|commit| commit.id().hex().starts_with(hash))
+            .collect_vec();
+        let commit = match matches.as_slice() {
+            [commit] => *commit,
+            [] => {
+                return Err(user_error(format!(
+                    r#"Revision "{hash}" is not one of the revisions being edited"#
+                )))
+            }
+            _ => return Err(user_error(format!(r#"Revision "{hash}" is ambiguous"#))),
+        };
+        if !seen.insert(commit.id().clone()) {
+            return Err(user_error(format!(
+                r#"Revision "{hash}" appears more than once"#
+            )));
+        }
+        actions.push((action, commit.clone()));
+    }
+    if actions.is_empty() {
+        return Ok(actions);
+    }
+    if let Some(missing) = commits.iter().find(|commit| !seen.contains(commit.id())) {
+        return Err(user_error_with_hint(
+            format!(
+                "Revision {} is missing from the plan",
+                short_commit_hash(missing.id())
+            ),
+            r#"Use "drop" to remove a revision."#,
+        ));
+    }
+    Ok(actions)
+}
+
+#[instrument(skip_all)]
+fn cmd_histedit(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &HisteditArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let targets: IndexSet<Commit> =
+        resolve_multiple_nonempty_revsets(&args.revisions, &workspace_command, ui)?;
+    for commit in &targets {
+        workspace_command.check_rewritable(commit)?;
+    }
+    let repo = workspace_command.repo().clone();
+    let target_ids = targets
+        .iter()
+        .map(|commit| commit.id().clone())
+        .collect_vec();
+    let commits: Vec<Commit> = repo
+        .index()
+        .topo_order(&mut target_ids.iter())
+        .iter()
+        .map(|id| repo.store().get_commit(id))
+        .try_collect()?;
+    let is_stack = commits
+        .iter()
+        .tuple_windows()
+        .all(|(parent, child)| child.parent_ids() == [parent.id().clone()]);
+    if !is_stack {
+        return Err(user_error(
+            "Cannot edit the history since the revisions don't form a linear stack",
+        ));
+    }
+    let old_head = commits.last().unwrap();
+    let head_children: Vec<Commit> = RevsetExpression::commit(old_head.id().clone())
+        .children()
+        .minus(&RevsetExpression::commits(target_ids))
+        .resolve(repo.as_ref())?
+        .evaluate(repo.as_ref())?
+        .iter()
+        .commits(repo.store())
+        .try_collect()?;
+
+    let plan = commits
+        .iter()
+        .map(|commit| {
+            format!(
+                "pick {} {}\n",
+                short_commit_hash(commit.id()),
+                commit.description().lines().next().unwrap_or_default()
+            )
+        })
+        .join("");
+    let plan = edit_temp_file(
+        &repo,
+        "plan",
+        ".jjhistedit",
+        &format!("{plan}\n{HISTEDIT_INSTRUCTIONS}"),
+        command.settings(),
+    )?;
+    let actions = parse_histedit_plan(&plan, &commits)?;
+    if actions.is_empty() {
+        writeln!(ui, "Nothing changed.")?;
+        return Ok(());
+    }
+    let mut groups: Vec<HisteditGroup> = vec![];
+    let mut dropped = vec![];
+    for (action, commit) in actions {
+        match action {
+            HisteditAction::Drop => dropped.push(commit),
+            HisteditAction::Squash | HisteditAction::Fixup => {
+                let group = groups.last_mut().ok_or_else(|| {
+                    user_error(format!(
+                        "Cannot squash revision {} since there's no revision above it",
+                        short_commit_hash(commit.id())
+                    ))
+                })?;
+                group.commits.push(commit.clone());
+                if action == HisteditAction::Squash {
+                    group.squashed.push(commit);
+                }
+            }
+            HisteditAction::Pick | HisteditAction::Reword | HisteditAction::Edit => {
+                groups.push(HisteditGroup {
+                    action,
+                    commits: vec![commit],
+                    squashed: vec![],
+                });
+            }
+        }
+    }
+    if groups
+        .iter()
+        .filter(|group| group.action == HisteditAction::Edit)
+        .count()
+        > 1
+    {
+        return Err(user_error("Only one revision can be marked \"edit\""));
+    }
+
+    let mut tx =
+        workspace_command.start_transaction(&format!("histedit {} commits", commits.len()));
+    let mut new_parents = commits[0].parents();
+    let mut edit_commit = None;
+    for group in &groups {
+        let first = &group.commits[0];
+        let mut description = first.description().to_owned();
+        if !group.squashed.is_empty() {
+            let squashed = group.squashed.iter().collect_vec();
+            description = combine_messages(tx.base_repo(), &squashed, first, command.settings())?;
+        }
+        if group.action == HisteditAction::Reword {
+            description = edit_description(tx.base_repo(), &description, command.settings())?;
+        }
+        let new_commit = if group.commits.len() == 1
+            && first.parents() == new_parents
+            && description == first.description()
+        {
+            first.clone()
+        } else {
+            // Apply the changes from each commit in the group on top of the new
+            // parents.
+            let mut tree = merge_commit_trees(tx.repo(), &new_parents)?;
+            for commit in &group.commits {
+                let parent_tree = merge_commit_trees(tx.repo(), &commit.parents())?;
+                tree = tree.merge(&parent_tree, &commit.tree()?)?;
+            }
+            let new_commit = tx
+                .mut_repo()
+                .rewrite_commit(command.settings(), first)
+                .set_parents(
+                    new_parents
+                        .iter()
+                        .map(|commit| commit.id().clone())
+                        .collect(),
+                )
+                .set_tree_id(tree.id())
+                .set_description(description)
+                .set_predecessors(
+                    group
+                        .commits
+                        .iter()
+                        .map(|commit| commit.id().clone())
+                        .collect(),
+                )
+                .write()?;
+            for commit in &group.commits[1..] {
+                tx.mut_repo()
+                    .record_rewritten_commit(commit.id().clone(), new_commit.id().clone());
+            }
+            new_commit
+        };
+        if group.action == HisteditAction::Edit {
+            edit_commit = Some(new_commit.clone());
+        }
+        new_parents = vec![new_commit];
+    }
+    for commit in &dropped {
+        tx.mut_repo().record_abandoned_commit(commit.id().clone());
+    }
+    let mut num_rebased = 0;
+    if let [new_head] = new_parents.as_slice() {
+        if !groups.is_empty() && new_head.id() != old_head.id() {
+            for child in &head_children {
+                let child_parents = child
+                    .parents()
+                    .into_iter()
+                    .map(|parent| {
+                        if parent.id() == old_head.id() {
+                            new_head.clone()
+                        } else {
+                            parent
+                        }
+                    })
+                    .collect_vec();
+                rebase_commit(command.settings(), tx.mut_repo(), child, &child_parents)?;
+                num_rebased += 1;
+            }
+        }
+    }
+    num_rebased += tx.mut_repo().rebase_descendants(command.settings())?;
+    if num_rebased > 0 {
+        writeln!(ui, "Rebased {num_rebased} descendant commits")?;
+    }
+    if let Some(commit) = edit_commit {
+        tx.edit(&commit)?;
+    }
+    tx.finish(ui)?;
+    Ok(())
+}
+
+#[instrument(skip_all)]
+fn cmd_move(ui: &mut Ui, command: &CommandHelper, args: &MoveArgs) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let source = workspace_command.resolve_single_rev(args.from.as_deref().unwrap_or("@"), ui)?;
+    let mut destination =
+        workspace_command.resolve_single_rev(args.to.as_deref().unwrap_or("@"), ui)?;
+    if source.id() == destination.id() {
+        return Err(user_error("Source and destination cannot be the same."));
+    }
+    workspace_command.check_rewritable(&source)?;
+    workspace_command.check_rewritable(&destination)?;
+    let matcher = workspace_command.matcher_from_values(&args.paths)?;
+    let mut tx = workspace_command.start_transaction(&format!(
+        "move changes from {} to {}",
+        source.id().hex(),
+        destination.id().hex()
+    ));
+    let parent_tree = merge_commit_trees(tx.repo(), &source.parents())?;
+    let source_tree = source.tree()?;
+    let instructions = format!(
+        "\
+You are moving changes from: {}
+into commit: {}
+
+The left side of the diff shows the contents of the parent commit. The
+right side initially shows the contents of the commit you're moving
+changes from.
+
+Adjust the right side until the diff shows the changes you want to move
+to the destination. If you don't make any changes, then all the changes
+from the source will be moved into the destination.
+",
+        tx.format_commit_summary(&source),
+        tx.format_commit_summary(&destination)
+    );
+    let new_parent_tree_id = tx.select_diff(
+        ui,
+        &parent_tree,
+        &source_tree,
+        &instructions,
+        args.interactive,
+        matcher.as_ref(),
+    )?;
+    if args.interactive && new_parent_tree_id == parent_tree.id() {
+        return Err(user_error("No changes to move"));
+    }
+    let new_parent_tree = tx.repo().store().get_root_tree(&new_parent_tree_id)?;
+    // Apply the reverse of the selected changes onto the source
+    let new_source_tree = source_tree.merge(&new_parent_tree, &parent_tree)?;
+    let abandon_source = new_source_tree.id() == parent_tree.id();
+    if abandon_source {
+        tx.mut_repo().record_abandoned_commit(source.id().clone());
+    } else {
+        tx.mut_repo()
+            .rewrite_commit(command.settings(), &source)
+            .set_tree_id(new_source_tree.id().clone())
+            .write()?;
+    }
+    if tx.repo().index().is_ancestor(source.id(), destination.id()) {
+        // If we're moving changes to a descendant, first rebase descendants onto the
+        // rewritten source. Otherwise it will likely already have the content
+        // changes we're moving, so applying them will have no effect and the
+        // changes will disappear.
+        let mut rebaser = tx.mut_repo().create_descendant_rebaser(command.settings());
+        rebaser.rebase_all()?;
+        let rebased_destination_id = rebaser.rebased().get(destination.id()).unwrap().clone();
+        destination = tx.mut_repo().store().get_commit(&rebased_destination_id)?;
+    }
+    // Apply the selected changes onto the destination
+    let destination_tree = destination.tree()?;
+    let new_destination_tree = destination_tree.merge(&parent_tree, &new_parent_tree)?;
+    let abandoned_sources = if abandon_source {
+        vec![&source]
+    } else {
+        vec![]
+    };
+    let description = combine_messages(
+        tx.base_repo(),
+        &abandoned_sources,
+        &destination,
+        command.settings(),
+    )?;
+    tx.mut_repo()
+        .rewrite_commit(command.settings(), &destination)
+        .set_tree_id(new_destination_tree.id().clone())
+        .set_description(description)
+        .write()?;
+    tx.finish(ui)?;
+    Ok(())
+}
+
+#[instrument(skip_all)]
+fn cmd_squash(ui: &mut Ui, command: &CommandHelper, args: &SquashArgs) -> Result<(), CommandError> {
+    if !args.from.is_empty() || args.into.is_some() {
+        return squash_into(ui, command, args);
+    }
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let commit = workspace_command.resolve_single_rev(&args.revision, ui)?;
+    workspace_command.check_rewritable(&commit)?;
+    let parents = commit.parents();
+    if parents.len() != 1 {
+        return Err(user_error("Cannot squash merge commits"));
+    }
+    let parent = &parents[0];
+    workspace_command.check_rewritable(parent)?;
+    let matcher = workspace_command.matcher_from_values(&args.paths)?;
+    let mut tx =
+        workspace_command.start_transaction(&format!("squash commit {}", commit.id().hex()));
+    let instructions = format!(
+        "\
+You are moving changes from: {}
+into its parent: {}
+
+The left side of the diff shows the contents of the parent commit. The
+right side initially shows the contents of the commit you're moving
+changes from.
 
 Adjust the right side until the diff shows the changes you want to move
 to the destination. If you don't make any changes, then all the changes
@@ -2740,12 +3470,12 @@ from the source will be moved into the parent.
     let description = if !args.message_paragraphs.is_empty() {
         cli_util::join_message_paragraphs(&args.message_paragraphs)
     } else {
+        let abandoned_sources = if abandon_child { vec![&commit] } else { vec![] };
         combine_messages(
             tx.base_repo(),
-            &commit,
+            &abandoned_sources,
             parent,
             command.settings(),
-            abandon_child,
         )?
     };
     let mut_repo = tx.mut_repo();
@@ -2768,6 +3498,223 @@ from the source will be moved into the parent.
     Ok(())
 }
 
+/// Squashes the `--from` revisions into the `--into` revision.
+fn squash_into(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    args: &SquashArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let sources = if args.from.is_empty() {
+        vec![workspace_command.resolve_single_rev("@", ui)?]
+    } else {
+        resolve_multiple_nonempty_revsets(&args.from, &workspace_command, ui)?
+            .into_iter()
+            .collect_vec()
+    };
+    let mut destination =
+        workspace_command.resolve_single_rev(args.into.as_deref().unwrap_or("@"), ui)?;
+    if sources.iter().any(|source| source.id() == destination.id()) {
+        return Err(user_error("Source and destination cannot be the same."));
+    }
+    for commit in sources.iter().chain([&destination]) {
+        workspace_command.check_rewritable(commit)?;
+    }
+    let matcher = workspace_command.matcher_from_values(&args.paths)?;
+    let mut tx = workspace_command
+        .start_transaction(&format!("squash commits into {}", destination.id().hex()));
+
+    // Select the changes to move out of each source before rewriting anything,
+    // so the diff editor always sees the original commits.
+    struct SourceChanges {
+        commit: Commit,
+        parent_tree: MergedTree,
+        selected_tree: MergedTree,
+        abandon: bool,
+    }
+    let mut source_changes = vec![];
+    for source in sources {
+        let parent_tree = merge_commit_trees(tx.repo(), &source.parents())?;
+        let source_tree = source.tree()?;
+        let instructions = format!(
+            "\
+You are moving changes from: {}
+into commit: {}
+
+The left side of the diff shows the contents of the parent commit. The
+right side initially shows the contents of the commit you're moving
+changes from.
+
+Adjust the right side until the diff shows the changes you want to move
+to the destination. If you don't make any changes, then all the changes
+from the source will be moved into the destination.
+",
+            tx.format_commit_summary(&source),
+            tx.format_commit_summary(&destination)
+        );
+        let selected_tree_id = tx.select_diff(
+            ui,
+            &parent_tree,
+            &source_tree,
+            &instructions,
+            args.interactive,
+            matcher.as_ref(),
+        )?;
+        let selected_tree = tx.repo().store().get_root_tree(&selected_tree_id)?;
+        // The source is abandoned if all its changes were selected
+        let abandon = selected_tree_id == source_tree.id();
+        source_changes.push(SourceChanges {
+            commit: source,
+            parent_tree,
+            selected_tree,
+            abandon,
+        });
+    }
+    if args.interactive
+        && source_changes
+            .iter()
+            .all(|changes| changes.selected_tree.id() == changes.parent_tree.id())
+    {
+        return Err(user_error("No changes selected"));
+    }
+
+    for changes in &source_changes {
+        if changes.abandon {
+            tx.mut_repo()
+                .record_abandoned_commit(changes.commit.id().clone());
+        } else {
+            // Apply the reverse of the selected changes onto the source
+            let source_tree = changes.commit.tree()?;
+            let new_source_tree =
+                source_tree.merge(&changes.selected_tree, &changes.parent_tree)?;
+            tx.mut_repo()
+                .rewrite_commit(command.settings(), &changes.commit)
+                .set_tree_id(new_source_tree.id().clone())
+                .write()?;
+        }
+    }
+    if source_changes.iter().any(|changes| {
+        tx.repo()
+            .index()
+            .is_ancestor(changes.commit.id(), destination.id())
+    }) {
+        // If we're moving changes to a descendant, first rebase descendants onto the
+        // rewritten sources. Otherwise it will likely already have the content
+        // changes we're moving, so applying them will have no effect and the
+        // changes will disappear.
+        let mut rebaser = tx.mut_repo().create_descendant_rebaser(command.settings());
+        rebaser.rebase_all()?;
+        let rebased_destination_id = rebaser.rebased().get(destination.id()).unwrap().clone();
+        destination = tx.mut_repo().store().get_commit(&rebased_destination_id)?;
+    }
+    // Apply the selected changes onto the destination
+    let mut new_destination_tree = destination.tree()?;
+    for changes in &source_changes {
+        new_destination_tree =
+            new_destination_tree.merge(&changes.parent_tree, &changes.selected_tree)?;
+    }
+    let description = if !args.message_paragraphs.is_empty() {
+        cli_util::join_message_paragraphs(&args.message_paragraphs)
+    } else {
+        let abandoned_sources = source_changes
+            .iter()
+            .filter(|changes| changes.abandon)
+            .map(|changes| &changes.commit)
+            .collect_vec();
+        combine_messages(
+            tx.base_repo(),
+            &abandoned_sources,
+            &destination,
+            command.settings(),
+        )?
+    };
+    let mut predecessors = vec![destination.id().clone()];
+    predecessors.extend(
+        source_changes
+            .iter()
+            .map(|changes| changes.commit.id().clone()),
+    );
+    tx.mut_repo()
+        .rewrite_commit(command.settings(), &destination)
+        .set_tree_id(new_destination_tree.id().clone())
+        .set_predecessors(predecessors)
+        .set_description(description)
+        .write()?;
+    tx.finish(ui)?;
+    Ok(())
+}
+
+#[instrument(skip_all)]
+fn cmd_absorb(ui: &mut Ui, command: &CommandHelper, args: &AbsorbArgs) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let source = workspace_command.resolve_single_rev(&args.from, ui)?;
+    if source.parent_ids().len() != 1 {
+        return Err(user_error("Cannot absorb changes from a merge commit"));
+    }
+    let into_expression = workspace_command.parse_revset(&args.into, Some(ui))?;
+    let destinations: Vec<Commit> = workspace_command
+        .evaluate_revset(
+            RevsetExpression::commits(source.parent_ids().to_vec())
+                .ancestors()
+                .intersection(&into_expression)
+                .minus(&RevsetExpression::root()),
+        )?
+        .iter()
+        .commits(workspace_command.repo().store())
+        .try_collect()?;
+    let matcher = workspace_command.matcher_from_values(&args.paths)?;
+    let new_trees = split_hunks_to_trees(
+        workspace_command.repo().as_ref(),
+        &source,
+        &destinations,
+        matcher.as_ref(),
+    )?;
+    if new_trees.is_empty() {
+        writeln!(ui, "Nothing changed.")?;
+        return Ok(());
+    }
+
+    let mut tx = workspace_command
+        .start_transaction(&format!("absorb changes from commit {}", source.id().hex()));
+    let mut rewritten_ids = vec![];
+    for destination in &destinations {
+        if let Some(tree_id) = new_trees.get(destination.id()) {
+            let new_commit = tx
+                .mut_repo()
+                .rewrite_commit(command.settings(), destination)
+                .set_tree_id(tree_id.clone())
+                .write()?;
+            rewritten_ids.push(new_commit.id().clone());
+        }
+    }
+    // Rewritten destinations that are descendants of other destinations get
+    // rebased too, so look up their final versions.
+    let mut rebaser = tx.mut_repo().create_descendant_rebaser(command.settings());
+    rebaser.rebase_all()?;
+    let rebased = rebaser.rebased().clone();
+    let absorbed_commits: Vec<Commit> = rewritten_ids
+        .iter()
+        .map(|id| rebased.get(id).unwrap_or(id))
+        .map(|id| tx.repo().store().get_commit(id))
+        .try_collect()?;
+    let num_rebased = rebased
+        .keys()
+        .filter(|id| !rewritten_ids.contains(id))
+        .count();
+
+    writeln!(ui, "Absorbed changes into these revisions:")?;
+    for commit in &absorbed_commits {
+        ui.write("  ")?;
+        tx.write_commit_summary(ui.stdout_formatter().as_mut(), commit)?;
+        ui.write("\n")?;
+    }
+    if num_rebased > 0 {
+        writeln!(ui, "Rebased {num_rebased} descendant commits")?;
+    }
+    tx.finish(ui)?;
+    Ok(())
+}
+
 #[instrument(skip_all)]
 fn cmd_unsquash(
     ui: &mut Ui,
@@ -2815,8 +3762,7 @@ aborted.
     // case).
     if new_parent_tree_id == parent_base_tree.id() {
         tx.mut_repo().record_abandoned_commit(parent.id().clone());
-        let description =
-            combine_messages(tx.base_repo(), parent, &commit, command.settings(), true)?;
+        let description = combine_messages(tx.base_repo(), &[parent], &commit, command.settings())?;
         // Commit the new child on top of the parent's parents.
         tx.mut_repo()
             .rewrite_commit(command.settings(), &commit)
@@ -2951,7 +3897,7 @@ fn cmd_resolve(
         "Resolve conflicts in commit {}",
         commit.id().hex()
     ));
-    let new_tree_id = tx.run_mergetool(ui, &tree, repo_path)?;
+    let new_tree_id = tx.run_mergetool(ui, &tree, repo_path, args.tool.as_deref())?;
     let new_commit = tx
         .mut_repo()
         .rewrite_commit(command.settings(), &commit)
@@ -3354,9 +4300,519 @@ fn cmd_merge(ui: &mut Ui, command: &CommandHelper, args: &NewArgs) -> Result<(),
     cmd_new(ui, command, args)
 }
 
-// TODO: Move to run.rs
-fn cmd_run(_ui: &mut Ui, _command: &CommandHelper, _args: &RunArgs) -> Result<(), CommandError> {
-    Err(user_error("This is a stub, do not use"))
+/// A tool configured in the `fix.tools` table.
+#[derive(serde::Deserialize)]
+struct FixToolConfig {
+    command: CommandNameAndArgs,
+    #[serde(default)]
+    patterns: Vec<String>,
+}
+
+struct FixTool {
+    name: String,
+    command: CommandNameAndArgs,
+    matcher: SparseMatcher,
+}
+
+fn get_fix_tools(
+    settings: &UserSettings,
+    workspace_root: &Path,
+) -> Result<Vec<FixTool>, CommandError> {
+    let configs = match settings
+        .config()
+        .get::<BTreeMap<String, FixToolConfig>>("fix.tools")
+    {
+        Ok(configs) => configs,
+        Err(config::ConfigError::NotFound(_)) => BTreeMap::new(),
+        Err(err) => return Err(err.into()),
+    };
+    if configs.is_empty() {
+        return Err(user_error_with_hint(
+            "No fix tools are configured",
+            "Add a tool to the `fix.tools` table in the config. See `jj help fix`.",
+        ));
+    }
+    configs
+        .into_iter()
+        .map(|(name, config)| {
+            let patterns: Vec<_> = config
+                .patterns
+                .iter()
+                .map(|pattern| {
+                    parse_sparse_pattern(pattern, |path| {
+                        Ok(RepoPath::parse_fs_path(
+                            workspace_root,
+                            workspace_root,
+                            path,
+                        )?)
+                    })
+                })
+                .try_collect()?;
+            Ok(FixTool {
+                name,
+                command: config.command,
+                matcher: SparseMatcher::new(&patterns),
+            })
+        })
+        .try_collect()
+}
+
+#[instrument(skip_all)]
+fn cmd_fix(ui: &mut Ui, command: &CommandHelper, args: &FixArgs) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let tools = get_fix_tools(command.settings(), workspace_command.workspace_root())?;
+    let root_commits = resolve_multiple_nonempty_revsets(&args.source, &workspace_command, ui)?;
+    let matcher = workspace_command.matcher_from_values(&args.paths)?;
+    let repo = workspace_command.repo().clone();
+    let root_ids = root_commits
+        .iter()
+        .map(|commit| commit.id().clone())
+        .collect_vec();
+    // Parents before children
+    let commits: Vec<Commit> = RevsetExpression::commits(root_ids)
+        .descendants()
+        .resolve(repo.as_ref())?
+        .evaluate(repo.as_ref())?
+        .iter()
+        .commits(repo.store())
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .rev()
+        .collect();
+    for commit in &commits {
+        workspace_command.check_rewritable(commit)?;
+    }
+
+    // Find the paths to fix in each commit. A path that is fixed in a commit is
+    // also fixed in its descendants, so the fixes aren't undone by descendants
+    // that didn't modify the file.
+    let mut commit_paths: HashMap<CommitId, BTreeSet<RepoPath>> = HashMap::new();
+    for commit in &commits {
+        let mut paths: BTreeSet<RepoPath> = commit
+            .parent_ids()
+            .iter()
+            .filter_map(|parent_id| commit_paths.get(parent_id))
+            .flatten()
+            .cloned()
+            .collect();
+        let parent_tree = merge_commit_trees(repo.as_ref(), &commit.parents())?;
+        let tree = commit.tree()?;
+        for (path, _before, after) in parent_tree.diff(&tree, matcher.as_ref()) {
+            if matches!(after.as_resolved(), Some(Some(TreeValue::File { .. })))
+                && tools.iter().any(|tool| tool.matcher.matches(&path))
+            {
+                paths.insert(path);
+            }
+        }
+        commit_paths.insert(commit.id().clone(), paths);
+    }
+
+    let mut tx = workspace_command.start_transaction("fix commits");
+    let workspace_root = tx.base_workspace_helper().workspace_root().clone();
+    let mut fixed_file_ids: HashMap<(RepoPath, FileId), FileId> = HashMap::new();
+    let mut rewritten_ids: HashMap<CommitId, CommitId> = HashMap::new();
+    let mut num_fixed_commits = 0;
+    for commit in &commits {
+        let tree = commit.tree()?;
+        let mut tree_builder = MergedTreeBuilder::new(commit.tree_id().clone());
+        let mut has_fixes = false;
+        for path in &commit_paths[commit.id()] {
+            let value = tree.path_value(path);
+            let Some(Some(TreeValue::File { id, executable })) = value.as_resolved() else {
+                continue;
+            };
+            let key = (path.clone(), id.clone());
+            let new_id = match fixed_file_ids.get(&key) {
+                Some(new_id) => new_id.clone(),
+                None => {
+                    let display_path = tx.base_workspace_helper().format_file_path(path);
+                    let new_id = fix_file(
+                        ui,
+                        &workspace_root,
+                        &tools,
+                        tx.repo().store(),
+                        path,
+                        &display_path,
+                        id,
+                    )?;
+                    fixed_file_ids.insert(key, new_id.clone());
+                    new_id
+                }
+            };
+            if new_id != *id {
+                tree_builder.set_or_remove(
+                    path.clone(),
+                    Merge::normal(TreeValue::File {
+                        id: new_id,
+                        executable: *executable,
+                    }),
+                );
+                has_fixes = true;
+            }
+        }
+        let new_parent_ids = commit
+            .parent_ids()
+            .iter()
+            .map(|parent_id| rewritten_ids.get(parent_id).unwrap_or(parent_id).clone())
+            .collect_vec();
+        if !has_fixes && new_parent_ids == commit.parent_ids() {
+            continue;
+        }
+        let new_tree_id = if has_fixes {
+            num_fixed_commits += 1;
+            tree_builder.write_tree(tx.repo().store())?
+        } else {
+            commit.tree_id().clone()
+        };
+        let new_commit = tx
+            .mut_repo()
+            .rewrite_commit(command.settings(), commit)
+            .set_parents(new_parent_ids)
+            .set_tree_id(new_tree_id)
+            .write()?;
+        rewritten_ids.insert(commit.id().clone(), new_commit.id().clone());
+    }
+    writeln!(
+        ui,
+        "Fixed {num_fixed_commits} commits of {} checked.",
+        commits.len()
+    )?;
+    tx.set_description(&format!("fix {num_fixed_commits} commits"));
+    tx.finish(ui)?;
+    Ok(())
+}
+
+/// Runs the tools matching `path` on the file, each on the output of the
+/// previous one, and returns the id of the resulting content.
+fn fix_file(
+    ui: &mut Ui,
+    workspace_root: &Path,
+    tools: &[FixTool],
+    store: &Store,
+    path: &RepoPath,
+    display_path: &str,
+    id: &FileId,
+) -> Result<FileId, CommandError> {
+    let mut content = vec![];
+    store.read_file(path, id)?.read_to_end(&mut content)?;
+    let mut new_content = content.clone();
+    for tool in tools.iter().filter(|tool| tool.matcher.matches(path)) {
+        match run_fix_tool(workspace_root, &tool.command, path, &new_content) {
+            Ok(output) => new_content = output,
+            Err(err) => writeln!(
+                ui.warning(),
+                "warning: Fix tool {:?} failed on {display_path}: {err}",
+                tool.name
+            )?,
+        }
+    }
+    if new_content == content {
+        Ok(id.clone())
+    } else {
+        Ok(store.write_file(path, &mut new_content.as_slice())?)
+    }
+}
+
+fn run_fix_tool(
+    workspace_root: &Path,
+    tool_command: &CommandNameAndArgs,
+    path: &RepoPath,
+    content: &[u8],
+) -> io::Result<Vec<u8>> {
+    let path_string = path.to_internal_file_string();
+    let (name, args) = tool_command.split_name_and_args();
+    let mut cmd = std::process::Command::new(name.as_ref());
+    if let CommandNameAndArgs::Structured { env, .. } = tool_command {
+        cmd.envs(env);
+    }
+    let args = args.iter().map(|arg| match arg.as_str() {
+        "$path" => path_string.as_str(),
+        _ => arg.as_str(),
+    });
+    let mut child = cmd
+        .args(args)
+        .current_dir(workspace_root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    // Write the input from another thread so a tool that writes its output
+    // before reading all of its input can't block on a full pipe.
+    let output = std::thread::scope(|scope| {
+        scope.spawn(move || {
+            // A tool may exit without reading all of its input
+            stdin.write_all(content).ok();
+        });
+        child.wait_with_output()
+    })?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            output.status.to_string(),
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[instrument(skip_all)]
+fn cmd_run(ui: &mut Ui, command: &CommandHelper, args: &RunArgs) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let commits = resolve_multiple_nonempty_revsets(&args.revisions, &workspace_command, ui)?;
+    if args.commit_changes {
+        for commit in &commits {
+            workspace_command.check_rewritable(commit)?;
+        }
+    }
+    let num_jobs = args
+        .jobs
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(commits.len());
+
+    // The temporary working copies aren't added to the view as workspaces, so
+    // nothing is left behind if the command fails, and `jj run` is recorded as
+    // at most one operation.
+    let repo = workspace_command.repo().clone();
+    let mut run_workspaces: Vec<RunWorkspace> = (0..num_jobs)
+        .map(|i| RunWorkspace::init(&repo, i))
+        .try_collect()?;
+
+    let start_tracking_matcher = workspace_command.auto_tracking_matcher()?;
+    let context = RunContext {
+        shell_command: &args.shell_command,
+        operation_id: repo.op_id().clone(),
+        checkout_options: workspace_command.checkout_options()?,
+        base_ignores: workspace_command.base_ignores(),
+        start_tracking_matcher: start_tracking_matcher.as_ref(),
+        max_new_file_size: command.settings().max_new_file_size()?,
+        conflict_marker_style: command.settings().conflict_marker_style()?,
+    };
+    let job_queue = Mutex::new(commits.iter().enumerate());
+    let mut num_failed = 0;
+    let mut new_tree_ids = HashMap::new();
+    let mut report_result =
+        |ui: &mut Ui, commit: &Commit, result: Result<RunResult, String>| -> io::Result<()> {
+            let commit_summary = workspace_command.format_commit_summary(commit);
+            let RunResult {
+                output,
+                new_tree_id,
+            } = match result {
+                Ok(result) => result,
+                Err(message) => {
+                    num_failed += 1;
+                    return writeln!(ui.error(), "{commit_summary}: Error: {message}");
+                }
+            };
+            ui.stdout_formatter().write_all(&output.stdout)?;
+            ui.stderr_formatter().write_all(&output.stderr)?;
+            writeln!(ui, "{commit_summary}: {}", output.status)?;
+            if !output.status.success() {
+                num_failed += 1;
+            }
+            if new_tree_id != *commit.tree_id() {
+                new_tree_ids.insert(commit.id().clone(), new_tree_id);
+            }
+            Ok(())
+        };
+    std::thread::scope(|scope| -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        for (i, run_workspace) in run_workspaces.iter_mut().enumerate() {
+            let repo = &repo;
+            let context = &context;
+            let job_queue = &job_queue;
+            let sender = sender.clone();
+            scope.spawn(move || loop {
+                let Some((index, commit)) = job_queue.lock().unwrap().next() else {
+                    break;
+                };
+                let result = run_on_commit(&mut run_workspace.working_copy, commit, context);
+                let failed = result.is_err();
+                if sender.send((index, result)).is_err() {
+                    break;
+                }
+                if failed {
+                    // The working copy may have been left in an unknown state, so
+                    // continue in a new one. If that fails, the other threads run
+                    // the remaining jobs.
+                    match RunWorkspace::init(repo, i) {
+                        Ok(new_run_workspace) => *run_workspace = new_run_workspace,
+                        Err(_) => break,
+                    }
+                }
+            });
+        }
+        drop(sender);
+        // Print the results in the order of the revisions, each as soon as it and
+        // the ones before it are done.
+        let mut results = commits.iter().map(|_| None).collect_vec();
+        let mut num_reported = 0;
+        for (index, result) in receiver {
+            results[index] = Some(result);
+            while let Some(result) = results.get_mut(num_reported).and_then(Option::take) {
+                report_result(ui, &commits[num_reported], result)?;
+                num_reported += 1;
+            }
+        }
+        // Revisions whose thread panicked, or that no thread could run
+        for commit in commits.iter().skip(num_reported) {
+            let message = "The command wasn't run on this revision".to_string();
+            report_result(ui, commit, Err(message))?;
+        }
+        Ok(())
+    })?;
+    // The temporary working copies are deleted here, even if the command
+    // couldn't be run on some revisions
+    drop(run_workspaces);
+
+    if args.commit_changes {
+        let mut tx = workspace_command
+            .start_transaction(&format!("run command on {} commits", commits.len()));
+        // Rewrite the parents before the children, so the children keep the
+        // trees the command left instead of being rebased.
+        let commit_ids = commits
+            .iter()
+            .map(|commit| commit.id().clone())
+            .collect_vec();
+        let mut rewritten_ids: HashMap<CommitId, CommitId> = HashMap::new();
+        for commit_id in tx.repo().index().topo_order(&mut commit_ids.iter()) {
+            let commit = tx.repo().store().get_commit(&commit_id)?;
+            let new_parent_ids = commit
+                .parent_ids()
+                .iter()
+                .map(|parent_id| rewritten_ids.get(parent_id).unwrap_or(parent_id).clone())
+                .collect_vec();
+            let new_tree_id = new_tree_ids.get(&commit_id);
+            if new_tree_id.is_none() && new_parent_ids == commit.parent_ids() {
+                continue;
+            }
+            let new_commit = tx
+                .mut_repo()
+                .rewrite_commit(command.settings(), &commit)
+                .set_parents(new_parent_ids)
+                .set_tree_id(new_tree_id.unwrap_or(commit.tree_id()).clone())
+                .write()?;
+            rewritten_ids.insert(commit_id, new_commit.id().clone());
+        }
+        tx.finish(ui)?;
+    } else if !new_tree_ids.is_empty() {
+        writeln!(
+            ui.hint(),
+            "Hint: The command changed files in {} revisions. Use --commit-changes to record the \
+             changes.",
+            new_tree_ids.len()
+        )?;
+    }
+    if num_failed > 0 {
+        return Err(user_error(format!(
+            "The command failed on {num_failed} of {} revisions",
+            commits.len()
+        )));
+    }
+    Ok(())
+}
+
+/// A temporary working copy in which `jj run` checks out revisions.
+struct RunWorkspace {
+    working_copy: WorkingCopy,
+    /// Deletes the working copy when dropped.
+    _temp_dir: tempfile::TempDir,
+}
+
+impl RunWorkspace {
+    fn init(repo: &ReadonlyRepo, index: usize) -> Result<Self, CommandError> {
+        let temp_dir = tempfile::Builder::new().prefix("jj-run-").tempdir()?;
+        let working_copy_path = temp_dir.path().join("working_copy");
+        let state_path = temp_dir.path().join("state");
+        std::fs::create_dir(&working_copy_path)?;
+        std::fs::create_dir(&state_path)?;
+        let working_copy = WorkingCopy::init(
+            repo.store().clone(),
+            working_copy_path,
+            state_path,
+            repo.op_id().clone(),
+            WorkspaceId::new(format!("run-{index}")),
+        )
+        .map_err(|err| {
+            CommandError::InternalError(format!("Failed to create working copy: {err}"))
+        })?;
+        Ok(RunWorkspace {
+            working_copy,
+            _temp_dir: temp_dir,
+        })
+    }
+}
+
+/// What `jj run` needs to check out revisions and run the command on them.
+struct RunContext<'a> {
+    shell_command: &'a str,
+    operation_id: OperationId,
+    checkout_options: CheckoutOptions,
+    base_ignores: Arc<GitIgnoreFile>,
+    start_tracking_matcher: &'a dyn Matcher,
+    max_new_file_size: u64,
+    conflict_marker_style: ConflictMarkerStyle,
+}
+
+struct RunResult {
+    output: std::process::Output,
+    /// The tree of the workspace after running the command.
+    new_tree_id: MergedTreeId,
+}
+
+/// Returns a command that runs `command` with the platform's shell.
+fn shell_command(command: &str) -> std::process::Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = std::process::Command::new("cmd");
+        shell.arg("/c");
+        shell
+    } else {
+        let mut shell = std::process::Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+/// Checks out `commit` and runs the command on it. Errors are returned as
+/// messages, so they can be reported next to the revision.
+fn run_on_commit(
+    working_copy: &mut WorkingCopy,
+    commit: &Commit,
+    context: &RunContext,
+) -> Result<RunResult, String> {
+    let tree = commit.tree().map_err(|err| err.to_string())?;
+    let workspace_root = working_copy.working_copy_path().to_path_buf();
+    let mut locked_wc = working_copy
+        .start_mutation()
+        .map_err(|err| format!("Failed to lock the working copy: {err}"))?;
+    locked_wc
+        .check_out(&tree, &context.checkout_options)
+        .map_err(|err| format!("Failed to check out the revision: {err}"))?;
+    let output = shell_command(context.shell_command)
+        .current_dir(&workspace_root)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| format!("Failed to run the command: {err}"))?;
+    // Snapshot even if the changes aren't recorded, so that new files are
+    // removed when the next revision is checked out.
+    let (new_tree_id, _stats) = locked_wc
+        .snapshot(SnapshotOptions {
+            base_ignores: context.base_ignores.clone(),
+            fsmonitor_kind: None,
+            progress: None,
+            start_tracking_matcher: context.start_tracking_matcher,
+            max_new_file_size: context.max_new_file_size,
+            conflict_marker_style: context.conflict_marker_style,
+        })
+        .map_err(|err| format!("Failed to snapshot the changes: {err}"))?;
+    locked_wc
+        .finish(context.operation_id.clone())
+        .map_err(|err| format!("Failed to update the working copy state: {err}"))?;
+    Ok(RunResult {
+        output,
+        new_tree_id,
+    })
 }
 
 #[instrument(skip_all)]
@@ -3630,10 +5086,77 @@ fn cmd_util(
             let buf = include_bytes!("../config-schema.json");
             ui.stdout_formatter().write_all(buf)?;
         }
+        UtilCommands::UpgradeRepo(_upgrade_repo_matches) => {
+            cmd_util_upgrade_repo(ui, command)?;
+        }
     }
     Ok(())
 }
 
+fn cmd_util_upgrade_repo(ui: &mut Ui, command: &CommandHelper) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let config_path = get_new_config_file_path(&ConfigSource::Repo, command)?;
+
+    let mut tx = workspace_command.start_transaction("upgrade repo to tree-level conflicts");
+    let base_repo = tx.base_repo().clone();
+    let store = base_repo.store();
+    // Only commits with conflicts in the legacy format need to be rewritten,
+    // along with their descendants. Other commits keep their ids.
+    let legacy_conflict_ids: Vec<CommitId> = RevsetExpression::visible_heads()
+        .ancestors()
+        .intersection(&RevsetExpression::filter(
+            RevsetFilterPredicate::HasConflict,
+        ))
+        .resolve(base_repo.as_ref())?
+        .evaluate(base_repo.as_ref())?
+        .iter()
+        .commits(store)
+        .filter_ok(|commit| matches!(commit.tree_id(), MergedTreeId::Legacy(_)))
+        .map_ok(|commit| commit.id().clone())
+        .try_collect()?;
+    let commits_to_rewrite: Vec<Commit> = RevsetExpression::commits(legacy_conflict_ids)
+        .descendants()
+        .resolve(base_repo.as_ref())?
+        .evaluate(base_repo.as_ref())?
+        .iter()
+        .commits(store)
+        .try_collect()?;
+    let mut rewritten_ids: HashMap<CommitId, CommitId> = HashMap::new();
+    // Rewrite parents before their children so the children can be rewritten
+    // onto them.
+    for commit in commits_to_rewrite.into_iter().rev() {
+        let new_parent_ids = commit
+            .parent_ids()
+            .iter()
+            .map(|id| rewritten_ids.get(id).unwrap_or(id).clone())
+            .collect_vec();
+        let new_tree_id = match commit.tree_id() {
+            MergedTreeId::Legacy(tree_id) => {
+                let legacy_tree = store.get_tree(&RepoPath::root(), tree_id)?;
+                MergedTree::from_legacy_tree(legacy_tree)?.id()
+            }
+            MergedTreeId::Merge(_) => commit.tree_id().clone(),
+        };
+        let new_commit = tx
+            .mut_repo()
+            .rewrite_commit(command.settings(), &commit)
+            .set_parents(new_parent_ids)
+            .set_tree_id(new_tree_id)
+            .write()?;
+        rewritten_ids.insert(commit.id().clone(), new_commit.id().clone());
+    }
+    tx.finish(ui)?;
+
+    // Only switch the format once the existing conflicts have been converted
+    write_config_value_to_file("format.tree-level-conflicts", "true", &config_path)?;
+    writeln!(
+        ui,
+        "Enabled tree-level conflicts in the repo config and rewrote {} commits",
+        rewritten_ids.len()
+    )?;
+    Ok(())
+}
+
 #[instrument(skip_all)]
 fn cmd_workspace(
     ui: &mut Ui,
@@ -3801,6 +5324,7 @@ fn cmd_workspace_update_stale(
     let mut workspace_command = command.workspace_helper_no_snapshot(ui)?;
 
     let repo = workspace_command.repo().clone();
+    let checkout_options = workspace_command.checkout_options()?;
     let (mut locked_wc, desired_wc_commit) =
         workspace_command.unchecked_start_working_copy_mutation()?;
     match check_stale_working_copy(&locked_wc, &desired_wc_commit, &repo) {
@@ -3814,13 +5338,15 @@ fn cmd_workspace_update_stale(
                 return Err(user_error("Concurrent working copy operation. Try again."));
             }
             let desired_tree = desired_wc_commit.tree()?;
-            let stats = locked_wc.check_out(&desired_tree).map_err(|err| {
-                CommandError::InternalError(format!(
-                    "Failed to check out commit {}: {}",
-                    desired_wc_commit.id().hex(),
-                    err
-                ))
-            })?;
+            let stats = locked_wc
+                .check_out(&desired_tree, &checkout_options)
+                .map_err(|err| {
+                    CommandError::InternalError(format!(
+                        "Failed to check out commit {}: {}",
+                        desired_wc_commit.id().hex(),
+                        err
+                    ))
+                })?;
             locked_wc.finish(repo.op_id().clone())?;
             ui.write("Working copy now at: ")?;
             ui.stdout_formatter().with_label("working_copy", |fmt| {
@@ -3838,6 +5364,8 @@ fn cmd_sparse(ui: &mut Ui, command: &CommandHelper, args: &SparseArgs) -> Result
     match args {
         SparseArgs::List(sub_args) => cmd_sparse_list(ui, command, sub_args),
         SparseArgs::Set(sub_args) => cmd_sparse_set(ui, command, sub_args),
+        SparseArgs::Edit(sub_args) => cmd_sparse_edit(ui, command, sub_args),
+        SparseArgs::Reset(sub_args) => cmd_sparse_reset(ui, command, sub_args),
     }
 }
 
@@ -3848,9 +5376,11 @@ fn cmd_sparse_list(
     _args: &SparseListArgs,
 ) -> Result<(), CommandError> {
     let workspace_command = command.workspace_helper(ui)?;
-    for path in workspace_command.working_copy().sparse_patterns()? {
-        let ui_path = workspace_command.format_file_path(path);
-        writeln!(ui, "{ui_path}")?;
+    for pattern in workspace_command.working_copy().sparse_patterns()? {
+        let ui_pattern = format_sparse_pattern(pattern, |path| {
+            Ok::<_, CommandError>(workspace_command.format_file_path(path))
+        })?;
+        writeln!(ui, "{ui_pattern}")?;
     }
     Ok(())
 }
@@ -3862,16 +5392,11 @@ fn cmd_sparse_set(
     args: &SparseSetArgs,
 ) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
-    let paths_to_add: Vec<_> = args
-        .add
-        .iter()
-        .map(|v| workspace_command.parse_file_path(v))
-        .try_collect()?;
-    let paths_to_remove: Vec<_> = args
-        .remove
-        .iter()
-        .map(|v| workspace_command.parse_file_path(v))
-        .try_collect()?;
+    let parse_pattern = |input: &str| {
+        parse_sparse_pattern(input, |path| Ok(workspace_command.parse_file_path(path)?))
+    };
+    let patterns_to_add: Vec<_> = args.add.iter().map(|v| parse_pattern(v)).try_collect()?;
+    let patterns_to_remove: Vec<_> = args.remove.iter().map(|v| parse_pattern(v)).try_collect()?;
     // Determine inputs of `edit` operation now, since `workspace_command` is
     // inaccessible while the working copy is locked.
     let edit_inputs = args.edit.then(|| {
@@ -3880,19 +5405,20 @@ fn cmd_sparse_set(
             workspace_command.workspace_root().clone(),
         )
     });
-    let (mut locked_wc, _wc_commit) = workspace_command.start_working_copy_mutation()?;
+    let checkout_options = workspace_command.checkout_options()?;
+    let (locked_wc, _wc_commit) = workspace_command.start_working_copy_mutation()?;
     let mut new_patterns = HashSet::new();
     if args.reset {
-        new_patterns.insert(RepoPath::root());
+        new_patterns.insert(SparsePattern::Prefix(RepoPath::root()));
     } else {
         if !args.clear {
             new_patterns.extend(locked_wc.sparse_patterns()?.iter().cloned());
-            for path in paths_to_remove {
-                new_patterns.remove(&path);
+            for pattern in patterns_to_remove {
+                new_patterns.remove(&pattern);
             }
         }
-        for path in paths_to_add {
-            new_patterns.insert(path);
+        for pattern in patterns_to_add {
+            new_patterns.insert(pattern);
         }
     }
     let mut new_patterns = new_patterns.into_iter().collect_vec();
@@ -3906,9 +5432,59 @@ fn cmd_sparse_set(
         )?;
         new_patterns.sort();
     }
-    let stats = locked_wc.set_sparse_patterns(new_patterns).map_err(|err| {
-        CommandError::InternalError(format!("Failed to update working copy paths: {err}"))
-    })?;
+    update_sparse_patterns(ui, locked_wc, new_patterns, &checkout_options)
+}
+
+#[instrument(skip_all)]
+fn cmd_sparse_edit(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &SparseEditArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let repo = workspace_command.repo().clone();
+    let workspace_root = workspace_command.workspace_root().clone();
+    let checkout_options = workspace_command.checkout_options()?;
+    let (locked_wc, _wc_commit) = workspace_command.start_working_copy_mutation()?;
+    let mut new_patterns = edit_sparse(
+        &workspace_root,
+        repo.repo_path(),
+        locked_wc.sparse_patterns()?,
+        command.settings(),
+    )?;
+    new_patterns.sort();
+    new_patterns.dedup();
+    update_sparse_patterns(ui, locked_wc, new_patterns, &checkout_options)
+}
+
+#[instrument(skip_all)]
+fn cmd_sparse_reset(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &SparseResetArgs,
+) -> Result<(), CommandError> {
+    let mut workspace_command = command.workspace_helper(ui)?;
+    let checkout_options = workspace_command.checkout_options()?;
+    let (locked_wc, _wc_commit) = workspace_command.start_working_copy_mutation()?;
+    update_sparse_patterns(
+        ui,
+        locked_wc,
+        vec![SparsePattern::Prefix(RepoPath::root())],
+        &checkout_options,
+    )
+}
+
+fn update_sparse_patterns(
+    ui: &mut Ui,
+    mut locked_wc: LockedWorkingCopy,
+    new_patterns: Vec<SparsePattern>,
+    options: &CheckoutOptions,
+) -> Result<(), CommandError> {
+    let stats = locked_wc
+        .set_sparse_patterns(new_patterns, options)
+        .map_err(|err| {
+            CommandError::InternalError(format!("Failed to update working copy paths: {err}"))
+        })?;
     let operation_id = locked_wc.old_operation_id().clone();
     locked_wc.finish(operation_id)?;
     print_checkout_stats(ui, stats)?;
@@ -3929,8 +5505,10 @@ pub fn run_command(ui: &mut Ui, command_helper: &CommandHelper) -> Result<(), Co
         Commands::Init(sub_args) => cmd_init(ui, command_helper, sub_args),
         Commands::Config(sub_args) => cmd_config(ui, command_helper, sub_args),
         Commands::Checkout(sub_args) => cmd_checkout(ui, command_helper, sub_args),
+        Commands::Track(sub_args) => cmd_track(ui, command_helper, sub_args),
         Commands::Untrack(sub_args) => cmd_untrack(ui, command_helper, sub_args),
         Commands::Files(sub_args) => cmd_files(ui, command_helper, sub_args),
+        Commands::Fix(sub_args) => cmd_fix(ui, command_helper, sub_args),
         Commands::Cat(sub_args) => cmd_cat(ui, command_helper, sub_args),
         Commands::Diff(sub_args) => cmd_diff(ui, command_helper, sub_args),
         Commands::Show(sub_args) => cmd_show(ui, command_helper, sub_args),
@@ -3938,10 +5516,13 @@ pub fn run_command(ui: &mut Ui, command_helper: &CommandHelper) -> Result<(), Co
         Commands::Log(sub_args) => cmd_log(ui, command_helper, sub_args),
         Commands::Interdiff(sub_args) => cmd_interdiff(ui, command_helper, sub_args),
         Commands::Obslog(sub_args) => cmd_obslog(ui, command_helper, sub_args),
+        Commands::Parallelize(sub_args) => cmd_parallelize(ui, command_helper, sub_args),
+        Commands::Histedit(sub_args) => cmd_histedit(ui, command_helper, sub_args),
         Commands::Describe(sub_args) => cmd_describe(ui, command_helper, sub_args),
         Commands::Commit(sub_args) => cmd_commit(ui, command_helper, sub_args),
         Commands::Duplicate(sub_args) => cmd_duplicate(ui, command_helper, sub_args),
         Commands::Abandon(sub_args) => cmd_abandon(ui, command_helper, sub_args),
+        Commands::Absorb(sub_args) => cmd_absorb(ui, command_helper, sub_args),
         Commands::Edit(sub_args) => cmd_edit(ui, command_helper, sub_args),
         Commands::Next(sub_args) => cmd_next(ui, command_helper, sub_args),
         Commands::Prev(sub_args) => cmd_prev(ui, command_helper, sub_args),
@@ -3957,6 +5538,7 @@ pub fn run_command(ui: &mut Ui, command_helper: &CommandHelper) -> Result<(), Co
         Commands::Rebase(sub_args) => cmd_rebase(ui, command_helper, sub_args),
         Commands::Backout(sub_args) => cmd_backout(ui, command_helper, sub_args),
         Commands::Resolve(sub_args) => cmd_resolve(ui, command_helper, sub_args),
+        Commands::Bisect(sub_args) => bisect::cmd_bisect(ui, command_helper, sub_args),
         Commands::Branch(sub_args) => branch::cmd_branch(ui, command_helper, sub_args),
         Commands::Undo(sub_args) => operation::cmd_op_undo(ui, command_helper, sub_args),
         Commands::Operation(sub_args) => operation::cmd_operation(ui, command_helper, sub_args),
@@ -13,15 +13,17 @@
 // limitations under the License.
 
 use std::cmp::max;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io;
 use std::rc::Rc;
 
 use itertools::Itertools as _;
 use jj_lib::backend::{ChangeId, CommitId, ObjectId as _};
 use jj_lib::commit::Commit;
+use jj_lib::git_backend::GitBackend;
 use jj_lib::hex_util::to_reverse_hex;
 use jj_lib::id_prefix::IdPrefixContext;
+use jj_lib::merged_tree::MergedTree;
 use jj_lib::op_store::{RefTarget, WorkspaceId};
 use jj_lib::repo::Repo;
 use jj_lib::{git, rewrite};
@@ -178,6 +180,7 @@ struct CommitKeywordCache {
     branches_index: OnceCell<Rc<RefNamesIndex>>,
     tags_index: OnceCell<Rc<RefNamesIndex>>,
     git_refs_index: OnceCell<Rc<RefNamesIndex>>,
+    shallow_commits: OnceCell<Rc<HashSet<CommitId>>>,
 }
 
 impl CommitKeywordCache {
@@ -195,6 +198,17 @@ impl CommitKeywordCache {
         self.git_refs_index
             .get_or_init(|| Rc::new(build_ref_names_index(repo.view().git_refs())))
     }
+
+    fn shallow_commits(&self, repo: &dyn Repo) -> &Rc<HashSet<CommitId>> {
+        self.shallow_commits.get_or_init(|| {
+            let shallow_commits = repo
+                .store()
+                .backend_impl()
+                .downcast_ref::<GitBackend>()
+                .and_then(|git_backend| git_backend.shallow_commits().ok());
+            Rc::new(shallow_commits.unwrap_or_default())
+        })
+    }
 }
 
 fn build_commit_keyword<'repo>(
@@ -304,16 +318,38 @@ fn build_commit_keyword_opt<'repo>(
         })),
         "empty" => language.wrap_boolean(wrap_fn(property, |commit| {
             let parent_tree = rewrite::merge_commit_trees(repo, &commit.parents()).unwrap();
-            *commit.tree_id() == parent_tree.id()
+            is_same_tree(&commit.tree().unwrap(), &parent_tree)
         })),
         "root" => language.wrap_boolean(wrap_fn(property, move |commit| {
             commit.id() == repo.store().root_commit_id()
         })),
+        "shallow" => {
+            let shallow_commits = cache.shallow_commits(repo).clone();
+            language.wrap_boolean(wrap_fn(property, move |commit| {
+                shallow_commits.contains(commit.id())
+            }))
+        }
         _ => return None,
     };
     Some(property)
 }
 
+/// Compares the trees regardless of whether their conflicts are in the legacy
+/// format. `jj util upgrade-repo` leaves commits without conflicts in the
+/// legacy format, so their merges can be compared to upgraded descendants.
+/// Only trees in different formats are converted, since that requires walking
+/// the legacy tree.
+fn is_same_tree(tree: &MergedTree, other: &MergedTree) -> bool {
+    match (tree, other) {
+        (MergedTree::Legacy(legacy_tree), MergedTree::Merge(_)) => {
+            MergedTree::from_legacy_tree(legacy_tree.clone())
+                .is_ok_and(|tree| tree.id() == other.id())
+        }
+        (MergedTree::Merge(_), MergedTree::Legacy(_)) => is_same_tree(other, tree),
+        _ => tree.id() == other.id(),
+    }
+}
+
 // TODO: return Vec<String>
 fn extract_working_copies(repo: &dyn Repo, commit: &Commit) -> String {
     let wc_commit_ids = repo.view().wc_commit_ids();
@@ -21,8 +21,10 @@ use std::sync::Arc;
 use itertools::Itertools;
 use jj_lib::backend::{ObjectId, TreeValue};
 use jj_lib::commit::Commit;
+use jj_lib::conflicts::ConflictMarkerStyle;
 use jj_lib::diff::{Diff, DiffHunk};
 use jj_lib::files::DiffLine;
+use jj_lib::gitattributes::TreeGitAttributes;
 use jj_lib::matchers::Matcher;
 use jj_lib::merge::Merge;
 use jj_lib::merged_tree::{MergedTree, TreeDiffIterator};
@@ -182,14 +184,29 @@ pub fn show_diff(
             }
             DiffFormat::Git => {
                 let tree_diff = from_tree.diff(to_tree, matcher);
-                show_git_diff(formatter, workspace_command, tree_diff)?;
+                let mut git_attributes = TreeGitAttributes::new(to_tree);
+                show_git_diff(formatter, workspace_command, tree_diff, &mut git_attributes)?;
             }
             DiffFormat::ColorWords => {
                 let tree_diff = from_tree.diff(to_tree, matcher);
-                show_color_words_diff(formatter, workspace_command, tree_diff)?;
+                let mut git_attributes = TreeGitAttributes::new(to_tree);
+                show_color_words_diff(
+                    formatter,
+                    workspace_command,
+                    tree_diff,
+                    &mut git_attributes,
+                )?;
             }
             DiffFormat::Tool(tool) => {
-                merge_tools::generate_diff(ui, formatter.raw(), from_tree, to_tree, matcher, tool)?;
+                merge_tools::generate_diff(
+                    ui,
+                    formatter.raw(),
+                    from_tree,
+                    to_tree,
+                    matcher,
+                    tool,
+                    &workspace_command.checkout_options()?,
+                )?;
             }
         }
     }
@@ -335,6 +352,7 @@ fn diff_content(
     repo: &Arc<ReadonlyRepo>,
     path: &RepoPath,
     value: &Merge<Option<TreeValue>>,
+    conflict_marker_style: ConflictMarkerStyle,
 ) -> Result<Vec<u8>, CommandError> {
     match value.as_resolved() {
         Some(None) => Ok(vec![]),
@@ -349,11 +367,18 @@ fn diff_content(
             Ok(target.into_bytes())
         }
         Some(Some(TreeValue::GitSubmodule(id))) => {
-            Ok(format!("Git submodule checked out at {}", id.hex()).into_bytes())
+            Ok(format!("Git submodule checked out at {}\n", id.hex()).into_bytes())
         }
         None => {
             let mut content = vec![];
-            conflicts::materialize(value, repo.store(), path, &mut content).unwrap();
+            conflicts::materialize(
+                value,
+                repo.store(),
+                path,
+                conflict_marker_style,
+                &mut content,
+            )
+            .unwrap();
             Ok(content)
         }
         Some(Some(TreeValue::Tree(_))) | Some(Some(TreeValue::Conflict(_))) => {
@@ -384,17 +409,32 @@ fn basic_diff_file_type(values: &Merge<Option<TreeValue>>) -> String {
     }
 }
 
+/// Returns whether the contents of the file at `path` should be shown as
+/// binary, as determined by the `.gitattributes` of the new tree and the
+/// contents themselves.
+fn is_binary_diff(
+    git_attributes: &mut TreeGitAttributes,
+    path: &RepoPath,
+    contents: &[&[u8]],
+) -> Result<bool, CommandError> {
+    Ok(git_attributes
+        .for_file(path)?
+        .is_binary_for_diff(&path.to_internal_file_string(), contents))
+}
+
 pub fn show_color_words_diff(
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
     tree_diff: TreeDiffIterator,
+    git_attributes: &mut TreeGitAttributes,
 ) -> Result<(), CommandError> {
     let repo = workspace_command.repo();
+    let conflict_marker_style = workspace_command.checkout_options()?.conflict_marker_style;
     formatter.push_label("diff")?;
     for (path, left_value, right_value) in tree_diff {
         let ui_path = workspace_command.format_file_path(&path);
         if left_value.is_absent() {
-            let right_content = diff_content(repo, &path, &right_value)?;
+            let right_content = diff_content(repo, &path, &right_value, conflict_marker_style)?;
             let description = basic_diff_file_type(&right_value);
             writeln!(
                 formatter.labeled("header"),
@@ -402,12 +442,14 @@ pub fn show_color_words_diff(
             )?;
             if right_content.is_empty() {
                 writeln!(formatter.labeled("empty"), "    (empty)")?;
+            } else if is_binary_diff(git_attributes, &path, &[&right_content])? {
+                writeln!(formatter.labeled("binary"), "    (binary)")?;
             } else {
                 show_color_words_diff_hunks(&[], &right_content, formatter)?;
             }
         } else if right_value.is_present() {
-            let left_content = diff_content(repo, &path, &left_value)?;
-            let right_content = diff_content(repo, &path, &right_value)?;
+            let left_content = diff_content(repo, &path, &left_value, conflict_marker_style)?;
+            let right_content = diff_content(repo, &path, &right_value, conflict_marker_style)?;
             let description = match (left_value.into_resolved(), right_value.into_resolved()) {
                 (
                     Ok(Some(TreeValue::File {
@@ -435,6 +477,9 @@ pub fn show_color_words_diff(
                 (Ok(Some(TreeValue::Symlink(_))), Ok(Some(TreeValue::Symlink(_)))) => {
                     "Symlink target changed at".to_string()
                 }
+                (Ok(Some(TreeValue::GitSubmodule(_))), Ok(Some(TreeValue::GitSubmodule(_)))) => {
+                    "Modified Git submodule".to_string()
+                }
                 (Ok(left_value), Ok(right_value)) => {
                     let left_type = basic_diff_file_type(&Merge::resolved(left_value));
                     let right_type = basic_diff_file_type(&Merge::resolved(right_value));
@@ -448,9 +493,15 @@ pub fn show_color_words_diff(
                 }
             };
             writeln!(formatter.labeled("header"), "{description} {ui_path}:")?;
-            show_color_words_diff_hunks(&left_content, &right_content, formatter)?;
+            if is_binary_diff(git_attributes, &path, &[&left_content, &right_content])? {
+                if left_content != right_content {
+                    writeln!(formatter.labeled("binary"), "    (binary)")?;
+                }
+            } else {
+                show_color_words_diff_hunks(&left_content, &right_content, formatter)?;
+            }
         } else {
-            let left_content = diff_content(repo, &path, &left_value)?;
+            let left_content = diff_content(repo, &path, &left_value, conflict_marker_style)?;
             let description = basic_diff_file_type(&left_value);
             writeln!(
                 formatter.labeled("header"),
@@ -458,6 +509,8 @@ pub fn show_color_words_diff(
             )?;
             if left_content.is_empty() {
                 writeln!(formatter.labeled("empty"), "    (empty)")?;
+            } else if is_binary_diff(git_attributes, &path, &[&left_content])? {
+                writeln!(formatter.labeled("binary"), "    (binary)")?;
             } else {
                 show_color_words_diff_hunks(&left_content, &[], formatter)?;
             }
@@ -477,6 +530,7 @@ fn git_diff_part(
     repo: &Arc<ReadonlyRepo>,
     path: &RepoPath,
     value: &Merge<Option<TreeValue>>,
+    conflict_marker_style: ConflictMarkerStyle,
 ) -> Result<GitDiffPart, CommandError> {
     let mode;
     let hash;
@@ -499,14 +553,22 @@ fn git_diff_part(
             content = target.into_bytes();
         }
         Some(Some(TreeValue::GitSubmodule(id))) => {
-            // TODO: What should we actually do here?
-            mode = "040000".to_string();
+            // Like `git diff --submodule=short`
+            mode = "160000".to_string();
             hash = id.hex();
+            content = format!("Subproject commit {hash}\n").into_bytes();
         }
         None => {
             mode = "100644".to_string();
             hash = "0000000000".to_string();
-            conflicts::materialize(value, repo.store(), path, &mut content).unwrap();
+            conflicts::materialize(
+                value,
+                repo.store(),
+                path,
+                conflict_marker_style,
+                &mut content,
+            )
+            .unwrap();
         }
         Some(Some(TreeValue::Tree(_))) | Some(Some(TreeValue::Conflict(_))) | Some(None) => {
             panic!("Unexpected {value:?} in diff at path {path:?}");
@@ -660,24 +722,41 @@ pub fn show_git_diff(
     formatter: &mut dyn Formatter,
     workspace_command: &WorkspaceCommandHelper,
     tree_diff: TreeDiffIterator,
+    git_attributes: &mut TreeGitAttributes,
 ) -> Result<(), CommandError> {
     let repo = workspace_command.repo();
+    let conflict_marker_style = workspace_command.checkout_options()?.conflict_marker_style;
     formatter.push_label("diff")?;
     for (path, left_value, right_value) in tree_diff {
         let path_string = path.to_internal_file_string();
         if left_value.is_absent() {
-            let right_part = git_diff_part(repo, &path, &right_value)?;
+            let right_part = git_diff_part(repo, &path, &right_value, conflict_marker_style)?;
+            let binary = is_binary_diff(git_attributes, &path, &[&right_part.content])?;
             formatter.with_label("file_header", |formatter| {
                 writeln!(formatter, "diff --git a/{path_string} b/{path_string}")?;
                 writeln!(formatter, "new file mode {}", &right_part.mode)?;
                 writeln!(formatter, "index 0000000000..{}", &right_part.hash)?;
-                writeln!(formatter, "--- /dev/null")?;
-                writeln!(formatter, "+++ b/{path_string}")
+                if binary {
+                    writeln!(
+                        formatter,
+                        "Binary files /dev/null and b/{path_string} differ"
+                    )
+                } else {
+                    writeln!(formatter, "--- /dev/null")?;
+                    writeln!(formatter, "+++ b/{path_string}")
+                }
             })?;
-            show_unified_diff_hunks(formatter, &[], &right_part.content)?;
+            if !binary {
+                show_unified_diff_hunks(formatter, &[], &right_part.content)?;
+            }
         } else if right_value.is_present() {
-            let left_part = git_diff_part(repo, &path, &left_value)?;
-            let right_part = git_diff_part(repo, &path, &right_value)?;
+            let left_part = git_diff_part(repo, &path, &left_value, conflict_marker_style)?;
+            let right_part = git_diff_part(repo, &path, &right_value, conflict_marker_style)?;
+            let binary = is_binary_diff(
+                git_attributes,
+                &path,
+                &[&left_part.content, &right_part.content],
+            )?;
             formatter.with_label("file_header", |formatter| {
                 writeln!(formatter, "diff --git a/{path_string} b/{path_string}")?;
                 if left_part.mode != right_part.mode {
@@ -694,22 +773,41 @@ pub fn show_git_diff(
                     )?;
                 }
                 if left_part.content != right_part.content {
-                    writeln!(formatter, "--- a/{path_string}")?;
-                    writeln!(formatter, "+++ b/{path_string}")?;
+                    if binary {
+                        writeln!(
+                            formatter,
+                            "Binary files a/{path_string} and b/{path_string} differ"
+                        )?;
+                    } else {
+                        writeln!(formatter, "--- a/{path_string}")?;
+                        writeln!(formatter, "+++ b/{path_string}")?;
+                    }
                 }
                 Ok(())
             })?;
-            show_unified_diff_hunks(formatter, &left_part.content, &right_part.content)?;
+            if !binary {
+                show_unified_diff_hunks(formatter, &left_part.content, &right_part.content)?;
+            }
         } else {
-            let left_part = git_diff_part(repo, &path, &left_value)?;
+            let left_part = git_diff_part(repo, &path, &left_value, conflict_marker_style)?;
+            let binary = is_binary_diff(git_attributes, &path, &[&left_part.content])?;
             formatter.with_label("file_header", |formatter| {
                 writeln!(formatter, "diff --git a/{path_string} b/{path_string}")?;
                 writeln!(formatter, "deleted file mode {}", &left_part.mode)?;
                 writeln!(formatter, "index {}..0000000000", &left_part.hash)?;
-                writeln!(formatter, "--- a/{path_string}")?;
-                writeln!(formatter, "+++ /dev/null")
+                if binary {
+                    writeln!(
+                        formatter,
+                        "Binary files a/{path_string} and /dev/null differ"
+                    )
+                } else {
+                    writeln!(formatter, "--- a/{path_string}")?;
+                    writeln!(formatter, "+++ /dev/null")
+                }
             })?;
-            show_unified_diff_hunks(formatter, &left_part.content, &[])?;
+            if !binary {
+                show_unified_diff_hunks(formatter, &left_part.content, &[])?;
+            }
         }
     }
     formatter.pop_label()?;
@@ -780,13 +878,24 @@ pub fn show_diff_stat(
     workspace_command: &WorkspaceCommandHelper,
     tree_diff: TreeDiffIterator,
 ) -> Result<(), CommandError> {
+    let conflict_marker_style = workspace_command.checkout_options()?.conflict_marker_style;
     let mut stats: Vec<DiffStat> = vec![];
     let mut max_path_width = 0;
     let mut max_diffs = 0;
     for (repo_path, left, right) in tree_diff {
         let path = workspace_command.format_file_path(&repo_path);
-        let left_content = diff_content(workspace_command.repo(), &repo_path, &left)?;
-        let right_content = diff_content(workspace_command.repo(), &repo_path, &right)?;
+        let left_content = diff_content(
+            workspace_command.repo(),
+            &repo_path,
+            &left,
+            conflict_marker_style,
+        )?;
+        let right_content = diff_content(
+            workspace_command.repo(),
+            &repo_path,
+            &right,
+            conflict_marker_style,
+        )?;
         max_path_width = max(max_path_width, path.width());
         let stat = get_diff_stat(path, &left_content, &right_content);
         max_diffs = max(max_diffs, stat.added + stat.removed);
@@ -0,0 +1,184 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use jj_lib::git;
+use jj_lib::git_hooks::{self, GitHookError};
+use jj_lib::settings::UserSettings;
+
+use crate::cli_util::{user_error, CommandError, WorkspaceCommandHelper};
+use crate::progress::Progress;
+use crate::ui::Ui;
+
+/// Shows the output of a Git hook, and turns a hook failure into an error
+/// that aborts the command.
+pub fn handle_git_hook_result<T>(
+    ui: &Ui,
+    result: Result<(T, Option<Vec<u8>>), GitHookError>,
+) -> Result<T, CommandError> {
+    match result {
+        Ok((value, output)) => {
+            if let Some(output) = output {
+                ui.stderr_formatter().write_all(&output)?;
+            }
+            Ok(value)
+        }
+        Err(err) => {
+            if let GitHookError::Failed { output, .. } = &err {
+                ui.stderr_formatter().write_all(output)?;
+            }
+            Err(user_error(err.to_string()))
+        }
+    }
+}
+
+/// Runs the `commit-msg` hook on `description` if enabled by `git.run-hooks`,
+/// and returns the description as modified by the hook. Empty descriptions are
+/// left alone.
+pub fn run_commit_msg_hook(
+    ui: &Ui,
+    settings: &UserSettings,
+    workspace_command: &WorkspaceCommandHelper,
+    description: String,
+) -> Result<String, CommandError> {
+    if description.is_empty() || !settings.git_settings().should_run_hook("commit-msg") {
+        return Ok(description);
+    }
+    let Some(git_backend) = workspace_command.git_backend() else {
+        return Ok(description);
+    };
+    let git_repo = git_backend.git_repo_clone();
+    handle_git_hook_result(
+        ui,
+        git_hooks::run_commit_msg_hook(&git_repo, workspace_command.workspace_root(), &description),
+    )
+}
+
+pub fn with_remote_callbacks<T>(ui: &mut Ui, f: impl FnOnce(git::RemoteCallbacks<'_>) -> T) -> T {
+    let mut ui = Mutex::new(ui);
+    let mut callback = None;
+    if let Some(mut output) = ui.get_mut().unwrap().progress_output() {
+        let mut progress = Progress::new(Instant::now());
+        callback = Some(move |x: &git::Progress| {
+            _ = progress.update(Instant::now(), x, &mut output);
+        });
+    }
+    let mut callbacks = git::RemoteCallbacks::default();
+    callbacks.progress = callback
+        .as_mut()
+        .map(|x| x as &mut dyn FnMut(&git::Progress));
+    let mut get_ssh_keys = get_ssh_keys; // Coerce to unit fn type
+    callbacks.get_ssh_keys = Some(&mut get_ssh_keys);
+    let mut get_pw = |url: &str, _username: &str| {
+        pinentry_get_pw(url).or_else(|| terminal_get_pw(*ui.lock().unwrap(), url))
+    };
+    callbacks.get_password = Some(&mut get_pw);
+    let mut get_user_pw = |url: &str| {
+        let ui = &mut *ui.lock().unwrap();
+        Some((terminal_get_username(ui, url)?, terminal_get_pw(ui, url)?))
+    };
+    callbacks.get_username_password = Some(&mut get_user_pw);
+    f(callbacks)
+}
+
+fn terminal_get_username(ui: &mut Ui, url: &str) -> Option<String> {
+    ui.prompt(&format!("Username for {url}")).ok()
+}
+
+fn terminal_get_pw(ui: &mut Ui, url: &str) -> Option<String> {
+    ui.prompt_password(&format!("Passphrase for {url}: ")).ok()
+}
+
+fn pinentry_get_pw(url: &str) -> Option<String> {
+    let mut pinentry = Command::new("pinentry")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    #[rustfmt::skip]
+    pinentry
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            format!(
+                "SETTITLE jj passphrase\n\
+                 SETDESC Enter passphrase for {url}\n\
+                 SETPROMPT Passphrase:\n\
+                 GETPIN\n"
+            )
+            .as_bytes(),
+        )
+        .ok()?;
+    let mut out = String::new();
+    pinentry
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut out)
+        .ok()?;
+    _ = pinentry.wait();
+    for line in out.split('\n') {
+        if !line.starts_with("D ") {
+            continue;
+        }
+        let (_, encoded) = line.split_at(2);
+        return decode_assuan_data(encoded);
+    }
+    None
+}
+
+// https://www.gnupg.org/documentation/manuals/assuan/Server-responses.html#Server-responses
+fn decode_assuan_data(encoded: &str) -> Option<String> {
+    let encoded = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] != b'%' {
+            decoded.push(encoded[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        let byte =
+            u8::from_str_radix(std::str::from_utf8(encoded.get(i..i + 2)?).ok()?, 16).ok()?;
+        decoded.push(byte);
+        i += 2;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[tracing::instrument]
+fn get_ssh_keys(_username: &str) -> Vec<PathBuf> {
+    let mut paths = vec![];
+    if let Ok(home_dir) = std::env::var("HOME") {
+        let ssh_dir = Path::new(&home_dir).join(".ssh");
+        for filename in ["id_ed25519_sk", "id_ed25519", "id_rsa"] {
+            let key_path = ssh_dir.join(filename);
+            if key_path.is_file() {
+                tracing::info!(path = ?key_path, "found ssh key");
+                paths.push(key_path);
+            }
+        }
+    }
+    if paths.is_empty() {
+        tracing::info!("no ssh key found");
+    }
+    paths
+}
@@ -21,6 +21,7 @@ pub mod commit_templater;
 pub mod config;
 pub mod diff_util;
 pub mod formatter;
+pub mod git_util;
 pub mod graphlog;
 pub mod merge_tools;
 pub mod operation_templater;
@@ -1,12 +1,14 @@
 use std::borrow::Cow;
+use std::iter::zip;
 use std::path::Path;
 use std::sync::Arc;
 
 use itertools::Itertools;
 use jj_lib::backend::{BackendError, FileId, MergedTreeId, ObjectId, TreeValue};
 use jj_lib::diff::{find_line_ranges, Diff, DiffHunk};
+use jj_lib::files::{self, ContentHunk, FileMergeHunkLevel, MergeResult};
 use jj_lib::matchers::EverythingMatcher;
-use jj_lib::merge::Merge;
+use jj_lib::merge::{Merge, MergeBuilder};
 use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
 use jj_lib::repo_path::RepoPath;
 use jj_lib::store::Store;
@@ -35,6 +37,11 @@ pub enum BuiltinToolError {
     Unimplemented { item: &'static str, id: String },
     #[error("Backend error: {0:?}")]
     BackendError(#[from] jj_lib::backend::BackendError),
+    #[error(
+        "The conflict at {0:?} is in a binary file, which the builtin merge tool can't resolve \
+         interactively. Pick a side with `:ours`, `:theirs` or `:side-N` instead."
+    )]
+    BinaryConflict(RepoPath),
 }
 
 #[derive(Clone, Debug)]
@@ -424,6 +431,151 @@ pub fn edit_diff_builtin(
     Ok(tree_id)
 }
 
+/// Splits the conflict `content` into hunks and creates a file for
+/// `scm-record` where each side of each conflicting hunk is a section of added
+/// lines, in order. Resolved hunks are shown as unchanged context.
+pub fn make_merge_file(
+    path: &RepoPath,
+    content: &Merge<ContentHunk>,
+    hunk_level: FileMergeHunkLevel,
+) -> Result<(Vec<Merge<ContentHunk>>, scm_record::File<'static>), BuiltinToolError> {
+    if content
+        .iter()
+        .any(|side| side.0.contains(&0) || std::str::from_utf8(&side.0).is_err())
+    {
+        return Err(BuiltinToolError::BinaryConflict(path.clone()));
+    }
+    let removes = content
+        .removes()
+        .iter()
+        .map(|hunk| hunk.0.as_slice())
+        .collect_vec();
+    let adds = content
+        .adds()
+        .iter()
+        .map(|hunk| hunk.0.as_slice())
+        .collect_vec();
+    let hunks = match files::merge_with_hunk_level(&removes, &adds, hunk_level) {
+        MergeResult::Resolved(content) => vec![Merge::resolved(content)],
+        MergeResult::Conflict(hunks) => hunks,
+    };
+    let to_str = |hunk: &ContentHunk| std::str::from_utf8(&hunk.0).unwrap().to_owned();
+    let mut sections = vec![];
+    for hunk in &hunks {
+        if let Some(resolved) = hunk.as_resolved() {
+            sections.push(scm_record::Section::Unchanged {
+                lines: to_str(resolved)
+                    .split_inclusive('\n')
+                    .map(|line| Cow::Owned(line.to_owned()))
+                    .collect(),
+            });
+        } else {
+            for side in hunk.adds() {
+                sections.push(scm_record::Section::Changed {
+                    lines: make_section_changed_lines(&to_str(side), scm_record::ChangeType::Added),
+                });
+            }
+        }
+    }
+    let file = scm_record::File {
+        old_path: None,
+        path: Cow::Owned(path.to_fs_path(Path::new(""))),
+        file_mode: None,
+        sections,
+    };
+    Ok((hunks, file))
+}
+
+/// Applies the lines selected in `file`, as created by `make_merge_file()`, to
+/// the conflicting `hunks`. Each hunk is resolved to the selected lines of all
+/// its sides, in order. Hunks without any selected lines remain conflicted.
+pub fn apply_merge_builtin(
+    hunks: &[Merge<ContentHunk>],
+    file: &scm_record::File,
+) -> Vec<Merge<ContentHunk>> {
+    let mut sections = file.sections.iter();
+    let mut new_hunks = vec![];
+    for hunk in hunks {
+        if hunk.is_resolved() {
+            sections.next();
+            new_hunks.push(hunk.clone());
+            continue;
+        }
+        let mut selected = None;
+        for _ in hunk.adds() {
+            let Some(scm_record::Section::Changed { lines }) = sections.next() else {
+                panic!("sections don't match the conflict hunks");
+            };
+            for line in lines.iter().filter(|line| line.is_checked) {
+                selected
+                    .get_or_insert_with(Vec::new)
+                    .extend_from_slice(line.line.as_bytes());
+            }
+        }
+        match selected {
+            Some(content) => new_hunks.push(Merge::resolved(ContentHunk(content))),
+            None => new_hunks.push(hunk.clone()),
+        }
+    }
+    new_hunks
+}
+
+/// Resolves the conflict at `repo_path` hunk by hunk by selecting lines from
+/// its sides in `scm-record`. Works for conflicts with any number of sides.
+pub fn edit_merge_builtin(
+    tree: &MergedTree,
+    repo_path: &RepoPath,
+    conflict: &Merge<Option<TreeValue>>,
+    file_merge: &Merge<Option<FileId>>,
+    content: &Merge<ContentHunk>,
+) -> Result<MergedTreeId, BuiltinToolError> {
+    let (hunks, file) = make_merge_file(repo_path, content, tree.store().merge_hunk_level())?;
+    let recorder = scm_record::Recorder::new(
+        scm_record::RecordState {
+            is_read_only: false,
+            files: vec![file],
+        },
+        scm_record::EventSource::Crossterm,
+    );
+    let result = recorder.run().map_err(BuiltinToolError::Record)?;
+    let new_hunks = apply_merge_builtin(&hunks, &result.files[0]);
+
+    let store = tree.store();
+    let mut contents = file_merge.map(|_| vec![]);
+    for hunk in &new_hunks {
+        if let Some(resolved) = hunk.as_resolved() {
+            for content in contents.iter_mut() {
+                content.extend_from_slice(&resolved.0);
+            }
+        } else {
+            for (content, side) in zip(contents.iter_mut(), hunk.iter()) {
+                content.extend_from_slice(&side.0);
+            }
+        }
+    }
+    let new_tree_value = if new_hunks.iter().all(|hunk| hunk.is_resolved()) {
+        let id = store.write_file(repo_path, &mut contents.adds()[0].as_slice())?;
+        Merge::normal(TreeValue::File {
+            id,
+            executable: false,
+        })
+    } else {
+        // Absent sides are still represented by the empty content they were
+        // materialized as
+        let builder: Result<MergeBuilder<Option<FileId>>, BackendError> =
+            zip(contents.iter(), file_merge.iter())
+                .map(|(content, file_id)| match file_id {
+                    Some(_) => Ok(Some(store.write_file(repo_path, &mut content.as_slice())?)),
+                    None => Ok(None),
+                })
+                .collect();
+        conflict.with_new_file_ids(&builder?.build())
+    };
+    let mut tree_builder = MergedTreeBuilder::new(tree.id());
+    tree_builder.set_or_remove(repo_path.clone(), new_tree_value);
+    Ok(tree_builder.write_tree(store)?)
+}
+
 #[cfg(test)]
 mod tests {
     use jj_lib::repo::Repo;
@@ -572,4 +724,151 @@ mod tests {
             "all-changes tree was different",
         );
     }
+
+    #[test]
+    fn test_edit_merge_builtin() {
+        let path = RepoPath::from_internal_string("file");
+        let hunk = |text: &str| ContentHunk(text.as_bytes().to_vec());
+        // A 3-sided conflict with two conflicting hunks
+        let content = Merge::new(
+            vec![hunk("a\nbase\nb\nbase\n"), hunk("a\nbase\nb\nbase\n")],
+            vec![
+                hunk("a\nside1\nb\nside1\n"),
+                hunk("a\nside2\nb\nbase\n"),
+                hunk("a\nbase\nb\nside3\n"),
+            ],
+        );
+        let (hunks, file) = make_merge_file(&path, &content, FileMergeHunkLevel::Line).unwrap();
+        insta::assert_debug_snapshot!(file.sections, @r###"
+        [
+            Unchanged {
+                lines: [
+                    "a\n",
+                ],
+            },
+            Changed {
+                lines: [
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Added,
+                        line: "side1\n",
+                    },
+                ],
+            },
+            Changed {
+                lines: [
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Added,
+                        line: "side2\n",
+                    },
+                ],
+            },
+            Changed {
+                lines: [
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Added,
+                        line: "base\n",
+                    },
+                ],
+            },
+            Unchanged {
+                lines: [
+                    "b\n",
+                ],
+            },
+            Changed {
+                lines: [
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Added,
+                        line: "side1\n",
+                    },
+                ],
+            },
+            Changed {
+                lines: [
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Added,
+                        line: "base\n",
+                    },
+                ],
+            },
+            Changed {
+                lines: [
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Added,
+                        line: "side3\n",
+                    },
+                ],
+            },
+        ]
+        "###);
+
+        // Nothing selected: the conflict is unchanged
+        assert_eq!(apply_merge_builtin(&hunks, &file), hunks);
+
+        // Select side 2 in the first hunk, and sides 1 and 3 in the second hunk
+        let mut file = file;
+        for index in [2, 5, 7] {
+            let scm_record::Section::Changed { lines } = &mut file.sections[index] else {
+                panic!("unexpected section {index}");
+            };
+            for line in lines {
+                line.is_checked = true;
+            }
+        }
+        insta::assert_debug_snapshot!(apply_merge_builtin(&hunks, &file), @r###"
+        [
+            Merge {
+                removes: [],
+                adds: [
+                    "a\n",
+                ],
+            },
+            Merge {
+                removes: [],
+                adds: [
+                    "side2\n",
+                ],
+            },
+            Merge {
+                removes: [],
+                adds: [
+                    "b\n",
+                ],
+            },
+            Merge {
+                removes: [],
+                adds: [
+                    "side1\nside3\n",
+                ],
+            },
+        ]
+        "###);
+
+        // Only select lines in the first hunk: the second hunk remains conflicted
+        for index in [5, 7] {
+            let scm_record::Section::Changed { lines } = &mut file.sections[index] else {
+                panic!("unexpected section {index}");
+            };
+            for line in lines {
+                line.is_checked = false;
+            }
+        }
+        let new_hunks = apply_merge_builtin(&hunks, &file);
+        assert!(new_hunks[1].is_resolved());
+        assert_eq!(new_hunks[3], hunks[3]);
+
+        // Binary conflicts are rejected
+        let binary_content =
+            Merge::new(vec![hunk("base\0")], vec![hunk("side1\0"), hunk("side2\0")]);
+        assert!(matches!(
+            make_merge_file(&path, &binary_content, FileMergeHunkLevel::Line),
+            Err(BuiltinToolError::BinaryConflict(_))
+        ));
+    }
 }
@@ -18,16 +18,17 @@ mod external;
 use std::sync::Arc;
 
 use config::ConfigError;
-use jj_lib::backend::MergedTreeId;
+use jj_lib::backend::{MergedTreeId, TreeValue};
 use jj_lib::conflicts::extract_as_single_hunk;
 use jj_lib::gitignore::GitIgnoreFile;
-use jj_lib::merged_tree::MergedTree;
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
 use jj_lib::repo_path::RepoPath;
 use jj_lib::settings::{ConfigResultExt as _, UserSettings};
 use jj_lib::working_copy::SnapshotError;
 use thiserror::Error;
 
-use self::builtin::{edit_diff_builtin, BuiltinToolError};
+use self::builtin::{edit_diff_builtin, edit_merge_builtin, BuiltinToolError};
 use self::external::{edit_diff_external, DiffCheckoutError, ExternalToolError};
 pub use self::external::{generate_diff, ExternalMergeTool};
 use crate::config::CommandNameAndArgs;
@@ -70,8 +71,17 @@ pub enum ConflictResolveError {
          supported. Conflict summary for {0:?}:\n{1}"
     )]
     NotNormalFiles(RepoPath, String),
-    #[error("The conflict at {path:?} has {sides} sides. At most 2 sides are supported.")]
+    #[error(
+        "The conflict at {path:?} has {sides} sides. At most 2 sides are supported by external \
+         merge tools."
+    )]
     ConflictTooComplicated { path: RepoPath, sides: usize },
+    #[error("The conflict at {path:?} has {sides} sides, so there is no side #{side}")]
+    SideNotFound {
+        path: RepoPath,
+        side: usize,
+        sides: usize,
+    },
     #[error(
         "The output file is either unchanged or empty after the editor quit (run with --verbose \
          to see the exact invocation)."
@@ -85,6 +95,7 @@ pub fn run_mergetool(
     ui: &Ui,
     tree: &MergedTree,
     repo_path: &RepoPath,
+    tool_name: Option<&str>,
     settings: &UserSettings,
 ) -> Result<MergedTreeId, ConflictResolveError> {
     let conflict = match tree.path_value(repo_path).into_resolved() {
@@ -92,6 +103,15 @@ pub fn run_mergetool(
         Ok(Some(_)) => return Err(ConflictResolveError::NotAConflict(repo_path.clone())),
         Ok(None) => return Err(ConflictResolveError::PathNotFound(repo_path.clone())),
     };
+    let editor = match tool_name {
+        Some(name) => get_merge_tool_from_args(settings, &CommandNameAndArgs::from(name))?,
+        None => get_merge_tool_from_settings(ui, settings)?,
+    };
+    if let MergeTool::PickSide(side) = editor {
+        // Picking a side works for any kind of conflict, including binary files
+        // and conflicting executable bits.
+        return pick_conflict_side(tree, repo_path, &conflict, side);
+    }
     let file_merge = conflict.to_file_merge().ok_or_else(|| {
         let mut summary_bytes: Vec<u8> = vec![];
         conflict
@@ -102,24 +122,62 @@ pub fn run_mergetool(
             String::from_utf8_lossy(summary_bytes.as_slice()).to_string(),
         )
     })?;
-    // We only support conflicts with 2 sides (3-way conflicts)
-    if file_merge.num_sides() > 2 {
-        return Err(ConflictResolveError::ConflictTooComplicated {
-            path: repo_path.clone(),
-            sides: file_merge.num_sides(),
-        });
-    };
     let content = extract_as_single_hunk(&file_merge, tree.store(), repo_path);
 
-    let editor = get_merge_tool_from_settings(ui, settings)?;
     match editor {
-        MergeTool::Builtin => unimplemented!("run_mergetool with builtin mergetool"),
-        MergeTool::External(editor) => external::run_mergetool_external(
-            &editor, file_merge, content, repo_path, conflict, tree,
-        ),
+        MergeTool::Builtin => {
+            let new_tree_id = edit_merge_builtin(tree, repo_path, &conflict, &file_merge, &content)
+                .map_err(Box::new)?;
+            if new_tree_id == tree.id() {
+                return Err(ConflictResolveError::EmptyOrUnchanged);
+            }
+            Ok(new_tree_id)
+        }
+        MergeTool::External(editor) => {
+            // We only support conflicts with 2 sides (3-way conflicts)
+            if file_merge.num_sides() > 2 {
+                return Err(ConflictResolveError::ConflictTooComplicated {
+                    path: repo_path.clone(),
+                    sides: file_merge.num_sides(),
+                });
+            };
+            let conflict_marker_style = settings
+                .conflict_marker_style()
+                .map_err(ExternalToolError::Config)?;
+            external::run_mergetool_external(
+                &editor,
+                file_merge,
+                content,
+                repo_path,
+                conflict,
+                tree,
+                conflict_marker_style,
+            )
+        }
+        MergeTool::PickSide(_) => unreachable!(),
     }
 }
 
+/// Resolves the conflict at `repo_path` by taking the value of side `side`
+/// (zero-based) as it is.
+fn pick_conflict_side(
+    tree: &MergedTree,
+    repo_path: &RepoPath,
+    conflict: &Merge<Option<TreeValue>>,
+    side: usize,
+) -> Result<MergedTreeId, ConflictResolveError> {
+    let Some(value) = conflict.adds().get(side) else {
+        return Err(ConflictResolveError::SideNotFound {
+            path: repo_path.clone(),
+            side: side + 1,
+            sides: conflict.num_sides(),
+        });
+    };
+    let mut tree_builder = MergedTreeBuilder::new(tree.id());
+    tree_builder.set_or_remove(repo_path.clone(), Merge::resolved(value.clone()));
+    Ok(tree_builder.write_tree(tree.store())?)
+}
+
 pub fn edit_diff(
     ui: &Ui,
     left_tree: &MergedTree,
@@ -143,15 +201,33 @@ pub fn edit_diff(
             base_ignores,
             settings,
         ),
+        MergeTool::PickSide(_) => unreachable!("picking a side is not a diff editor"),
     }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum MergeTool {
     Builtin,
+    /// Resolves conflicts by picking the side with the given (zero-based)
+    /// index.
+    PickSide(usize),
     External(ExternalMergeTool),
 }
 
+/// Returns the builtin merge tool called `name`. The names of builtin tools
+/// start with `:` so they don't clash with external tools.
+fn get_builtin_merge_tool(name: &str) -> Option<MergeTool> {
+    match name {
+        ":builtin" => Some(MergeTool::Builtin),
+        ":ours" => Some(MergeTool::PickSide(0)),
+        ":theirs" => Some(MergeTool::PickSide(1)),
+        _ => {
+            let side: usize = name.strip_prefix(":side-")?.parse().ok()?;
+            side.checked_sub(1).map(MergeTool::PickSide)
+        }
+    }
+}
+
 /// Loads merge tool options from `[merge-tools.<name>]`.
 pub fn get_tool_config(
     settings: &UserSettings,
@@ -202,8 +278,20 @@ fn get_merge_tool_from_settings(
     settings: &UserSettings,
 ) -> Result<MergeTool, ExternalToolError> {
     let args = editor_args_from_settings(ui, settings, "ui.merge-editor")?;
-    let editor = get_tool_config_from_args(settings, &args)?
-        .unwrap_or_else(|| ExternalMergeTool::with_merge_args(&args));
+    get_merge_tool_from_args(settings, &args)
+}
+
+fn get_merge_tool_from_args(
+    settings: &UserSettings,
+    args: &CommandNameAndArgs,
+) -> Result<MergeTool, ExternalToolError> {
+    if let CommandNameAndArgs::String(name) = args {
+        if let Some(tool) = get_builtin_merge_tool(name) {
+            return Ok(tool);
+        }
+    }
+    let editor = get_tool_config_from_args(settings, args)?
+        .unwrap_or_else(|| ExternalMergeTool::with_merge_args(args));
     if editor.merge_args.is_empty() {
         Err(ExternalToolError::MergeArgsNotConfigured {
             tool_name: args.to_string(),
@@ -553,5 +641,30 @@ mod tests {
 
         // Invalid type
         assert!(get(r#"ui.merge-editor.k = 0"#).is_err());
+
+        // Builtin tools
+        assert_eq!(
+            get(r#"ui.merge-editor = ":builtin""#).unwrap(),
+            MergeTool::Builtin
+        );
+        assert_eq!(
+            get(r#"ui.merge-editor = ":ours""#).unwrap(),
+            MergeTool::PickSide(0)
+        );
+        assert_eq!(
+            get(r#"ui.merge-editor = ":theirs""#).unwrap(),
+            MergeTool::PickSide(1)
+        );
+        assert_eq!(
+            get(r#"ui.merge-editor = ":side-3""#).unwrap(),
+            MergeTool::PickSide(2)
+        );
+        // Unknown builtin names are looked up as external tools
+        insta::assert_debug_snapshot!(
+            get(r#"ui.merge-editor = ":side-0""#).unwrap_err(), @r###"
+        MergeArgsNotConfigured {
+            tool_name: ":side-0",
+        }
+        "###);
     }
 }
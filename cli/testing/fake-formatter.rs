@@ -0,0 +1,71 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::exit;
+
+use clap::Parser;
+
+/// A fake code formatter, useful for testing
+///
+/// Reads a file from stdin and writes the transformed file to stdout.
+#[derive(Parser, Debug)]
+#[clap()]
+struct Args {
+    /// Exit with a non-zero status without printing anything
+    #[arg(long)]
+    fail: bool,
+
+    /// Convert all characters to uppercase
+    #[arg(long)]
+    uppercase: bool,
+
+    /// Reverse the order of the lines
+    #[arg(long)]
+    reverse: bool,
+
+    /// Append the input to this file, to record each invocation
+    #[arg(long)]
+    tee: Option<PathBuf>,
+}
+
+fn main() {
+    let args: Args = Args::parse();
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).unwrap();
+    if let Some(path) = &args.tee {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(input.as_bytes()).unwrap();
+    }
+    if args.fail {
+        exit(1);
+    }
+    let mut output = input;
+    if args.uppercase {
+        output = output.to_uppercase();
+    }
+    if args.reverse {
+        output = output
+            .lines()
+            .rev()
+            .map(|line| format!("{line}\n"))
+            .collect();
+    }
+    std::io::stdout().write_all(output.as_bytes()).unwrap();
+}
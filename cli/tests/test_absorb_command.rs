@@ -0,0 +1,199 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::TestEnvironment;
+
+pub mod common;
+
+#[test]
+fn test_absorb() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m=a"]);
+    std::fs::write(repo_path.join("file1"), "1\n2\n3\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "-m=b"]);
+    std::fs::write(repo_path.join("file1"), "1\n2\n3b\n4\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    // Modify a line from each commit, add a line after a line from "b", and add
+    // a new file
+    std::fs::write(repo_path.join("file1"), "1x\n2\n3x\n4\n5\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "new\n").unwrap();
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  d6bf0dfdb294
+    ◉  95113df1957b b
+    ◉  e773c25dfd6b a
+    ◉  000000000000
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Absorbed changes into these revisions:
+      kkmpptxz b976ba8d b
+      qpvuntsm 1d80c331 a
+    Rebased 1 descendant commits
+    Working copy now at: zsuskuln be2ac215 (no description set)
+    Parent commit      : kkmpptxz b976ba8d b
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  be2ac215503e
+    ◉  b976ba8d697f b
+    ◉  1d80c331eeef a
+    ◉  000000000000
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file1", "-r=description(a)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    1x
+    2
+    3
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file1", "-r=description(b)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    1x
+    2
+    3x
+    4
+    5
+    "###);
+    // Only the new file is left in the working copy
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file2
+    "###);
+
+    // Nothing left to absorb
+    let stdout = test_env.jj_cmd_success(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+}
+
+#[test]
+fn test_absorb_ambiguous() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m=a"]);
+    std::fs::write(repo_path.join("file1"), "1\n2\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "-m=b"]);
+    std::fs::write(repo_path.join("file1"), "1\n2b\n3\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    // A line added between lines from different commits stays
+    std::fs::write(repo_path.join("file1"), "1\nx\n2b\n3\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+    // So does a hunk replacing lines from different commits
+    std::fs::write(repo_path.join("file1"), "1x\n2x\n3\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file1 b/file1
+    index fbbafbfdaa...06585d26db 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,3 +1,3 @@
+    -1
+    -2b
+    +1x
+    +2x
+     3
+    "###);
+}
+
+#[test]
+fn test_absorb_into_and_paths() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m=a"]);
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "a\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "-m=b"]);
+    std::fs::write(repo_path.join("file3"), "b\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "-m=c"]);
+    std::fs::write(repo_path.join("file1"), "c\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "c\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "c\n").unwrap();
+
+    // Lines last modified outside the destinations stay
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["absorb", "--from=description(c)", "--into=description(b)"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Absorbed changes into these revisions:
+      kkmpptxz 0e2bb191 b
+    Rebased 1 descendant commits
+    Working copy now at: zsuskuln 6d680f87 c
+    Parent commit      : kkmpptxz 0e2bb191 b
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r=description(c)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file1
+    M file2
+    "###);
+
+    // Only the given paths are absorbed
+    let stdout = test_env.jj_cmd_success(&repo_path, &["absorb", "-f=description(c)", "file1"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Absorbed changes into these revisions:
+      qpvuntsm ddfd8e41 a
+    Rebased 2 descendant commits
+    Working copy now at: zsuskuln c5a81ad3 c
+    Parent commit      : kkmpptxz 17a53535 b
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r=description(c)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file2
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c5a81ad3ea76 c
+    ◉  17a5353578cb b
+    ◉  ddfd8e41c898 a
+    ◉  000000000000
+    "###);
+}
+
+#[test]
+fn test_absorb_from_merge() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_success(&repo_path, &["new", "root()", "-m=left"]);
+    test_env.jj_cmd_success(&repo_path, &["new", "root()", "-m=right"]);
+    test_env.jj_cmd_success(
+        &repo_path,
+        &["new", "description(left)", "description(right)"],
+    );
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["absorb"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot absorb changes from a merge commit
+    "###);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    let template = r#"commit_id.short() ++ " " ++ description.first_line()"#;
+    test_env.jj_cmd_success(repo_path, &["log", "-T", template])
+}
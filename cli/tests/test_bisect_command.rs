@@ -0,0 +1,312 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::TestEnvironment;
+
+pub mod common;
+
+/// Creates a linear history of commits "c1" to "c8", with a file called "bug"
+/// added in "c5".
+fn init_linear_repo(test_env: &TestEnvironment) -> std::path::PathBuf {
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m=c1"]);
+    for i in 2..=8 {
+        test_env.jj_cmd_success(&repo_path, &["new", &format!("-m=c{i}")]);
+        if i == 5 {
+            std::fs::write(repo_path.join("bug"), "").unwrap();
+        }
+    }
+    repo_path
+}
+
+#[test]
+fn test_bisect() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_linear_repo(&test_env);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "bisect",
+            "start",
+            "--good=description(c1)",
+            "--bad=description(c8)",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Bisecting: 6 revisions left to test (roughly 3 steps)
+    Working copy now at: znkkpsqq 2eaaf29c (empty) (no description set)
+    Parent commit      : royxmykx 9a201cb3 c5
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  2eaaf29c63ec
+    │ ◉  4e0a790ca792 c8
+    │ ◉  fcbd459b7169 c7
+    │ ◉  406ffd20150d c6
+    ├─╯
+    ◉  9a201cb30834 c5
+    ◉  13a7e36184b1 c4
+    ◉  ed598a33afc9 c3
+    ◉  5972dc361a8c c2
+    ◉  90f352d2e623 c1
+    ◉  000000000000
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "bad"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Bisecting: 3 revisions left to test (roughly 2 steps)
+    Working copy now at: kmkuslsw af5f8059 (empty) (no description set)
+    Parent commit      : zsuskuln ed598a33 (empty) c3
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "good"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Bisecting: 1 revisions left to test (roughly 1 steps)
+    Working copy now at: wqnwkozp 4474386b (empty) (no description set)
+    Parent commit      : mzvwutvl 13a7e361 (empty) c4
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "good"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The first bad revision is:
+      royxmykx 9a201cb3 c5
+    "###);
+
+    // The bisection is done
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["bisect", "good"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No revision is being tested
+    Hint: Specify the revision to mark.
+    "###);
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["bisect", "start", "--good=root()", "--bad=@"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: A bisection is already in progress
+    Hint: Use `jj bisect reset` to end it.
+    "###);
+
+    // Resetting goes back to where the bisection started
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "reset"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: yostqsxw 4e0a790c (empty) c8
+    Parent commit      : vruxwmqv fcbd459b (empty) c7
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  4e0a790ca792 c8
+    ◉  fcbd459b7169 c7
+    ◉  406ffd20150d c6
+    ◉  9a201cb30834 c5
+    ◉  13a7e36184b1 c4
+    ◉  ed598a33afc9 c3
+    ◉  5972dc361a8c c2
+    ◉  90f352d2e623 c1
+    ◉  000000000000
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["bisect", "good"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No bisection in progress
+    Hint: Use `jj bisect start` to start one.
+    "###);
+}
+
+#[test]
+fn test_bisect_skip() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_linear_repo(&test_env);
+
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "bisect",
+            "start",
+            "--good=description(c3)",
+            "--bad=description(c6)",
+        ],
+    );
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "skip"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Bisecting: 2 revisions left to test (roughly 2 steps)
+    Working copy now at: kpqxywon 796ab481 (empty) (no description set)
+    Parent commit      : mzvwutvl 13a7e361 (empty) c4
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    // Only skipped revisions are left
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "skip"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The first bad revision could be any of:
+      yqosqzyt 406ffd20 (empty) c6
+      royxmykx 9a201cb3 c5
+      mzvwutvl 13a7e361 (empty) c4
+    "###);
+}
+
+#[test]
+fn test_bisect_bad_ancestor_of_good() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_linear_repo(&test_env);
+
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &[
+            "bisect",
+            "start",
+            "--good=description(c6)",
+            "--bad=description(c3)",
+        ],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The bad revision is an ancestor of a good revision
+    "###);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_bisect_run() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_linear_repo(&test_env);
+
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "bisect",
+            "start",
+            "--good=description(c1)",
+            "--bad=description(c8)",
+        ],
+    );
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "run", "test ! -e bug"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Marking 9a201cb30834 as bad (exit status: 1)
+    Bisecting: 3 revisions left to test (roughly 2 steps)
+    Working copy now at: kpqxywon de7b6475 (empty) (no description set)
+    Parent commit      : zsuskuln ed598a33 (empty) c3
+    Added 0 files, modified 0 files, removed 1 files
+    Marking ed598a33afc9 as good (exit status: 0)
+    Bisecting: 1 revisions left to test (roughly 1 steps)
+    Working copy now at: vzqnnsmr 796ab481 (empty) (no description set)
+    Parent commit      : mzvwutvl 13a7e361 (empty) c4
+    Marking 13a7e36184b1 as good (exit status: 0)
+    The first bad revision is:
+      royxmykx 9a201cb3 c5
+    "###);
+
+    // A status above 127 stops the bisection
+    test_env.jj_cmd_success(&repo_path, &["bisect", "reset"]);
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "bisect",
+            "start",
+            "--good=description(c1)",
+            "--bad=description(c8)",
+        ],
+    );
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["bisect", "run", "exit 130"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: The command failed with exit status: 130; stopping the bisection
+    "###);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_bisect_run_merges() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    // Several branches that are merged one after the other, with the bug added
+    // on one of them
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m=base"]);
+    test_env.jj_cmd_success(&repo_path, &["new", "-m=main1"]);
+    for branch in ["a", "b", "c"] {
+        test_env.jj_cmd_success(
+            &repo_path,
+            &["new", "description(base)", &format!("-m={branch}1")],
+        );
+        test_env.jj_cmd_success(&repo_path, &["new", &format!("-m={branch}2")]);
+        if branch == "b" {
+            std::fs::write(repo_path.join("bug"), "").unwrap();
+        }
+        test_env.jj_cmd_success(&repo_path, &["new", &format!("-m={branch}3")]);
+        test_env.jj_cmd_success(
+            &repo_path,
+            &[
+                "new",
+                "heads(description(main))",
+                "@",
+                &format!("-m=main-{branch}"),
+            ],
+        );
+    }
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @    af99cbf9bdd3 main-c
+    ├─╮
+    │ ◉  14de0943b993 c3
+    │ ◉  86b7174c80bb c2
+    │ ◉  39ea956e89a0 c1
+    ◉ │    0b2e067cf5b6 main-b
+    ├───╮
+    │ │ ◉  4577addcae49 b3
+    │ │ ◉  08d251cdbb94 b2
+    │ │ ◉  a67d4e0b9425 b1
+    │ ├─╯
+    ◉ │    fdb06fa034a3 main-a
+    ├───╮
+    │ │ ◉  ea70c7034b1f a3
+    │ │ ◉  c539de5fc6c3 a2
+    │ │ ◉  8e3fe382cd09 a1
+    │ ├─╯
+    ◉ │  a6671a28916a main1
+    ├─╯
+    ◉  de9c740fb408 base
+    ◉  000000000000
+    "###);
+
+    test_env.jj_cmd_success(
+        &repo_path,
+        &["bisect", "start", "--good=description(base)", "--bad=@"],
+    );
+    let stdout = test_env.jj_cmd_success(&repo_path, &["bisect", "run", "test ! -e bug"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Marking 86b7174c80bb as good (exit status: 0)
+    Bisecting: 10 revisions left to test (roughly 4 steps)
+    Working copy now at: xznxytkn 6ddcf4f6 (empty) (no description set)
+    Parent commit      : royxmykx ea70c703 (empty) a3
+    Marking ea70c7034b1f as good (exit status: 0)
+    Bisecting: 7 revisions left to test (roughly 3 steps)
+    Working copy now at: smwtzssm f478f82f (empty) (no description set)
+    Parent commit      : znkkpsqq 4577addc (empty) b3
+    Added 1 files, modified 0 files, removed 0 files
+    Marking 4577addcae49 as bad (exit status: 1)
+    Bisecting: 2 revisions left to test (roughly 2 steps)
+    Working copy now at: zlusorwl 0f5af4c7 (empty) (no description set)
+    Parent commit      : yostqsxw 08d251cd b2
+    Marking 08d251cdbb94 as bad (exit status: 1)
+    Bisecting: 1 revisions left to test (roughly 1 steps)
+    Working copy now at: ukzzzykq f225260e (empty) (no description set)
+    Parent commit      : vruxwmqv a67d4e0b (empty) b1
+    Added 0 files, modified 0 files, removed 1 files
+    Marking a67d4e0b9425 as good (exit status: 0)
+    The first bad revision is:
+      yostqsxw 08d251cd b2
+    "###);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    let template = r#"commit_id.short() ++ " " ++ description.first_line()"#;
+    test_env.jj_cmd_success(repo_path, &["log", "-T", template])
+}
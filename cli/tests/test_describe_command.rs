@@ -310,3 +310,59 @@ fn test_describe_author() {
     ~
     "###);
 }
+
+#[cfg(unix)]
+#[test]
+fn test_describe_commit_msg_hook() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config("git.run-hooks = true");
+
+    // core.hooksPath is relative to the workspace root
+    let git_repo = git2::Repository::open(repo_path.join(".jj/repo/store/git")).unwrap();
+    git_repo
+        .config()
+        .unwrap()
+        .set_str("core.hooksPath", "hooks")
+        .unwrap();
+    std::fs::create_dir(repo_path.join("hooks")).unwrap();
+    let hook_path = repo_path.join("hooks").join("commit-msg");
+    std::fs::write(
+        &hook_path,
+        "#!/bin/sh\necho 'Signed-off-by: Test User' >> \"$1\"\necho 'added trailer'\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&hook_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["describe", "-m", "description"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: qpvuntsm 24ed4c21 description
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    added trailer
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log", "-r@", "-Tdescription"]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  description
+    │  Signed-off-by: Test User
+    ~
+    "###);
+
+    // A failing hook aborts the command
+    std::fs::write(&hook_path, "#!/bin/sh\necho 'bad message'\nexit 1\n").unwrap();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["describe", "-m", "other"]);
+    insta::assert_snapshot!(stderr, @r###"
+    bad message
+    Error: The commit-msg hook failed (exit status: 1)
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log", "-r@", "-Tdescription"]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  description
+    │  Signed-off-by: Test User
+    ~
+    "###);
+}
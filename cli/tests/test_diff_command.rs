@@ -214,6 +214,53 @@ fn test_diff_types() {
     }
 }
 
+#[test]
+fn test_diff_binary() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    std::fs::write(repo_path.join(".gitattributes"), "*.dat -diff\n").unwrap();
+    std::fs::write(repo_path.join("removed"), "foo\0\n").unwrap();
+    std::fs::write(repo_path.join("modified"), "foo\0\n").unwrap();
+    std::fs::write(repo_path.join("text.dat"), "foo\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    std::fs::remove_file(repo_path.join("removed")).unwrap();
+    std::fs::write(repo_path.join("modified"), "bar\0\n").unwrap();
+    std::fs::write(repo_path.join("text.dat"), "bar\n").unwrap();
+    std::fs::write(repo_path.join("added"), "\0").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added regular file added:
+        (binary)
+    Modified regular file modified:
+        (binary)
+    Removed regular file removed:
+        (binary)
+    Modified regular file text.dat:
+        (binary)
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/added b/added
+    new file mode 100644
+    index 0000000000..f76dd238ad
+    Binary files /dev/null and b/added differ
+    diff --git a/modified b/modified
+    index 4af7a02cfb...080090e709 100644
+    Binary files a/modified and b/modified differ
+    diff --git a/removed b/removed
+    deleted file mode 100644
+    index 4af7a02cfb..0000000000
+    Binary files a/removed and /dev/null differ
+    diff --git a/text.dat b/text.dat
+    index 257cc5642c...5716ca5987 100644
+    Binary files a/text.dat and b/text.dat differ
+    "###);
+}
+
 #[test]
 fn test_diff_bad_args() {
     let test_env = TestEnvironment::default();
@@ -0,0 +1,208 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::{Path, PathBuf};
+
+use crate::common::TestEnvironment;
+
+pub mod common;
+
+fn init_repo() -> (TestEnvironment, PathBuf) {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    (test_env, repo_path)
+}
+
+fn add_fake_formatter(test_env: &TestEnvironment, name: &str, args: &[&str], pattern: &str) {
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    // Simplified TOML escaping, hoping that there are no '"' or control characters
+    // in the paths
+    let command = [formatter_path.to_str().unwrap()]
+        .iter()
+        .chain(args)
+        .map(|arg| format!(r#""{}""#, arg.replace('\\', r"\\")))
+        .collect::<Vec<_>>()
+        .join(", ");
+    test_env.add_config(&format!(
+        r#"
+        [fix.tools.{name}]
+        command = [{command}]
+        patterns = ["{pattern}"]
+        "#
+    ));
+}
+
+#[test]
+fn test_fix_no_config() {
+    let (test_env, repo_path) = init_repo();
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["fix"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: No fix tools are configured
+    Hint: Add a tool to the `fix.tools` table in the config. See `jj help fix`.
+    "###);
+}
+
+#[test]
+fn test_fix_stack() {
+    let (test_env, repo_path) = init_repo();
+    add_fake_formatter(&test_env, "upper", &["--uppercase"], "**/*.txt");
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m=a"]);
+    std::fs::write(repo_path.join("file.txt"), "a\n").unwrap();
+    std::fs::write(repo_path.join("file.md"), "a\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "-m=b"]);
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir").join("other.txt"), "b\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "-m=c", "description(a)"]);
+    std::fs::write(repo_path.join("file.md"), "c\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["fix", "-s=description(a)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Fixed 3 commits of 3 checked.
+    Working copy now at: zsuskuln c97125e3 c
+    Parent commit      : qpvuntsm 4094ac5b a
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  c97125e32d66 c
+    │ ◉  992e7254f578 b
+    ├─╯
+    ◉  4094ac5b9098 a
+    ◉  000000000000
+    "###);
+    // Only the matching files are changed
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file.txt", "-r=description(a)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file.md", "-r=description(a)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    "###);
+    let stdout =
+        test_env.jj_cmd_success(&repo_path, &["print", "dir/other.txt", "-r=description(b)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    B
+    "###);
+    // The descendants keep their own changes and the fixes from their ancestors
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r=description(b)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A dir/other.txt
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r=@"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file.md
+    "###);
+    // The working copy was updated
+    let content = std::fs::read_to_string(repo_path.join("file.txt")).unwrap();
+    insta::assert_snapshot!(content, @r###"
+    A
+    "###);
+
+    // Fixing again changes nothing
+    let stdout = test_env.jj_cmd_success(&repo_path, &["fix", "-s=description(a)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Fixed 0 commits of 3 checked.
+    Nothing changed.
+    "###);
+}
+
+#[test]
+fn test_fix_paths_and_failure() {
+    let (test_env, repo_path) = init_repo();
+    add_fake_formatter(&test_env, "upper", &["--uppercase"], "**");
+    add_fake_formatter(&test_env, "fail", &["--fail"], "failing");
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    std::fs::write(repo_path.join("file2"), "a\n").unwrap();
+    std::fs::write(repo_path.join("failing"), "a\n").unwrap();
+
+    // Only the given paths are fixed
+    let stdout = test_env.jj_cmd_success(&repo_path, &["fix", "file1"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Fixed 1 commits of 1 checked.
+    Working copy now at: qpvuntsm 5ef614a0 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file1"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file2"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    "###);
+
+    // The output of a failing tool is discarded, but the other tools still
+    // apply
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["fix", "failing"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Fixed 1 commits of 1 checked.
+    Working copy now at: qpvuntsm e49afa92 (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    warning: Fix tool "fail" failed on failing: exit status: 1
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "failing"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A
+    "###);
+}
+
+#[test]
+fn test_fix_chained_and_cached() {
+    let (test_env, repo_path) = init_repo();
+    let tee_path = test_env.env_root().join("tee");
+    let tee_arg = format!("--tee={}", tee_path.to_str().unwrap());
+    add_fake_formatter(&test_env, "1-upper", &["--uppercase", &tee_arg], "*.txt");
+    add_fake_formatter(&test_env, "2-reverse", &["--reverse"], "*.txt");
+    std::fs::write(repo_path.join("file1.txt"), "a\nb\n").unwrap();
+    std::fs::write(repo_path.join("file2.txt"), "a\nb\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file3.txt"), "c\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    std::fs::write(repo_path.join("file3.txt"), "c\n").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["fix", "-s=@--"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Fixed 3 commits of 3 checked.
+    Working copy now at: kkmpptxz 11d6f8cf (empty) (no description set)
+    Parent commit      : rlvkpnrz e07a4004 (no description set)
+    Added 0 files, modified 3 files, removed 0 files
+    "###);
+    // The tools run in order of their names
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file1.txt"]);
+    insta::assert_snapshot!(stdout, @r###"
+    B
+    A
+    "###);
+    // Each file content and path is only formatted once, even though the files
+    // are in several commits
+    let tee = std::fs::read_to_string(&tee_path).unwrap();
+    insta::assert_snapshot!(tee, @r###"
+    a
+    b
+    a
+    b
+    c
+    "###);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    let template = r#"commit_id.short() ++ " " ++ description.first_line()"#;
+    test_env.jj_cmd_success(repo_path, &["log", "-T", template])
+}
@@ -79,7 +79,7 @@ fn test_git_clone() {
     Fetching into new repo in "$TEST_ENV/failed"
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    Error: could not find repository from '$TEST_ENV/bad'; class=Repository (6)
+    Error: could not find repository at '$TEST_ENV/bad'; class=Repository (6)
     "###);
     assert!(!test_env.env_root().join("failed").exists());
 
@@ -95,7 +95,7 @@ fn test_git_clone() {
     Fetching into new repo in "$TEST_ENV/failed"
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    Error: could not find repository from '$TEST_ENV/bad'; class=Repository (6)
+    Error: could not find repository at '$TEST_ENV/bad'; class=Repository (6)
     "###);
     assert!(test_env.env_root().join("failed").exists());
     assert!(!test_env.env_root().join("failed").join(".jj").exists());
@@ -207,7 +207,7 @@ fn test_git_clone_colocate() {
         .map(|entry| format!("{:?} {}\n", entry.status(), entry.path().unwrap()))
         .collect();
     insta::assert_snapshot!(git_statuses, @r###"
-    IGNORED .jj/
+    Status(IGNORED) .jj/
     "###);
 
     // The old default branch "master" shouldn't exist.
@@ -237,7 +237,7 @@ fn test_git_clone_colocate() {
     Fetching into new repo in "$TEST_ENV/failed"
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    Error: could not find repository from '$TEST_ENV/bad'; class=Repository (6)
+    Error: could not find repository at '$TEST_ENV/bad'; class=Repository (6)
     "###);
     assert!(!test_env.env_root().join("failed").exists());
 
@@ -256,7 +256,7 @@ fn test_git_clone_colocate() {
     Fetching into new repo in "$TEST_ENV/failed"
     "###);
     insta::assert_snapshot!(stderr, @r###"
-    Error: could not find repository from '$TEST_ENV/bad'; class=Repository (6)
+    Error: could not find repository at '$TEST_ENV/bad'; class=Repository (6)
     "###);
     assert!(test_env.env_root().join("failed").exists());
     assert!(!test_env.env_root().join("failed").join(".git").exists());
@@ -467,3 +467,111 @@ fn test_git_colocated_unreachable_commits() {
     Error: Revision "8e713ff77b54928dd4a82aaabeca44b1ae91722c" doesn't exist
     "###);
 }
+
+#[test]
+fn test_git_colocated_shallow() {
+    let test_env = TestEnvironment::default();
+    let workspace_root = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::init(&workspace_root).unwrap();
+    let signature = git2::Signature::new(
+        "Someone",
+        "someone@example.com",
+        &git2::Time::new(1234567890, 60),
+    )
+    .unwrap();
+    let empty_tree_oid = git_repo.treebuilder(None).unwrap().write().unwrap();
+    let empty_tree = git_repo.find_tree(empty_tree_oid).unwrap();
+    let mut parents = vec![];
+    for description in ["first", "second", "third"] {
+        let parent_refs = parents.iter().collect::<Vec<_>>();
+        let oid = git_repo
+            .commit(
+                Some("refs/heads/master"),
+                &signature,
+                &signature,
+                description,
+                &empty_tree,
+                &parent_refs,
+            )
+            .unwrap();
+        parents = vec![git_repo.find_commit(oid).unwrap()];
+    }
+    // Turn it into a shallow clone of depth 2, like `git clone --depth=2` would
+    let second = parents[0].parent(0).unwrap();
+    let first_hex = second.parent_id(0).unwrap().to_string();
+    std::fs::remove_file(
+        git_repo
+            .path()
+            .join("objects")
+            .join(&first_hex[..2])
+            .join(&first_hex[2..]),
+    )
+    .unwrap();
+    std::fs::write(
+        git_repo.path().join("shallow"),
+        format!("{}\n", second.id()),
+    )
+    .unwrap();
+
+    // The shallow commit is shown as a child of the root commit
+    test_env.jj_cmd_success(&workspace_root, &["init", "--git-repo", "."]);
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["log", "-r", "all()"]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  sqpuoqvx test.user@example.com 2001-02-03 04:05:07.000 +07:00 b26fe133
+    │  (empty) (no description set)
+    ◉  vknkvopp someone@example.com 2009-02-14 00:31:30.000 +01:00 master HEAD@git 9f8f0b92
+    │  (empty) third
+    ◉  ymzxkwxn someone@example.com 2009-02-14 00:31:30.000 +01:00 2a93c556 shallow
+    │  (empty) second
+    ◉  zzzzzzzz root() 00000000
+    "###);
+}
+
+#[test]
+fn test_git_colocated_index() {
+    let test_env = TestEnvironment::default();
+    let workspace_root = test_env.env_root().join("repo");
+    let git_repo = git2::Repository::init(&workspace_root).unwrap();
+    test_env.jj_cmd_success(&workspace_root, &["init", "--git-repo", "."]);
+
+    let get_git_statuses = || {
+        let mut status_options = git2::StatusOptions::new();
+        status_options
+            .include_untracked(true)
+            .exclude_submodules(true);
+        git_repo
+            .statuses(Some(&mut status_options))
+            .unwrap()
+            .iter()
+            .map(|entry| format!("{} {:?}\n", entry.path().unwrap(), entry.status()))
+            .collect::<String>()
+    };
+
+    // The index matches the parent of the working-copy commit, so Git sees no
+    // staged changes
+    std::fs::write(workspace_root.join("file1"), "1").unwrap();
+    std::fs::write(workspace_root.join("file2"), "2").unwrap();
+    test_env.jj_cmd_success(&workspace_root, &["new"]);
+    insta::assert_snapshot!(get_git_statuses(), @"");
+    // The stat info of the files is taken from the working copy
+    let index = git_repo.index().unwrap();
+    let entry = index.get_path(Path::new("file1"), 0).unwrap();
+    assert_ne!(entry.mtime.seconds(), 0);
+    assert_eq!(entry.file_size, 1);
+
+    // Changes in the working copy are unstaged changes to Git
+    std::fs::write(workspace_root.join("file2"), "modified").unwrap();
+    std::fs::write(workspace_root.join("file3"), "3").unwrap();
+    test_env.jj_cmd_success(&workspace_root, &["status"]);
+    insta::assert_snapshot!(get_git_statuses(), @r###"
+    file2 Status(WT_MODIFIED)
+    file3 Status(WT_NEW)
+    "###);
+
+    test_env.jj_cmd_success(&workspace_root, &["new"]);
+    insta::assert_snapshot!(get_git_statuses(), @"");
+
+    // Moving to another commit updates the index
+    test_env.jj_cmd_success(&workspace_root, &["new", "@--"]);
+    insta::assert_snapshot!(get_git_statuses(), @"");
+}
@@ -644,3 +644,69 @@ fn test_git_push_conflicting_branches() {
     Branch branch2 is conflicted
     "###);
 }
+
+#[cfg(unix)]
+#[test]
+fn test_git_push_pre_push_hook() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (test_env, workspace_root) = set_up();
+    let hooks_dir = workspace_root
+        .join(".jj")
+        .join("repo")
+        .join("store")
+        .join("git")
+        .join("hooks");
+    std::fs::create_dir_all(&hooks_dir).unwrap();
+    let hook_path = hooks_dir.join("pre-push");
+    std::fs::write(
+        &hook_path,
+        "#!/bin/sh\necho \"pre-push $1\"\ncat\necho rejected >&2\nexit 1\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&hook_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    test_env.jj_cmd_success(&workspace_root, &["branch", "delete", "branch2"]);
+    test_env.jj_cmd_success(
+        &workspace_root,
+        &["describe", "branch1", "-m", "modified branch1 commit"],
+    );
+
+    // A failing hook aborts the push
+    test_env.add_config(r#"git.run-hooks = ["pre-push"]"#);
+    let assert = test_env
+        .jj_cmd(&workspace_root, &["git", "push", "--all"])
+        .assert()
+        .code(1);
+    insta::assert_snapshot!(get_stdout_string(&assert), @r###"
+    Branch changes to push to origin:
+      Force branch branch1 from 45a3aa29e907 to d47326d59ee1
+      Delete branch branch2 from 8476341eb395
+    "###);
+    insta::assert_snapshot!(get_stderr_string(&assert), @r###"
+    pre-push origin
+    refs/heads/branch1 d47326d59ee1d0728b7f923db7e409600e38c08c refs/heads/branch1 45a3aa29e9074f240bfec249201521729413bb07
+    (delete) 0000000000000000000000000000000000000000 refs/heads/branch2 8476341eb3955478919c198e0c04587b6b8ea071
+    rejected
+    Error: The pre-push hook failed (exit status: 1)
+    "###);
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["branch", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    branch1: lzmmnrxq d47326d5 (empty) modified branch1 commit
+      @origin (ahead by 1 commits, behind by 1 commits): lzmmnrxq 45a3aa29 (empty) description 1
+    branch2 (deleted)
+      @origin: rlzusymt 8476341e (empty) description 2
+      (this branch will be *deleted permanently* on the remote on the
+       next `jj git push`. Use `jj branch forget` to prevent this)
+    "###);
+
+    std::fs::write(&hook_path, "#!/bin/sh\necho checked\n").unwrap();
+    let (stdout, stderr) = test_env.jj_cmd_ok(&workspace_root, &["git", "push", "--all"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Branch changes to push to origin:
+      Force branch branch1 from 45a3aa29e907 to d47326d59ee1
+      Delete branch branch2 from 8476341eb395
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    checked
+    "###);
+}
@@ -63,3 +63,148 @@ fn test_gitsubmodule_print_gitmodules() {
 	path:new
     "###);
 }
+
+fn commit_to_git_repo(
+    git_repo: &git2::Repository,
+    tree_builder: git2::TreeBuilder,
+    parents: &[&git2::Commit],
+) -> git2::Oid {
+    let signature =
+        git2::Signature::new("Some One", "some.one@example.com", &git2::Time::new(0, 0)).unwrap();
+    let tree = git_repo.find_tree(tree_builder.write().unwrap()).unwrap();
+    git_repo
+        .commit(
+            Some("refs/heads/main"),
+            &signature,
+            &signature,
+            "message",
+            &tree,
+            parents,
+        )
+        .unwrap()
+}
+
+#[test]
+fn test_gitsubmodule_checkout_and_snapshot() {
+    let test_env = TestEnvironment::default();
+
+    // The submodule's upstream repo
+    let sub_git_repo = git2::Repository::init(test_env.env_root().join("sub")).unwrap();
+    let mut tree_builder = sub_git_repo.treebuilder(None).unwrap();
+    let file_oid = sub_git_repo.blob(b"sub content\n").unwrap();
+    tree_builder
+        .insert("file", file_oid, git2::FileMode::Blob.into())
+        .unwrap();
+    let sub_commit_oid = commit_to_git_repo(&sub_git_repo, tree_builder, &[]);
+
+    // The parent repo, referring to the submodule by a relative URL
+    let source_git_repo = git2::Repository::init(test_env.env_root().join("source")).unwrap();
+    let mut tree_builder = source_git_repo.treebuilder(None).unwrap();
+    let gitmodules_oid = source_git_repo
+        .blob(b"[submodule \"sub\"]\n\tpath = sub\n\turl = ../sub\n")
+        .unwrap();
+    tree_builder
+        .insert(".gitmodules", gitmodules_oid, git2::FileMode::Blob.into())
+        .unwrap();
+    tree_builder
+        .insert("sub", sub_commit_oid, git2::FileMode::Commit.into())
+        .unwrap();
+    commit_to_git_repo(&source_git_repo, tree_builder, &[]);
+    source_git_repo.set_head("refs/heads/main").unwrap();
+
+    // Cloning checks out the submodule
+    let stdout = test_env.jj_cmd_success(test_env.env_root(), &["git", "clone", "source", "repo"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Fetching into new repo in "$TEST_ENV/repo"
+    Working copy now at: sqpuoqvx d7d3540a (empty) (no description set)
+    Parent commit      : vwnlntzm e30cbaa1 main | message
+    Added 2 files, modified 0 files, removed 0 files
+    "###);
+    let workspace_root = test_env.env_root().join("repo");
+    assert_eq!(
+        std::fs::read_to_string(workspace_root.join("sub").join("file")).unwrap(),
+        "sub content\n"
+    );
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The working copy is clean
+    Working copy : sqpuoqvx d7d3540a (empty) (no description set)
+    Parent commit: vwnlntzm e30cbaa1 main | message
+    "###);
+
+    // Committing in the submodule moves its HEAD, which is snapshotted
+    let checked_out_repo = git2::Repository::open(workspace_root.join("sub")).unwrap();
+    let head_commit = checked_out_repo.head().unwrap().peel_to_commit().unwrap();
+    let mut tree_builder = checked_out_repo.treebuilder(None).unwrap();
+    let file_oid = checked_out_repo.blob(b"new content\n").unwrap();
+    tree_builder
+        .insert("file", file_oid, git2::FileMode::Blob.into())
+        .unwrap();
+    let signature =
+        git2::Signature::new("Some One", "some.one@example.com", &git2::Time::new(0, 0)).unwrap();
+    let tree = checked_out_repo
+        .find_tree(tree_builder.write().unwrap())
+        .unwrap();
+    checked_out_repo
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "change",
+            &tree,
+            &[&head_commit],
+        )
+        .unwrap();
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy changes:
+    M sub
+    Working copy : sqpuoqvx 66459186 (no description set)
+    Parent commit: vwnlntzm e30cbaa1 main | message
+    "###);
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/sub b/sub
+    index 1e2aced749...93ca4ac1c3 160000
+    --- a/sub
+    +++ b/sub
+    @@ -1,1 +1,1 @@
+    -Subproject commit 1e2aced749f6aa146a5edf38f5029b98bc503c89
+    +Subproject commit 93ca4ac1c3b45ee8cb7133fff4feb1ab2378474a
+    "###);
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["diff"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Modified Git submodule sub:
+       1    1: Git submodule checked out at 1e2aced749f6aa146a5edf38f5029b98bc503c8993ca4ac1c3b45ee8cb7133fff4feb1ab2378474a
+    "###);
+
+    // Going back to the parent checks out the old commit in the submodule
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["new", "@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: royxmykx 437dae99 (empty) (no description set)
+    Parent commit      : vwnlntzm e30cbaa1 main | message
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    assert_eq!(
+        std::fs::read_to_string(workspace_root.join("sub").join("file")).unwrap(),
+        "sub content\n"
+    );
+
+    // Checking out a commit without the submodule leaves its directory since it
+    // isn't empty, but its files aren't tracked
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["new", "root()"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: yqosqzyt 5b36783c (empty) (no description set)
+    Parent commit      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 0 files, removed 2 files
+    "###);
+    assert!(workspace_root.join("sub").join("file").exists());
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The working copy is clean
+    Untracked paths:
+    ? sub (Git repository)
+    Working copy : yqosqzyt 5b36783c (empty) (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+}
@@ -65,3 +65,35 @@ fn test_gitignores() {
     A file3
     "###);
 }
+
+#[test]
+fn test_gitignores_default_excludes_file_and_external_git_repo() {
+    let test_env = TestEnvironment::default();
+    let git_repo_path = test_env.env_root().join("git-repo");
+    git2::Repository::init(&git_repo_path).unwrap();
+    let workspace_root = test_env.env_root().join("repo");
+    std::fs::create_dir(&workspace_root).unwrap();
+    test_env.jj_cmd_success(&workspace_root, &["init", "--git-repo", "../git-repo"]);
+
+    // Without core.excludesFile, the excludes file defaults to
+    // "$XDG_CONFIG_HOME/git/ignore", or "~/.config/git/ignore" if that's unset
+    let git_config_dir = test_env.home_dir().join(".config").join("git");
+    std::fs::create_dir_all(&git_config_dir).unwrap();
+    std::fs::write(git_config_dir.join("ignore"), "file1\n").unwrap();
+
+    // The info/exclude file of an external Git repo is used too
+    std::fs::write(
+        git_repo_path.join(".git").join("info").join("exclude"),
+        "file2\n",
+    )
+    .unwrap();
+
+    std::fs::write(workspace_root.join("file0"), "contents").unwrap();
+    std::fs::write(workspace_root.join("file1"), "contents").unwrap();
+    std::fs::write(workspace_root.join("file2"), "contents").unwrap();
+
+    let stdout = test_env.jj_cmd_success(&workspace_root, &["diff", "-s"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file0
+    "###);
+}
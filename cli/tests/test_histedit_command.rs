@@ -0,0 +1,270 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::{Path, PathBuf};
+
+use crate::common::TestEnvironment;
+
+pub mod common;
+
+/// Creates a stack of commits "a", "b", and "c", each adding a file with the
+/// same name, and an empty working-copy commit on top.
+fn init_repo(test_env: &TestEnvironment) -> PathBuf {
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    for name in ["a", "b", "c"] {
+        test_env.jj_cmd_success(&repo_path, &["new", &format!("-m={name}")]);
+        std::fs::write(repo_path.join(name), format!("{name}\n")).unwrap();
+    }
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    repo_path
+}
+
+#[test]
+fn test_histedit_reorder_and_drop() {
+    let mut test_env = TestEnvironment::default();
+    let repo_path = init_repo(&test_env);
+    let edit_script = test_env.set_up_fake_editor();
+    let a = get_commit_id(&test_env, &repo_path, "description(a)");
+    let b = get_commit_id(&test_env, &repo_path, "description(b)");
+    let c = get_commit_id(&test_env, &repo_path, "description(c)");
+
+    std::fs::write(
+        &edit_script,
+        format!("dump editor0\0write\npick {c} c\npick {a} a\ndrop {b} b\n"),
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["histedit", "description(a)::@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Rebased 1 descendant commits
+    Working copy now at: mzvwutvl ecb63065 (empty) (no description set)
+    Parent commit      : rlvkpnrz 059e1e38 a
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("editor0")).unwrap(), @r###"
+    pick e83b4de5fe8e a
+    pick 5437b8830dce b
+    pick 39b4bd25a11b c
+
+    JJ: Reorder the lines or change the action at the start of a line:
+    JJ:   pick   = keep the revision
+    JJ:   reword = keep the revision, but edit its description
+    JJ:   edit   = keep the revision and make it the working-copy commit
+    JJ:   squash = squash the revision into the one above, combining their descriptions
+    JJ:   fixup  = squash the revision into the one above, keeping only the description above
+    JJ:   drop   = remove the revision and its changes
+    JJ: Removing all lines leaves the history unchanged.
+    JJ: Lines starting with "JJ: " (like this one) will be removed.
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  ecb630658602
+    ◉  059e1e38e15b a
+    ◉  542d0549a167 c
+    ◉  230dd059e1b0
+    ◉  000000000000
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    c
+    "###);
+
+    // All the changes are in a single operation
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  98ed635338f4
+    ◉  39b4bd25a11b c
+    ◉  5437b8830dce b
+    ◉  e83b4de5fe8e a
+    ◉  230dd059e1b0
+    ◉  000000000000
+    "###);
+}
+
+#[test]
+fn test_histedit_squash_and_reword() {
+    let mut test_env = TestEnvironment::default();
+    let repo_path = init_repo(&test_env);
+    let edit_script = test_env.set_up_fake_editor();
+    let a = get_commit_id(&test_env, &repo_path, "description(a)");
+    let b = get_commit_id(&test_env, &repo_path, "description(b)");
+    let c = get_commit_id(&test_env, &repo_path, "description(c)");
+
+    // "b" is squashed into "a" with a combined description, and "c" is squashed
+    // into that keeping only its description, which is then reworded
+    std::fs::write(
+        &edit_script,
+        [
+            &format!("write\nreword {a}\nsquash {b}\nfixup {c}\n"),
+            "next invocation\n",
+            "dump editor1\0write\na and b\n",
+            "next invocation\n",
+            "dump editor2\0write\nab\n",
+        ]
+        .join("\0"),
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["histedit", "description(a)::@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Rebased 1 descendant commits
+    Working copy now at: mzvwutvl 0d57c2c1 (empty) (no description set)
+    Parent commit      : rlvkpnrz 566c9887 ab
+    "###);
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("editor1")).unwrap(), @r###"
+    JJ: Enter a description for the combined commit.
+    JJ: Description from the destination commit:
+    a
+
+    JJ: Description from the source commit:
+    b
+
+    JJ: Lines starting with "JJ: " (like this one) will be removed.
+    "###);
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("editor2")).unwrap(), @r###"
+    a and b
+
+    JJ: Lines starting with "JJ: " (like this one) will be removed.
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  0d57c2c1e7fe
+    ◉  566c988764b3 ab
+    ◉  230dd059e1b0
+    ◉  000000000000
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files"]);
+    insta::assert_snapshot!(stdout, @r###"
+    a
+    b
+    c
+    "###);
+}
+
+#[test]
+fn test_histedit_edit() {
+    let mut test_env = TestEnvironment::default();
+    let repo_path = init_repo(&test_env);
+    let edit_script = test_env.set_up_fake_editor();
+    let a = get_commit_id(&test_env, &repo_path, "description(a)");
+    let b = get_commit_id(&test_env, &repo_path, "description(b)");
+    let c = get_commit_id(&test_env, &repo_path, "description(c)");
+
+    std::fs::write(&edit_script, format!("write\np {b}\ne {a}\np {c}\n")).unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["histedit", "description(a)::@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Rebased 1 descendant commits
+    Working copy now at: rlvkpnrz 9d7f3d8e a
+    Parent commit      : kkmpptxz ebd5f6be b
+    Added 0 files, modified 0 files, removed 1 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    ◉  e99e855f13b9 c
+    @  9d7f3d8e3098 a
+    ◉  ebd5f6be4460 b
+    ◉  230dd059e1b0
+    ◉  000000000000
+    "###);
+}
+
+#[test]
+fn test_histedit_no_changes() {
+    let mut test_env = TestEnvironment::default();
+    let repo_path = init_repo(&test_env);
+    let edit_script = test_env.set_up_fake_editor();
+
+    // Keeping the plan as is changes nothing
+    std::fs::write(&edit_script, "").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["histedit", "description(a)::@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+
+    // Removing all lines changes nothing either
+    std::fs::write(&edit_script, "write\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["histedit", "description(a)::@-"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+}
+
+#[test]
+fn test_histedit_errors() {
+    let mut test_env = TestEnvironment::default();
+    let repo_path = init_repo(&test_env);
+    let edit_script = test_env.set_up_fake_editor();
+    let a = get_commit_id(&test_env, &repo_path, "description(a)");
+    let b = get_commit_id(&test_env, &repo_path, "description(b)");
+    let c = get_commit_id(&test_env, &repo_path, "description(c)");
+
+    let stderr =
+        test_env.jj_cmd_failure(&repo_path, &["histedit", "description(a) | description(c)"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot edit the history since the revisions don't form a linear stack
+    "###);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["histedit", "root()"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot rewrite the root commit
+    "###);
+
+    let check_plan = |plan: String| {
+        std::fs::write(&edit_script, format!("write\n{plan}")).unwrap();
+        test_env.jj_cmd_failure(&repo_path, &["histedit", "description(a)::@-"])
+    };
+    insta::assert_snapshot!(check_plan(format!("pick {a}\npick {b}\n")), @r###"
+    Error: Revision 39b4bd25a11b is missing from the plan
+    Hint: Use "drop" to remove a revision.
+    "###);
+    insta::assert_snapshot!(check_plan(format!("pick {a}\nmove {b}\npick {c}\n")), @r###"
+    Error: Unknown action "move" in line "move 5437b8830dce"
+    "###);
+    insta::assert_snapshot!(check_plan(format!("pick {a}\npick\npick {c}\n")), @r###"
+    Error: Missing revision in line "pick"
+    "###);
+    insta::assert_snapshot!(check_plan(format!("pick {a}\npick {a}\npick {b}\npick {c}\n")), @r###"
+    Error: Revision "e83b4de5fe8e" appears more than once
+    "###);
+    insta::assert_snapshot!(check_plan(format!("pick {a}\npick {b}\npick {c}\npick 000\n")), @r###"
+    Error: Revision "000" is not one of the revisions being edited
+    "###);
+    insta::assert_snapshot!(check_plan(format!("drop {a}\nsquash {b}\npick {c}\n")), @r###"
+    Error: Cannot squash revision 5437b8830dce since there's no revision above it
+    "###);
+    insta::assert_snapshot!(check_plan(format!("edit {a}\nedit {b}\npick {c}\n")), @r###"
+    Error: Only one revision can be marked "edit"
+    "###);
+
+    // Nothing changed
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  98ed635338f4
+    ◉  39b4bd25a11b c
+    ◉  5437b8830dce b
+    ◉  e83b4de5fe8e a
+    ◉  230dd059e1b0
+    ◉  000000000000
+    "###);
+}
+
+fn get_commit_id(test_env: &TestEnvironment, repo_path: &Path, revision: &str) -> String {
+    test_env.jj_cmd_success(
+        repo_path,
+        &["log", "--no-graph", "-T=commit_id.short()", "-r", revision],
+    )
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    let template = r#"commit_id.short() ++ " " ++ description.first_line()"#;
+    test_env.jj_cmd_success(repo_path, &["log", "-T", template])
+}
@@ -0,0 +1,150 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use crate::common::TestEnvironment;
+
+pub mod common;
+
+#[test]
+fn test_parallelize_linear() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    for n in 1..=3 {
+        test_env.jj_cmd_success(&repo_path, &["new", &format!("-m={n}")]);
+        std::fs::write(repo_path.join(format!("file{n}")), n.to_string()).unwrap();
+    }
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @
+    ◉  3
+    ◉  2
+    ◉  1
+    ◉
+    ◉
+    "###);
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["parallelize", "description(1)::description(3)"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: mzvwutvl cd0c562b (empty) (no description set)
+    Parent commit      : rlvkpnrz 36273bae 1
+    Parent commit      : kkmpptxz 5eca56cc 2
+    Parent commit      : zsuskuln 15af1561 3
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @
+    ├─┬─╮
+    │ │ ◉  3
+    │ ◉ │  2
+    │ ├─╯
+    ◉ │  1
+    ├─╯
+    ◉
+    ◉
+    "###);
+    // The merge has the changes of all of them
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file1
+    file2
+    file3
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r=description(3)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file3
+    "###);
+}
+
+#[test]
+fn test_parallelize_with_other_children() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    for n in 1..=3 {
+        test_env.jj_cmd_success(&repo_path, &["new", &format!("-m={n}")]);
+        std::fs::write(repo_path.join(format!("file{n}")), n.to_string()).unwrap();
+    }
+    test_env.jj_cmd_success(&repo_path, &["new", "description(1)", "-m=side"]);
+    test_env.jj_cmd_success(&repo_path, &["new", "description(3)", "-m=top"]);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  top
+    ◉  3
+    ◉  2
+    │ ◉  side
+    ├─╯
+    ◉  1
+    ◉
+    ◉
+    "###);
+
+    // Only the children of the head become merges
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "parallelize",
+            "description(1)",
+            "description(2)",
+            "description(3)",
+        ],
+    );
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @      top
+    ├─┬─╮
+    │ │ ◉  3
+    │ ◉ │  2
+    │ ├─╯
+    │ │ ◉  side
+    ├───╯
+    ◉ │  1
+    ├─╯
+    ◉
+    ◉
+    "###);
+}
+
+#[test]
+fn test_parallelize_disconnected() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    for n in 1..=3 {
+        test_env.jj_cmd_success(&repo_path, &["new", &format!("-m={n}")]);
+    }
+    let stderr = test_env.jj_cmd_failure(
+        &repo_path,
+        &["parallelize", "description(1)", "description(3)"],
+    );
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Cannot parallelize since the target revisions are not connected.
+    "###);
+
+    // A single revision is left alone
+    let stdout = test_env.jj_cmd_success(&repo_path, &["parallelize", "description(2)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    let template = r#"description.first_line()"#;
+    test_env.jj_cmd_success(repo_path, &["log", "-T", template])
+}
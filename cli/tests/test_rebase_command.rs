@@ -524,3 +524,60 @@ fn test_rebase_with_descendants() {
 fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
     test_env.jj_cmd_success(repo_path, &["log", "-T", "branches"])
 }
+
+#[test]
+fn test_rebase_with_merge_driver() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(
+        r#"
+        [merge.drivers]
+        "CHANGELOG.md" = "union"
+        "#,
+    );
+
+    std::fs::write(repo_path.join("CHANGELOG.md"), "# Changes\n\n").unwrap();
+    std::fs::write(repo_path.join("other"), "base\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "base"]);
+    test_env.jj_cmd_success(&repo_path, &["new", "base", "-m", "a"]);
+    std::fs::write(repo_path.join("CHANGELOG.md"), "# Changes\n\n* a\n").unwrap();
+    std::fs::write(repo_path.join("other"), "a\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "a"]);
+    test_env.jj_cmd_success(&repo_path, &["new", "base", "-m", "b"]);
+    std::fs::write(repo_path.join("CHANGELOG.md"), "# Changes\n\n* b\n").unwrap();
+    std::fs::write(repo_path.join("other"), "b\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "b"]);
+
+    // The changelog is merged by the driver while the other file conflicts
+    let stdout = test_env.jj_cmd_success(&repo_path, &["rebase", "-r", "b", "-d", "a"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: mzvwutvl c8966805 b | (conflict) b
+    Parent commit      : kkmpptxz b639eb49 a | a
+    Added 0 files, modified 2 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "-r", "b", "CHANGELOG.md"]);
+    insta::assert_snapshot!(stdout, @r###"
+    # Changes
+
+    * a
+    * b
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "--list", "-r", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    other    2-sided conflict
+    "###);
+
+    // Invalid config is reported
+    test_env.add_config(
+        r#"
+        [merge.drivers]
+        "other" = "mine"
+        "#,
+    );
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["log"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Config error: Invalid merge config: merge.drivers."other": unknown builtin merge driver: mine
+    For help, see https://github.com/martinvonz/jj/blob/main/docs/config.md.
+    "###);
+}
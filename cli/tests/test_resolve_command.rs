@@ -288,8 +288,8 @@ fn check_resolve_produces_input_file(
     // in the future. See also https://github.com/mitsuhiko/insta/issues/313.
     assert_eq!(
         &test_env.jj_cmd_failure(repo_path, &["resolve", "--config-toml", &merge_arg_config]),
-        "Error: Failed to use external tool to resolve: The output file is either unchanged or \
-         empty after the editor quit (run with --verbose to see the exact invocation).\n"
+        "Error: Failed to resolve conflicts: The output file is either unchanged or empty after \
+         the editor quit (run with --verbose to see the exact invocation).\n"
     );
 }
 
@@ -397,7 +397,8 @@ fn test_too_many_parents() {
 
     let error = test_env.jj_cmd_failure(&repo_path, &["resolve"]);
     insta::assert_snapshot!(error, @r###"
-    Error: Failed to use external tool to resolve: The conflict at "file" has 3 sides. At most 2 sides are supported.
+    Using default editor 'meld'; you can change this by setting ui.merge-editor
+    Error: Failed to resolve conflicts: The conflict at "file" has 3 sides. At most 2 sides are supported by external merge tools.
     "###);
 }
 
@@ -472,12 +473,19 @@ fn test_file_vs_dir() {
     "###);
     let error = test_env.jj_cmd_failure(&repo_path, &["resolve"]);
     insta::assert_snapshot!(error, @r###"
-    Error: Failed to use external tool to resolve: Only conflicts that involve normal files (not symlinks, not executable, etc.) are supported. Conflict summary for "file":
+    Using default editor 'meld'; you can change this by setting ui.merge-editor
+    Error: Failed to resolve conflicts: Only conflicts that involve normal files (not symlinks, not executable, etc.) are supported. Conflict summary for "file":
     Conflict:
       Removing file with id df967b96a579e45a18b8251732d16804b2e56a55
       Adding file with id 78981922613b2afb6025042ff6bd878ac1994e85
       Adding tree with id 133bb38fc4e4bf6b551f1f04db7e48f04cac2877
+    "###);
 
+    // The directory can be picked as the resolution
+    test_env.jj_cmd_success(&repo_path, &["resolve", "--tool", ":theirs"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file/placeholder
     "###);
 }
 
@@ -526,13 +534,13 @@ fn test_description_with_dir_and_deletion() {
     "###);
     let error = test_env.jj_cmd_failure(&repo_path, &["resolve"]);
     insta::assert_snapshot!(error, @r###"
-    Error: Failed to use external tool to resolve: Only conflicts that involve normal files (not symlinks, not executable, etc.) are supported. Conflict summary for "file":
+    Using default editor 'meld'; you can change this by setting ui.merge-editor
+    Error: Failed to resolve conflicts: Only conflicts that involve normal files (not symlinks, not executable, etc.) are supported. Conflict summary for "file":
     Conflict:
       Removing file with id df967b96a579e45a18b8251732d16804b2e56a55
       Removing file with id df967b96a579e45a18b8251732d16804b2e56a55
       Adding file with id 61780798228d17af2d34fce4cfbdf35556832472
       Adding tree with id 133bb38fc4e4bf6b551f1f04db7e48f04cac2877
-
     "###);
 }
 
@@ -728,3 +736,121 @@ fn test_multiple_conflicts() {
     Error: No conflicts found at this revision
     "###);
 }
+
+#[test]
+fn test_resolve_pick_side() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(
+        &test_env,
+        &repo_path,
+        "base",
+        &[],
+        &[("file", "base\n"), ("binary", "base\0")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "a",
+        &["base"],
+        &[("file", "a\n"), ("binary", "a\0")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "b",
+        &["base"],
+        &[("file", "b\n"), ("binary", "b\0")],
+    );
+    create_commit(
+        &test_env,
+        &repo_path,
+        "c",
+        &["base"],
+        &[("file", "c\n"), ("binary", "c\0")],
+    );
+    create_commit(&test_env, &repo_path, "conflict", &["a", "b"], &[]);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["resolve", "--list"]), @r###"
+    binary    2-sided conflict
+    file      2-sided conflict
+    "###);
+
+    // Binary files can be resolved by picking a side
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "--tool", ":theirs", "binary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: znkkpsqq 8e3aea90 conflict | (conflict) conflict
+    Parent commit      : zsuskuln 803af62a a | a
+    Parent commit      : royxmykx dd8abddd b | b
+    Added 0 files, modified 1 files, removed 0 files
+    After this operation, some files at this revision still have conflicts:
+    file    2-sided conflict
+    "###);
+    assert_eq!(std::fs::read(repo_path.join("binary")).unwrap(), b"b\0");
+    test_env.jj_cmd_success(&repo_path, &["resolve", "--tool", ":ours", "file"]);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["cat", "file"]), @r###"
+    a
+    "###);
+
+    // Any side of a conflict with more sides can be picked
+    create_commit(&test_env, &repo_path, "conflict3", &["a", "b", "c"], &[]);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["resolve", "--tool", ":side-4", "file"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Failed to resolve conflicts: The conflict at "file" has 3 sides, so there is no side #4
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "--tool", ":side-3", "file"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: nkmrtpmo 8d49481a conflict3 | (conflict) conflict3
+    Parent commit      : zsuskuln 803af62a a | a
+    Parent commit      : royxmykx dd8abddd b | b
+    Parent commit      : vruxwmqv 5797f933 c | c
+    Added 0 files, modified 1 files, removed 0 files
+    After this operation, some files at this revision still have conflicts:
+    binary    3-sided conflict
+    "###);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["cat", "file"]), @r###"
+    c
+    "###);
+
+    // The builtin tools can also be configured as the default merge tool
+    test_env.jj_cmd_success(
+        &repo_path,
+        &["resolve", "--config-toml=ui.merge-editor=':side-2'"],
+    );
+    assert_eq!(std::fs::read(repo_path.join("binary")).unwrap(), b"b\0");
+}
+
+#[test]
+fn test_resolve_pick_side_executable() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    create_commit(&test_env, &repo_path, "base", &[], &[("file", "base\n")]);
+    create_commit(&test_env, &repo_path, "a", &["base"], &[("file", "a\n")]);
+    test_env.jj_cmd_success(&repo_path, &["chmod", "x", "file"]);
+    create_commit(&test_env, &repo_path, "b", &["base"], &[("file", "b\n")]);
+    create_commit(&test_env, &repo_path, "conflict", &["a", "b"], &[]);
+    insta::assert_snapshot!(test_env.jj_cmd_success(&repo_path, &["resolve", "--list"]), @r###"
+    file    2-sided conflict including an executable
+    "###);
+
+    // The executable bit is taken from the picked side
+    test_env.jj_cmd_success(&repo_path, &["resolve", "--tool", ":ours"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git", "-r", "@"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file b/file
+    old mode 100644
+    new mode 100755
+    index 0000000000...7898192261
+    --- a/file
+    +++ b/file
+    @@ -1,4 +1,1 @@
+    -Conflict:
+    -  Removing file with id df967b96a579e45a18b8251732d16804b2e56a55
+    -  Adding executable file with id 78981922613b2afb6025042ff6bd878ac1994e85
+    -  Adding file with id 61780798228d17af2d34fce4cfbdf35556832472
+    +a
+    "###);
+}
@@ -0,0 +1,224 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(unix)]
+
+use std::path::Path;
+
+use crate::common::{get_stderr_string, get_stdout_string, TestEnvironment};
+
+pub mod common;
+
+fn init_stack(test_env: &TestEnvironment) -> std::path::PathBuf {
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    for name in ["a", "b", "c"] {
+        test_env.jj_cmd_success(&repo_path, &["new", &format!("-m={name}")]);
+        std::fs::write(repo_path.join("file"), format!("{name}\n")).unwrap();
+    }
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    repo_path
+}
+
+#[test]
+fn test_run() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_stack(&test_env);
+    // A file in the working copy that must not be touched
+    std::fs::write(repo_path.join("untracked"), "").unwrap();
+
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "run",
+            "cat file",
+            "-r=description(a)::description(c)",
+            "-j=2",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    c
+    zsuskuln 37047592 c: exit status: 0
+    b
+    kkmpptxz 224e25a9 b: exit status: 0
+    a
+    rlvkpnrz 171989ed a: exit status: 0
+    "###);
+    // No workspaces are added for the temporary working copies
+    let stdout = test_env.jj_cmd_success(&repo_path, &["workspace", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    default: mzvwutvl fb9bd017 (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  fb9bd017fdb3
+    ◉  3704759258f2 c
+    ◉  224e25a915c2 b
+    ◉  171989ed63bd a
+    ◉  230dd059e1b0
+    ◉  000000000000
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["st"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy changes:
+    A untracked
+    Working copy : mzvwutvl fb9bd017 (no description set)
+    Parent commit: zsuskuln 37047592 c
+    "###);
+}
+
+#[test]
+fn test_run_failure() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_stack(&test_env);
+
+    let assert = test_env
+        .jj_cmd(
+            &repo_path,
+            &["run", "grep -q b file", "-r=description(a)::description(c)"],
+        )
+        .assert()
+        .code(1);
+    insta::assert_snapshot!(get_stdout_string(&assert), @r###"
+    zsuskuln 37047592 c: exit status: 1
+    kkmpptxz 224e25a9 b: exit status: 0
+    rlvkpnrz 171989ed a: exit status: 1
+    "###);
+    insta::assert_snapshot!(get_stderr_string(&assert), @r###"
+    Error: The command failed on 2 of 3 revisions
+    "###);
+}
+
+#[test]
+fn test_run_commit_changes() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_stack(&test_env);
+
+    // Without --commit-changes, the changes are discarded
+    let (stdout, stderr) =
+        test_env.jj_cmd_ok(&repo_path, &["run", "echo x >> file", "-r=description(b)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    kkmpptxz 224e25a9 b: exit status: 0
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Hint: The command changed files in 1 revisions. Use --commit-changes to record the changes.
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  2d8c295a2f0c
+    ◉  3704759258f2 c
+    ◉  224e25a915c2 b
+    ◉  171989ed63bd a
+    ◉  230dd059e1b0
+    ◉  000000000000
+    "###);
+
+    // With it, the revisions are rewritten with the changes the command made, and
+    // their descendants are rebased
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "run",
+            "echo new > new-file",
+            "-r=description(a)|description(b)",
+            "--commit-changes",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    kkmpptxz 224e25a9 b: exit status: 0
+    rlvkpnrz 171989ed a: exit status: 0
+    Rebased 2 descendant commits
+    Working copy now at: mzvwutvl 68a0a66a (empty) (no description set)
+    Parent commit      : zsuskuln 64dc6390 c
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  68a0a66acac2
+    ◉  64dc6390caea c
+    ◉  eecdff34905b b
+    ◉  287b9edf9467 a
+    ◉  230dd059e1b0
+    ◉  000000000000
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r=description(a)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file
+    A new-file
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r=description(b)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "new-file", "-r=description(c)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    new
+    "###);
+    // Each run with --commit-changes is a single operation. Runs without it don't
+    // create any.
+    let stdout = test_env.jj_cmd_success(&repo_path, &["op", "log", "-Tdescription", "--limit=2"]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  run command on 2 commits
+    ◉  new empty commit
+    "###);
+}
+
+#[test]
+fn test_run_error() {
+    let test_env = TestEnvironment::default();
+    let repo_path = init_stack(&test_env);
+
+    // A file with a non-UTF-8 name can't be snapshotted. The error is reported
+    // next to the revision, and the changes in the other revisions are still
+    // committed.
+    let assert = test_env
+        .jj_cmd(
+            &repo_path,
+            &[
+                "run",
+                r#"echo x >> file; if grep -q b file; then touch "$(printf '\377')"; fi"#,
+                "-r=description(a)::description(c)",
+                "-j=1",
+                "--commit-changes",
+            ],
+        )
+        .assert()
+        .code(1);
+    insta::assert_snapshot!(get_stdout_string(&assert), @r###"
+    zsuskuln 37047592 c: exit status: 0
+    rlvkpnrz 171989ed a: exit status: 0
+    Rebased 1 descendant commits
+    Working copy now at: mzvwutvl 5a5c3e1c (empty) (no description set)
+    Parent commit      : zsuskuln a09017f0 c
+    Added 0 files, modified 1 files, removed 0 files
+    "###);
+    insta::assert_snapshot!(get_stderr_string(&assert), @r###"
+    kkmpptxz 224e25a9 b: Error: Failed to snapshot the changes: Working copy path � is not valid UTF-8
+    Error: The command failed on 1 of 3 revisions
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  5a5c3e1c5f56
+    ◉  a09017f081dd c
+    ◉  b21f49b33e40 b
+    ◉  715036bc414f a
+    ◉  230dd059e1b0
+    ◉  000000000000
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r=description(b)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file
+    "###);
+}
+
+fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
+    let template = r#"commit_id.short() ++ " " ++ description.first_line()"#;
+    test_env.jj_cmd_success(repo_path, &["log", "-T", template])
+}
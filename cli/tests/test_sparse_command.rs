@@ -169,3 +169,125 @@ fn test_sparse_manage_patterns() {
     file3
     "###);
 }
+
+#[test]
+fn test_sparse_glob_and_exclude_patterns() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    for dir in ["services/a/src", "services/a/testdata", "services/b/src"] {
+        std::fs::create_dir_all(repo_path.join(dir)).unwrap();
+        std::fs::write(repo_path.join(dir).join("file"), "contents").unwrap();
+    }
+    std::fs::write(repo_path.join("README.md"), "contents").unwrap();
+
+    // Globs are relative to the workspace root, and other patterns are relative
+    // to the current directory
+    let stdout = test_env.jj_cmd_success(
+        &repo_path.join("services"),
+        &[
+            "sparse",
+            "set",
+            "--clear",
+            "--add",
+            ".",
+            "--add",
+            "*.md",
+            "--add",
+            "!services/*/testdata",
+            "--add",
+            "!b",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Added 0 files, modified 0 files, removed 2 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["sparse", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    services
+    *.md
+    !services/b
+    !services/*/testdata
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path.join("services"), &["sparse", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    .
+    *.md
+    !b
+    !services/*/testdata
+    "###);
+    assert!(repo_path.join("README.md").exists());
+    assert!(repo_path.join("services/a/src/file").exists());
+    assert!(!repo_path.join("services/a/testdata").exists());
+    assert!(!repo_path.join("services/b").exists());
+
+    // Patterns can be removed
+    let stdout = test_env.jj_cmd_success(&repo_path, &["sparse", "set", "--remove", "!services/b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added 1 files, modified 0 files, removed 0 files
+    "###);
+    assert!(repo_path.join("services/b/src/file").exists());
+
+    // Invalid globs are rejected
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["sparse", "set", "--add", "[a"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Invalid glob "[a": Pattern syntax error near position 0: invalid range pattern
+    "###);
+}
+
+#[test]
+fn test_sparse_edit_and_reset() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    let edit_script = test_env.set_up_fake_editor();
+
+    std::fs::create_dir_all(repo_path.join("dir/sub")).unwrap();
+    std::fs::write(repo_path.join("dir/file"), "contents").unwrap();
+    std::fs::write(repo_path.join("dir/sub/file"), "contents").unwrap();
+    std::fs::write(repo_path.join("file.txt"), "contents").unwrap();
+
+    // The patterns are written relative to the workspace root, and read back in
+    // the same syntax
+    std::fs::write(&edit_script, "dump patterns0\0write\ndir\n!dir/sub\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path.join("dir"), &["sparse", "edit"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added 0 files, modified 0 files, removed 2 files
+    "###);
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("patterns0")).unwrap(), @r###"
+    .
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["sparse", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    dir
+    !dir/sub
+    "###);
+    assert!(repo_path.join("dir/file").exists());
+    assert!(!repo_path.join("dir/sub").exists());
+    assert!(!repo_path.join("file.txt").exists());
+
+    std::fs::write(&edit_script, "dump patterns1\0write\n*.txt\n").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["sparse", "edit"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added 1 files, modified 0 files, removed 1 files
+    "###);
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("patterns1")).unwrap(), @r###"
+    dir
+    !dir/sub
+    "###);
+    assert!(!repo_path.join("dir").exists());
+    assert!(repo_path.join("file.txt").exists());
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["sparse", "reset"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Added 2 files, modified 0 files, removed 0 files
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["sparse", "list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    .
+    "###);
+    assert!(repo_path.join("dir/sub/file").exists());
+}
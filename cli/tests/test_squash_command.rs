@@ -259,6 +259,128 @@ fn test_squash_partial() {
     "###);
 }
 
+#[test]
+fn test_squash_from_to() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "a"]);
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "b"]);
+    std::fs::write(repo_path.join("file2"), "b\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "c"]);
+    std::fs::write(repo_path.join("file1"), "c\n").unwrap();
+    std::fs::write(repo_path.join("file3"), "c\n").unwrap();
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  76b531c5171a c
+    ◉  3e759b123f54 b
+    ◉  90aeefd03044 a
+    ◉  000000000000
+    "###);
+
+    // Squashes the working copy into an ancestor and rebases the descendants
+    let stdout = test_env.jj_cmd_success(&repo_path, &["squash", "--into", "a"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Rebased 1 descendant commits
+    Working copy now at: vruxwmqv a7485743 (empty) (no description set)
+    Parent commit      : kkmpptxz aade998a b c | (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  a7485743cba9
+    ◉  aade998a12fe b c
+    ◉  db349a4e7769 a
+    ◉  000000000000
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["print", "file1", "-r", "a"]);
+    insta::assert_snapshot!(stdout, @r###"
+    c
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file2
+    "###);
+
+    // Only the given paths are moved
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["squash", "--into", "a", "file3"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Rebased 2 descendant commits
+    Working copy now at: mzvwutvl 8be768a1 c | (no description set)
+    Parent commit      : kkmpptxz 495a6c77 b | (no description set)
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r", "a"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file1
+    A file3
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file1
+    "###);
+
+    // Can squash from an ancestor into a descendant
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["squash", "--from", "b"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: mzvwutvl 0005a616 c | (no description set)
+    Parent commit      : qpvuntsm 90aeefd0 a b | (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  0005a616fe90 c
+    ◉  90aeefd03044 a b
+    ◉  000000000000
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s"]);
+    insta::assert_snapshot!(stdout, @r###"
+    M file1
+    A file2
+    A file3
+    "###);
+
+    // Can squash several revisions at once
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &["squash", "--from", "b", "--from", "c", "--into", "a"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: tlkvzzqu 80cb694a (empty) (no description set)
+    Parent commit      : qpvuntsm 0690788c a b c | (no description set)
+    "###);
+    insta::assert_snapshot!(get_log_output(&test_env, &repo_path), @r###"
+    @  80cb694a2838
+    ◉  0690788c1aff a b c
+    ◉  000000000000
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "-s", "-r", "a"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file1
+    A file2
+    A file3
+    "###);
+
+    // Cannot squash a revision into itself
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["squash", "--from", "a", "--into", "a"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Source and destination cannot be the same.
+    "###);
+
+    // -r cannot be combined with --from or --into
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["squash", "-r", "b", "--into", "a"]);
+    insta::assert_snapshot!(stderr, @r###"
+    error: the argument '--revision <REVISION>' cannot be used with '--into <INTO>'
+
+    Usage: jj squash --revision <REVISION> [PATHS]...
+
+    For more information, try '--help'.
+    "###);
+}
+
 fn get_log_output(test_env: &TestEnvironment, repo_path: &Path) -> String {
     let template = r#"commit_id.short() ++ " " ++ branches"#;
     test_env.jj_cmd_success(repo_path, &["log", "-T", template])
@@ -376,6 +498,67 @@ fn test_squash_empty() {
     "###);
 }
 
+#[test]
+fn test_squash_from_multiple_description() {
+    let mut test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    test_env.jj_cmd_success(&repo_path, &["describe", "-m", "destination"]);
+    std::fs::write(repo_path.join("file1"), "a\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "-m", "source 1"]);
+    std::fs::write(repo_path.join("file2"), "b\n").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "-m", "source 2"]);
+    std::fs::write(repo_path.join("file3"), "c\n").unwrap();
+    std::fs::write(repo_path.join("file4"), "c\n").unwrap();
+
+    // The descriptions of all the abandoned sources are combined in one editor
+    let edit_script = test_env.set_up_fake_editor();
+    std::fs::write(&edit_script, "dump editor0").unwrap();
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "squash",
+            "--from",
+            "@|@-",
+            "--into",
+            "description(destination)",
+        ],
+    );
+    insta::assert_snapshot!(get_description(&test_env, &repo_path, "@-"), @r###"
+    destination
+
+    source 2
+
+    source 1
+    "###);
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("editor0")).unwrap(), @r###"
+    JJ: Enter a description for the combined commit.
+    JJ: Description from the destination commit:
+    destination
+
+    JJ: Description from the source commit:
+    source 2
+
+    JJ: Description from the source commit:
+    source 1
+
+    JJ: Lines starting with "JJ: " (like this one) will be removed.
+    "###);
+
+    // A source that doesn't become empty keeps its description
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    std::fs::write(&edit_script, "fail").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["squash", "--into", "@--", "file3"]);
+    insta::assert_snapshot!(get_description(&test_env, &repo_path, "@--"), @r###"
+    destination
+    "###);
+    insta::assert_snapshot!(get_description(&test_env, &repo_path, "@"), @r###"
+    source 2
+    "###);
+}
+
 fn get_description(test_env: &TestEnvironment, repo_path: &Path, rev: &str) -> String {
     test_env.jj_cmd_success(
         repo_path,
@@ -0,0 +1,156 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::TestEnvironment;
+
+pub mod common;
+
+#[test]
+fn test_auto_track_and_track() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"snapshot.auto-track = ["src", "*.md"]"#);
+
+    std::fs::create_dir_all(repo_path.join("src/generated")).unwrap();
+    std::fs::write(repo_path.join("src/lib.rs"), "lib").unwrap();
+    std::fs::write(repo_path.join("src/generated/out.rs"), "generated").unwrap();
+    std::fs::write(repo_path.join("README.md"), "readme").unwrap();
+    std::fs::write(repo_path.join("secret.env"), "secret").unwrap();
+    std::fs::create_dir(repo_path.join("build")).unwrap();
+    std::fs::write(repo_path.join("build/output"), "output").unwrap();
+
+    // Only the new files matching the patterns are tracked, and the others are
+    // reported as untracked
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy changes:
+    A README.md
+    A src/generated/out.rs
+    A src/lib.rs
+    Untracked paths:
+    ? build/output
+    ? secret.env
+    Working copy : qpvuntsm db5fa6b9 (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path.join("build"), &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy changes:
+    A ../README.md
+    A ../src/generated/out.rs
+    A ../src/lib.rs
+    Untracked paths:
+    ? output
+    ? ../secret.env
+    Working copy : qpvuntsm db5fa6b9 (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+
+    // Files can be tracked explicitly
+    let stdout = test_env.jj_cmd_success(&repo_path, &["track", "build"]);
+    insta::assert_snapshot!(stdout, @"");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["files"]);
+    insta::assert_snapshot!(stdout, @r###"
+    README.md
+    build/output
+    src/generated/out.rs
+    src/lib.rs
+    "###);
+
+    // Tracked files stay tracked even if they don't match the patterns
+    std::fs::write(repo_path.join("build/output"), "modified").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A README.md
+    A build/output
+    A src/generated/out.rs
+    A src/lib.rs
+    "###);
+
+    // Tracking files that are already tracked does nothing
+    let stdout = test_env.jj_cmd_success(&repo_path, &["track", "build"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+
+    // Excluded paths aren't tracked automatically
+    test_env.add_config(r#"snapshot.auto-track = ["src", "!src/generated"]"#);
+    std::fs::write(repo_path.join("src/generated/more.rs"), "generated").unwrap();
+    std::fs::write(repo_path.join("src/main.rs"), "main").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy changes:
+    A README.md
+    A build/output
+    A src/generated/out.rs
+    A src/lib.rs
+    A src/main.rs
+    Untracked paths:
+    ? secret.env
+    ? src/generated/more.rs
+    Working copy : qpvuntsm ffb0433e (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+}
+
+#[test]
+fn test_auto_track_none() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"snapshot.auto-track = []"#);
+
+    std::fs::write(repo_path.join("file1"), "contents").unwrap();
+    std::fs::write(repo_path.join("file2"), "contents").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The working copy is clean
+    Untracked paths:
+    ? file1
+    ? file2
+    Working copy : qpvuntsm 230dd059 (empty) (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["track", "file1"]);
+    insta::assert_snapshot!(stdout, @"");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy changes:
+    A file1
+    Untracked paths:
+    ? file2
+    Working copy : qpvuntsm 8b9fcc92 (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+
+    // Errors out when no path is specified
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["track"]);
+    insta::assert_snapshot!(stderr, @r###"
+    error: the following required arguments were not provided:
+      <PATHS>...
+
+    Usage: jj track <PATHS>...
+
+    For more information, try '--help'.
+    "###);
+
+    // Errors out on invalid patterns
+    test_env.add_config(r#"snapshot.auto-track = "[""#);
+    let stderr = test_env.jj_cmd_failure(&repo_path, &["status"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Error: Invalid glob "[": Pattern syntax error near position 0: invalid range pattern
+    "###);
+}
@@ -53,12 +53,12 @@ fn test_enable_tree_level_conflicts() {
 
     // Enable tree-level conflicts
     test_env.add_config(r#"format.tree-level-conflicts = true"#);
-    // We get a new working-copy commit. The working copy unfortunately appears
-    // non-empty
+    // We get a new working-copy commit since the working copy is snapshotted
+    // with the new format. It still appears empty.
     let stdout = test_env.jj_cmd_success(&repo_path, &["log"]);
     insta::assert_snapshot!(stdout, @r###"
     @  mzvwutvl test.user@example.com 2001-02-03 04:05:13.000 +07:00 54c562fa conflict
-    │  (no description set)
+    │  (empty) (no description set)
     ◉    zsuskuln test.user@example.com 2001-02-03 04:05:10.000 +07:00 5100e4e1 conflict
     ├─╮  (empty) merge
     │ ◉  kkmpptxz test.user@example.com 2001-02-03 04:05:10.000 +07:00 0b65c8fb
@@ -83,3 +83,95 @@ fn test_enable_tree_level_conflicts() {
     let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
     insta::assert_snapshot!(stdout, @"");
 }
+
+#[test]
+fn test_upgrade_repo() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    // Create a conflicted merge and a branch in the legacy format
+    let file_path = repo_path.join("file");
+    test_env.jj_cmd_success(&repo_path, &["new", "root()", "-m=left"]);
+    std::fs::write(&file_path, "left").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["new", "root()", "-m=right"]);
+    std::fs::write(&file_path, "right").unwrap();
+    test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "new",
+            r#"description("left")"#,
+            r#"description("right")"#,
+            "-m=merge",
+        ],
+    );
+    test_env.jj_cmd_success(&repo_path, &["branch", "create", "merge"]);
+    test_env.jj_cmd_success(&repo_path, &["new"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log"]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  royxmykx test.user@example.com 2001-02-03 04:05:12.000 +07:00 f543845c conflict
+    │  (empty) (no description set)
+    ◉    zsuskuln test.user@example.com 2001-02-03 04:05:10.000 +07:00 merge 5100e4e1 conflict
+    ├─╮  (empty) merge
+    │ ◉  kkmpptxz test.user@example.com 2001-02-03 04:05:10.000 +07:00 0b65c8fb
+    │ │  right
+    ◉ │  rlvkpnrz test.user@example.com 2001-02-03 04:05:09.000 +07:00 32003b88
+    ├─╯  left
+    ◉  zzzzzzzz root() 00000000
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["util", "upgrade-repo"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy now at: royxmykx 5b17340d (conflict) (empty) (no description set)
+    Parent commit      : zsuskuln 5f5ea6e6 merge | (conflict) (empty) merge
+    Enabled tree-level conflicts in the repo config and rewrote 2 commits
+    "###);
+    let config = std::fs::read_to_string(repo_path.join(".jj/repo/config.toml")).unwrap();
+    insta::assert_snapshot!(config, @r###"
+    [format]
+    tree-level-conflicts = true
+    "###);
+    // The commits were rewritten but still have the same conflicts, and the
+    // working copy still has no diff
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log"]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  royxmykx test.user@example.com 2001-02-03 04:05:14.000 +07:00 5b17340d conflict
+    │  (empty) (no description set)
+    ◉    zsuskuln test.user@example.com 2001-02-03 04:05:14.000 +07:00 merge 5f5ea6e6 conflict
+    ├─╮  (empty) merge
+    │ ◉  kkmpptxz test.user@example.com 2001-02-03 04:05:10.000 +07:00 0b65c8fb
+    │ │  right
+    ◉ │  rlvkpnrz test.user@example.com 2001-02-03 04:05:09.000 +07:00 32003b88
+    ├─╯  left
+    ◉  zzzzzzzz root() 00000000
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff"]);
+    insta::assert_snapshot!(stdout, @"");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "--list", "-r=merge"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file    2-sided conflict
+    "###);
+
+    // Running it again doesn't rewrite anything
+    let stdout = test_env.jj_cmd_success(&repo_path, &["util", "upgrade-repo"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    Enabled tree-level conflicts in the repo config and rewrote 0 commits
+    "###);
+
+    // The rewrite can be undone. The working copy gets a new commit again
+    // since it's snapshotted with the new format.
+    test_env.jj_cmd_success(&repo_path, &["undo"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["log"]);
+    insta::assert_snapshot!(stdout, @r###"
+    @  royxmykx test.user@example.com 2001-02-03 04:05:20.000 +07:00 798b5d9c conflict
+    │  (empty) (no description set)
+    ◉    zsuskuln test.user@example.com 2001-02-03 04:05:10.000 +07:00 merge 5100e4e1 conflict
+    ├─╮  (empty) merge
+    │ ◉  kkmpptxz test.user@example.com 2001-02-03 04:05:10.000 +07:00 0b65c8fb
+    │ │  right
+    ◉ │  rlvkpnrz test.user@example.com 2001-02-03 04:05:09.000 +07:00 32003b88
+    ├─╯  left
+    ◉  zzzzzzzz root() 00000000
+    "###);
+}
@@ -24,10 +24,321 @@ fn test_snapshot_large_file() {
 
     test_env.add_config(r#"snapshot.max-new-file-size = "10""#);
     std::fs::write(repo_path.join("large"), "a lot of text").unwrap();
-    let stderr = test_env.jj_cmd_failure(&repo_path, &["files"]);
+    std::fs::create_dir(repo_path.join("dir")).unwrap();
+    std::fs::write(repo_path.join("dir/larger"), "even more text").unwrap();
+    std::fs::write(repo_path.join("small"), "text").unwrap();
+
+    // The large files are left untracked instead of failing the command
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["files"]);
+    insta::assert_snapshot!(stdout, @r###"
+    small
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Refused to snapshot some files larger than snapshot.max-new-file-size (10.0B):
+      dir/larger: 14.0B
+      large: 13.0B
+    Hint: To track them, increase snapshot.max-new-file-size in your config, or for a single command:
+      jj --config-toml 'snapshot.max-new-file-size=14' status
+    Otherwise add them to your `.gitignore` file.
+    "###);
+
+    let (stdout, _stderr) = test_env.jj_cmd_ok(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy changes:
+    A small
+    Untracked paths:
+    ? dir/larger (14.0B, larger than snapshot.max-new-file-size 10.0B)
+    ? large (13.0B, larger than snapshot.max-new-file-size 10.0B)
+    Working copy : qpvuntsm 5c2708a3 (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+
+    // Tracking explicitly doesn't bypass the limit
+    let (stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["track", "large"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Nothing changed.
+    "###);
+    insta::assert_snapshot!(stderr, @r###"
+    Refused to snapshot some files larger than snapshot.max-new-file-size (10.0B):
+      dir/larger: 14.0B
+      large: 13.0B
+    Hint: To track them, increase snapshot.max-new-file-size in your config, or for a single command:
+      jj --config-toml 'snapshot.max-new-file-size=14' status
+    Otherwise add them to your `.gitignore` file.
+    "###);
+
+    // The limit can be overridden for a single command
+    let (stdout, stderr) = test_env.jj_cmd_ok(
+        &repo_path,
+        &["--config-toml", "snapshot.max-new-file-size=14", "status"],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy changes:
+    A dir/larger
+    A large
+    A small
+    Working copy : qpvuntsm a3f0c583 (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+    insta::assert_snapshot!(stderr, @"");
+    let (stdout, _stderr) = test_env.jj_cmd_ok(&repo_path, &["files"]);
+    insta::assert_snapshot!(stdout, @r###"
+    dir/larger
+    large
+    small
+    "###);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_snapshot_inotify_fsmonitor() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_success(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"core.fsmonitor = "inotify""#);
+
+    // The first snapshot starts the daemon and crawls the working copy
+    std::fs::write(repo_path.join("file1"), "1").unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file1
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "debug",
+            "inotify",
+            "query-changed-files",
+            "--ignore-working-copy",
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    Changed files: Some([])
+    "###);
+
+    // Later snapshots only look at the changed files. They wait for the daemon
+    // to catch up, so changes made right before are seen.
+    std::fs::write(repo_path.join("file2"), "2").unwrap();
+    std::fs::remove_file(repo_path.join("file1")).unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file2
+    "###);
+
+    // Untracked files are found even if they didn't change since the last
+    // snapshot
+    test_env.add_config(r#"snapshot.auto-track = []"#);
+    std::fs::write(repo_path.join("file3"), "3").unwrap();
+    std::fs::write(repo_path.join("file4"), "4").unwrap();
+    test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["track", "file3"]);
+    insta::assert_snapshot!(stdout, @"");
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--summary"]);
+    insta::assert_snapshot!(stdout, @r###"
+    A file2
+    A file3
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    Working copy changes:
+    A file2
+    A file3
+    Untracked paths:
+    ? file4
+    Working copy : qpvuntsm 1bd1b019 (no description set)
+    Parent commit: zzzzzzzz 00000000 (empty) (no description set)
+    "###);
+
+    let stdout = test_env.jj_cmd_success(&repo_path, &["debug", "inotify", "stop"]);
+    insta::assert_snapshot!(stdout, @"");
+}
+
+#[test]
+fn test_conflict_marker_style() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"ui.conflict-marker-style = "git""#);
+
+    std::fs::write(repo_path.join("file"), "line 1\nbase\nline 3\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "left"]);
+    std::fs::write(repo_path.join("file"), "line 1\nleft\nline 3\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "right"]);
+    std::fs::write(repo_path.join("file"), "line 1\nright\nline 3\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["new", "description(left)", "@", "-m", "merge"],
+    );
+
+    insta::assert_snapshot!(std::fs::read_to_string(repo_path.join("file")).unwrap(), @r###"
+    line 1
+    <<<<<<<
+    left
+    |||||||
+    base
+    =======
+    right
+    >>>>>>>
+    line 3
+    "###);
+    // Unchanged conflicts aren't modified by the snapshot
+    let (stdout, _stderr) = test_env.jj_cmd_ok(&repo_path, &["status"]);
+    insta::assert_snapshot!(stdout, @r###"
+    The working copy is clean
+    There are unresolved conflicts at these paths:
+    file    2-sided conflict
+    Working copy : zsuskuln 312f94d9 (conflict) (empty) merge
+    Parent commit: rlvkpnrz 44e699d5 left
+    Parent commit: kkmpptxz 46397c72 right
+    "###);
+
+    // Conflicts are printed in the configured style too
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "file", "-r", "@"]);
+    insta::assert_snapshot!(stdout, @r###"
+    line 1
+    <<<<<<<
+    left
+    |||||||
+    base
+    =======
+    right
+    >>>>>>>
+    line 3
+    "###);
+    let stdout = test_env.jj_cmd_success(
+        &repo_path,
+        &[
+            "cat",
+            "file",
+            "-r",
+            "@",
+            "--config-toml",
+            r#"ui.conflict-marker-style="snapshot""#,
+        ],
+    );
+    insta::assert_snapshot!(stdout, @r###"
+    line 1
+    <<<<<<<
+    +++++++
+    left
+    -------
+    base
+    +++++++
+    right
+    >>>>>>>
+    line 3
+    "###);
+
+    // Git style markers are parsed when the file is edited
+    std::fs::write(
+        repo_path.join("file"),
+        "line 1 edited\n<<<<<<<\nleft\n|||||||\nbase\n=======\nright edited\n>>>>>>>\nline 3\n",
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["diff", "--git"]);
+    insta::assert_snapshot!(stdout, @r###"
+    diff --git a/file b/file
+    --- a/file
+    +++ b/file
+    @@ -1,9 +1,9 @@
+    -line 1
+    +line 1 edited
+     <<<<<<<
+     left
+     |||||||
+     base
+     =======
+    -right
+    +right edited
+     >>>>>>>
+     line 3
+    "###);
+
+    // So are the markers of other styles
+    std::fs::write(
+        repo_path.join("file"),
+        "line 1\n<<<<<<<\n%%%%%%%\n-base\n+left edited\n+++++++\nright\n>>>>>>>\nline 3\n",
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "file", "-r", "@"]);
+    insta::assert_snapshot!(stdout, @r###"
+    line 1
+    <<<<<<<
+    left edited
+    |||||||
+    base
+    =======
+    right
+    >>>>>>>
+    line 3
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "--list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file    2-sided conflict
+    "###);
+}
+
+#[test]
+fn test_conflict_marker_length_stored_in_working_copy() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["init", "repo", "--git"]);
+    let repo_path = test_env.env_root().join("repo");
+    test_env.add_config(r#"ui.conflict-marker-style = "git""#);
+
+    // The right side contains a line that looks like a conflict marker
+    std::fs::write(repo_path.join("file"), "line 1\nbase\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "-m", "left"]);
+    std::fs::write(repo_path.join("file"), "line 1\nleft\n").unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["new", "@-", "-m", "right"]);
+    std::fs::write(repo_path.join("file"), "line 1\nright\n=======\nmore\n").unwrap();
+    test_env.jj_cmd_ok(
+        &repo_path,
+        &["new", "description(left)", "@", "-m", "merge"],
+    );
+
+    let content = std::fs::read_to_string(repo_path.join("file")).unwrap();
+    insta::assert_snapshot!(content, @r###"
+    line 1
+    <<<<<<<<
+    left
+    ||||||||
+    base
+    ========
+    right
+    =======
+    more
+    >>>>>>>>
+    "###);
+
+    // Edit the file outside the conflict. The longer markers are still
+    // recognized, and the line in the right side isn't mistaken for a marker.
+    std::fs::write(
+        repo_path.join("file"),
+        content.replace("line 1", "edited 1"),
+    )
+    .unwrap();
+    let stdout = test_env.jj_cmd_success(&repo_path, &["resolve", "--list"]);
+    insta::assert_snapshot!(stdout, @r###"
+    file    2-sided conflict
+    "###);
+    let stdout = test_env.jj_cmd_success(&repo_path, &["cat", "file", "-r", "description(merge)"]);
+    insta::assert_snapshot!(stdout, @r###"
+    edited 1
+    <<<<<<<<
+    left
+    ||||||||
+    base
+    ========
+    right
+    =======
+    more
+    >>>>>>>>
+    "###);
+
+    // Resolving the conflict makes the file a regular file again
+    std::fs::write(repo_path.join("file"), "edited 1\nresolved\n").unwrap();
+    let stderr = test_env.jj_cmd_cli_error(&repo_path, &["resolve", "--list"]);
     insta::assert_snapshot!(stderr, @r###"
-    Error: Failed to snapshot the working copy: New file $TEST_ENV/repo/large of size ~13.0B exceeds snapshot.max-new-file-size (10.0B)
-    Hint: Increase the value of the `snapshot.max-new-file-size` config option if you
-    want this file to be snapshotted. Otherwise add it to your `.gitignore` file.
+    Error: No conflicts found at this revision
     "###);
 }
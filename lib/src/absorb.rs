@@ -0,0 +1,321 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Moves changes from a commit into the ancestors that last modified the
+//! changed lines.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+use std::ops::Range;
+
+use crate::backend::{
+    BackendError, BackendResult, CommitId, FileId, MergedTreeId, ObjectId, TreeValue,
+};
+use crate::commit::Commit;
+use crate::diff::{find_line_ranges, Diff, DiffHunk};
+use crate::files;
+use crate::matchers::Matcher;
+use crate::merge::Merge;
+use crate::merged_tree::MergedTreeBuilder;
+use crate::repo::Repo;
+use crate::repo_path::RepoPath;
+use crate::store::Store;
+
+/// A replacement of a range of lines in a file.
+#[derive(Clone, Debug)]
+struct LineEdit {
+    lines: Range<usize>,
+    content: Vec<u8>,
+}
+
+/// Finds the hunks of the changes in `source` that can be moved into one of
+/// the `destinations`, and returns the new trees of the destinations that
+/// receive any hunks.
+///
+/// Each hunk of a modified file is attributed to the destination that last
+/// modified the lines the hunk replaces, or, for hunks that only add lines, the
+/// lines around them. Hunks that touch lines from several destinations or from
+/// commits that aren't destinations are left in `source`, as are changes to
+/// files that are added, removed, binary, or conflicted.
+///
+/// Only files matching `matcher` are considered. `source` must have a single
+/// parent. `destinations` must be ancestors of `source` in reverse topological
+/// order (children before parents).
+pub fn split_hunks_to_trees(
+    repo: &dyn Repo,
+    source: &Commit,
+    destinations: &[Commit],
+    matcher: &dyn Matcher,
+) -> BackendResult<BTreeMap<CommitId, MergedTreeId>> {
+    let parents = source.parents();
+    assert_eq!(parents.len(), 1, "cannot absorb from a merge commit");
+    let parent = &parents[0];
+    let store = repo.store();
+    let destinations_by_id: HashMap<&CommitId, &Commit> = destinations
+        .iter()
+        .map(|commit| (commit.id(), commit))
+        .collect();
+
+    let mut edits: BTreeMap<CommitId, BTreeMap<RepoPath, Vec<LineEdit>>> = BTreeMap::new();
+    let parent_tree = parent.tree()?;
+    let source_tree = source.tree()?;
+    for (path, before, after) in parent_tree.diff(&source_tree, matcher) {
+        let (Some(before_id), Some(after_id)) = (file_id(&before), file_id(&after)) else {
+            continue;
+        };
+        let before_content = read_file(store, &path, before_id)?;
+        let after_content = read_file(store, &path, after_id)?;
+        if files::is_binary(&before_content) || files::is_binary(&after_content) {
+            continue;
+        }
+        let annotation = annotate_lines(store, parent, &path, &before_content, destinations)?;
+        let after_lines = find_line_ranges(&after_content);
+        for (before_range, after_range) in changed_line_ranges(&before_content, &after_content) {
+            let Some((commit_id, lines)) = find_destination(&annotation, &before_range) else {
+                continue;
+            };
+            let content = match (after_lines.get(after_range.start), after_range.is_empty()) {
+                (Some(first), false) => {
+                    after_content[first.start..after_lines[after_range.end - 1].end].to_vec()
+                }
+                _ => vec![],
+            };
+            edits
+                .entry(commit_id)
+                .or_default()
+                .entry(path.clone())
+                .or_default()
+                .push(LineEdit { lines, content });
+        }
+    }
+
+    let mut new_trees = BTreeMap::new();
+    for (commit_id, path_edits) in edits {
+        let commit = destinations_by_id[&commit_id];
+        let tree = commit.tree()?;
+        let mut tree_builder = MergedTreeBuilder::new(commit.tree_id().clone());
+        for (path, path_edits) in path_edits {
+            let value = tree.path_value(&path);
+            let Some(Some(TreeValue::File { id, executable })) = value.as_resolved() else {
+                panic!("destination should have a file at {path:?}");
+            };
+            let content = read_file(store, &path, id)?;
+            let new_content = apply_edits(&content, &path_edits);
+            let new_id = store.write_file(&path, &mut new_content.as_slice())?;
+            tree_builder.set_or_remove(
+                path,
+                Merge::normal(TreeValue::File {
+                    id: new_id,
+                    executable: *executable,
+                }),
+            );
+        }
+        new_trees.insert(commit_id, tree_builder.write_tree(store)?);
+    }
+    Ok(new_trees)
+}
+
+/// Finds the commit among `destinations` that introduced each line of
+/// `content`, the contents of the file at `path` in `start`. Returns the commit
+/// and the index of the line in the commit's version of the file.
+fn annotate_lines(
+    store: &Store,
+    start: &Commit,
+    path: &RepoPath,
+    content: &[u8],
+    destinations: &[Commit],
+) -> BackendResult<Vec<Option<(CommitId, usize)>>> {
+    let destination_ids: HashSet<&CommitId> = destinations.iter().map(Commit::id).collect();
+    let num_lines = find_line_ranges(content).len();
+    let mut annotation = vec![None; num_lines];
+    // Maps destinations to pairs of line indexes in the commit's version of the
+    // file and in `content`, for the lines not attributed to a commit yet.
+    // Lines that come from other commits are dropped.
+    let mut pending: HashMap<CommitId, Vec<(usize, usize)>> = HashMap::new();
+    if destination_ids.contains(start.id()) {
+        pending.insert(start.id().clone(), (0..num_lines).map(|i| (i, i)).collect());
+    }
+    for commit in destinations {
+        if pending.is_empty() {
+            break;
+        }
+        let Some(lines) = pending.remove(commit.id()) else {
+            continue;
+        };
+        let commit_content = read_file_at(store, commit, path)?.unwrap_or_default();
+        let mut parent_mappings = vec![];
+        for parent in commit.parents() {
+            let parent_content = read_file_at(store, &parent, path)?.unwrap_or_default();
+            parent_mappings.push((
+                parent.id().clone(),
+                unchanged_lines(&commit_content, &parent_content),
+            ));
+        }
+        for (line, original_line) in lines {
+            let parent_line = parent_mappings.iter().find_map(|(parent_id, mapping)| {
+                mapping
+                    .get(&line)
+                    .map(|parent_line| (parent_id, *parent_line))
+            });
+            match parent_line {
+                Some((parent_id, parent_line)) => {
+                    if destination_ids.contains(parent_id) {
+                        pending
+                            .entry(parent_id.clone())
+                            .or_default()
+                            .push((parent_line, original_line));
+                    }
+                }
+                None => {
+                    annotation[original_line] = Some((commit.id().clone(), line));
+                }
+            }
+        }
+    }
+    Ok(annotation)
+}
+
+/// Returns the destination and its lines to replace for a hunk that replaces
+/// the lines `before_range` of the annotated file, or `None` if the hunk can't
+/// be attributed to a single destination.
+fn find_destination(
+    annotation: &[Option<(CommitId, usize)>],
+    before_range: &Range<usize>,
+) -> Option<(CommitId, Range<usize>)> {
+    // A hunk that only adds lines is attributed to the lines around it.
+    let context = if before_range.is_empty() {
+        before_range.start.saturating_sub(1)..(before_range.end + 1).min(annotation.len())
+    } else {
+        before_range.clone()
+    };
+    if context.is_empty() {
+        return None;
+    }
+    let annotated = annotation[context.clone()]
+        .iter()
+        .cloned()
+        .collect::<Option<Vec<_>>>()?;
+    let (commit_id, first_line) = annotated[0].clone();
+    // The lines must be adjacent in the destination too.
+    let is_contiguous = annotated
+        .iter()
+        .enumerate()
+        .all(|(i, (id, line))| *id == commit_id && *line == first_line + i);
+    if !is_contiguous {
+        return None;
+    }
+    let lines = if before_range.is_empty() {
+        let position = if context.start < before_range.start {
+            first_line + 1
+        } else {
+            first_line
+        };
+        position..position
+    } else {
+        first_line..first_line + annotated.len()
+    };
+    Some((commit_id, lines))
+}
+
+/// Returns pairs of line ranges in `left` and `right` that differ.
+fn changed_line_ranges(left: &[u8], right: &[u8]) -> Vec<(Range<usize>, Range<usize>)> {
+    let mut ranges = vec![];
+    let mut left_line = 0;
+    let mut right_line = 0;
+    for hunk in Diff::for_tokenizer(&[left, right], &find_line_ranges).hunks() {
+        match hunk {
+            DiffHunk::Matching(content) => {
+                let num_lines = find_line_ranges(content).len();
+                left_line += num_lines;
+                right_line += num_lines;
+            }
+            DiffHunk::Different(contents) => {
+                let left_end = left_line + find_line_ranges(contents[0]).len();
+                let right_end = right_line + find_line_ranges(contents[1]).len();
+                ranges.push((left_line..left_end, right_line..right_end));
+                left_line = left_end;
+                right_line = right_end;
+            }
+        }
+    }
+    ranges
+}
+
+/// Maps the indexes of the lines in `left` that are unchanged in `right` to
+/// their indexes in `right`.
+fn unchanged_lines(left: &[u8], right: &[u8]) -> HashMap<usize, usize> {
+    let mut mapping = HashMap::new();
+    let mut left_line = 0;
+    let mut right_line = 0;
+    for (left_range, right_range) in changed_line_ranges(left, right) {
+        while left_line < left_range.start {
+            mapping.insert(left_line, right_line);
+            left_line += 1;
+            right_line += 1;
+        }
+        left_line = left_range.end;
+        right_line = right_range.end;
+    }
+    let num_left_lines = find_line_ranges(left).len();
+    while left_line < num_left_lines {
+        mapping.insert(left_line, right_line);
+        left_line += 1;
+        right_line += 1;
+    }
+    mapping
+}
+
+fn apply_edits(content: &[u8], edits: &[LineEdit]) -> Vec<u8> {
+    let lines = find_line_ranges(content);
+    let line_start = |line: usize| lines.get(line).map_or(content.len(), |range| range.start);
+    let mut edits = edits.to_vec();
+    edits.sort_by_key(|edit| (edit.lines.start, edit.lines.end));
+    let mut new_content = vec![];
+    let mut position = 0;
+    for edit in edits {
+        new_content.extend_from_slice(&content[position..line_start(edit.lines.start)]);
+        new_content.extend_from_slice(&edit.content);
+        position = line_start(edit.lines.end);
+    }
+    new_content.extend_from_slice(&content[position..]);
+    new_content
+}
+
+fn file_id(value: &Merge<Option<TreeValue>>) -> Option<&FileId> {
+    match value.as_resolved() {
+        Some(Some(TreeValue::File { id, .. })) => Some(id),
+        _ => None,
+    }
+}
+
+fn read_file_at(store: &Store, commit: &Commit, path: &RepoPath) -> BackendResult<Option<Vec<u8>>> {
+    let value = commit.tree()?.path_value(path);
+    match file_id(&value) {
+        Some(id) => Ok(Some(read_file(store, path, id)?)),
+        None => Ok(None),
+    }
+}
+
+fn read_file(store: &Store, path: &RepoPath, id: &FileId) -> BackendResult<Vec<u8>> {
+    let mut content = vec![];
+    store
+        .read_file(path, id)?
+        .read_to_end(&mut content)
+        .map_err(|err| BackendError::ReadObject {
+            object_type: "file".to_owned(),
+            hash: id.hex(),
+            source: Box::new(err),
+        })?;
+    Ok(content)
+}
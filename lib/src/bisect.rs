@@ -0,0 +1,105 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finds the first bad commit in a range of commits by binary search.
+
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::backend::CommitId;
+use crate::repo::{ReadonlyRepo, Repo};
+use crate::revset::{RevsetEvaluationError, RevsetExpression, RevsetResolutionError};
+
+/// The next step of a bisection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BisectionStep {
+    /// The commit to test next.
+    Next {
+        /// The commit to test.
+        commit_id: CommitId,
+        /// The number of commits that could be the first bad commit, excluding
+        /// the known bad commit.
+        num_candidates: usize,
+    },
+    /// The first bad commit was found. If it can't be told apart from some
+    /// skipped commits, those are included too. The known bad commit comes
+    /// first.
+    Found(Vec<CommitId>),
+}
+
+/// Error from [`next_step()`].
+#[derive(Debug, Error)]
+pub enum BisectionError {
+    /// The good and bad commits contradict each other.
+    #[error("The bad revision is an ancestor of a good revision")]
+    BadIsAncestorOfGood,
+    /// Failed to resolve the candidates.
+    #[error(transparent)]
+    Resolution(#[from] RevsetResolutionError),
+    /// Failed to evaluate the candidates.
+    #[error(transparent)]
+    Evaluation(#[from] RevsetEvaluationError),
+}
+
+/// Finds the commit to test next when searching for the first commit that's an
+/// ancestor of `bad` but not of any of `good`.
+///
+/// The candidates are sorted by generation number and the one in the middle is
+/// picked, which halves the range in a linear history and stays close to that
+/// in histories with merges. Commits in `skipped` are never picked; if only
+/// they remain, the search ends.
+pub fn next_step(
+    repo: &ReadonlyRepo,
+    good: &[CommitId],
+    bad: &CommitId,
+    skipped: &HashSet<CommitId>,
+) -> Result<BisectionStep, BisectionError> {
+    let candidates_expression =
+        RevsetExpression::commits(good.to_vec()).range(&RevsetExpression::commit(bad.clone()));
+    let mut candidates: Vec<CommitId> = candidates_expression
+        .resolve(repo)?
+        .evaluate(repo)?
+        .iter()
+        .collect();
+    if !candidates.contains(bad) {
+        return Err(BisectionError::BadIsAncestorOfGood);
+    }
+    candidates.retain(|id| id != bad);
+    // Parents first, so commits with the same generation number stay in
+    // topological order
+    candidates.reverse();
+    let index = repo.index();
+    candidates.sort_by_cached_key(|id| index.generation_number(id));
+
+    // Pick the middle candidate, or the closest one to it that wasn't skipped.
+    let num_candidates = candidates.len();
+    let middle = num_candidates / 2;
+    let next = (0..=middle.max(num_candidates - middle))
+        .flat_map(|distance| [middle.checked_add(distance), middle.checked_sub(distance)])
+        .flatten()
+        .filter_map(|i| candidates.get(i))
+        .find(|id| !skipped.contains(id));
+    match next {
+        Some(commit_id) => Ok(BisectionStep::Next {
+            commit_id: commit_id.clone(),
+            num_candidates,
+        }),
+        None => {
+            let mut first_bad = vec![bad.clone()];
+            first_bad.extend(candidates.into_iter().rev());
+            Ok(BisectionStep::Found(first_bad))
+        }
+    }
+}
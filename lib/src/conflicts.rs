@@ -14,24 +14,142 @@
 
 #![allow(missing_docs)]
 
+use std::cmp::max;
 use std::io::Write;
 use std::iter::zip;
+use std::str::FromStr;
 
 use itertools::Itertools;
 
 use crate::backend::{BackendResult, FileId, TreeValue};
 use crate::diff::{find_line_ranges, Diff, DiffHunk};
 use crate::files;
-use crate::files::{ContentHunk, MergeResult};
+use crate::files::{ContentHunk, FileMergeHunkLevel, MergeResult};
 use crate::merge::{Merge, MergeBuilder};
 use crate::repo_path::RepoPath;
 use crate::store::Store;
 
-const CONFLICT_START_LINE: &[u8] = b"<<<<<<<\n";
-const CONFLICT_END_LINE: &[u8] = b">>>>>>>\n";
-const CONFLICT_DIFF_LINE: &[u8] = b"%%%%%%%\n";
-const CONFLICT_MINUS_LINE: &[u8] = b"-------\n";
-const CONFLICT_PLUS_LINE: &[u8] = b"+++++++\n";
+/// The length of conflict markers, unless the contents of the conflict
+/// contain lines that could be mistaken for markers of that length.
+pub const MIN_CONFLICT_MARKER_LEN: usize = 7;
+
+/// A conflict marker is a line consisting only of one of these characters,
+/// repeated at least as many times as the marker length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ConflictMarkerKind {
+    Start,
+    End,
+    Diff,
+    Minus,
+    Plus,
+    GitAncestor,
+    GitSeparator,
+}
+
+impl ConflictMarkerKind {
+    fn from_char(c: u8) -> Option<Self> {
+        match c {
+            b'<' => Some(Self::Start),
+            b'>' => Some(Self::End),
+            b'%' => Some(Self::Diff),
+            b'-' => Some(Self::Minus),
+            b'+' => Some(Self::Plus),
+            b'|' => Some(Self::GitAncestor),
+            b'=' => Some(Self::GitSeparator),
+            _ => None,
+        }
+    }
+
+    fn to_char(self) -> u8 {
+        match self {
+            Self::Start => b'<',
+            Self::End => b'>',
+            Self::Diff => b'%',
+            Self::Minus => b'-',
+            Self::Plus => b'+',
+            Self::GitAncestor => b'|',
+            Self::GitSeparator => b'=',
+        }
+    }
+}
+
+fn write_conflict_marker(
+    output: &mut dyn Write,
+    kind: ConflictMarkerKind,
+    marker_len: usize,
+) -> std::io::Result<()> {
+    output.write_all(&vec![kind.to_char(); marker_len])?;
+    output.write_all(b"\n")
+}
+
+/// If `line` consists only of a character used in conflict markers, returns
+/// the kind of marker and the length of the line, excluding the newline.
+fn parse_marker_like_line(line: &[u8]) -> Option<(ConflictMarkerKind, usize)> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let (&first, rest) = line.split_first()?;
+    let kind = ConflictMarkerKind::from_char(first)?;
+    rest.iter()
+        .all(|&c| c == first)
+        .then_some((kind, line.len()))
+}
+
+/// Returns the kind of marker if `line` is a conflict marker of at least
+/// `marker_len` characters.
+fn parse_conflict_marker(line: &[u8], marker_len: usize) -> Option<ConflictMarkerKind> {
+    if !line.ends_with(b"\n") {
+        return None;
+    }
+    parse_marker_like_line(line)
+        .filter(|&(_, len)| len >= marker_len)
+        .map(|(kind, _)| kind)
+}
+
+/// Returns a conflict marker length that is longer than any line in the
+/// conflict that looks like a conflict marker, so they can't be confused.
+pub fn choose_materialized_conflict_marker_len(single_hunk: &Merge<ContentHunk>) -> usize {
+    let max_existing_marker_len = single_hunk
+        .iter()
+        .flat_map(|term| term.0.split_inclusive(|b| *b == b'\n'))
+        .filter_map(parse_marker_like_line)
+        .map(|(kind, len)| match kind {
+            // Lines in diffs get a "-" or "+" prefix, which makes lines of
+            // those characters one longer
+            ConflictMarkerKind::Minus | ConflictMarkerKind::Plus => len + 1,
+            _ => len,
+        })
+        .max()
+        .unwrap_or(0);
+    max(max_existing_marker_len + 1, MIN_CONFLICT_MARKER_LEN)
+}
+
+/// How conflicts are written when they're materialized in files, such as in
+/// the working copy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictMarkerStyle {
+    /// Write a diff from a base to a side, and the remaining sides in full.
+    #[default]
+    Diff,
+    /// Write every side and every base in full.
+    Snapshot,
+    /// Write Git's "diff3" style markers. Conflicts with more than two sides
+    /// can't be represented that way and are written like `Snapshot`.
+    Git,
+}
+
+impl FromStr for ConflictMarkerStyle {
+    type Err = config::ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "diff" => Ok(Self::Diff),
+            "snapshot" => Ok(Self::Snapshot),
+            "git" => Ok(Self::Git),
+            other => Err(config::ConfigError::Message(format!(
+                "unknown conflict marker style: {other}"
+            ))),
+        }
+    }
+}
 
 fn write_diff_hunks(hunks: &[DiffHunk], file: &mut dyn Write) -> std::io::Result<()> {
     for hunk in hunks {
@@ -86,11 +204,17 @@ pub fn materialize(
     conflict: &Merge<Option<TreeValue>>,
     store: &Store,
     path: &RepoPath,
+    conflict_marker_style: ConflictMarkerStyle,
     output: &mut dyn Write,
 ) -> std::io::Result<()> {
     if let Some(file_merge) = conflict.to_file_merge() {
         let content = extract_as_single_hunk(&file_merge, store, path);
-        materialize_merge_result(&content, output)
+        materialize_merge_result(
+            &content,
+            conflict_marker_style,
+            store.merge_hunk_level(),
+            output,
+        )
     } else {
         // Unless all terms are regular files, we can't do much better than to try to
         // describe the merge.
@@ -98,12 +222,35 @@ pub fn materialize(
     }
 }
 
+/// Writes the merge of `single_hunk`, with conflict markers of a length chosen
+/// by `choose_materialized_conflict_marker_len()`.
 pub fn materialize_merge_result(
     single_hunk: &Merge<ContentHunk>,
+    conflict_marker_style: ConflictMarkerStyle,
+    hunk_level: FileMergeHunkLevel,
+    output: &mut dyn Write,
+) -> std::io::Result<()> {
+    let conflict_marker_len = choose_materialized_conflict_marker_len(single_hunk);
+    materialize_merge_result_with_marker_len(
+        single_hunk,
+        conflict_marker_style,
+        conflict_marker_len,
+        hunk_level,
+        output,
+    )
+}
+
+/// Writes the merge of `single_hunk`. Only the hunks that don't merge cleanly
+/// at `hunk_level` get conflict markers.
+pub fn materialize_merge_result_with_marker_len(
+    single_hunk: &Merge<ContentHunk>,
+    conflict_marker_style: ConflictMarkerStyle,
+    conflict_marker_len: usize,
+    hunk_level: FileMergeHunkLevel,
     output: &mut dyn Write,
 ) -> std::io::Result<()> {
     let slices = single_hunk.map(|content| content.0.as_slice());
-    let merge_result = files::merge(slices.removes(), slices.adds());
+    let merge_result = files::merge_with_hunk_level(slices.removes(), slices.adds(), hunk_level);
     match merge_result {
         MergeResult::Resolved(content) => {
             output.write_all(&content.0)?;
@@ -113,53 +260,23 @@ pub fn materialize_merge_result(
                 if let Some(content) = hunk.as_resolved() {
                     output.write_all(&content.0)?;
                 } else {
-                    output.write_all(CONFLICT_START_LINE)?;
-                    let mut add_index = 0;
-                    for left in hunk.removes() {
-                        let right1 = if let Some(right1) = hunk.adds().get(add_index) {
-                            right1
-                        } else {
-                            // If we have no more positive terms, emit the remaining negative
-                            // terms as snapshots.
-                            output.write_all(CONFLICT_MINUS_LINE)?;
-                            output.write_all(&left.0)?;
-                            continue;
-                        };
-                        let diff1 = Diff::for_tokenizer(&[&left.0, &right1.0], &find_line_ranges)
-                            .hunks()
-                            .collect_vec();
-                        // Check if the diff against the next positive term is better. Since
-                        // we want to preserve the order of the terms, we don't match against
-                        // any later positive terms.
-                        if let Some(right2) = hunk.adds().get(add_index + 1) {
-                            let diff2 =
-                                Diff::for_tokenizer(&[&left.0, &right2.0], &find_line_ranges)
-                                    .hunks()
-                                    .collect_vec();
-                            if diff_size(&diff2) < diff_size(&diff1) {
-                                // If the next positive term is a better match, emit
-                                // the current positive term as a snapshot and the next
-                                // positive term as a diff.
-                                output.write_all(CONFLICT_PLUS_LINE)?;
-                                output.write_all(&right1.0)?;
-                                output.write_all(CONFLICT_DIFF_LINE)?;
-                                write_diff_hunks(&diff2, output)?;
-                                add_index += 2;
-                                continue;
+                    write_conflict_marker(output, ConflictMarkerKind::Start, conflict_marker_len)?;
+                    match conflict_marker_style {
+                        ConflictMarkerStyle::Diff => {
+                            materialize_diff_hunk(&hunk, conflict_marker_len, output)?;
+                        }
+                        ConflictMarkerStyle::Snapshot => {
+                            materialize_snapshot_hunk(&hunk, conflict_marker_len, output)?;
+                        }
+                        ConflictMarkerStyle::Git => {
+                            if hunk.num_sides() == 2 {
+                                materialize_git_hunk(&hunk, conflict_marker_len, output)?;
+                            } else {
+                                materialize_snapshot_hunk(&hunk, conflict_marker_len, output)?;
                             }
                         }
-
-                        output.write_all(CONFLICT_DIFF_LINE)?;
-                        write_diff_hunks(&diff1, output)?;
-                        add_index += 1;
-                    }
-
-                    //  Emit the remaining positive terms as snapshots.
-                    for slice in &hunk.adds()[add_index..] {
-                        output.write_all(CONFLICT_PLUS_LINE)?;
-                        output.write_all(&slice.0)?;
                     }
-                    output.write_all(CONFLICT_END_LINE)?;
+                    write_conflict_marker(output, ConflictMarkerKind::End, conflict_marker_len)?;
                 }
             }
         }
@@ -167,6 +284,96 @@ pub fn materialize_merge_result(
     Ok(())
 }
 
+fn materialize_diff_hunk(
+    hunk: &Merge<ContentHunk>,
+    conflict_marker_len: usize,
+    output: &mut dyn Write,
+) -> std::io::Result<()> {
+    let mut add_index = 0;
+    for left in hunk.removes() {
+        let right1 = if let Some(right1) = hunk.adds().get(add_index) {
+            right1
+        } else {
+            // If we have no more positive terms, emit the remaining negative
+            // terms as snapshots.
+            write_conflict_marker(output, ConflictMarkerKind::Minus, conflict_marker_len)?;
+            output.write_all(&left.0)?;
+            continue;
+        };
+        let diff1 = Diff::for_tokenizer(&[&left.0, &right1.0], &find_line_ranges)
+            .hunks()
+            .collect_vec();
+        // Check if the diff against the next positive term is better. Since
+        // we want to preserve the order of the terms, we don't match against
+        // any later positive terms.
+        if let Some(right2) = hunk.adds().get(add_index + 1) {
+            let diff2 = Diff::for_tokenizer(&[&left.0, &right2.0], &find_line_ranges)
+                .hunks()
+                .collect_vec();
+            if diff_size(&diff2) < diff_size(&diff1) {
+                // If the next positive term is a better match, emit
+                // the current positive term as a snapshot and the next
+                // positive term as a diff.
+                write_conflict_marker(output, ConflictMarkerKind::Plus, conflict_marker_len)?;
+                output.write_all(&right1.0)?;
+                write_conflict_marker(output, ConflictMarkerKind::Diff, conflict_marker_len)?;
+                write_diff_hunks(&diff2, output)?;
+                add_index += 2;
+                continue;
+            }
+        }
+
+        write_conflict_marker(output, ConflictMarkerKind::Diff, conflict_marker_len)?;
+        write_diff_hunks(&diff1, output)?;
+        add_index += 1;
+    }
+
+    //  Emit the remaining positive terms as snapshots.
+    for slice in &hunk.adds()[add_index..] {
+        write_conflict_marker(output, ConflictMarkerKind::Plus, conflict_marker_len)?;
+        output.write_all(&slice.0)?;
+    }
+    Ok(())
+}
+
+fn materialize_snapshot_hunk(
+    hunk: &Merge<ContentHunk>,
+    conflict_marker_len: usize,
+    output: &mut dyn Write,
+) -> std::io::Result<()> {
+    // Emit the terms in the order they're merged: each side followed by the
+    // base it's merged with.
+    for (i, add) in hunk.adds().iter().enumerate() {
+        write_conflict_marker(output, ConflictMarkerKind::Plus, conflict_marker_len)?;
+        output.write_all(&add.0)?;
+        if let Some(remove) = hunk.removes().get(i) {
+            write_conflict_marker(output, ConflictMarkerKind::Minus, conflict_marker_len)?;
+            output.write_all(&remove.0)?;
+        }
+    }
+    Ok(())
+}
+
+fn materialize_git_hunk(
+    hunk: &Merge<ContentHunk>,
+    conflict_marker_len: usize,
+    output: &mut dyn Write,
+) -> std::io::Result<()> {
+    let [left, right] = hunk.adds() else {
+        panic!("Git style markers can only represent 2-sided conflicts");
+    };
+    output.write_all(&left.0)?;
+    write_conflict_marker(output, ConflictMarkerKind::GitAncestor, conflict_marker_len)?;
+    output.write_all(&hunk.removes()[0].0)?;
+    write_conflict_marker(
+        output,
+        ConflictMarkerKind::GitSeparator,
+        conflict_marker_len,
+    )?;
+    output.write_all(&right.0)?;
+    Ok(())
+}
+
 fn diff_size(hunks: &[DiffHunk]) -> usize {
     hunks
         .iter()
@@ -179,11 +386,17 @@ fn diff_size(hunks: &[DiffHunk]) -> usize {
 
 /// Parses conflict markers from a slice. Returns None if there were no valid
 /// conflict markers. The caller has to provide the expected number of merge
-/// sides (adds). Conflict markers that are otherwise valid will be considered
-/// invalid if they don't have the expected arity.
+/// sides (adds) and the length of the markers. Conflict markers that are
+/// otherwise valid will be considered invalid if they don't have the expected
+/// arity. Lines that are shorter than `conflict_marker_len` are never
+/// considered markers.
 // TODO: "parse" is not usually the opposite of "materialize", so maybe we
 // should rename them to "serialize" and "deserialize"?
-pub fn parse_conflict(input: &[u8], num_sides: usize) -> Option<Vec<Merge<ContentHunk>>> {
+pub fn parse_conflict(
+    input: &[u8],
+    num_sides: usize,
+    conflict_marker_len: usize,
+) -> Option<Vec<Merge<ContentHunk>>> {
     if input.is_empty() {
         return None;
     }
@@ -192,13 +405,16 @@ pub fn parse_conflict(input: &[u8], num_sides: usize) -> Option<Vec<Merge<Conten
     let mut resolved_start = 0;
     let mut conflict_start = None;
     for line in input.split_inclusive(|b| *b == b'\n') {
-        if line == CONFLICT_START_LINE {
-            conflict_start = Some(pos);
-        } else if conflict_start.is_some() && line == CONFLICT_END_LINE {
-            let conflict_body = &input[conflict_start.unwrap() + CONFLICT_START_LINE.len()..pos];
-            let hunk = parse_conflict_hunk(conflict_body);
+        let marker = parse_conflict_marker(line, conflict_marker_len);
+        if marker == Some(ConflictMarkerKind::Start) {
+            conflict_start = Some((pos, line.len()));
+        } else if let (Some((start_pos, start_len)), Some(ConflictMarkerKind::End)) =
+            (conflict_start, marker)
+        {
+            let conflict_body = &input[start_pos + start_len..pos];
+            let hunk = parse_conflict_hunk(conflict_body, conflict_marker_len);
             if hunk.num_sides() == num_sides {
-                let resolved_slice = &input[resolved_start..conflict_start.unwrap()];
+                let resolved_slice = &input[resolved_start..start_pos];
                 if !resolved_slice.is_empty() {
                     hunks.push(Merge::resolved(ContentHunk(resolved_slice.to_vec())));
                 }
@@ -222,7 +438,20 @@ pub fn parse_conflict(input: &[u8], num_sides: usize) -> Option<Vec<Merge<Conten
     }
 }
 
-fn parse_conflict_hunk(input: &[u8]) -> Merge<ContentHunk> {
+fn parse_conflict_hunk(input: &[u8], conflict_marker_len: usize) -> Merge<ContentHunk> {
+    // Hunks written with Git style markers start with the first side's
+    // contents rather than with one of our own markers.
+    let first_marker = input
+        .split_inclusive(|b| *b == b'\n')
+        .next()
+        .and_then(|line| parse_conflict_marker(line, conflict_marker_len));
+    if !matches!(
+        first_marker,
+        Some(ConflictMarkerKind::Diff | ConflictMarkerKind::Minus | ConflictMarkerKind::Plus)
+    ) {
+        return parse_git_conflict_hunk(input, conflict_marker_len);
+    }
+
     enum State {
         Diff,
         Minus,
@@ -233,19 +462,19 @@ fn parse_conflict_hunk(input: &[u8]) -> Merge<ContentHunk> {
     let mut removes = vec![];
     let mut adds = vec![];
     for line in input.split_inclusive(|b| *b == b'\n') {
-        match line {
-            CONFLICT_DIFF_LINE => {
+        match parse_conflict_marker(line, conflict_marker_len) {
+            Some(ConflictMarkerKind::Diff) => {
                 state = State::Diff;
                 removes.push(ContentHunk(vec![]));
                 adds.push(ContentHunk(vec![]));
                 continue;
             }
-            CONFLICT_MINUS_LINE => {
+            Some(ConflictMarkerKind::Minus) => {
                 state = State::Minus;
                 removes.push(ContentHunk(vec![]));
                 continue;
             }
-            CONFLICT_PLUS_LINE => {
+            Some(ConflictMarkerKind::Plus) => {
                 state = State::Plus;
                 adds.push(ContentHunk(vec![]));
                 continue;
@@ -282,14 +511,53 @@ fn parse_conflict_hunk(input: &[u8]) -> Merge<ContentHunk> {
     Merge::new(removes, adds)
 }
 
+fn parse_git_conflict_hunk(input: &[u8], conflict_marker_len: usize) -> Merge<ContentHunk> {
+    #[derive(PartialEq, Eq)]
+    enum State {
+        Left,
+        Base,
+        Right,
+    }
+    let mut state = State::Left;
+    let mut left = ContentHunk(vec![]);
+    let mut base = ContentHunk(vec![]);
+    let mut right = ContentHunk(vec![]);
+    for line in input.split_inclusive(|b| *b == b'\n') {
+        let marker = parse_conflict_marker(line, conflict_marker_len);
+        match (&state, marker) {
+            (State::Left, Some(ConflictMarkerKind::GitAncestor)) => state = State::Base,
+            (State::Base, Some(ConflictMarkerKind::GitSeparator)) => state = State::Right,
+            (State::Left, _) => left.0.extend_from_slice(line),
+            (State::Base, _) => base.0.extend_from_slice(line),
+            (
+                State::Right,
+                Some(ConflictMarkerKind::GitAncestor | ConflictMarkerKind::GitSeparator),
+            ) => {
+                // Doesn't look like a conflict
+                return Merge::resolved(ContentHunk(vec![]));
+            }
+            (State::Right, _) => right.0.extend_from_slice(line),
+        }
+    }
+    if state != State::Right {
+        // Doesn't look like a conflict
+        return Merge::resolved(ContentHunk(vec![]));
+    }
+    Merge::new(vec![base], vec![left, right])
+}
+
 /// Parses conflict markers in `content` and returns an updated version of
 /// `file_ids` with the new contents. If no (valid) conflict markers remain, a
-/// single resolves `FileId` will be returned.
+/// single resolves `FileId` will be returned. Markers of any style are parsed;
+/// `conflict_marker_style` and `conflict_marker_len` are the style and the
+/// marker length the conflict was materialized with.
 pub fn update_from_content(
     file_ids: &Merge<Option<FileId>>,
     store: &Store,
     path: &RepoPath,
     content: &[u8],
+    conflict_marker_style: ConflictMarkerStyle,
+    conflict_marker_len: usize,
 ) -> BackendResult<Merge<Option<FileId>>> {
     // First check if the new content is unchanged compared to the old content. If
     // it is, we don't need parse the content or write any new objects to the
@@ -298,12 +566,19 @@ pub fn update_from_content(
     // copy.
     let mut old_content = Vec::with_capacity(content.len());
     let merge_hunk = extract_as_single_hunk(file_ids, store, path);
-    materialize_merge_result(&merge_hunk, &mut old_content).unwrap();
+    materialize_merge_result_with_marker_len(
+        &merge_hunk,
+        conflict_marker_style,
+        conflict_marker_len,
+        store.merge_hunk_level(),
+        &mut old_content,
+    )
+    .unwrap();
     if content == old_content {
         return Ok(file_ids.clone());
     }
 
-    let Some(hunks) = parse_conflict(content, file_ids.num_sides()) else {
+    let Some(hunks) = parse_conflict(content, file_ids.num_sides(), conflict_marker_len) else {
         // Either there are no self markers of they don't have the expected arity
         let file_id = store.write_file(path, &mut &content[..])?;
         return Ok(Merge::normal(file_id));
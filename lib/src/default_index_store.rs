@@ -658,6 +658,10 @@ impl Index for MutableIndexImpl {
         CompositeIndex(self).is_ancestor(ancestor_id, descendant_id)
     }
 
+    fn generation_number(&self, commit_id: &CommitId) -> Option<u32> {
+        CompositeIndex(self).generation_number(commit_id)
+    }
+
     fn common_ancestors(&self, set1: &[CommitId], set2: &[CommitId]) -> Vec<CommitId> {
         CompositeIndex(self).common_ancestors(set1, set2)
     }
@@ -1040,6 +1044,10 @@ impl Index for CompositeIndex<'_> {
         self.is_ancestor_pos(ancestor_pos, descendant_pos)
     }
 
+    fn generation_number(&self, commit_id: &CommitId) -> Option<u32> {
+        Some(self.entry_by_id(commit_id)?.generation_number())
+    }
+
     fn common_ancestors(&self, set1: &[CommitId], set2: &[CommitId]) -> Vec<CommitId> {
         let pos1 = set1
             .iter()
@@ -1960,6 +1968,10 @@ impl Index for ReadonlyIndexImpl {
         CompositeIndex(self).is_ancestor(ancestor_id, descendant_id)
     }
 
+    fn generation_number(&self, commit_id: &CommitId) -> Option<u32> {
+        CompositeIndex(self).generation_number(commit_id)
+    }
+
     fn common_ancestors(&self, set1: &[CommitId], set2: &[CommitId]) -> Vec<CommitId> {
         CompositeIndex(self).common_ancestors(set1, set2)
     }
@@ -2546,6 +2558,34 @@ mod tests {
         assert!(!index.is_ancestor(&id_4, &id_2));
     }
 
+    #[test]
+    fn test_generation_number() {
+        let mut new_change_id = change_id_generator();
+        let mut index = MutableIndexImpl::full(3, 16);
+        // 3
+        // |\
+        // 2 |
+        // | |
+        // 1 |
+        // |/
+        // 0
+        let id_0 = CommitId::from_hex("000000");
+        let id_1 = CommitId::from_hex("111111");
+        let id_2 = CommitId::from_hex("222222");
+        let id_3 = CommitId::from_hex("333333");
+        index.add_commit_data(id_0.clone(), new_change_id(), &[]);
+        index.add_commit_data(id_1.clone(), new_change_id(), &[id_0.clone()]);
+        index.add_commit_data(id_2.clone(), new_change_id(), &[id_1.clone()]);
+        index.add_commit_data(id_3.clone(), new_change_id(), &[id_2.clone(), id_0.clone()]);
+
+        assert_eq!(index.generation_number(&id_0), Some(0));
+        assert_eq!(index.generation_number(&id_1), Some(1));
+        assert_eq!(index.generation_number(&id_2), Some(2));
+        // The longest path from the root counts
+        assert_eq!(index.generation_number(&id_3), Some(3));
+        assert_eq!(index.generation_number(&CommitId::from_hex("999999")), None);
+    }
+
     #[test]
     fn test_common_ancestors() {
         let mut new_change_id = change_id_generator();
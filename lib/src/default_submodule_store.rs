@@ -14,13 +14,13 @@
 
 #![allow(missing_docs)]
 
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
-use crate::submodule_store::SubmoduleStore;
+use crate::submodule_store::{SubmoduleStore, SubmoduleStoreError};
 
+/// Keeps a bare Git repo for each submodule in `<store path>/repos/<name>`.
 #[derive(Debug)]
 pub struct DefaultSubmoduleStore {
-    #[allow(dead_code)]
     path: PathBuf,
 }
 
@@ -41,10 +41,46 @@ impl DefaultSubmoduleStore {
     pub fn name() -> &'static str {
         "default"
     }
+
+    fn repo_path(&self, name: &str) -> Result<PathBuf, SubmoduleStoreError> {
+        // Like Git, refuse names that would escape the store directory.
+        let relative_path = Path::new(name);
+        let is_valid = !name.is_empty()
+            && relative_path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !is_valid {
+            return Err(SubmoduleStoreError::InvalidName(name.to_owned()));
+        }
+        Ok(self.path.join("repos").join(relative_path))
+    }
 }
 
 impl SubmoduleStore for DefaultSubmoduleStore {
     fn name(&self) -> &str {
         DefaultSubmoduleStore::name()
     }
+
+    fn load_submodule(&self, name: &str) -> Result<Option<git2::Repository>, SubmoduleStoreError> {
+        let repo_path = self.repo_path(name)?;
+        if !repo_path.exists() {
+            return Ok(None);
+        }
+        let repo = git2::Repository::open(repo_path)
+            .map_err(|err| SubmoduleStoreError::Other(err.into()))?;
+        Ok(Some(repo))
+    }
+
+    fn init_submodule(
+        &self,
+        name: &str,
+        url: &str,
+    ) -> Result<git2::Repository, SubmoduleStoreError> {
+        let repo_path = self.repo_path(name)?;
+        let repo = git2::Repository::init_bare(repo_path)
+            .map_err(|err| SubmoduleStoreError::Other(err.into()))?;
+        repo.remote("origin", url)
+            .map_err(|err| SubmoduleStoreError::Other(err.into()))?;
+        Ok(repo)
+    }
 }
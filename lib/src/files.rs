@@ -17,6 +17,7 @@
 use std::collections::VecDeque;
 use std::fmt::{Debug, Error, Formatter};
 use std::ops::Range;
+use std::str::FromStr;
 
 use itertools::Itertools;
 
@@ -47,6 +48,12 @@ impl DiffLine<'_> {
     }
 }
 
+/// Returns whether the content looks binary. Like Git, this checks for a NUL
+/// byte in the first 8000 bytes.
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|&b| b == 0)
+}
+
 pub fn diff<'a>(left: &'a [u8], right: &'a [u8]) -> DiffLineIterator<'a> {
     let diff_hunks = diff::diff(left, right);
     DiffLineIterator::new(diff_hunks)
@@ -167,9 +174,41 @@ struct SyncRegion {
     right: Range<usize>,
 }
 
+/// The granularity at which changes to the same region of a file conflict.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileMergeHunkLevel {
+    /// Changes to the same line conflict.
+    #[default]
+    Line,
+    /// Conflicting lines are merged again word by word, so only changes to
+    /// the same words conflict.
+    Word,
+}
+
+impl FromStr for FileMergeHunkLevel {
+    type Err = config::ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "line" => Ok(Self::Line),
+            "word" => Ok(Self::Word),
+            other => Err(config::ConfigError::Message(format!(
+                "unknown merge hunk level: {other}"
+            ))),
+        }
+    }
+}
+
 pub fn merge(removes: &[&[u8]], adds: &[&[u8]]) -> MergeResult {
+    merge_with_hunk_level(removes, adds, FileMergeHunkLevel::Line)
+}
+
+pub fn merge_with_hunk_level(
+    removes: &[&[u8]],
+    adds: &[&[u8]],
+    hunk_level: FileMergeHunkLevel,
+) -> MergeResult {
     assert_eq!(adds.len(), removes.len() + 1);
-    let num_diffs = removes.len();
     // TODO: Using the first remove as base (first in the inputs) is how it's
     // usually done for 3-way conflicts. Are there better heuristics when there are
     // more than 3 parts?
@@ -177,6 +216,17 @@ pub fn merge(removes: &[&[u8]], adds: &[&[u8]]) -> MergeResult {
     diff_inputs.extend(adds);
 
     let diff = Diff::for_tokenizer(&diff_inputs, &diff::find_line_ranges);
+    let merge_hunks = match merge_diff_hunks(&diff, removes.len()) {
+        MergeResult::Resolved(content) => return MergeResult::Resolved(content),
+        MergeResult::Conflict(merge_hunks) => merge_hunks,
+    };
+    match hunk_level {
+        FileMergeHunkLevel::Line => MergeResult::Conflict(merge_hunks),
+        FileMergeHunkLevel::Word => refine_conflicts_by_words(merge_hunks),
+    }
+}
+
+fn merge_diff_hunks(diff: &Diff, num_diffs: usize) -> MergeResult {
     let mut resolved_hunk = ContentHunk(vec![]);
     let mut merge_hunks: Vec<Merge<ContentHunk>> = vec![];
     for diff_hunk in diff.hunks() {
@@ -217,6 +267,47 @@ pub fn merge(removes: &[&[u8]], adds: &[&[u8]]) -> MergeResult {
     }
 }
 
+/// Merges each conflicting hunk again at word granularity. A hunk is replaced
+/// by the result only if that resolves it completely, so the remaining
+/// conflicts still consist of whole lines.
+fn refine_conflicts_by_words(merge_hunks: Vec<Merge<ContentHunk>>) -> MergeResult {
+    let mut resolved_hunk = ContentHunk(vec![]);
+    let mut refined_hunks: Vec<Merge<ContentHunk>> = vec![];
+    for merge_hunk in merge_hunks {
+        let resolved = if let Some(content) = merge_hunk.as_resolved() {
+            Some(content.clone())
+        } else {
+            let diff_inputs = itertools::chain(merge_hunk.removes(), merge_hunk.adds())
+                .map(|part| part.0.as_slice())
+                .collect_vec();
+            let mut diff = Diff::for_tokenizer(&diff_inputs, &diff::find_word_ranges);
+            diff.refine_changed_regions(&diff::find_nonword_ranges);
+            match merge_diff_hunks(&diff, merge_hunk.removes().len()) {
+                MergeResult::Resolved(content) => Some(content),
+                MergeResult::Conflict(_) => None,
+            }
+        };
+        if let Some(content) = resolved {
+            resolved_hunk.0.extend(content.0);
+        } else {
+            if !resolved_hunk.0.is_empty() {
+                refined_hunks.push(Merge::resolved(resolved_hunk));
+                resolved_hunk = ContentHunk(vec![]);
+            }
+            refined_hunks.push(merge_hunk);
+        }
+    }
+
+    if refined_hunks.is_empty() {
+        MergeResult::Resolved(resolved_hunk)
+    } else {
+        if !resolved_hunk.0.is_empty() {
+            refined_hunks.push(Merge::resolved(resolved_hunk));
+        }
+        MergeResult::Conflict(refined_hunks)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -427,4 +518,61 @@ b {
             ))
         );
     }
+
+    #[test]
+    fn test_merge_word_level() {
+        let merge_words = |removes: &[&[u8]], adds: &[&[u8]]| {
+            merge_with_hunk_level(removes, adds, FileMergeHunkLevel::Word)
+        };
+        // Two sides changed different words on the same line: no conflict
+        assert_eq!(
+            merge_words(&[b"a b c\n"], &[b"a2 b c\n", b"a b c2\n"]),
+            MergeResult::Resolved(hunk(b"a2 b c2\n"))
+        );
+        // ...but the line-level merge conflicts
+        assert_eq!(
+            merge(&[b"a b c\n"], &[b"a2 b c\n", b"a b c2\n"]),
+            MergeResult::Conflict(vec![Merge::new(
+                vec![hunk(b"a b c\n")],
+                vec![hunk(b"a2 b c\n"), hunk(b"a b c2\n")]
+            )])
+        );
+        // Changes to different cells of a table row
+        assert_eq!(
+            merge_words(
+                &[b"| x | 1 | foo |\n"],
+                &[b"| x | 2 | foo |\n", b"| x | 1 | bar |\n"]
+            ),
+            MergeResult::Resolved(hunk(b"| x | 2 | bar |\n"))
+        );
+        // Two sides changed the same word in different ways: the whole line
+        // conflicts
+        assert_eq!(
+            merge_words(&[b"a b c\n"], &[b"a b1 c\n", b"a b2 c\n"]),
+            MergeResult::Conflict(vec![Merge::new(
+                vec![hunk(b"a b c\n")],
+                vec![hunk(b"a b1 c\n"), hunk(b"a b2 c\n")]
+            )])
+        );
+        // Only the hunks whose words conflict are kept as conflicts
+        assert_eq!(
+            merge_words(
+                &[b"a\nb c\nx\nd e\nf\n"],
+                &[b"a\nb1 c\nx\nd1 e\nf\n", b"a\nb c1\nx\nd2 e\nf\n"]
+            ),
+            MergeResult::Conflict(vec![
+                Merge::resolved(hunk(b"a\nb1 c1\nx\n")),
+                Merge::new(vec![hunk(b"d e\n")], vec![hunk(b"d1 e\n"), hunk(b"d2 e\n")]),
+                Merge::resolved(hunk(b"f\n"))
+            ])
+        );
+        // Three-way merge where all sides changed different words
+        assert_eq!(
+            merge_words(
+                &[b"a b c\n", b"a b c\n"],
+                &[b"a1 b c\n", b"a b1 c\n", b"a b c1\n"]
+            ),
+            MergeResult::Resolved(hunk(b"a1 b1 c1\n"))
+        );
+    }
 }
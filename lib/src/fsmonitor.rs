@@ -28,6 +28,10 @@ pub enum FsmonitorKind {
     /// The Watchman filesystem monitor (https://facebook.github.io/watchman/).
     Watchman,
 
+    /// The built-in filesystem monitor, which uses inotify and is only
+    /// available on Linux.
+    Inotify,
+
     /// Only used in tests.
     Test {
         /// The set of changed files to pretend that the filesystem monitor is
@@ -42,6 +46,7 @@ impl FromStr for FsmonitorKind {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "watchman" => Ok(Self::Watchman),
+            "inotify" => Ok(Self::Inotify),
             "test" => Err(config::ConfigError::Message(
                 "cannot use test fsmonitor in real repository".to_string(),
             )),
@@ -215,3 +220,454 @@ pub mod watchman {
         }
     }
 }
+
+/// Built-in filesystem monitor using inotify.
+///
+/// A daemon process watches the working copy and appends the paths of changed
+/// files to a journal in the working copy's state directory. A clock is an
+/// offset into the journal, so a query returns the paths appended since the
+/// previous clock. A new journal is started whenever the daemon starts, when
+/// the journal grows too large, and when events may have been lost, in which
+/// case the caller must crawl the entire working copy.
+#[cfg(all(feature = "inotify", target_os = "linux"))]
+pub mod inotify {
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Component, Path, PathBuf};
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use itertools::Itertools;
+    use notify::event::CreateKind;
+    use notify::{EventKind, RecursiveMode, Watcher};
+    use rustix::fs::FlockOperation;
+    use thiserror::Error;
+    use tracing::{info, instrument};
+
+    const LOCK_FILE_NAME: &str = "daemon.lock";
+    const JOURNAL_FILE_NAME: &str = "journal";
+    /// Prefix of the cookie files written to the state directory by queries.
+    const COOKIE_PREFIX: &str = "cookie-";
+    /// How long a query waits for the daemon to journal its cookie.
+    const SYNC_TIMEOUT: Duration = Duration::from_secs(10);
+    /// Size above which the daemon starts a new journal.
+    const MAX_JOURNAL_SIZE: u64 = 16 << 20;
+    /// How often the daemon checks whether it should exit.
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Represents a position in the journal of changed paths. See
+    /// [`super::watchman::Clock`] for how clocks are used.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Clock {
+        instance_id: String,
+        offset: u64,
+    }
+
+    impl From<crate::protos::working_copy::InotifyClock> for Clock {
+        fn from(clock: crate::protos::working_copy::InotifyClock) -> Self {
+            Clock {
+                instance_id: clock.instance_id,
+                offset: clock.offset,
+            }
+        }
+    }
+
+    impl From<Clock> for crate::protos::working_copy::InotifyClock {
+        fn from(clock: Clock) -> Self {
+            crate::protos::working_copy::InotifyClock {
+                instance_id: clock.instance_id,
+                offset: clock.offset,
+            }
+        }
+    }
+
+    #[allow(missing_docs)]
+    #[derive(Debug, Error)]
+    pub enum Error {
+        #[error("The filesystem monitor daemon is not running")]
+        NotRunning,
+
+        #[error("Failed to watch the working copy: {0}")]
+        WatchError(notify::Error),
+
+        #[error("Failed to access the filesystem monitor state: {0}")]
+        StateError(io::Error),
+
+        #[error("Timed out waiting for the filesystem monitor daemon to catch up")]
+        SyncTimeout,
+    }
+
+    /// Returns the directory where the daemon of the working copy whose state
+    /// is in `working_copy_state_path` keeps its state.
+    pub fn state_dir(working_copy_state_path: &Path) -> PathBuf {
+        working_copy_state_path.join("fsmonitor")
+    }
+
+    /// Returns whether a daemon is running for the given state directory.
+    pub fn is_daemon_running(state_dir: &Path) -> Result<bool, Error> {
+        let lock_file = match File::open(state_dir.join(LOCK_FILE_NAME)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(Error::StateError(err)),
+        };
+        // The daemon holds an exclusive lock for as long as it's running
+        match rustix::fs::flock(&lock_file, FlockOperation::NonBlockingLockShared) {
+            Ok(()) => Ok(false),
+            Err(rustix::io::Errno::WOULDBLOCK) => Ok(true),
+            Err(err) => Err(Error::StateError(err.into())),
+        }
+    }
+
+    /// Stops the daemon, if any. The daemon exits within a second.
+    pub fn stop_daemon(state_dir: &Path) -> Result<(), Error> {
+        match fs::remove_file(state_dir.join(LOCK_FILE_NAME)) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(Error::StateError(err)),
+        }
+    }
+
+    /// Query for changed files since the previous point in time.
+    ///
+    /// The returned list of paths is absolute. If it is `None`, then the
+    /// caller must crawl the entire working copy themselves.
+    ///
+    /// Like Watchman's sync cookies, the query writes a cookie file and waits
+    /// for the daemon to journal it, so the result includes every change made
+    /// before the query even if the daemon hasn't processed it yet.
+    #[instrument]
+    pub fn query_changed_files(
+        working_copy_path: &Path,
+        state_dir: &Path,
+        previous_clock: Option<Clock>,
+    ) -> Result<(Clock, Option<Vec<PathBuf>>), Error> {
+        info!("Querying the filesystem monitor journal for changed files...");
+        if !is_daemon_running(state_dir)? {
+            return Err(Error::NotRunning);
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let cookie_path = state_dir.join(format!(
+            "{COOKIE_PREFIX}{}-{}",
+            std::process::id(),
+            now.as_nanos()
+        ));
+        fs::write(&cookie_path, "").map_err(Error::StateError)?;
+        let deadline = Instant::now() + SYNC_TIMEOUT;
+        let result = loop {
+            match read_journal(working_copy_path, state_dir, &previous_clock, &cookie_path) {
+                Ok((clock, changed_files, true)) => break Ok((clock, changed_files)),
+                Ok((_, _, false)) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Ok((_, _, false)) => break Err(Error::SyncTimeout),
+                Err(err) => break Err(err),
+            }
+        };
+        fs::remove_file(&cookie_path).ok();
+        result
+    }
+
+    /// Reads the changed files since `previous_clock` from the journal. Also
+    /// returns whether the daemon has journaled the cookie at `cookie_path`.
+    fn read_journal(
+        working_copy_path: &Path,
+        state_dir: &Path,
+        previous_clock: &Option<Clock>,
+        cookie_path: &Path,
+    ) -> Result<(Clock, Option<Vec<PathBuf>>, bool), Error> {
+        // The daemon replaces the journal atomically, so the open file is
+        // consistent even if a new journal is started concurrently.
+        let file = match File::open(state_dir.join(JOURNAL_FILE_NAME)) {
+            Ok(file) => file,
+            // The daemon is still starting up
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(Error::NotRunning),
+            Err(err) => return Err(Error::StateError(err)),
+        };
+        let mut reader = BufReader::new(file);
+        let mut instance_id = String::new();
+        reader
+            .read_line(&mut instance_id)
+            .map_err(Error::StateError)?;
+        let instance_id = instance_id.trim_end().to_owned();
+        let header_len = reader.stream_position().map_err(Error::StateError)?;
+        let start = match previous_clock {
+            Some(clock) if clock.instance_id == instance_id && clock.offset >= header_len => {
+                Some(clock.offset)
+            }
+            _ => None,
+        };
+        reader
+            .seek(SeekFrom::Start(start.unwrap_or(header_len)))
+            .map_err(Error::StateError)?;
+        let mut offset = reader.stream_position().map_err(Error::StateError)?;
+        let mut paths = vec![];
+        let mut synced = false;
+        let mut line = vec![];
+        loop {
+            line.clear();
+            let len = reader
+                .read_until(b'\n', &mut line)
+                .map_err(Error::StateError)?;
+            // Stop at a partially written line
+            if line.last() != Some(&b'\n') {
+                break;
+            }
+            offset += len as u64;
+            let path = Path::new(std::ffi::OsStr::from_bytes(&line[..len - 1]));
+            if path.is_absolute() {
+                // Cookies are journaled by their absolute path
+                synced |= path == cookie_path;
+            } else if start.is_some() {
+                paths.push(working_copy_path.join(path));
+            }
+        }
+        let clock = Clock {
+            instance_id,
+            offset,
+        };
+        Ok((
+            clock,
+            start.map(|_| paths.into_iter().unique().collect()),
+            synced,
+        ))
+    }
+
+    /// Runs the daemon watching the working copy at `working_copy_path`,
+    /// keeping its state in `state_dir`.
+    ///
+    /// Returns immediately if another daemon is already running. Otherwise,
+    /// runs until [`stop_daemon()`] is called or the working copy is deleted.
+    pub fn run_daemon(working_copy_path: &Path, state_dir: &Path) -> Result<(), Error> {
+        fs::create_dir_all(state_dir).map_err(Error::StateError)?;
+        let lock_path = state_dir.join(LOCK_FILE_NAME);
+        let lock_file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(Error::StateError)?;
+        if rustix::fs::flock(&lock_file, FlockOperation::NonBlockingLockExclusive).is_err() {
+            info!("Another filesystem monitor daemon is already running");
+            return Ok(());
+        }
+        // Remove the journal of a previous daemon so it's not mistaken for
+        // ours while we're starting up
+        match fs::remove_file(state_dir.join(JOURNAL_FILE_NAME)) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(Error::StateError(err)),
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(Error::WatchError)?;
+        watcher
+            .watch(working_copy_path, RecursiveMode::Recursive)
+            .map_err(Error::WatchError)?;
+        if !state_dir.starts_with(working_copy_path) {
+            // Queries write their cookies to the state directory
+            watcher
+                .watch(state_dir, RecursiveMode::NonRecursive)
+                .map_err(Error::WatchError)?;
+        }
+        // Start the journal only after the watch is set up, so no changes are
+        // missed after a clock is handed out.
+        let mut journal = Journal::create(state_dir)?;
+        loop {
+            match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(Ok(event)) if !event.need_rescan() => {
+                    if event.kind == EventKind::Create(CreateKind::Folder) {
+                        // The watcher reports a new directory before it starts
+                        // watching it. Watch it now, so that files created in
+                        // it after the directory is journaled aren't missed.
+                        // Files created before that are covered by the
+                        // directory's path.
+                        for path in &event.paths {
+                            watcher.watch(path, RecursiveMode::Recursive).ok();
+                        }
+                    }
+                    journal.append(working_copy_path, state_dir, &event.paths)?;
+                }
+                Ok(Ok(_)) | Ok(Err(_)) => {
+                    // Events may have been lost
+                    tracing::warn!("Filesystem monitor needs to rescan, starting a new journal");
+                    journal = Journal::create(state_dir)?;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if !lock_path.exists() || !working_copy_path.exists() {
+                break;
+            }
+            if journal.size > MAX_JOURNAL_SIZE {
+                journal = Journal::create(state_dir)?;
+            }
+        }
+        info!("Filesystem monitor daemon exiting");
+        Ok(())
+    }
+
+    struct Journal {
+        state_dir: PathBuf,
+        file: File,
+        size: u64,
+    }
+
+    impl Journal {
+        fn create(state_dir: &Path) -> Result<Self, Error> {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let header = format!("{}-{}\n", std::process::id(), now.as_nanos());
+            let temp_path = state_dir.join(format!("{JOURNAL_FILE_NAME}.tmp"));
+            let path = state_dir.join(JOURNAL_FILE_NAME);
+            fs::write(&temp_path, &header).map_err(Error::StateError)?;
+            fs::rename(&temp_path, &path).map_err(Error::StateError)?;
+            let file = OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .map_err(Error::StateError)?;
+            Ok(Journal {
+                state_dir: state_dir.to_owned(),
+                file,
+                size: header.len() as u64,
+            })
+        }
+
+        fn append(
+            &mut self,
+            working_copy_path: &Path,
+            state_dir: &Path,
+            paths: &[PathBuf],
+        ) -> Result<(), Error> {
+            let mut buf = vec![];
+            for path in paths {
+                let is_cookie = path.parent() == Some(state_dir)
+                    && path
+                        .file_name()
+                        .is_some_and(|name| name.as_bytes().starts_with(COOKIE_PREFIX.as_bytes()));
+                if is_cookie {
+                    // Journaled by their absolute path, which can't be mistaken
+                    // for a changed file
+                    let bytes = path.as_os_str().as_bytes();
+                    if !bytes.contains(&b'\n') {
+                        buf.extend_from_slice(bytes);
+                        buf.push(b'\n');
+                    }
+                    continue;
+                }
+                let Ok(relative_path) = path.strip_prefix(working_copy_path) else {
+                    continue;
+                };
+                if relative_path.as_os_str().is_empty() {
+                    continue;
+                }
+                // Changes to the repo's own state don't affect the working copy
+                if let Some(Component::Normal(first)) = relative_path.components().next() {
+                    if first == ".jj" || first == ".git" {
+                        continue;
+                    }
+                }
+                let bytes = relative_path.as_os_str().as_bytes();
+                if bytes.contains(&b'\n') {
+                    // Can't be represented in the journal, so make the next query
+                    // crawl everything.
+                    *self = Journal::create(&self.state_dir)?;
+                    return Ok(());
+                }
+                buf.extend_from_slice(bytes);
+                buf.push(b'\n');
+            }
+            if !buf.is_empty() {
+                self.file.write_all(&buf).map_err(Error::StateError)?;
+                self.size += buf.len() as u64;
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::time::Instant;
+
+        use assert_matches::assert_matches;
+
+        use super::*;
+
+        #[test]
+        fn test_inotify_daemon() {
+            let temp_dir = testutils::new_temp_dir();
+            let working_copy_path = temp_dir.path().canonicalize().unwrap();
+            let state_dir = working_copy_path.join(".jj").join("fsmonitor");
+            assert!(!is_daemon_running(&state_dir).unwrap());
+            assert_matches!(
+                query_changed_files(&working_copy_path, &state_dir, None),
+                Err(Error::NotRunning)
+            );
+
+            let daemon = std::thread::spawn({
+                let working_copy_path = working_copy_path.clone();
+                let state_dir = state_dir.clone();
+                move || run_daemon(&working_copy_path, &state_dir)
+            });
+            let deadline = Instant::now() + Duration::from_secs(10);
+            let clock = loop {
+                match query_changed_files(&working_copy_path, &state_dir, None) {
+                    // Without a previous clock, the caller must crawl everything
+                    Ok((clock, changed_files)) => {
+                        assert_eq!(changed_files, None);
+                        break clock;
+                    }
+                    Err(Error::NotRunning) if Instant::now() < deadline => {
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                    Err(err) => panic!("daemon didn't start: {err}"),
+                }
+            };
+            assert!(is_daemon_running(&state_dir).unwrap());
+            // Another daemon exits immediately
+            run_daemon(&working_copy_path, &state_dir).unwrap();
+
+            // Nothing has changed yet
+            let (_, changed_files) =
+                query_changed_files(&working_copy_path, &state_dir, Some(clock.clone())).unwrap();
+            assert_eq!(changed_files, Some(vec![]));
+
+            // Changes are reported, except for those in .jj/
+            fs::write(working_copy_path.join(".jj").join("ignored"), "").unwrap();
+            let dir_path = working_copy_path.join("dir");
+            fs::create_dir(&dir_path).unwrap();
+            // Files created in a new directory before it's watched are covered by
+            // the directory's path. Queries wait for the daemon to catch up, so
+            // changes are reported as soon as they're made.
+            let (_, changed_files) =
+                query_changed_files(&working_copy_path, &state_dir, Some(clock.clone())).unwrap();
+            assert_eq!(changed_files, Some(vec![dir_path.clone()]));
+            let file_path = dir_path.join("file");
+            fs::write(&file_path, "contents").unwrap();
+            let (_, changed_files) =
+                query_changed_files(&working_copy_path, &state_dir, Some(clock.clone())).unwrap();
+            assert_eq!(changed_files, Some(vec![dir_path, file_path]));
+            // Cookies aren't left behind
+            assert!(!fs::read_dir(&state_dir).unwrap().any(|entry| {
+                let name = entry.unwrap().file_name();
+                name.as_bytes().starts_with(COOKIE_PREFIX.as_bytes())
+            }));
+
+            // A clock from another journal requires a full crawl
+            let other_clock = Clock {
+                instance_id: "other".to_owned(),
+                offset: clock.offset,
+            };
+            let (_, changed_files) =
+                query_changed_files(&working_copy_path, &state_dir, Some(other_clock)).unwrap();
+            assert_eq!(changed_files, None);
+
+            stop_daemon(&state_dir).unwrap();
+            daemon.join().unwrap().unwrap();
+            assert!(!is_daemon_running(&state_dir).unwrap());
+        }
+    }
+}
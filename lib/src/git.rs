@@ -17,21 +17,26 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::default::Default;
 use std::io::Read;
-use std::iter;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::{fs, iter};
 
 use git2::Oid;
 use itertools::Itertools;
 use tempfile::NamedTempFile;
 use thiserror::Error;
 
-use crate::backend::{BackendError, CommitId, ObjectId};
+use crate::backend::{BackendError, CommitId, ObjectId, TreeValue};
 use crate::git_backend::GitBackend;
+use crate::matchers::Matcher;
+use crate::merged_tree::MergedTree;
 use crate::op_store::{BranchTarget, RefTarget, RefTargetOptionExt};
 use crate::repo::{MutableRepo, Repo};
+use crate::repo_path::RepoPath;
 use crate::revset;
 use crate::settings::GitSettings;
+use crate::submodule_store::SubmoduleStoreError;
 use crate::view::{RefName, View};
+use crate::working_copy::FileState;
 
 /// Reserved remote name for the backing Git repo.
 pub const REMOTE_NAME_FOR_LOCAL_GIT_REPO: &str = "git";
@@ -660,6 +665,81 @@ fn update_git_ref(
     Ok(())
 }
 
+/// Rewrites the Git index to match the tree of `head_commit_id` (or to be
+/// empty if there's no such commit), so Git sees no staged changes in a
+/// colocated repo.
+///
+/// `wc_tree` is the tree the working copy's `file_states` were recorded for.
+/// The modification time and size of unchanged files are copied from there,
+/// and the remaining stat info is read from `workspace_root`, so Git doesn't
+/// have to rehash them. Other entries get zeroed stat info, which makes Git
+/// compare their contents.
+pub fn reset_head_index(
+    git_repo: &git2::Repository,
+    workspace_root: &Path,
+    head_commit_id: Option<&CommitId>,
+    wc_tree: &MergedTree,
+    file_states: &BTreeMap<RepoPath, FileState>,
+) -> Result<(), git2::Error> {
+    let mut index = git_repo.index()?;
+    if let Some(commit_id) = head_commit_id {
+        let git_commit = git_repo.find_commit(Oid::from_bytes(commit_id.as_bytes()).unwrap())?;
+        index.read_tree(&git_commit.tree()?)?;
+    } else {
+        index.clear()?;
+    }
+    let entries = index.iter().collect_vec();
+    for mut entry in entries {
+        let Ok(path_str) = std::str::from_utf8(&entry.path) else {
+            continue;
+        };
+        let path = RepoPath::from_internal_string(path_str);
+        let Some(file_state) = file_states.get(&path) else {
+            continue;
+        };
+        let unchanged = match wc_tree.path_value(&path).as_resolved() {
+            Some(Some(TreeValue::File { id, executable })) => {
+                id.as_bytes() == entry.id.as_bytes()
+                    && entry.mode == if *executable { 0o100755 } else { 0o100644 }
+            }
+            Some(Some(TreeValue::Symlink(id))) => {
+                id.as_bytes() == entry.id.as_bytes() && entry.mode == 0o120000
+            }
+            _ => false,
+        };
+        if !unchanged {
+            continue;
+        }
+        let millis = file_state.mtime.0;
+        let mtime = git2::IndexTime::new(
+            millis.div_euclid(1000) as i32,
+            (millis.rem_euclid(1000) * 1_000_000) as u32,
+        );
+        entry.mtime = mtime;
+        entry.ctime = mtime;
+        entry.file_size = file_state.size as u32;
+        set_index_entry_platform_stat(&mut entry, &path.to_fs_path(workspace_root));
+        index.add(&entry)?;
+    }
+    index.write()
+}
+
+/// Fills in the stat info Git compares but the working copy doesn't record.
+#[cfg(unix)]
+fn set_index_entry_platform_stat(entry: &mut git2::IndexEntry, disk_path: &Path) {
+    use std::os::unix::fs::MetadataExt as _;
+    if let Ok(metadata) = disk_path.symlink_metadata() {
+        entry.ctime = git2::IndexTime::new(metadata.ctime() as i32, metadata.ctime_nsec() as u32);
+        entry.dev = metadata.dev() as u32;
+        entry.ino = metadata.ino() as u32;
+        entry.uid = metadata.uid();
+        entry.gid = metadata.gid();
+    }
+}
+
+#[cfg(not(unix))]
+fn set_index_entry_platform_stat(_entry: &mut git2::IndexEntry, _disk_path: &Path) {}
+
 #[derive(Debug, Error)]
 pub enum GitRemoteManagementError {
     #[error("No git remote named '{0}'")]
@@ -807,6 +887,7 @@ pub fn fetch(
     git_repo: &git2::Repository,
     remote_name: &str,
     branch_name_globs: Option<&[&str]>,
+    depth: Option<u32>,
     callbacks: RemoteCallbacks<'_>,
     git_settings: &GitSettings,
 ) -> Result<Option<String>, GitFetchError> {
@@ -878,6 +959,11 @@ pub fn fetch(
     let mut proxy_options = git2::ProxyOptions::new();
     proxy_options.auto();
     fetch_options.proxy_options(proxy_options);
+    if let Some(depth) = depth {
+        // Commits at the cut-off point are recorded in the `shallow` file, and
+        // are read as having no parents.
+        fetch_options.depth(depth.try_into().unwrap_or(i32::MAX));
+    }
     let callbacks = callbacks.into_git();
     fetch_options.remote_callbacks(callbacks);
     let refspecs = {
@@ -895,6 +981,10 @@ pub fn fetch(
     };
     tracing::debug!("remote.download");
     remote.download(&refspecs, Some(&mut fetch_options))?;
+    // The commits at the cut-off point may have changed
+    if let Some(git_backend) = mut_repo.store().backend_impl().downcast_ref::<GitBackend>() {
+        git_backend.invalidate_shallow_commits();
+    }
     tracing::debug!("remote.prune");
     remote.prune(None)?;
     tracing::debug!("remote.update_tips");
@@ -1062,6 +1152,17 @@ pub struct RemoteCallbacks<'a> {
 }
 
 impl<'a> RemoteCallbacks<'a> {
+    /// Borrows the callbacks for a single remote operation, so they can be
+    /// used for several of them.
+    fn reborrow(&mut self) -> RemoteCallbacks<'_> {
+        RemoteCallbacks {
+            progress: self.progress.as_mut().map(|f| &mut **f as _),
+            get_ssh_keys: self.get_ssh_keys.as_mut().map(|f| &mut **f as _),
+            get_password: self.get_password.as_mut().map(|f| &mut **f as _),
+            get_username_password: self.get_username_password.as_mut().map(|f| &mut **f as _),
+        }
+    }
+
     fn into_git(mut self) -> git2::RemoteCallbacks<'a> {
         let mut callbacks = git2::RemoteCallbacks::new();
         if let Some(progress_cb) = self.progress {
@@ -1237,3 +1338,169 @@ pub fn parse_gitmodules(
         .collect();
     Ok(ret)
 }
+
+#[derive(Error, Debug)]
+pub enum GitSubmoduleError {
+    #[error("Failed to read .gitmodules: {0}")]
+    GitmodulesError(#[from] GitConfigParseError),
+    #[error(transparent)]
+    SubmoduleStoreError(#[from] SubmoduleStoreError),
+    #[error(
+        "Cannot resolve relative URL \"{0}\" of submodule without an \"origin\" remote in the \
+         parent repo"
+    )]
+    RelativeUrlWithoutOrigin(String),
+    #[error("Commit {commit_id} of submodule at {path} was not found in {url}", commit_id = commit_id.hex())]
+    CommitNotFound {
+        path: String,
+        url: String,
+        commit_id: CommitId,
+    },
+    #[error("Failed to create submodule directory {path}: {err}", path = path.display())]
+    IoError {
+        path: PathBuf,
+        #[source]
+        err: std::io::Error,
+    },
+    #[error(transparent)]
+    BackendError(#[from] BackendError),
+    #[error("Unexpected git error when updating submodule: {0}")]
+    InternalGitError(#[from] git2::Error),
+}
+
+/// Describes what `check_out_submodules()` did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SubmoduleCheckoutStats {
+    /// Submodules whose working copy was updated.
+    pub checked_out: Vec<RepoPath>,
+    /// Submodules that are not described in `.gitmodules`, and therefore
+    /// couldn't be checked out.
+    pub unconfigured: Vec<RepoPath>,
+}
+
+/// Initializes and checks out the submodules matching `matcher` that changed
+/// between `old_tree` and `new_tree`, which must already have been checked out
+/// to the workspace at `workspace_root`. The repo backing each submodule lives
+/// in the repo's submodule store and is fetched from the URL in `.gitmodules`
+/// if it doesn't have the commit yet.
+pub fn check_out_submodules(
+    repo: &dyn Repo,
+    workspace_root: &Path,
+    old_tree: &MergedTree,
+    new_tree: &MergedTree,
+    matcher: &dyn Matcher,
+    mut callbacks: RemoteCallbacks<'_>,
+) -> Result<SubmoduleCheckoutStats, GitSubmoduleError> {
+    let mut stats = SubmoduleCheckoutStats::default();
+    let changed_submodules = old_tree
+        .diff(new_tree, matcher)
+        .filter_map(|(path, _before, after)| match after.into_resolved() {
+            Ok(Some(TreeValue::GitSubmodule(id))) => Some((path, id)),
+            _ => None,
+        })
+        .collect_vec();
+    if changed_submodules.is_empty() {
+        return Ok(stats);
+    }
+
+    let gitmodules_path = RepoPath::from_internal_string(".gitmodules");
+    let submodule_configs = match new_tree.path_value(&gitmodules_path).into_resolved() {
+        Ok(Some(TreeValue::File { id, .. })) => {
+            let mut reader = repo.store().read_file(&gitmodules_path, &id)?;
+            parse_gitmodules(&mut reader)?
+        }
+        _ => BTreeMap::new(),
+    };
+    let submodule_store = repo.submodule_store();
+    for (path, commit_id) in changed_submodules {
+        let path_string = path.to_internal_file_string();
+        let Some(config) = submodule_configs
+            .values()
+            .find(|config| config.path.trim_end_matches('/') == path_string)
+        else {
+            stats.unconfigured.push(path);
+            continue;
+        };
+        let url = resolve_submodule_url(repo, &config.url)?;
+        let git_repo = match submodule_store.load_submodule(&config.name)? {
+            Some(git_repo) => git_repo,
+            None => submodule_store.init_submodule(&config.name, &url)?,
+        };
+        let oid = Oid::from_bytes(commit_id.as_bytes()).unwrap();
+        if git_repo.find_commit(oid).is_err() {
+            fetch_submodule(&git_repo, callbacks.reborrow())?;
+            if git_repo.find_commit(oid).is_err() {
+                return Err(GitSubmoduleError::CommitNotFound {
+                    path: path_string,
+                    url,
+                    commit_id,
+                });
+            }
+        }
+
+        let disk_path = path.to_fs_path(workspace_root);
+        fs::create_dir_all(&disk_path).map_err(|err| GitSubmoduleError::IoError {
+            path: disk_path.clone(),
+            err,
+        })?;
+        // The submodule's working copy refers to the repo in the store through
+        // a `.git` file, just like the ones in `.git/modules/` do.
+        if git_repo.workdir() != Some(disk_path.as_path()) {
+            git_repo.set_workdir(&disk_path, true)?;
+            git_repo.config()?.set_bool("core.bare", false)?;
+        }
+        git_repo.set_head_detached(oid)?;
+        git_repo.checkout_head(Some(git2::build::CheckoutBuilder::new().safe()))?;
+        stats.checked_out.push(path);
+    }
+    Ok(stats)
+}
+
+/// Resolves a URL from `.gitmodules`. Like in Git, URLs starting with `./` or
+/// `../` are relative to the URL of the parent repo's `origin` remote.
+fn resolve_submodule_url(repo: &dyn Repo, url: &str) -> Result<String, GitSubmoduleError> {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return Ok(url.to_owned());
+    }
+    let base_url = repo
+        .store()
+        .backend_impl()
+        .downcast_ref::<GitBackend>()
+        .and_then(|git_backend| {
+            let git_repo = git_backend.git_repo();
+            let remote = git_repo.find_remote("origin").ok()?;
+            remote.url().map(|url| url.to_owned())
+        })
+        .ok_or_else(|| GitSubmoduleError::RelativeUrlWithoutOrigin(url.to_owned()))?;
+    let mut base = base_url.trim_end_matches('/');
+    let mut rest = url;
+    loop {
+        if let Some(tail) = rest.strip_prefix("./") {
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("../") {
+            rest = tail;
+            base = base
+                .rsplit_once(['/', ':'])
+                .map_or("", |(parent, _)| parent);
+        } else {
+            break;
+        }
+    }
+    Ok(format!("{base}/{rest}"))
+}
+
+fn fetch_submodule(
+    git_repo: &git2::Repository,
+    callbacks: RemoteCallbacks<'_>,
+) -> Result<(), git2::Error> {
+    let mut remote = git_repo.find_remote("origin")?;
+    let mut fetch_options = git2::FetchOptions::new();
+    let mut proxy_options = git2::ProxyOptions::new();
+    proxy_options.auto();
+    fetch_options.proxy_options(proxy_options);
+    fetch_options.remote_callbacks(callbacks.into_git());
+    // Use the refspecs configured for the remote, i.e. all branches
+    remote.fetch::<&str>(&[], Some(&mut fetch_options), None)?;
+    remote.disconnect()?;
+    Ok(())
+}
@@ -15,12 +15,13 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
+use std::collections::HashSet;
 use std::fmt::{Debug, Error, Formatter};
-use std::fs;
 use std::io::{Cursor, Read};
 use std::ops::Deref;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::{fs, io};
 
 use git2::Oid;
 use itertools::Itertools;
@@ -98,6 +99,7 @@ pub struct GitBackend {
     empty_tree_id: TreeId,
     extra_metadata_store: TableStore,
     cached_extra_metadata: Mutex<Option<Arc<ReadonlyTable>>>,
+    cached_shallow_commits: Mutex<Option<Arc<HashSet<CommitId>>>>,
 }
 
 impl GitBackend {
@@ -112,6 +114,7 @@ impl GitBackend {
             empty_tree_id,
             extra_metadata_store,
             cached_extra_metadata: Mutex::new(None),
+            cached_shallow_commits: Mutex::new(None),
         }
     }
 
@@ -158,6 +161,24 @@ impl GitBackend {
         self.repo.lock().unwrap()
     }
 
+    /// Path to the git directory, e.g. `.git/` if the repo is colocated.
+    pub fn git_repo_path(&self) -> PathBuf {
+        self.git_repo().path().to_owned()
+    }
+
+    /// Returns the commits whose parents are missing because the repo is a
+    /// shallow clone. They're listed in the Git repo's `shallow` file.
+    pub fn shallow_commits(&self) -> BackendResult<HashSet<CommitId>> {
+        let shallow_commits = self.cached_shallow_commits(&self.git_repo())?;
+        Ok(shallow_commits.as_ref().clone())
+    }
+
+    /// Makes the shallow commits be read again from the Git repo, which is
+    /// needed after fetching since that can change them.
+    pub fn invalidate_shallow_commits(&self) {
+        *self.cached_shallow_commits.lock().unwrap() = None;
+    }
+
     pub fn git_repo_clone(&self) -> git2::Repository {
         let path = self.repo.lock().unwrap().path().to_owned();
         git2::Repository::open(path).unwrap()
@@ -178,6 +199,21 @@ impl GitBackend {
         }
     }
 
+    fn cached_shallow_commits(
+        &self,
+        git_repo: &git2::Repository,
+    ) -> BackendResult<Arc<HashSet<CommitId>>> {
+        let mut locked_shallow_commits = self.cached_shallow_commits.lock().unwrap();
+        match locked_shallow_commits.as_ref() {
+            Some(shallow_commits) => Ok(shallow_commits.clone()),
+            None => {
+                let shallow_commits = Arc::new(read_shallow_commits(git_repo)?);
+                *locked_shallow_commits = Some(shallow_commits.clone());
+                Ok(shallow_commits)
+            }
+        }
+    }
+
     fn read_extra_metadata_table_locked(&self) -> BackendResult<(Arc<ReadonlyTable>, FileLock)> {
         let table = self
             .extra_metadata_store
@@ -224,6 +260,7 @@ impl GitBackend {
             "import extra metadata entries"
         );
         let locked_repo = self.repo.lock().unwrap();
+        let shallow_commits = self.cached_shallow_commits(&locked_repo)?;
         let (table, table_lock) = self.read_extra_metadata_table_locked()?;
         let mut mut_table = table.start_mutation();
         // Concurrent write_commit() might have updated the table before taking a lock.
@@ -233,6 +270,7 @@ impl GitBackend {
             &mut mut_table,
             &table_lock,
             &missing_head_ids,
+            &shallow_commits,
             uses_tree_conflict_format,
         )?;
         for &id in &missing_head_ids {
@@ -242,6 +280,26 @@ impl GitBackend {
     }
 }
 
+/// Reads the commits at the boundary of a shallow clone from the `shallow`
+/// file. Their parents are missing, so we treat them like commits without
+/// parents. (libgit2 only does that if the file existed when the repo was
+/// opened.)
+fn read_shallow_commits(git_repo: &git2::Repository) -> BackendResult<HashSet<CommitId>> {
+    if !git_repo.is_shallow() {
+        return Ok(HashSet::new());
+    }
+    let content = match fs::read_to_string(git_repo.path().join("shallow")) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(err) => return Err(BackendError::Other(err.into())),
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| Oid::from_str(line.trim()).ok())
+        .map(|oid| CommitId::from_bytes(oid.as_bytes()))
+        .collect())
+}
+
 fn commit_from_git_without_root_parent(
     commit: &git2::Commit,
     uses_tree_conflict_format: bool,
@@ -433,6 +491,7 @@ fn import_extra_metadata_entries_from_heads(
     mut_table: &mut MutableTable,
     _table_lock: &FileLock,
     missing_head_ids: &[&CommitId],
+    shallow_commits: &HashSet<CommitId>,
     uses_tree_conflict_format: bool,
 ) -> BackendResult<()> {
     let mut work_ids = missing_head_ids.iter().map(|&id| id.clone()).collect_vec();
@@ -443,8 +502,12 @@ fn import_extra_metadata_entries_from_heads(
         // TODO(#1624): Should we read the root tree here and check if it has a
         // `.jjconflict-...` entries? That could happen if the user used `git` to e.g.
         // change the description of a commit with tree-level conflicts.
-        let commit = commit_from_git_without_root_parent(&git_commit, uses_tree_conflict_format);
+        let mut commit =
+            commit_from_git_without_root_parent(&git_commit, uses_tree_conflict_format);
         mut_table.add_entry(id.to_bytes(), serialize_extras(&commit));
+        if shallow_commits.contains(&id) {
+            commit.parents.clear();
+        }
         work_ids.extend(
             commit
                 .parents
@@ -687,6 +750,9 @@ impl Backend for GitBackend {
             .find_commit(git_commit_id)
             .map_err(|err| map_not_found_err(err, id))?;
         let mut commit = commit_from_git_without_root_parent(&commit, false);
+        if !commit.parents.is_empty() && self.cached_shallow_commits(&locked_repo)?.contains(id) {
+            commit.parents.clear();
+        }
         if commit.parents.is_empty() {
             commit.parents.push(self.root_commit_id.clone());
         };
@@ -0,0 +1,313 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for running the hooks of the backing Git repo.
+//!
+//! Hooks are looked up in `core.hooksPath` if set, or in the `hooks`
+//! directory of the Git repo otherwise. A missing or non-executable hook is
+//! skipped, just like Git does.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+use thiserror::Error;
+
+use crate::backend::ObjectId as _;
+use crate::git::GitRefUpdate;
+
+/// Error that may occur when running a Git hook.
+#[derive(Error, Debug)]
+pub enum GitHookError {
+    /// The Git config couldn't be read to find the hooks directory.
+    #[error("Failed to read Git config: {0}")]
+    Config(#[source] git2::Error),
+    /// The hook couldn't be started, or its input or output couldn't be
+    /// transferred.
+    #[error("Failed to run the {name} hook: {source}")]
+    Io {
+        /// Name of the hook.
+        name: String,
+        /// The underlying error.
+        #[source]
+        source: io::Error,
+    },
+    /// The hook exited with non-zero status.
+    #[error("The {name} hook failed ({status})")]
+    Failed {
+        /// Name of the hook.
+        name: String,
+        /// Exit status of the hook.
+        status: ExitStatus,
+        /// The combined stdout and stderr of the hook.
+        output: Vec<u8>,
+    },
+}
+
+/// Returns the path to the hook named `name`, if it exists and is executable.
+pub fn find_hook(
+    git_repo: &git2::Repository,
+    workdir: &Path,
+    name: &str,
+) -> Result<Option<PathBuf>, GitHookError> {
+    let config = git_repo.config().map_err(GitHookError::Config)?;
+    let hooks_dir = match config.get_path("core.hooksPath") {
+        // Like Git, resolve a relative path against the directory the hooks
+        // run in
+        Ok(path) => workdir.join(path),
+        Err(err) if err.code() == git2::ErrorCode::NotFound => git_repo.path().join("hooks"),
+        Err(err) => return Err(GitHookError::Config(err)),
+    };
+    let path = hooks_dir.join(name);
+    Ok(is_executable(&path).then_some(path))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Runs the hook named `name` in `workdir` with the given arguments and
+/// standard input.
+///
+/// Returns `None` if there's no such hook, or the combined output of the hook
+/// if it succeeded. A hook exiting with non-zero status is reported as
+/// [`GitHookError::Failed`].
+pub fn run_hook(
+    git_repo: &git2::Repository,
+    workdir: &Path,
+    name: &str,
+    args: &[&str],
+    stdin: &[u8],
+) -> Result<Option<Vec<u8>>, GitHookError> {
+    let Some(path) = find_hook(git_repo, workdir, name)? else {
+        return Ok(None);
+    };
+    let io_error = |source| GitHookError::Io {
+        name: name.to_owned(),
+        source,
+    };
+    let mut child = Command::new(&path)
+        .args(args)
+        .current_dir(workdir)
+        .env("GIT_DIR", git_repo.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(io_error)?;
+    let mut child_stdin = child.stdin.take().unwrap();
+    let mut child_stdout = child.stdout.take().unwrap();
+    let mut child_stderr = child.stderr.take().unwrap();
+    let (stdout, stderr) = std::thread::scope(|scope| {
+        scope.spawn(move || {
+            // The hook may exit without reading its input, so ignore errors
+            // such as a broken pipe.
+            child_stdin.write_all(stdin).ok();
+        });
+        let stderr_thread = scope.spawn(move || {
+            let mut buf = vec![];
+            child_stderr.read_to_end(&mut buf).map(|_| buf)
+        });
+        let mut stdout = vec![];
+        let stdout_result = child_stdout.read_to_end(&mut stdout).map(|_| stdout);
+        (stdout_result, stderr_thread.join().unwrap())
+    });
+    let mut output = stdout.map_err(io_error)?;
+    output.extend(stderr.map_err(io_error)?);
+    let status = child.wait().map_err(io_error)?;
+    if status.success() {
+        Ok(Some(output))
+    } else {
+        Err(GitHookError::Failed {
+            name: name.to_owned(),
+            status,
+            output,
+        })
+    }
+}
+
+/// Runs the `pre-push` hook for pushing `updates` to the remote.
+///
+/// The hook is passed one line per update on its standard input, in the same
+/// format as Git uses. The remote side is taken from the remote-tracking
+/// branches.
+pub fn run_pre_push_hook(
+    git_repo: &git2::Repository,
+    workdir: &Path,
+    remote_name: &str,
+    updates: &[GitRefUpdate],
+) -> Result<Option<Vec<u8>>, GitHookError> {
+    let remote_url = git_repo
+        .find_remote(remote_name)
+        .ok()
+        .and_then(|remote| remote.url().map(|url| url.to_owned()))
+        .unwrap_or_else(|| remote_name.to_owned());
+    let null_hex = "0".repeat(40);
+    let mut stdin = String::new();
+    for update in updates {
+        let (local_ref, local_hex) = match &update.new_target {
+            Some(new_target) => (update.qualified_name.as_str(), new_target.hex()),
+            None => ("(delete)", null_hex.clone()),
+        };
+        let remote_hex = update
+            .qualified_name
+            .strip_prefix("refs/heads/")
+            .and_then(|branch| {
+                git_repo
+                    .find_reference(&format!("refs/remotes/{remote_name}/{branch}"))
+                    .ok()
+            })
+            .and_then(|reference| reference.target())
+            .map(|oid| oid.to_string())
+            .unwrap_or_else(|| null_hex.clone());
+        stdin.push_str(&format!(
+            "{local_ref} {local_hex} {} {remote_hex}\n",
+            update.qualified_name
+        ));
+    }
+    run_hook(
+        git_repo,
+        workdir,
+        "pre-push",
+        &[remote_name, &remote_url],
+        stdin.as_bytes(),
+    )
+}
+
+/// Runs the `commit-msg` hook on `description`, and returns the description
+/// as modified by the hook.
+///
+/// The description is passed to the hook in a `COMMIT_EDITMSG` file in the Git
+/// repo directory, like Git does.
+pub fn run_commit_msg_hook(
+    git_repo: &git2::Repository,
+    workdir: &Path,
+    description: &str,
+) -> Result<(String, Option<Vec<u8>>), GitHookError> {
+    if find_hook(git_repo, workdir, "commit-msg")?.is_none() {
+        return Ok((description.to_owned(), None));
+    }
+    let io_error = |source| GitHookError::Io {
+        name: "commit-msg".to_owned(),
+        source,
+    };
+    let message_path = git_repo.path().join("COMMIT_EDITMSG");
+    fs::write(&message_path, description).map_err(io_error)?;
+    let output = run_hook(
+        git_repo,
+        workdir,
+        "commit-msg",
+        &[message_path.to_str().unwrap()],
+        b"",
+    )?;
+    let description = fs::read_to_string(&message_path).map_err(io_error)?;
+    Ok((description, output))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    fn write_hook(dir: &Path, name: &str, script: &str) {
+        fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{script}")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_run_hook() {
+        let temp_dir = testutils::new_temp_dir();
+        let git_repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let workdir = temp_dir.path();
+
+        // Missing hook
+        assert_eq!(
+            run_hook(&git_repo, workdir, "pre-push", &[], b"").unwrap(),
+            None
+        );
+
+        // Non-executable hook
+        let hooks_dir = git_repo.path().join("hooks");
+        fs::write(hooks_dir.join("pre-push"), "#!/bin/sh\nexit 1").unwrap();
+        assert_eq!(
+            run_hook(&git_repo, workdir, "pre-push", &[], b"").unwrap(),
+            None
+        );
+
+        // Successful hook reading its arguments and input
+        write_hook(&hooks_dir, "pre-push", "echo \"$1\"; cat; echo err >&2");
+        assert_eq!(
+            run_hook(&git_repo, workdir, "pre-push", &["origin"], b"input\n").unwrap(),
+            Some(b"origin\ninput\nerr\n".to_vec())
+        );
+
+        // Failing hook
+        write_hook(&hooks_dir, "pre-push", "echo rejected; exit 1");
+        assert_matches::assert_matches!(
+            run_hook(&git_repo, workdir, "pre-push", &[], b""),
+            Err(GitHookError::Failed { output, .. }) if output == b"rejected\n"
+        );
+
+        // core.hooksPath is relative to the working directory
+        git_repo
+            .config()
+            .unwrap()
+            .set_str("core.hooksPath", "my-hooks")
+            .unwrap();
+        assert_eq!(
+            run_hook(&git_repo, workdir, "pre-push", &[], b"").unwrap(),
+            None
+        );
+        write_hook(&workdir.join("my-hooks"), "pre-push", "echo custom");
+        assert_eq!(
+            run_hook(&git_repo, workdir, "pre-push", &[], b"").unwrap(),
+            Some(b"custom\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_run_commit_msg_hook() {
+        let temp_dir = testutils::new_temp_dir();
+        let git_repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let workdir = temp_dir.path();
+
+        assert_eq!(
+            run_commit_msg_hook(&git_repo, workdir, "message\n").unwrap(),
+            ("message\n".to_owned(), None)
+        );
+
+        write_hook(
+            &git_repo.path().join("hooks"),
+            "commit-msg",
+            "echo 'Change-Id: 1234' >> \"$1\"",
+        );
+        assert_eq!(
+            run_commit_msg_hook(&git_repo, workdir, "message\n").unwrap(),
+            ("message\nChange-Id: 1234\n".to_owned(), Some(vec![]))
+        );
+    }
+}
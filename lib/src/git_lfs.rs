@@ -0,0 +1,237 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for files managed by [Git LFS](https://git-lfs.com/).
+//!
+//! Paths with the `filter=lfs` attribute are stored in the repo as small
+//! pointer files referring to an object in the local LFS object store
+//! (`.git/lfs/objects`). The working copy contains the real contents.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+
+use crate::gitattributes::{GitAttributeValue, GitAttributesFile};
+
+const POINTER_VERSION: &str = "https://git-lfs.github.com/spec/v1";
+
+/// Pointer files are always smaller than this many bytes.
+pub const MAX_POINTER_SIZE: u64 = 1024;
+
+/// Returns whether the path has the `filter=lfs` attribute.
+pub fn is_lfs_path(attributes: &GitAttributesFile, path: &str) -> bool {
+    attributes.get(path, "filter") == GitAttributeValue::Value("lfs".to_owned())
+}
+
+/// The contents of an LFS pointer file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LfsPointer {
+    /// Hex-encoded SHA-256 hash of the object contents.
+    pub oid: String,
+    /// Size of the object contents in bytes.
+    pub size: u64,
+}
+
+impl LfsPointer {
+    /// Parses the contents of a pointer file. Returns `None` if the contents
+    /// are not a valid pointer.
+    pub fn parse(content: &[u8]) -> Option<Self> {
+        if content.len() as u64 >= MAX_POINTER_SIZE {
+            return None;
+        }
+        let content = std::str::from_utf8(content).ok()?;
+        let mut lines = content.lines();
+        if lines.next()? != format!("version {POINTER_VERSION}") {
+            return None;
+        }
+        let mut oid = None;
+        let mut size = None;
+        for line in lines {
+            let (key, value) = line.split_once(' ')?;
+            match key {
+                "oid" => {
+                    let hash = value.strip_prefix("sha256:")?;
+                    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                        return None;
+                    }
+                    oid = Some(hash.to_ascii_lowercase());
+                }
+                "size" => size = Some(value.parse().ok()?),
+                // Extension keys are allowed, but we don't support any of them
+                _ => {}
+            }
+        }
+        Some(LfsPointer {
+            oid: oid?,
+            size: size?,
+        })
+    }
+
+    /// Formats the pointer as the canonical pointer file contents.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format!(
+            "version {POINTER_VERSION}\noid sha256:{}\nsize {}\n",
+            self.oid, self.size
+        )
+        .into_bytes()
+    }
+}
+
+/// The local store of LFS objects, typically `.git/lfs/objects`.
+#[derive(Clone, Debug)]
+pub struct LfsStore {
+    objects_dir: PathBuf,
+}
+
+impl LfsStore {
+    /// Creates a store keeping its objects in `objects_dir`.
+    pub fn new(objects_dir: PathBuf) -> Self {
+        LfsStore { objects_dir }
+    }
+
+    /// The LFS store of the Git repo whose git directory is `git_dir`.
+    pub fn for_git_dir(git_dir: &Path) -> Self {
+        LfsStore::new(git_dir.join("lfs").join("objects"))
+    }
+
+    /// The directory containing the objects.
+    pub fn objects_dir(&self) -> &Path {
+        &self.objects_dir
+    }
+
+    /// The path of an object, using the same fan-out as `git-lfs`.
+    pub fn object_path(&self, oid: &str) -> PathBuf {
+        self.objects_dir.join(&oid[0..2]).join(&oid[2..4]).join(oid)
+    }
+
+    /// Opens the object referred to by the pointer. Returns `None` if the
+    /// object hasn't been downloaded to the local store.
+    pub fn read_object(&self, pointer: &LfsPointer) -> io::Result<Option<File>> {
+        match File::open(self.object_path(&pointer.oid)) {
+            Ok(file) => Ok(Some(file)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Copies the contents into the store and returns a pointer to them.
+    pub fn write_object(&self, contents: &mut dyn Read) -> io::Result<LfsPointer> {
+        fs::create_dir_all(&self.objects_dir)?;
+        let mut temp_file = NamedTempFile::new_in(&self.objects_dir)?;
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let len = match contents.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            hasher.update(&buf[..len]);
+            temp_file.write_all(&buf[..len])?;
+            size += len as u64;
+        }
+        let pointer = LfsPointer {
+            oid: hex::encode(hasher.finalize()),
+            size,
+        };
+        let object_path = self.object_path(&pointer.oid);
+        if !object_path.exists() {
+            fs::create_dir_all(object_path.parent().unwrap())?;
+            temp_file.persist(&object_path).map_err(|err| err.error)?;
+        }
+        Ok(pointer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pointer_round_trip() {
+        let pointer = LfsPointer {
+            oid: "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393".to_owned(),
+            size: 12345,
+        };
+        let bytes = pointer.to_bytes();
+        insta::assert_snapshot!(String::from_utf8(bytes.clone()).unwrap(), @r###"
+        version https://git-lfs.github.com/spec/v1
+        oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393
+        size 12345
+        "###);
+        assert_eq!(LfsPointer::parse(&bytes), Some(pointer));
+    }
+
+    #[test]
+    fn test_pointer_parse_invalid() {
+        assert_eq!(LfsPointer::parse(b""), None);
+        assert_eq!(LfsPointer::parse(b"not a pointer\n"), None);
+        // Missing size
+        assert_eq!(
+            LfsPointer::parse(
+                b"version https://git-lfs.github.com/spec/v1\n\
+                  oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\n"
+            ),
+            None
+        );
+        // Bad hash
+        assert_eq!(
+            LfsPointer::parse(
+                b"version https://git-lfs.github.com/spec/v1\noid sha256:xyz\nsize 1\n"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_store_write_and_read() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = LfsStore::for_git_dir(temp_dir.path());
+        let pointer = store.write_object(&mut b"hello\n".as_slice()).unwrap();
+        assert_eq!(
+            pointer.oid,
+            "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+        );
+        assert_eq!(pointer.size, 6);
+        assert!(temp_dir
+            .path()
+            .join("lfs/objects/58/91")
+            .join(&pointer.oid)
+            .is_file());
+        let mut contents = vec![];
+        store
+            .read_object(&pointer)
+            .unwrap()
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, b"hello\n");
+        // Writing the same contents again is fine
+        assert_eq!(
+            store.write_object(&mut b"hello\n".as_slice()).unwrap(),
+            pointer
+        );
+
+        let missing = LfsPointer {
+            oid: "0".repeat(64),
+            size: 0,
+        };
+        assert!(store.read_object(&missing).unwrap().is_none());
+    }
+}
@@ -0,0 +1,573 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![allow(missing_docs)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::backend::{BackendError, BackendResult, FileId, ObjectId, TreeValue};
+use crate::files;
+use crate::gitignore::glob_to_regex;
+use crate::merged_tree::MergedTree;
+use crate::repo_path::{RepoPath, RepoPathComponent, RepoPathJoin};
+use crate::store::Store;
+use crate::tree::Tree;
+
+/// The state of a single attribute for a path, as described in
+/// `gitattributes(5)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GitAttributeValue {
+    /// The attribute was set, e.g. `text`.
+    Set,
+    /// The attribute was unset, e.g. `-text`.
+    Unset,
+    /// The attribute was set to a value, e.g. `eol=lf`.
+    Value(String),
+    /// No line mentions the attribute, or it was reset with `!text`.
+    Unspecified,
+}
+
+#[derive(Debug)]
+struct GitAttributesLine {
+    regex: Regex,
+    attributes: Vec<(String, GitAttributeValue)>,
+}
+
+impl GitAttributesLine {
+    fn parse(prefix: &str, input: &str) -> Option<GitAttributesLine> {
+        assert!(prefix.is_empty() || prefix.ends_with('/'));
+        let input = input.trim();
+        if input.is_empty() || input.starts_with('#') {
+            return None;
+        }
+        let mut words = input.split_ascii_whitespace();
+        let pattern = words.next()?;
+        // Negative patterns are forbidden, and patterns matching only
+        // directories never apply to files.
+        if pattern.starts_with('!') || pattern.ends_with('/') {
+            return None;
+        }
+        let (mut is_rooted, pattern) = match pattern.strip_prefix('/') {
+            None => (false, pattern),
+            Some(rest) => (true, rest),
+        };
+        is_rooted |= pattern.contains('/');
+        let mut regex = glob_to_regex(prefix, pattern, is_rooted);
+        regex.push('$');
+        let regex = Regex::new(&regex).unwrap();
+
+        let mut attributes = vec![];
+        for word in words {
+            if let Some(name) = word.strip_prefix('-') {
+                attributes.push((name.to_owned(), GitAttributeValue::Unset));
+            } else if let Some(name) = word.strip_prefix('!') {
+                attributes.push((name.to_owned(), GitAttributeValue::Unspecified));
+            } else if let Some((name, value)) = word.split_once('=') {
+                attributes.push((name.to_owned(), GitAttributeValue::Value(value.to_owned())));
+            } else {
+                attributes.push((word.to_owned(), GitAttributeValue::Set));
+                // The built-in `binary` macro
+                if word == "binary" {
+                    for name in ["diff", "merge", "text"] {
+                        attributes.push((name.to_owned(), GitAttributeValue::Unset));
+                    }
+                }
+            }
+        }
+        Some(GitAttributesLine { regex, attributes })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+}
+
+/// Models the effective contents of multiple .gitattributes files.
+#[derive(Debug)]
+pub struct GitAttributesFile {
+    parent: Option<Arc<GitAttributesFile>>,
+    lines: Vec<GitAttributesLine>,
+}
+
+impl GitAttributesFile {
+    pub fn empty() -> Arc<GitAttributesFile> {
+        Arc::new(GitAttributesFile {
+            parent: None,
+            lines: vec![],
+        })
+    }
+
+    pub fn chain(
+        self: &Arc<GitAttributesFile>,
+        prefix: &str,
+        input: &[u8],
+    ) -> Arc<GitAttributesFile> {
+        let mut lines = vec![];
+        for input_line in input.split(|b| *b == b'\n') {
+            // Skip non-utf8 lines
+            if let Ok(line_string) = std::str::from_utf8(input_line) {
+                if let Some(line) = GitAttributesLine::parse(prefix, line_string) {
+                    lines.push(line);
+                }
+            }
+        }
+
+        Arc::new(GitAttributesFile {
+            parent: Some(self.clone()),
+            lines,
+        })
+    }
+
+    pub fn chain_with_file(
+        self: &Arc<GitAttributesFile>,
+        prefix: &str,
+        file: PathBuf,
+    ) -> Arc<GitAttributesFile> {
+        if file.is_file() {
+            let buf = fs::read(file).unwrap();
+            self.chain(prefix, &buf)
+        } else {
+            self.clone()
+        }
+    }
+
+    fn all_lines_reversed<'a>(&'a self) -> Box<dyn Iterator<Item = &'a GitAttributesLine> + 'a> {
+        if let Some(parent) = &self.parent {
+            Box::new(self.lines.iter().rev().chain(parent.all_lines_reversed()))
+        } else {
+            Box::new(self.lines.iter().rev())
+        }
+    }
+
+    /// Returns the state of the attribute `name` for the file at `path`. The
+    /// last line matching the path and mentioning the attribute wins.
+    pub fn get(&self, path: &str, name: &str) -> GitAttributeValue {
+        for line in self.all_lines_reversed() {
+            if !line.matches(path) {
+                continue;
+            }
+            if let Some((_, value)) = line.attributes.iter().rev().find(|(n, _)| n == name) {
+                return value.clone();
+            }
+        }
+        GitAttributeValue::Unspecified
+    }
+
+    /// Returns how the line endings of the file at `path` are converted, as
+    /// determined by its `text` and `eol` attributes.
+    pub fn eol_conversion(&self, path: &str) -> EolConversion {
+        let crlf = match self.get(path, "eol") {
+            GitAttributeValue::Value(value) if value == "crlf" => Some(true),
+            GitAttributeValue::Value(value) if value == "lf" => Some(false),
+            _ => None,
+        };
+        match self.get(path, "text") {
+            GitAttributeValue::Set => EolConversion::Text {
+                auto: false,
+                crlf: crlf.unwrap_or(false),
+            },
+            GitAttributeValue::Value(value) if value == "auto" => EolConversion::Text {
+                auto: true,
+                crlf: crlf.unwrap_or(false),
+            },
+            GitAttributeValue::Unset => EolConversion::None,
+            // Setting `eol` implies `text`
+            _ => match crlf {
+                Some(crlf) => EolConversion::Text { auto: false, crlf },
+                None => EolConversion::None,
+            },
+        }
+    }
+
+    /// Returns whether the file at `path` should be diffed as binary, either
+    /// because of its `diff` attribute or because any of `contents` looks
+    /// binary.
+    pub fn is_binary_for_diff(&self, path: &str, contents: &[&[u8]]) -> bool {
+        is_binary_for(self.get(path, "diff"), contents)
+    }
+
+    /// Returns whether the file at `path` should be merged as binary, i.e.
+    /// without merging the contents line by line, either because of its
+    /// `merge` attribute or because any of `contents` looks binary.
+    pub fn is_binary_for_merge(&self, path: &str, contents: &[&[u8]]) -> bool {
+        is_binary_for(self.get(path, "merge"), contents)
+    }
+
+    /// Chains the `.gitattributes` file in `tree`, if any.
+    pub fn chain_with_tree(self: &Arc<GitAttributesFile>, tree: &Tree) -> BackendResult<Arc<Self>> {
+        let basename = RepoPathComponent::from(".gitattributes");
+        match tree.value(&basename) {
+            Some(TreeValue::File { id, .. }) => {
+                let path = tree.dir().join(&basename);
+                let content = read_file(tree.store(), &path, id)?;
+                Ok(self.chain(&tree.dir().to_internal_dir_string(), &content))
+            }
+            _ => Ok(self.clone()),
+        }
+    }
+}
+
+fn is_binary_for(value: GitAttributeValue, contents: &[&[u8]]) -> bool {
+    match value {
+        GitAttributeValue::Set => false,
+        GitAttributeValue::Unset => true,
+        GitAttributeValue::Value(_) | GitAttributeValue::Unspecified => {
+            contents.iter().any(|content| files::is_binary(content))
+        }
+    }
+}
+
+fn read_file(store: &Store, path: &RepoPath, id: &FileId) -> BackendResult<Vec<u8>> {
+    let mut content = vec![];
+    store
+        .read_file(path, id)?
+        .read_to_end(&mut content)
+        .map_err(|err| BackendError::ReadObject {
+            object_type: "file".to_owned(),
+            hash: id.hex(),
+            source: Box::new(err),
+        })?;
+    Ok(content)
+}
+
+/// How the line endings of a file are converted between the working copy and
+/// the repo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EolConversion {
+    /// The contents are stored and checked out as they are.
+    None,
+    /// CRLF line endings are normalized to LF in the repo, and converted back
+    /// to CRLF on checkout if `crlf` is set. With `auto`, contents that look
+    /// binary or already have CRLF line endings in the repo are left alone.
+    Text { auto: bool, crlf: bool },
+}
+
+impl EolConversion {
+    /// Converts contents read from the working copy to the contents to store.
+    pub fn convert_to_repo(self, content: Vec<u8>) -> Vec<u8> {
+        match self {
+            EolConversion::Text { auto, .. } if !(auto && files::is_binary(&content)) => {
+                crlf_to_lf(content)
+            }
+            _ => content,
+        }
+    }
+
+    /// Converts contents read from the repo to the contents to write to the
+    /// working copy.
+    pub fn convert_to_working_copy(self, content: Vec<u8>) -> Vec<u8> {
+        match self {
+            EolConversion::Text { auto, crlf: true }
+                if !(auto && (files::is_binary(&content) || has_crlf(&content))) =>
+            {
+                lf_to_crlf(&content)
+            }
+            _ => content,
+        }
+    }
+}
+
+/// Returns whether the content has any CRLF line endings.
+pub fn has_crlf(content: &[u8]) -> bool {
+    content.windows(2).any(|window| window == b"\r\n")
+}
+
+fn crlf_to_lf(content: Vec<u8>) -> Vec<u8> {
+    if !has_crlf(&content) {
+        return content;
+    }
+    let mut result = Vec::with_capacity(content.len());
+    let mut iter = content.iter().peekable();
+    while let Some(&byte) = iter.next() {
+        if byte == b'\r' && iter.peek() == Some(&&b'\n') {
+            continue;
+        }
+        result.push(byte);
+    }
+    result
+}
+
+fn lf_to_crlf(content: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(content.len());
+    let mut previous = None;
+    for &byte in content {
+        if byte == b'\n' && previous != Some(b'\r') {
+            result.push(b'\r');
+        }
+        result.push(byte);
+        previous = Some(byte);
+    }
+    result
+}
+
+/// Loads the `.gitattributes` files of a tree lazily, one directory at a time.
+pub struct TreeGitAttributes<'a> {
+    tree: &'a MergedTree,
+    dirs: HashMap<RepoPath, Arc<GitAttributesFile>>,
+}
+
+impl<'a> TreeGitAttributes<'a> {
+    pub fn new(tree: &'a MergedTree) -> Self {
+        TreeGitAttributes {
+            tree,
+            dirs: HashMap::new(),
+        }
+    }
+
+    /// Returns the attributes that apply to files in `dir`.
+    pub fn for_dir(&mut self, dir: &RepoPath) -> BackendResult<Arc<GitAttributesFile>> {
+        if let Some(git_attributes) = self.dirs.get(dir) {
+            return Ok(git_attributes.clone());
+        }
+        let parent_git_attributes = match dir.parent() {
+            Some(parent_dir) => self.for_dir(&parent_dir)?,
+            None => GitAttributesFile::empty(),
+        };
+        let path = dir.join(&RepoPathComponent::from(".gitattributes"));
+        let git_attributes = match self.tree.path_value(&path).into_resolved() {
+            Ok(Some(TreeValue::File { id, .. })) => {
+                let content = read_file(self.tree.store(), &path, &id)?;
+                parent_git_attributes.chain(&dir.to_internal_dir_string(), &content)
+            }
+            _ => parent_git_attributes,
+        };
+        self.dirs.insert(dir.clone(), git_attributes.clone());
+        Ok(git_attributes)
+    }
+
+    /// Returns the attributes that apply to the file at `path`.
+    pub fn for_file(&mut self, path: &RepoPath) -> BackendResult<Arc<GitAttributesFile>> {
+        self.for_dir(&path.parent().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(input: &[u8], path: &str, name: &str) -> GitAttributeValue {
+        let file = GitAttributesFile::empty().chain("", input);
+        file.get(path, name)
+    }
+
+    #[test]
+    fn test_gitattributes_empty_file() {
+        let file = GitAttributesFile::empty();
+        assert_eq!(file.get("foo", "text"), GitAttributeValue::Unspecified);
+    }
+
+    #[test]
+    fn test_gitattributes_states() {
+        let input = b"* text -diff eol=lf !merge\n";
+        assert_eq!(get(input, "foo", "text"), GitAttributeValue::Set);
+        assert_eq!(get(input, "foo", "diff"), GitAttributeValue::Unset);
+        assert_eq!(
+            get(input, "foo", "eol"),
+            GitAttributeValue::Value("lf".to_owned())
+        );
+        assert_eq!(get(input, "foo", "merge"), GitAttributeValue::Unspecified);
+        assert_eq!(get(input, "foo", "filter"), GitAttributeValue::Unspecified);
+    }
+
+    #[test]
+    fn test_gitattributes_comments_and_whitespace() {
+        let input = b"# *.bin filter=lfs\n\n  *.bin\tfilter=lfs  \r\n";
+        assert_eq!(
+            get(input, "a.bin", "filter"),
+            GitAttributeValue::Value("lfs".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_gitattributes_patterns() {
+        let input = b"*.bin filter=lfs\n/root.txt text\nsub/*.c -text\n";
+        assert_eq!(
+            get(input, "dir/a.bin", "filter"),
+            GitAttributeValue::Value("lfs".to_owned())
+        );
+        assert_eq!(
+            get(input, "a.binx", "filter"),
+            GitAttributeValue::Unspecified
+        );
+        assert_eq!(get(input, "root.txt", "text"), GitAttributeValue::Set);
+        assert_eq!(
+            get(input, "dir/root.txt", "text"),
+            GitAttributeValue::Unspecified
+        );
+        assert_eq!(get(input, "sub/x.c", "text"), GitAttributeValue::Unset);
+        assert_eq!(
+            get(input, "dir/sub/x.c", "text"),
+            GitAttributeValue::Unspecified
+        );
+    }
+
+    #[test]
+    fn test_gitattributes_directory_patterns_do_not_apply_to_files() {
+        let input = b"dir filter=lfs\ndir/ text\n!*.c text\n";
+        // Unlike ignores, a pattern matching a directory doesn't match its contents
+        assert_eq!(
+            get(input, "dir/file", "filter"),
+            GitAttributeValue::Unspecified
+        );
+        assert_eq!(get(input, "dir", "text"), GitAttributeValue::Unspecified);
+        assert_eq!(get(input, "x.c", "text"), GitAttributeValue::Unspecified);
+    }
+
+    #[test]
+    fn test_gitattributes_line_ordering() {
+        let input = b"*.bin filter=lfs\nsmall.bin !filter\n";
+        assert_eq!(
+            get(input, "big.bin", "filter"),
+            GitAttributeValue::Value("lfs".to_owned())
+        );
+        assert_eq!(
+            get(input, "small.bin", "filter"),
+            GitAttributeValue::Unspecified
+        );
+        assert_eq!(
+            get(b"* -text text\n", "foo", "text"),
+            GitAttributeValue::Set
+        );
+    }
+
+    #[test]
+    fn test_gitattributes_file_ordering() {
+        let file1 = GitAttributesFile::empty().chain("", b"*.bin filter=lfs\n");
+        let file2 = file1.chain("dir/", b"*.bin -filter\n");
+        assert_eq!(
+            file2.get("a.bin", "filter"),
+            GitAttributeValue::Value("lfs".to_owned())
+        );
+        assert_eq!(file2.get("dir/a.bin", "filter"), GitAttributeValue::Unset);
+        assert_eq!(
+            file1.get("dir/a.bin", "filter"),
+            GitAttributeValue::Value("lfs".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_gitattributes_binary_macro() {
+        let input = b"*.png binary
+";
+        assert_eq!(get(input, "a.png", "binary"), GitAttributeValue::Set);
+        assert_eq!(get(input, "a.png", "diff"), GitAttributeValue::Unset);
+        assert_eq!(get(input, "a.png", "merge"), GitAttributeValue::Unset);
+        assert_eq!(get(input, "a.png", "text"), GitAttributeValue::Unset);
+        // A later line can override the expanded attributes
+        let input = b"*.png binary
+*.png diff
+";
+        assert_eq!(get(input, "a.png", "diff"), GitAttributeValue::Set);
+        assert_eq!(get(input, "a.png", "merge"), GitAttributeValue::Unset);
+    }
+
+    #[test]
+    fn test_gitattributes_eol_conversion() {
+        let file = GitAttributesFile::empty().chain(
+            "",
+            b"*.txt text
+*.auto text=auto
+*.bat eol=crlf
+*.sh text eol=lf
+*.bin -text
+",
+        );
+        assert_eq!(file.eol_conversion("a.rs"), EolConversion::None);
+        assert_eq!(
+            file.eol_conversion("a.txt"),
+            EolConversion::Text {
+                auto: false,
+                crlf: false
+            }
+        );
+        assert_eq!(
+            file.eol_conversion("a.auto"),
+            EolConversion::Text {
+                auto: true,
+                crlf: false
+            }
+        );
+        assert_eq!(
+            file.eol_conversion("a.bat"),
+            EolConversion::Text {
+                auto: false,
+                crlf: true
+            }
+        );
+        assert_eq!(
+            file.eol_conversion("a.sh"),
+            EolConversion::Text {
+                auto: false,
+                crlf: false
+            }
+        );
+        assert_eq!(file.eol_conversion("a.bin"), EolConversion::None);
+    }
+
+    #[test]
+    fn test_eol_conversion_convert() {
+        let text = EolConversion::Text {
+            auto: false,
+            crlf: true,
+        };
+        let auto = EolConversion::Text {
+            auto: true,
+            crlf: true,
+        };
+        assert_eq!(text.convert_to_repo(b"a\r\nb\rc\n".to_vec()), b"a\nb\rc\n");
+        assert_eq!(
+            text.convert_to_working_copy(b"a\nb\r\nc".to_vec()),
+            b"a\r\nb\r\nc"
+        );
+        assert_eq!(
+            EolConversion::None.convert_to_repo(b"a\r\n".to_vec()),
+            b"a\r\n"
+        );
+        assert_eq!(
+            EolConversion::None.convert_to_working_copy(b"a\n".to_vec()),
+            b"a\n"
+        );
+        // Binary contents are left alone with text=auto
+        assert_eq!(auto.convert_to_repo(b"\0\r\n".to_vec()), b"\0\r\n");
+        assert_eq!(auto.convert_to_working_copy(b"\0\n".to_vec()), b"\0\n");
+        assert_eq!(text.convert_to_repo(b"\0\r\n".to_vec()), b"\0\n");
+        // Contents already having CRLF in the repo are checked out as they are
+        assert_eq!(
+            auto.convert_to_working_copy(b"a\r\nb\n".to_vec()),
+            b"a\r\nb\n"
+        );
+    }
+
+    #[test]
+    fn test_gitattributes_is_binary() {
+        let file = GitAttributesFile::empty().chain(
+            "",
+            b"*.txt diff
+*.dat -diff -merge
+",
+        );
+        assert!(!file.is_binary_for_diff("a.rs", &[b"text"]));
+        assert!(file.is_binary_for_diff("a.rs", &[b"text", b"\0"]));
+        assert!(!file.is_binary_for_diff("a.txt", &[b"\0"]));
+        assert!(file.is_binary_for_diff("a.dat", &[b"text"]));
+        assert!(file.is_binary_for_merge("a.dat", &[b"text"]));
+        assert!(file.is_binary_for_merge("a.txt", &[b"\0"]));
+    }
+}
@@ -21,6 +21,70 @@ use std::sync::Arc;
 use itertools::Itertools;
 use regex::{escape as regex_escape, Regex};
 
+/// Translates a gitignore-style glob `input` into a regex matching paths
+/// relative to the directory `prefix`. The regex is anchored at the start but
+/// not at the end, so the caller decides what may follow a matched path.
+pub(crate) fn glob_to_regex(prefix: &str, input: &str, is_rooted: bool) -> String {
+    let mut regex = String::new();
+    regex.push('^');
+    regex.push_str(prefix);
+    if !is_rooted {
+        regex.push_str("(.*/)?");
+    }
+
+    let components = input.split('/').collect_vec();
+    for (i, component) in components.iter().enumerate() {
+        if *component == "**" {
+            if i == components.len() - 1 {
+                regex.push_str(".*");
+            } else {
+                regex.push_str("(.*/)?");
+            }
+        } else {
+            let mut in_escape = false;
+            let mut character_class: Option<String> = None;
+            for c in component.chars() {
+                if in_escape {
+                    in_escape = false;
+                    if !matches!(c, ' ' | '#' | '!' | '?' | '\\' | '*') {
+                        regex.push_str(&regex_escape("\\"));
+                    }
+                    regex.push_str(&regex_escape(&c.to_string()));
+                } else if c == '\\' {
+                    in_escape = true;
+                } else if let Some(characters) = &mut character_class {
+                    if c == ']' {
+                        regex.push('[');
+                        regex.push_str(characters);
+                        regex.push(']');
+                        character_class = None;
+                    } else {
+                        characters.push(c);
+                    }
+                } else {
+                    in_escape = false;
+                    if c == '?' {
+                        regex.push_str("[^/]");
+                    } else if c == '*' {
+                        regex.push_str("[^/]*");
+                    } else if c == '[' {
+                        character_class = Some(String::new());
+                    } else {
+                        regex.push_str(&regex_escape(&c.to_string()));
+                    }
+                }
+            }
+            if in_escape {
+                regex.push_str(&regex_escape("\\"));
+            }
+            if i < components.len() - 1 {
+                regex.push('/');
+            }
+        }
+    }
+    regex
+}
+
 #[derive(Debug)]
 struct GitIgnoreLine {
     is_negative: bool,
@@ -74,63 +138,7 @@ impl GitIgnoreLine {
         };
         is_rooted |= input.contains('/');
 
-        let mut regex = String::new();
-        regex.push('^');
-        regex.push_str(prefix);
-        if !is_rooted {
-            regex.push_str("(.*/)?");
-        }
-
-        let components = input.split('/').collect_vec();
-        for (i, component) in components.iter().enumerate() {
-            if *component == "**" {
-                if i == components.len() - 1 {
-                    regex.push_str(".*");
-                } else {
-                    regex.push_str("(.*/)?");
-                }
-            } else {
-                let mut in_escape = false;
-                let mut character_class: Option<String> = None;
-                for c in component.chars() {
-                    if in_escape {
-                        in_escape = false;
-                        if !matches!(c, ' ' | '#' | '!' | '?' | '\\' | '*') {
-                            regex.push_str(&regex_escape("\\"));
-                        }
-                        regex.push_str(&regex_escape(&c.to_string()));
-                    } else if c == '\\' {
-                        in_escape = true;
-                    } else if let Some(characters) = &mut character_class {
-                        if c == ']' {
-                            regex.push('[');
-                            regex.push_str(characters);
-                            regex.push(']');
-                            character_class = None;
-                        } else {
-                            characters.push(c);
-                        }
-                    } else {
-                        in_escape = false;
-                        if c == '?' {
-                            regex.push_str("[^/]");
-                        } else if c == '*' {
-                            regex.push_str("[^/]*");
-                        } else if c == '[' {
-                            character_class = Some(String::new());
-                        } else {
-                            regex.push_str(&regex_escape(&c.to_string()));
-                        }
-                    }
-                }
-                if in_escape {
-                    regex.push_str(&regex_escape("\\"));
-                }
-                if i < components.len() - 1 {
-                    regex.push('/');
-                }
-            }
-        }
+        let mut regex = glob_to_regex(prefix, input, is_rooted);
         if matches_only_directory {
             regex.push_str("/.*");
         } else {
@@ -56,6 +56,10 @@ pub trait Index: Send + Sync {
 
     fn is_ancestor(&self, ancestor_id: &CommitId, descendant_id: &CommitId) -> bool;
 
+    /// The length of the longest path from the root commit to the commit, or
+    /// `None` if the commit isn't in the index.
+    fn generation_number(&self, commit_id: &CommitId) -> Option<u32>;
+
     fn common_ancestors(&self, set1: &[CommitId], set2: &[CommitId]) -> Vec<CommitId>;
 
     fn heads(&self, candidates: &mut dyn Iterator<Item = &CommitId>) -> Vec<CommitId>;
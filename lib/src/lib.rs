@@ -20,7 +20,9 @@
 #[macro_use]
 mod content_hash;
 
+pub mod absorb;
 pub mod backend;
+pub mod bisect;
 pub mod commit;
 pub mod commit_builder;
 pub mod conflicts;
@@ -36,6 +38,9 @@ pub mod fmt_util;
 pub mod fsmonitor;
 pub mod git;
 pub mod git_backend;
+pub mod git_hooks;
+pub mod git_lfs;
+pub mod gitattributes;
 pub mod gitignore;
 pub mod hex_util;
 pub mod id_prefix;
@@ -44,6 +49,7 @@ pub mod local_backend;
 pub mod lock;
 pub mod matchers;
 pub mod merge;
+pub mod merge_driver;
 pub mod merged_tree;
 pub mod op_heads_store;
 pub mod op_store;
@@ -59,6 +65,7 @@ pub mod rewrite;
 pub mod settings;
 pub mod simple_op_heads_store;
 pub mod simple_op_store;
+pub mod sparse;
 pub mod stacked_table;
 pub mod store;
 pub mod submodule_store;
@@ -17,6 +17,7 @@
 use std::collections::{HashMap, HashSet};
 use std::iter;
 
+use itertools::Itertools;
 use tracing::instrument;
 
 use crate::repo_path::{RepoPath, RepoPathComponent};
@@ -259,6 +260,132 @@ impl Matcher for IntersectionMatcher<'_> {
     }
 }
 
+/// Matches paths that are matched by either input matcher.
+pub struct UnionMatcher<'input> {
+    input1: &'input dyn Matcher,
+    input2: &'input dyn Matcher,
+}
+
+impl<'input> UnionMatcher<'input> {
+    pub fn new(input1: &'input dyn Matcher, input2: &'input dyn Matcher) -> Self {
+        Self { input1, input2 }
+    }
+}
+
+impl Matcher for UnionMatcher<'_> {
+    fn matches(&self, file: &RepoPath) -> bool {
+        self.input1.matches(file) || self.input2.matches(file)
+    }
+
+    fn visit(&self, dir: &RepoPath) -> Visit {
+        match self.input1.visit(dir) {
+            Visit::AllRecursively => Visit::AllRecursively,
+            Visit::Nothing => self.input2.visit(dir),
+            Visit::Specific {
+                dirs: dirs1,
+                files: files1,
+            } => match self.input2.visit(dir) {
+                Visit::AllRecursively => Visit::AllRecursively,
+                Visit::Nothing => Visit::Specific {
+                    dirs: dirs1,
+                    files: files1,
+                },
+                Visit::Specific {
+                    dirs: dirs2,
+                    files: files2,
+                } => {
+                    let dirs = match (dirs1, dirs2) {
+                        (VisitDirs::Set(dirs1), VisitDirs::Set(dirs2)) => {
+                            VisitDirs::Set(dirs1.union(&dirs2).cloned().collect())
+                        }
+                        _ => VisitDirs::All,
+                    };
+                    let files = match (files1, files2) {
+                        (VisitFiles::Set(files1), VisitFiles::Set(files2)) => {
+                            VisitFiles::Set(files1.union(&files2).cloned().collect())
+                        }
+                        _ => VisitFiles::All,
+                    };
+                    Visit::Specific { dirs, files }
+                }
+            },
+        }
+    }
+}
+
+/// Matches paths that match any of the glob patterns, and everything under
+/// them. A `*` doesn't match `/`, but `**` matches any number of directories.
+pub struct GlobMatcher {
+    globs: Vec<glob::Pattern>,
+    /// The leading components of each glob without any wildcards, used to
+    /// skip directories that can't contain matches.
+    literal_prefixes: PrefixMatcher,
+}
+
+impl GlobMatcher {
+    #[instrument]
+    pub fn new(globs: &[glob::Pattern]) -> Self {
+        let literal_prefixes = globs
+            .iter()
+            .map(|glob| {
+                let components = glob
+                    .as_str()
+                    .split('/')
+                    .take_while(|component| {
+                        !component.contains(['*', '?', '[']) && !component.is_empty()
+                    })
+                    .map(RepoPathComponent::from)
+                    .collect_vec();
+                RepoPath::from_components(components)
+            })
+            .collect_vec();
+        GlobMatcher {
+            globs: globs.to_vec(),
+            literal_prefixes: PrefixMatcher::new(&literal_prefixes),
+        }
+    }
+
+    /// Returns whether any of the globs matches `path` or one of its parent
+    /// directories.
+    fn matches_self_or_ancestor(&self, path: &RepoPath) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        let components = path.components();
+        (1..=components.len()).any(|len| {
+            let path = components[..len]
+                .iter()
+                .map(|component| component.as_str())
+                .join("/");
+            self.globs
+                .iter()
+                .any(|glob| glob.matches_with(&path, options))
+        })
+    }
+}
+
+impl Matcher for GlobMatcher {
+    fn matches(&self, file: &RepoPath) -> bool {
+        self.matches_self_or_ancestor(file)
+    }
+
+    fn visit(&self, dir: &RepoPath) -> Visit {
+        if self.matches_self_or_ancestor(dir) {
+            return Visit::AllRecursively;
+        }
+        match self.literal_prefixes.visit(dir) {
+            // Some glob may match paths in the directory, so visit everything
+            Visit::AllRecursively => Visit::Specific {
+                dirs: VisitDirs::All,
+                files: VisitFiles::All,
+            },
+            visit => visit,
+        }
+    }
+}
+
 /// Keeps track of which subdirectories and files of each directory need to be
 /// visited.
 #[derive(PartialEq, Eq, Debug)]
@@ -0,0 +1,320 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-path merge drivers.
+//!
+//! The `merge.drivers` config table maps path patterns to drivers that are
+//! used when the built-in line-based merge of a file leaves conflicts. A driver
+//! is either one of the builtins (`"union"`, `"ours"`, `"theirs"`) or an
+//! external command given as a list of arguments.
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+use config::ValueKind;
+use itertools::Itertools;
+use regex::Regex;
+
+use crate::backend::{FileId, TreeValue};
+use crate::files::{self, FileMergeHunkLevel, MergeResult};
+use crate::gitattributes::GitAttributesFile;
+use crate::gitignore::glob_to_regex;
+use crate::merge::Merge;
+use crate::repo_path::RepoPath;
+use crate::store::Store;
+use crate::tree::TreeMergeError;
+
+/// How to resolve a file conflict that the built-in merge couldn't resolve.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergeDriver {
+    /// Keeps the lines of all sides in conflicting regions, in order.
+    Union,
+    /// Picks the first side, e.g. the destination when rebasing.
+    Ours,
+    /// Picks the second side, e.g. the commit being rebased.
+    Theirs,
+    /// Runs an external command. The arguments `$base`, `$left` and `$right`
+    /// are replaced by paths to files with the contents of the sides, and
+    /// `$output` by a path to which the command should write the result.
+    /// `$path` is replaced by the path of the file in the repo. A non-zero
+    /// exit status means the command couldn't merge the file.
+    External(Vec<String>),
+}
+
+impl MergeDriver {
+    fn from_config_value(value: config::Value) -> Result<Self, config::ConfigError> {
+        match value.kind {
+            ValueKind::String(name) => match name.as_str() {
+                "union" => Ok(MergeDriver::Union),
+                "ours" => Ok(MergeDriver::Ours),
+                "theirs" => Ok(MergeDriver::Theirs),
+                _ => Err(config::ConfigError::Message(format!(
+                    "unknown builtin merge driver: {name}"
+                ))),
+            },
+            ValueKind::Array(_) => {
+                let args: Vec<String> = value.try_deserialize()?;
+                if args.is_empty() {
+                    return Err(config::ConfigError::Message(
+                        "merge driver command must not be empty".to_string(),
+                    ));
+                }
+                Ok(MergeDriver::External(args))
+            }
+            _ => Err(config::ConfigError::Message(
+                "merge driver must be a builtin name or a command".to_string(),
+            )),
+        }
+    }
+
+    /// Tries to resolve the conflict between the files in `conflict` at
+    /// `path`. Returns `None` if the driver doesn't apply to the conflict or
+    /// couldn't resolve it. Only conflicts where all sides are files are
+    /// considered, and `ours`, `theirs` and external commands only handle
+    /// conflicts with two sides.
+    pub fn try_resolve(
+        &self,
+        store: &Store,
+        path: &RepoPath,
+        conflict: &Merge<Option<TreeValue>>,
+        git_attributes: &GitAttributesFile,
+    ) -> Result<Option<TreeValue>, TreeMergeError> {
+        let Some(file_conflict) = conflict.maybe_map(|term| match term {
+            Some(TreeValue::File { id, executable }) => Some((id, *executable)),
+            _ => None,
+        }) else {
+            return Ok(None);
+        };
+        let is_two_sided = file_conflict.adds().len() == 2;
+        let pick_side = |index: usize| {
+            let (id, executable): &(&FileId, bool) = &file_conflict.adds()[index];
+            Some(TreeValue::File {
+                id: (*id).clone(),
+                executable: *executable,
+            })
+        };
+        match self {
+            MergeDriver::Ours if is_two_sided => return Ok(pick_side(0)),
+            MergeDriver::Theirs if is_two_sided => return Ok(pick_side(1)),
+            MergeDriver::Ours | MergeDriver::Theirs => return Ok(None),
+            MergeDriver::Union | MergeDriver::External(_) => {}
+        }
+
+        let Some(&executable) = file_conflict
+            .map(|(_, executable)| *executable)
+            .resolve_trivial()
+        else {
+            // We're unable to determine whether the result should be executable
+            return Ok(None);
+        };
+        let read_contents = |ids: &[(&FileId, bool)]| -> Result<Vec<Vec<u8>>, TreeMergeError> {
+            ids.iter()
+                .map(|(id, _)| read_file_contents(store, path, id))
+                .try_collect()
+        };
+        let removed_contents = read_contents(file_conflict.removes())?;
+        let added_contents = read_contents(file_conflict.adds())?;
+        let merged_content = match self {
+            MergeDriver::Union => {
+                let removed_contents = removed_contents.iter().map(Vec::as_slice).collect_vec();
+                let added_contents = added_contents.iter().map(Vec::as_slice).collect_vec();
+                let all_contents =
+                    [removed_contents.as_slice(), added_contents.as_slice()].concat();
+                if git_attributes
+                    .is_binary_for_merge(&path.to_internal_file_string(), &all_contents)
+                {
+                    return Ok(None);
+                }
+                union_merge(&removed_contents, &added_contents, store.merge_hunk_level())
+            }
+            MergeDriver::External(args) if is_two_sided => {
+                let Some(content) = run_external_driver(
+                    path,
+                    args,
+                    &removed_contents[0],
+                    &added_contents[0],
+                    &added_contents[1],
+                )?
+                else {
+                    return Ok(None);
+                };
+                content
+            }
+            _ => return Ok(None),
+        };
+        let id = store.write_file(path, &mut merged_content.as_slice())?;
+        Ok(Some(TreeValue::File { id, executable }))
+    }
+}
+
+/// Merges the contents like the built-in merge, but resolves each conflicting
+/// region by concatenating the sides.
+fn union_merge(removes: &[&[u8]], adds: &[&[u8]], hunk_level: FileMergeHunkLevel) -> Vec<u8> {
+    match files::merge_with_hunk_level(removes, adds, hunk_level) {
+        MergeResult::Resolved(content) => content.0,
+        MergeResult::Conflict(hunks) => {
+            let mut content = vec![];
+            for hunk in hunks {
+                if let Some(resolved) = hunk.as_resolved() {
+                    content.extend_from_slice(&resolved.0);
+                } else {
+                    for add in hunk.adds() {
+                        content.extend_from_slice(&add.0);
+                    }
+                }
+            }
+            content
+        }
+    }
+}
+
+fn run_external_driver(
+    path: &RepoPath,
+    args: &[String],
+    base: &[u8],
+    left: &[u8],
+    right: &[u8],
+) -> Result<Option<Vec<u8>>, TreeMergeError> {
+    let driver_error = |source| TreeMergeError::MergeDriverError {
+        path: path.clone(),
+        source,
+    };
+    let temp_dir = tempfile::Builder::new()
+        .prefix("jj-merge-")
+        .tempdir()
+        .map_err(driver_error)?;
+    let base_path = temp_dir.path().join("base");
+    let left_path = temp_dir.path().join("left");
+    let right_path = temp_dir.path().join("right");
+    let output_path = temp_dir.path().join("output");
+    for (file_path, content) in [(&base_path, base), (&left_path, left), (&right_path, right)] {
+        std::fs::write(file_path, content).map_err(driver_error)?;
+    }
+    // Like Git's merge drivers, start with the left side in the output file so
+    // commands that edit it in place work too.
+    std::fs::write(&output_path, left).map_err(driver_error)?;
+    let path_string = path.to_internal_file_string();
+    let args = args
+        .iter()
+        .map(|arg| match arg.as_str() {
+            "$base" => base_path.to_str().unwrap(),
+            "$left" => left_path.to_str().unwrap(),
+            "$right" => right_path.to_str().unwrap(),
+            "$output" => output_path.to_str().unwrap(),
+            "$path" => path_string.as_str(),
+            _ => arg.as_str(),
+        })
+        .collect_vec();
+    let status = Command::new(args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .map_err(driver_error)?;
+    if !status.success() {
+        return Ok(None);
+    }
+    let mut content = vec![];
+    std::fs::File::open(&output_path)
+        .and_then(|mut file| file.read_to_end(&mut content))
+        .map_err(driver_error)?;
+    Ok(Some(content))
+}
+
+fn read_file_contents(
+    store: &Store,
+    path: &RepoPath,
+    id: &FileId,
+) -> Result<Vec<u8>, TreeMergeError> {
+    let mut content = vec![];
+    store
+        .read_file(path, id)?
+        .read_to_end(&mut content)
+        .map_err(|err| TreeMergeError::ReadError {
+            source: err,
+            file_id: id.clone(),
+        })?;
+    Ok(content)
+}
+
+#[derive(Debug)]
+struct MergeDriverRule {
+    pattern: String,
+    regex: Regex,
+    driver: MergeDriver,
+}
+
+/// The merge drivers configured for paths in the repo.
+#[derive(Debug, Default)]
+pub struct MergeDrivers {
+    rules: Vec<MergeDriverRule>,
+}
+
+impl MergeDrivers {
+    /// Creates an instance without any drivers.
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Parses the `merge.drivers` table of `config`. The keys are
+    /// gitignore-style glob patterns. Like in `.gitattributes`, a pattern
+    /// without a `/` matches files with that name in any directory.
+    pub fn from_config(config: &config::Config) -> Result<Self, config::ConfigError> {
+        let table = match config.get_table("merge.drivers") {
+            Ok(table) => table,
+            Err(config::ConfigError::NotFound(_)) => return Ok(Self::empty()),
+            Err(err) => return Err(err),
+        };
+        let mut rules = vec![];
+        for (pattern, value) in table {
+            let (is_rooted, glob) = match pattern.strip_prefix('/') {
+                None => (pattern.contains('/'), pattern.as_str()),
+                Some(rest) => (true, rest),
+            };
+            let mut regex = glob_to_regex("", glob, is_rooted);
+            regex.push('$');
+            let regex = Regex::new(&regex).map_err(|err| {
+                config::ConfigError::Message(format!(
+                    "invalid merge driver pattern {pattern:?}: {err}"
+                ))
+            })?;
+            let driver = MergeDriver::from_config_value(value).map_err(|err| {
+                config::ConfigError::Message(format!("merge.drivers.{pattern:?}: {err}"))
+            })?;
+            rules.push(MergeDriverRule {
+                pattern,
+                regex,
+                driver,
+            });
+        }
+        // Config tables are unordered, so make more specific (longer) patterns
+        // take precedence when several match.
+        rules.sort_by(|a, b| (b.pattern.len(), &a.pattern).cmp(&(a.pattern.len(), &b.pattern)));
+        Ok(MergeDrivers { rules })
+    }
+
+    /// Whether no drivers are configured.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns the driver for the file at `path`, if any.
+    pub fn driver_for_path(&self, path: &RepoPath) -> Option<&MergeDriver> {
+        let path = path.to_internal_file_string();
+        self.rules
+            .iter()
+            .find(|rule| rule.regex.is_match(&path))
+            .map(|rule| &rule.driver)
+    }
+}
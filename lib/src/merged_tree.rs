@@ -23,6 +23,7 @@ use std::{iter, vec};
 use itertools::Itertools;
 
 use crate::backend::{BackendError, BackendResult, ConflictId, MergedTreeId, TreeId, TreeValue};
+use crate::gitattributes::GitAttributesFile;
 use crate::matchers::{EverythingMatcher, Matcher};
 use crate::merge::{Merge, MergeBuilder};
 use crate::repo_path::{RepoPath, RepoPathComponent, RepoPathJoin};
@@ -206,7 +207,7 @@ impl MergedTree {
     pub fn resolve(&self) -> Result<Merge<Tree>, TreeMergeError> {
         match self {
             MergedTree::Legacy(tree) => Ok(Merge::resolved(tree.clone())),
-            MergedTree::Merge(trees) => merge_trees(trees),
+            MergedTree::Merge(trees) => merge_trees(trees, &GitAttributesFile::empty()),
         }
     }
 
@@ -386,7 +387,7 @@ impl MergedTree {
                 vec![to_merge(base)?],
                 vec![to_merge(self)?, to_merge(other)?],
             );
-            let tree = merge_trees(&nested.flatten().simplify())?;
+            let tree = merge_trees(&nested.flatten().simplify(), &GitAttributesFile::empty())?;
             // If the result can be resolved, then `merge_trees()` above would have returned
             // a resolved merge. However, that function will always preserve the arity of
             // conflicts it cannot resolve. So we simplify the conflict again
@@ -403,7 +404,12 @@ fn all_tree_conflict_names(trees: &Merge<Tree>) -> impl Iterator<Item = &RepoPat
         .dedup()
 }
 
-fn merge_trees(merge: &Merge<Tree>) -> Result<Merge<Tree>, TreeMergeError> {
+/// Merges trees in a directory whose parent directories have the given
+/// attributes. Like Git, the `.gitattributes` files of the first side are used.
+fn merge_trees(
+    merge: &Merge<Tree>,
+    parent_git_attributes: &Arc<GitAttributesFile>,
+) -> Result<Merge<Tree>, TreeMergeError> {
     if let Some(tree) = merge.resolve_trivial() {
         return Ok(Merge::resolved(tree.clone()));
     }
@@ -411,6 +417,7 @@ fn merge_trees(merge: &Merge<Tree>) -> Result<Merge<Tree>, TreeMergeError> {
     let base_tree = &merge.adds()[0];
     let store = base_tree.store();
     let dir = base_tree.dir();
+    let git_attributes = parent_git_attributes.chain_with_tree(base_tree)?;
     // Keep resolved entries in `new_tree` and conflicted entries in `conflicts` to
     // start with. Then we'll create the full trees later, and only if there are
     // any conflicts.
@@ -418,7 +425,8 @@ fn merge_trees(merge: &Merge<Tree>) -> Result<Merge<Tree>, TreeMergeError> {
     let mut conflicts = vec![];
     for basename in all_tree_conflict_names(merge) {
         let path_merge = merge.map(|tree| tree.value(basename).cloned());
-        let path_merge = merge_tree_values(store, dir, path_merge)?;
+        let path_merge =
+            merge_tree_values(store, &dir.join(basename), path_merge, &git_attributes)?;
         match path_merge.into_resolved() {
             Ok(value) => {
                 new_tree.set_or_remove(basename, value);
@@ -464,6 +472,7 @@ fn merge_tree_values(
     store: &Arc<Store>,
     path: &RepoPath,
     values: Merge<Option<TreeValue>>,
+    git_attributes: &Arc<GitAttributesFile>,
 ) -> Result<Merge<Option<TreeValue>>, TreeMergeError> {
     if let Some(resolved) = values.resolve_trivial() {
         return Ok(Merge::resolved(resolved.clone()));
@@ -472,7 +481,7 @@ fn merge_tree_values(
     if let Some(trees) = values.to_tree_merge(store, path)? {
         // If all sides are trees or missing, merge the trees recursively, treating
         // missing trees as empty.
-        let merged_tree = merge_trees(&trees)?;
+        let merged_tree = merge_trees(&trees, git_attributes)?;
         if merged_tree.as_resolved().map(|tree| tree.id()) == Some(store.empty_tree_id()) {
             Ok(Merge::absent())
         } else {
@@ -481,7 +490,7 @@ fn merge_tree_values(
     } else {
         // Try to resolve file conflicts by merging the file contents. Treats missing
         // files as empty.
-        if let Some(resolved) = try_resolve_file_conflict(store, path, &values)? {
+        if let Some(resolved) = try_resolve_file_conflict(store, path, &values, git_attributes)? {
             Ok(Merge::normal(resolved))
         } else {
             // Failed to merge the files, or the paths are not files
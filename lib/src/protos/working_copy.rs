@@ -11,12 +11,22 @@ pub struct FileState {
     #[deprecated]
     #[prost(bytes = "vec", tag = "4")]
     pub conflict_id: ::prost::alloc::vec::Vec<u8>,
+    /// The length of the conflict markers if the file was written as a
+    /// materialized conflict, otherwise 0.
+    #[prost(uint32, tag = "5")]
+    pub conflict_marker_len: u32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SparsePatterns {
     #[prost(string, repeated, tag = "1")]
     pub prefixes: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, repeated, tag = "2")]
+    pub globs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, repeated, tag = "3")]
+    pub exclude_prefixes: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, repeated, tag = "4")]
+    pub exclude_globs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -36,6 +46,8 @@ pub struct TreeState {
     pub sparse_patterns: ::core::option::Option<SparsePatterns>,
     #[prost(message, optional, tag = "4")]
     pub watchman_clock: ::core::option::Option<WatchmanClock>,
+    #[prost(message, optional, tag = "6")]
+    pub inotify_clock: ::core::option::Option<InotifyClock>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -54,6 +66,18 @@ pub mod watchman_clock {
         UnixTimestamp(i64),
     }
 }
+/// Position in the journal of the built-in filesystem monitor.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InotifyClock {
+    /// Identifies the journal, which changes whenever the daemon restarts or
+    /// starts a new journal.
+    #[prost(string, tag = "1")]
+    pub instance_id: ::prost::alloc::string::String,
+    /// Offset in bytes into the journal.
+    #[prost(uint64, tag = "2")]
+    pub offset: u64,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Checkout {
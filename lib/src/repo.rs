@@ -152,7 +152,14 @@ impl ReadonlyRepo {
         let backend = backend_factory(&store_path)?;
         let backend_path = store_path.join("type");
         fs::write(&backend_path, backend.name()).context(&backend_path)?;
-        let store = Store::new(backend, user_settings.use_tree_conflict_format());
+        // A new repo has nothing to merge yet. Invalid merge config is reported
+        // when the repo is loaded.
+        let store = Store::new(
+            backend,
+            user_settings.use_tree_conflict_format(),
+            user_settings.merge_drivers().unwrap_or_default(),
+            user_settings.merge_hunk_level().unwrap_or_default(),
+        );
         let repo_settings = user_settings.with_repo(&repo_path).unwrap();
 
         let op_store_path = repo_path.join("op_store");
@@ -409,6 +416,8 @@ pub enum StoreLoadError {
     },
     #[error(transparent)]
     Backend(#[from] BackendLoadError),
+    #[error("Invalid merge config: {0}")]
+    MergeConfig(#[source] config::ConfigError),
 }
 
 impl StoreFactories {
@@ -588,6 +597,12 @@ impl RepoLoader {
         let store = Store::new(
             store_factories.load_backend(&repo_path.join("store"))?,
             user_settings.use_tree_conflict_format(),
+            user_settings
+                .merge_drivers()
+                .map_err(StoreLoadError::MergeConfig)?,
+            user_settings
+                .merge_hunk_level()
+                .map_err(StoreLoadError::MergeConfig)?,
         );
         let repo_settings = user_settings.with_repo(repo_path).unwrap();
         let op_store = Arc::from(store_factories.load_op_store(&repo_path.join("op_store"))?);
@@ -22,8 +22,11 @@ use rand::prelude::*;
 use rand_chacha::ChaCha20Rng;
 
 use crate::backend::{ChangeId, ObjectId, Signature, Timestamp};
+use crate::conflicts::ConflictMarkerStyle;
+use crate::files::FileMergeHunkLevel;
 use crate::fmt_util::binary_prefix;
 use crate::fsmonitor::FsmonitorKind;
+use crate::merge_driver::MergeDrivers;
 
 #[derive(Debug, Clone)]
 pub struct UserSettings {
@@ -40,20 +43,44 @@ pub struct RepoSettings {
 #[derive(Debug, Clone)]
 pub struct GitSettings {
     pub auto_local_branch: bool,
+    /// Names of the Git hooks to run.
+    pub run_hooks: Vec<String>,
 }
 
 impl GitSettings {
     pub fn from_config(config: &config::Config) -> Self {
         GitSettings {
             auto_local_branch: config.get_bool("git.auto-local-branch").unwrap_or(true),
+            run_hooks: get_run_hooks_config(config),
         }
     }
+
+    pub fn should_run_hook(&self, name: &str) -> bool {
+        self.run_hooks.iter().any(|hook| hook == name)
+    }
+}
+
+/// `git.run-hooks` is either a boolean enabling all supported hooks, or a
+/// list of hook names.
+fn get_run_hooks_config(config: &config::Config) -> Vec<String> {
+    if let Ok(enabled) = config.get_bool("git.run-hooks") {
+        if enabled {
+            vec!["pre-push".to_owned(), "commit-msg".to_owned()]
+        } else {
+            vec![]
+        }
+    } else {
+        config
+            .get::<Vec<String>>("git.run-hooks")
+            .unwrap_or_default()
+    }
 }
 
 impl Default for GitSettings {
     fn default() -> Self {
         GitSettings {
             auto_local_branch: true,
+            run_hooks: vec![],
         }
     }
 }
@@ -103,6 +130,18 @@ impl UserSettings {
             .unwrap_or(false)
     }
 
+    pub fn merge_drivers(&self) -> Result<MergeDrivers, config::ConfigError> {
+        MergeDrivers::from_config(&self.config)
+    }
+
+    pub fn merge_hunk_level(&self) -> Result<FileMergeHunkLevel, config::ConfigError> {
+        match self.config.get_string("merge.hunk-level") {
+            Ok(level) => level.parse(),
+            Err(config::ConfigError::NotFound(_)) => Ok(FileMergeHunkLevel::default()),
+            Err(err) => Err(err),
+        }
+    }
+
     pub fn user_name(&self) -> String {
         self.config.get_string("user.name").unwrap_or_default()
     }
@@ -196,6 +235,14 @@ impl UserSettings {
             .unwrap_or_else(|_| "curved".to_string())
     }
 
+    pub fn conflict_marker_style(&self) -> Result<ConflictMarkerStyle, config::ConfigError> {
+        match self.config.get_string("ui.conflict-marker-style") {
+            Ok(style) => style.parse(),
+            Err(config::ConfigError::NotFound(_)) => Ok(ConflictMarkerStyle::default()),
+            Err(err) => Err(err),
+        }
+    }
+
     pub fn max_new_file_size(&self) -> Result<u64, config::ConfigError> {
         let cfg = self
             .config
@@ -284,6 +331,15 @@ impl<'de> serde::Deserialize<'de> for HumanByteSize {
                 Ok(HumanByteSize(v))
             }
 
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                // TOML integers are signed
+                let bytes = u64::try_from(v).map_err(|_| Error::custom("must not be negative"))?;
+                Ok(HumanByteSize(bytes))
+            }
+
             fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
             where
                 E: Error,
@@ -352,4 +408,19 @@ mod tests {
         );
         assert_eq!(parse_human_byte_size(""), Err("must start with a number"));
     }
+
+    #[test]
+    fn byte_size_from_config() {
+        fn parse(toml: &str) -> Result<HumanByteSize, config::ConfigError> {
+            config::Config::builder()
+                .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+                .build()
+                .unwrap()
+                .get("size")
+        }
+        assert_eq!(parse("size = 42").unwrap(), HumanByteSize(42));
+        assert_eq!(parse("size = '42'").unwrap(), HumanByteSize(42));
+        assert_eq!(parse("size = '42KiB'").unwrap(), HumanByteSize(42 * 1024));
+        assert!(parse("size = -1").is_err());
+    }
 }
@@ -0,0 +1,185 @@
+// Copyright 2023 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Patterns selecting which paths are present in a sparse working copy.
+//!
+//! A path is present if it's matched by at least one including pattern and by
+//! no excluding pattern, regardless of the order of the patterns. A pattern
+//! matching a directory matches everything in it.
+
+use itertools::Itertools;
+
+use crate::matchers::{
+    DifferenceMatcher, GlobMatcher, Matcher, PrefixMatcher, UnionMatcher, Visit,
+};
+use crate::repo_path::RepoPath;
+
+/// A pattern in the sparse checkout configuration.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SparsePattern {
+    /// Includes the path and everything under it.
+    Prefix(RepoPath),
+    /// Includes the paths matching the glob, relative to the workspace root,
+    /// and everything under them.
+    Glob(String),
+    /// Excludes the path and everything under it.
+    ExcludePrefix(RepoPath),
+    /// Excludes the paths matching the glob, relative to the workspace root,
+    /// and everything under them.
+    ExcludeGlob(String),
+}
+
+impl SparsePattern {
+    /// Returns whether the pattern excludes the paths it matches.
+    pub fn is_exclude(&self) -> bool {
+        matches!(
+            self,
+            SparsePattern::ExcludePrefix(_) | SparsePattern::ExcludeGlob(_)
+        )
+    }
+}
+
+impl From<RepoPath> for SparsePattern {
+    fn from(path: RepoPath) -> Self {
+        SparsePattern::Prefix(path)
+    }
+}
+
+/// Returns whether `pattern` should be interpreted as a glob rather than a
+/// path.
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Matches the paths that are present in a working copy with the given sparse
+/// patterns.
+pub struct SparseMatcher {
+    include_prefixes: PrefixMatcher,
+    include_globs: GlobMatcher,
+    exclude_prefixes: PrefixMatcher,
+    exclude_globs: GlobMatcher,
+}
+
+impl SparseMatcher {
+    /// Creates a matcher for `patterns`. Invalid globs don't match anything.
+    pub fn new(patterns: &[SparsePattern]) -> Self {
+        let prefixes = |exclude: bool| {
+            let paths = patterns
+                .iter()
+                .filter_map(|pattern| match pattern {
+                    SparsePattern::Prefix(path) if !exclude => Some(path.clone()),
+                    SparsePattern::ExcludePrefix(path) if exclude => Some(path.clone()),
+                    _ => None,
+                })
+                .collect_vec();
+            PrefixMatcher::new(&paths)
+        };
+        let globs = |exclude: bool| {
+            let globs = patterns
+                .iter()
+                .filter_map(|pattern| match pattern {
+                    SparsePattern::Glob(glob) if !exclude => Some(glob),
+                    SparsePattern::ExcludeGlob(glob) if exclude => Some(glob),
+                    _ => None,
+                })
+                .filter_map(|glob| glob::Pattern::new(glob).ok())
+                .collect_vec();
+            GlobMatcher::new(&globs)
+        };
+        SparseMatcher {
+            include_prefixes: prefixes(false),
+            include_globs: globs(false),
+            exclude_prefixes: prefixes(true),
+            exclude_globs: globs(true),
+        }
+    }
+}
+
+impl Matcher for SparseMatcher {
+    fn matches(&self, file: &RepoPath) -> bool {
+        (self.include_prefixes.matches(file) || self.include_globs.matches(file))
+            && !self.exclude_prefixes.matches(file)
+            && !self.exclude_globs.matches(file)
+    }
+
+    fn visit(&self, dir: &RepoPath) -> Visit {
+        let include = UnionMatcher::new(&self.include_prefixes, &self.include_globs);
+        let exclude = UnionMatcher::new(&self.exclude_prefixes, &self.exclude_globs);
+        DifferenceMatcher::new(&include, &exclude).visit(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(value: &str) -> RepoPath {
+        RepoPath::from_internal_string(value)
+    }
+
+    #[test]
+    fn test_sparse_matcher_prefixes() {
+        let m = SparseMatcher::new(&[SparsePattern::Prefix(path("foo"))]);
+        assert!(m.matches(&path("foo")));
+        assert!(m.matches(&path("foo/bar")));
+        assert!(!m.matches(&path("bar")));
+        assert_eq!(m.visit(&path("foo/bar")), Visit::AllRecursively);
+        assert_eq!(m.visit(&path("bar")), Visit::Nothing);
+    }
+
+    #[test]
+    fn test_sparse_matcher_globs() {
+        let m = SparseMatcher::new(&[
+            SparsePattern::Glob("services/*/src".to_owned()),
+            SparsePattern::Glob("**/*.md".to_owned()),
+        ]);
+        assert!(m.matches(&path("services/a/src/main.rs")));
+        assert!(m.matches(&path("services/a/src")));
+        assert!(!m.matches(&path("services/a/test/main.rs")));
+        assert!(!m.matches(&path("services/a/b/src/main.rs")));
+        assert!(m.matches(&path("README.md")));
+        assert!(m.matches(&path("docs/dir/README.md")));
+        assert!(!m.matches(&path("docs/dir/README.txt")));
+        assert_eq!(m.visit(&path("services/a/src")), Visit::AllRecursively);
+
+        // Without the recursive glob, only directories that may contain
+        // matches are visited
+        let m = SparseMatcher::new(&[SparsePattern::Glob("services/*/src".to_owned())]);
+        assert_eq!(m.visit(&path("other")), Visit::Nothing);
+        assert!(!m.visit(&path("services/a")).is_nothing());
+    }
+
+    #[test]
+    fn test_sparse_matcher_excludes() {
+        let m = SparseMatcher::new(&[
+            SparsePattern::Prefix(path("services")),
+            SparsePattern::ExcludeGlob("services/*/testdata".to_owned()),
+            SparsePattern::ExcludePrefix(path("services/big")),
+        ]);
+        assert!(m.matches(&path("services/a/src/main.rs")));
+        assert!(!m.matches(&path("services/a/testdata/file")));
+        assert!(!m.matches(&path("services/big/file")));
+        assert!(!m.matches(&path("other")));
+        assert_eq!(m.visit(&path("services/a/testdata")), Visit::Nothing);
+        assert_eq!(m.visit(&path("services/big")), Visit::Nothing);
+        assert!(!m.visit(&path("services/a")).is_nothing());
+
+        // Excludes win regardless of the order
+        let m = SparseMatcher::new(&[
+            SparsePattern::ExcludePrefix(path("foo/bar")),
+            SparsePattern::Prefix(path("foo/bar/baz")),
+        ]);
+        assert!(!m.matches(&path("foo/bar/baz")));
+    }
+}
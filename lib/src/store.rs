@@ -26,7 +26,9 @@ use crate::backend::{
     TreeId, TreeValue,
 };
 use crate::commit::Commit;
+use crate::files::FileMergeHunkLevel;
 use crate::merge::Merge;
+use crate::merge_driver::MergeDrivers;
 use crate::merged_tree::MergedTree;
 use crate::repo_path::RepoPath;
 use crate::tree::Tree;
@@ -39,6 +41,8 @@ pub struct Store {
     commit_cache: RwLock<HashMap<CommitId, Arc<backend::Commit>>>,
     tree_cache: RwLock<HashMap<(RepoPath, TreeId), Arc<backend::Tree>>>,
     use_tree_conflict_format: bool,
+    merge_drivers: MergeDrivers,
+    merge_hunk_level: FileMergeHunkLevel,
 }
 
 impl Debug for Store {
@@ -50,12 +54,19 @@ impl Debug for Store {
 }
 
 impl Store {
-    pub fn new(backend: Box<dyn Backend>, use_tree_conflict_format: bool) -> Arc<Self> {
+    pub fn new(
+        backend: Box<dyn Backend>,
+        use_tree_conflict_format: bool,
+        merge_drivers: MergeDrivers,
+        merge_hunk_level: FileMergeHunkLevel,
+    ) -> Arc<Self> {
         Arc::new(Store {
             backend,
             commit_cache: Default::default(),
             tree_cache: Default::default(),
             use_tree_conflict_format,
+            merge_drivers,
+            merge_hunk_level,
         })
     }
 
@@ -68,6 +79,18 @@ impl Store {
         self.use_tree_conflict_format
     }
 
+    /// The merge drivers used for file conflicts that can't be merged
+    /// line by line.
+    pub fn merge_drivers(&self) -> &MergeDrivers {
+        &self.merge_drivers
+    }
+
+    /// The granularity at which the built-in merge of files detects
+    /// conflicts.
+    pub fn merge_hunk_level(&self) -> FileMergeHunkLevel {
+        self.merge_hunk_level
+    }
+
     pub fn commit_id_length(&self) -> usize {
         self.backend.commit_id_length()
     }
@@ -16,6 +16,30 @@
 
 use std::fmt::Debug;
 
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SubmoduleStoreError {
+    #[error("Invalid submodule name \"{0}\"")]
+    InvalidName(String),
+    #[error(transparent)]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Stores the repos backing the Git submodules of a repo, keyed by the name
+/// the submodule has in `.gitmodules`.
 pub trait SubmoduleStore: Send + Sync + Debug {
     fn name(&self) -> &str;
+
+    /// Opens the Git repo backing the submodule. Returns `None` if the
+    /// submodule hasn't been initialized yet.
+    fn load_submodule(&self, name: &str) -> Result<Option<git2::Repository>, SubmoduleStoreError>;
+
+    /// Creates an empty Git repo backing the submodule, with an `origin`
+    /// remote pointing to `url`.
+    fn init_submodule(
+        &self,
+        name: &str,
+        url: &str,
+    ) -> Result<git2::Repository, SubmoduleStoreError>;
 }
@@ -28,6 +28,7 @@ use crate::backend::{
     TreeValue,
 };
 use crate::files::MergeResult;
+use crate::gitattributes::GitAttributesFile;
 use crate::matchers::{EverythingMatcher, Matcher};
 use crate::merge::{trivial_merge, Merge};
 use crate::repo_path::{RepoPath, RepoPathComponent, RepoPathJoin};
@@ -43,6 +44,11 @@ pub enum TreeMergeError {
     },
     #[error("Backend error: {0}")]
     BackendError(#[from] BackendError),
+    #[error("Failed to run merge driver for {}", .path.to_internal_file_string())]
+    MergeDriverError {
+        path: RepoPath,
+        source: std::io::Error,
+    },
 }
 
 #[derive(Clone)]
@@ -295,6 +301,22 @@ pub fn merge_trees(
     side1_tree: &Tree,
     base_tree: &Tree,
     side2_tree: &Tree,
+) -> Result<Tree, TreeMergeError> {
+    merge_trees_in_dir(
+        side1_tree,
+        base_tree,
+        side2_tree,
+        &GitAttributesFile::empty(),
+    )
+}
+
+/// Merges trees in a directory whose parent directories have the given
+/// attributes. Like Git, the `.gitattributes` files of side 1 are used.
+fn merge_trees_in_dir(
+    side1_tree: &Tree,
+    base_tree: &Tree,
+    side2_tree: &Tree,
+    parent_git_attributes: &Arc<GitAttributesFile>,
 ) -> Result<Tree, TreeMergeError> {
     let store = base_tree.store();
     let dir = base_tree.dir();
@@ -304,6 +326,7 @@ pub fn merge_trees(
     if let Some(resolved) = trivial_merge(&[base_tree], &[side1_tree, side2_tree]) {
         return Ok((*resolved).clone());
     }
+    let git_attributes = parent_git_attributes.chain_with_tree(side1_tree)?;
 
     // Start with a tree identical to side 1 and modify based on changes from base
     // to side 2.
@@ -318,8 +341,15 @@ pub fn merge_trees(
             // value
         } else {
             // The two sides changed in different ways
-            let new_value =
-                merge_tree_value(store, dir, basename, maybe_base, maybe_side1, maybe_side2)?;
+            let new_value = merge_tree_value(
+                store,
+                dir,
+                basename,
+                maybe_base,
+                maybe_side1,
+                maybe_side2,
+                &git_attributes,
+            )?;
             new_tree.set_or_remove(basename, new_value);
         }
     }
@@ -346,6 +376,7 @@ fn merge_tree_value(
     maybe_base: Option<&TreeValue>,
     maybe_side1: Option<&TreeValue>,
     maybe_side2: Option<&TreeValue>,
+    git_attributes: &Arc<GitAttributesFile>,
 ) -> Result<Option<TreeValue>, TreeMergeError> {
     // Resolve non-trivial conflicts:
     //   * resolve tree conflicts by recursing
@@ -363,7 +394,8 @@ fn merge_tree_value(
             let base_tree = store.get_tree(&subdir, base_id)?;
             let side1_tree = store.get_tree(&subdir, side1_id)?;
             let side2_tree = store.get_tree(&subdir, side2_id)?;
-            let merged_tree = merge_trees(&side1_tree, &base_tree, &side2_tree)?;
+            let merged_tree =
+                merge_trees_in_dir(&side1_tree, &base_tree, &side2_tree, git_attributes)?;
             if merged_tree.id() == empty_tree_id {
                 None
             } else {
@@ -387,7 +419,7 @@ fn merge_tree_value(
                 Ok(value) => value,
                 Err(conflict) => {
                     if let Some(tree_value) =
-                        try_resolve_file_conflict(store, &filename, &conflict)?
+                        try_resolve_file_conflict(store, &filename, &conflict, git_attributes)?
                     {
                         Some(tree_value)
                     } else {
@@ -400,10 +432,31 @@ fn merge_tree_value(
     })
 }
 
+/// Tries to resolve a conflict between files by merging their contents.
+/// Contents that should be merged as binary according to `git_attributes`
+/// are only resolved if the conflict is trivial. If the contents can't be
+/// merged, the merge driver configured for the path gets a chance to resolve
+/// the conflict.
 pub fn try_resolve_file_conflict(
     store: &Store,
     filename: &RepoPath,
     conflict: &Merge<Option<TreeValue>>,
+    git_attributes: &GitAttributesFile,
+) -> Result<Option<TreeValue>, TreeMergeError> {
+    if let Some(resolved) = try_merge_file_contents(store, filename, conflict, git_attributes)? {
+        return Ok(Some(resolved));
+    }
+    match store.merge_drivers().driver_for_path(filename) {
+        Some(driver) => driver.try_resolve(store, filename, conflict, git_attributes),
+        None => Ok(None),
+    }
+}
+
+fn try_merge_file_contents(
+    store: &Store,
+    filename: &RepoPath,
+    conflict: &Merge<Option<TreeValue>>,
+    git_attributes: &GitAttributesFile,
 ) -> Result<Option<TreeValue>, TreeMergeError> {
     // If there are any non-file or any missing parts in the conflict, we can't
     // merge it. We check early so we don't waste time reading file contents if
@@ -457,10 +510,14 @@ pub fn try_resolve_file_conflict(
             })?;
         added_contents.push(content);
     }
-    let merge_result = files::merge(
-        &removed_contents.iter().map(Vec::as_slice).collect_vec(),
-        &added_contents.iter().map(Vec::as_slice).collect_vec(),
-    );
+    let removed_contents = removed_contents.iter().map(Vec::as_slice).collect_vec();
+    let added_contents = added_contents.iter().map(Vec::as_slice).collect_vec();
+    let all_contents = [removed_contents.as_slice(), added_contents.as_slice()].concat();
+    if git_attributes.is_binary_for_merge(&filename.to_internal_file_string(), &all_contents) {
+        return Ok(None);
+    }
+    let merge_result =
+        files::merge_with_hunk_level(&removed_contents, &added_contents, store.merge_hunk_level());
     match merge_result {
         MergeResult::Resolved(merged_content) => {
             let id = store.write_file(filename, &mut merged_content.0.as_slice())?;
@@ -40,12 +40,18 @@ use thiserror::Error;
 use tracing::{instrument, trace_span};
 
 use crate::backend::{
-    BackendError, FileId, MergedTreeId, MillisSinceEpoch, ObjectId, SymlinkId, TreeId, TreeValue,
+    BackendError, CommitId, FileId, MergedTreeId, MillisSinceEpoch, ObjectId, SymlinkId, TreeId,
+    TreeValue,
 };
-use crate::conflicts;
+use crate::conflicts::{self, ConflictMarkerStyle, MIN_CONFLICT_MARKER_LEN};
+#[cfg(all(feature = "inotify", target_os = "linux"))]
+use crate::fsmonitor::inotify;
 #[cfg(feature = "watchman")]
 use crate::fsmonitor::watchman;
 use crate::fsmonitor::FsmonitorKind;
+use crate::git_backend::GitBackend;
+use crate::git_lfs::{self, LfsPointer, LfsStore};
+use crate::gitattributes::{self, EolConversion, GitAttributesFile, TreeGitAttributes};
 use crate::gitignore::GitIgnoreFile;
 use crate::lock::FileLock;
 use crate::matchers::{
@@ -55,7 +61,7 @@ use crate::merge::{Merge, MergeBuilder};
 use crate::merged_tree::{MergedTree, MergedTreeBuilder};
 use crate::op_store::{OperationId, WorkspaceId};
 use crate::repo_path::{FsPathParseError, RepoPath, RepoPathComponent, RepoPathJoin};
-use crate::settings::HumanByteSize;
+use crate::sparse::{SparseMatcher, SparsePattern};
 use crate::store::Store;
 use crate::tree::Tree;
 
@@ -76,6 +82,9 @@ pub struct FileState {
     pub file_type: FileType,
     pub mtime: MillisSinceEpoch,
     pub size: u64,
+    /// The length of the conflict markers if the file was written as a
+    /// materialized conflict.
+    pub conflict_marker_len: Option<usize>,
     /* TODO: What else do we need here? Git stores a lot of fields.
      * TODO: Could possibly handle case-insensitive file systems keeping an
      *       Option<PathBuf> with the actual path here. */
@@ -93,6 +102,7 @@ impl FileState {
             file_type: FileType::Normal { executable },
             mtime: mtime_from_metadata(metadata),
             size,
+            conflict_marker_len: None,
         }
     }
 
@@ -104,6 +114,7 @@ impl FileState {
             file_type: FileType::Symlink,
             mtime: mtime_from_metadata(metadata),
             size: metadata.len(),
+            conflict_marker_len: None,
         }
     }
 
@@ -112,6 +123,7 @@ impl FileState {
             file_type: FileType::GitSubmodule,
             mtime: MillisSinceEpoch(0),
             size: 0,
+            conflict_marker_len: None,
         }
     }
 }
@@ -123,13 +135,20 @@ pub struct TreeState {
     tree_id: MergedTreeId,
     file_states: BTreeMap<RepoPath, FileState>,
     // Currently only path prefixes
-    sparse_patterns: Vec<RepoPath>,
+    sparse_patterns: Vec<SparsePattern>,
     own_mtime: MillisSinceEpoch,
 
     /// The most recent clock value returned by Watchman. Will only be set if
     /// the repo is configured to use the Watchman filesystem monitor and
     /// Watchman has been queried at least once.
     watchman_clock: Option<crate::protos::working_copy::WatchmanClock>,
+    /// The most recent clock value returned by the built-in filesystem
+    /// monitor. Will only be set if the repo is configured to use it and it
+    /// has been queried at least once.
+    inotify_clock: Option<crate::protos::working_copy::InotifyClock>,
+    /// Where the contents of files with the `filter=lfs` attribute are kept.
+    /// Only set if the repo is backed by Git.
+    lfs_store: Option<LfsStore>,
 }
 
 fn file_state_from_proto(proto: crate::protos::working_copy::FileState) -> FileState {
@@ -152,6 +171,8 @@ fn file_state_from_proto(proto: crate::protos::working_copy::FileState) -> FileS
         file_type,
         mtime: MillisSinceEpoch(proto.mtime_millis_since_epoch),
         size: proto.size,
+        conflict_marker_len: (proto.conflict_marker_len != 0)
+            .then_some(proto.conflict_marker_len as usize),
     }
 }
 
@@ -170,6 +191,7 @@ fn file_state_to_proto(file_state: &FileState) -> crate::protos::working_copy::F
     proto.file_type = file_type as i32;
     proto.mtime_millis_since_epoch = file_state.mtime.0;
     proto.size = file_state.size;
+    proto.conflict_marker_len = file_state.conflict_marker_len.unwrap_or(0) as u32;
     proto
 }
 
@@ -184,16 +206,31 @@ fn file_states_from_proto(
     file_states
 }
 
-fn sparse_patterns_from_proto(proto: &crate::protos::working_copy::TreeState) -> Vec<RepoPath> {
+fn sparse_patterns_from_proto(
+    proto: &crate::protos::working_copy::TreeState,
+) -> Vec<SparsePattern> {
     let mut sparse_patterns = vec![];
     if let Some(proto_sparse_patterns) = proto.sparse_patterns.as_ref() {
         for prefix in &proto_sparse_patterns.prefixes {
-            sparse_patterns.push(RepoPath::from_internal_string(prefix.as_str()));
+            sparse_patterns.push(SparsePattern::Prefix(RepoPath::from_internal_string(
+                prefix.as_str(),
+            )));
+        }
+        for glob in &proto_sparse_patterns.globs {
+            sparse_patterns.push(SparsePattern::Glob(glob.clone()));
+        }
+        for prefix in &proto_sparse_patterns.exclude_prefixes {
+            sparse_patterns.push(SparsePattern::ExcludePrefix(
+                RepoPath::from_internal_string(prefix.as_str()),
+            ));
+        }
+        for glob in &proto_sparse_patterns.exclude_globs {
+            sparse_patterns.push(SparsePattern::ExcludeGlob(glob.clone()));
         }
     } else {
         // For compatibility with old working copies.
         // TODO: Delete this is late 2022 or so.
-        sparse_patterns.push(RepoPath::root());
+        sparse_patterns.push(SparsePattern::Prefix(RepoPath::root()));
     }
     sparse_patterns
 }
@@ -275,6 +312,7 @@ fn file_state(metadata: &Metadata) -> Option<FileState> {
             file_type,
             mtime,
             size,
+            conflict_marker_len: None,
         }
     })
 }
@@ -304,12 +342,6 @@ pub enum SnapshotError {
     InternalBackendError(#[from] BackendError),
     #[error(transparent)]
     TreeStateError(#[from] TreeStateError),
-    #[error("New file {path} of size ~{size} exceeds snapshot.max-new-file-size ({max_size})")]
-    NewFileTooLarge {
-        path: PathBuf,
-        size: HumanByteSize,
-        max_size: HumanByteSize,
-    },
 }
 
 #[derive(Debug, Error)]
@@ -358,7 +390,13 @@ pub struct SnapshotOptions<'a> {
     pub base_ignores: Arc<GitIgnoreFile>,
     pub fsmonitor_kind: Option<FsmonitorKind>,
     pub progress: Option<&'a SnapshotProgress<'a>>,
+    /// New files are only tracked if they match this matcher. Other new files
+    /// are reported in `SnapshotStats::untracked_paths`.
+    pub start_tracking_matcher: &'a dyn Matcher,
     pub max_new_file_size: u64,
+    /// The style conflicts were materialized with, so unchanged conflicts can
+    /// be recognized. Markers of any style are parsed.
+    pub conflict_marker_style: ConflictMarkerStyle,
 }
 
 impl SnapshotOptions<'_> {
@@ -367,14 +405,54 @@ impl SnapshotOptions<'_> {
             base_ignores: GitIgnoreFile::empty(),
             fsmonitor_kind: None,
             progress: None,
+            start_tracking_matcher: &EverythingMatcher,
             max_new_file_size: u64::MAX,
+            conflict_marker_style: ConflictMarkerStyle::default(),
         }
     }
 }
 
+/// Options for writing files to the working copy.
+#[derive(Clone, Debug, Default)]
+pub struct CheckoutOptions {
+    /// How conflicts are materialized in files.
+    pub conflict_marker_style: ConflictMarkerStyle,
+}
+
+impl CheckoutOptions {
+    pub fn empty_for_test() -> Self {
+        CheckoutOptions::default()
+    }
+}
+
+/// Information about a snapshot, in addition to the new tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotStats {
+    /// New files that were left untracked, and why.
+    pub untracked_paths: BTreeMap<RepoPath, UntrackedReason>,
+}
+
+/// The reason why a new file was left untracked by a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UntrackedReason {
+    /// The file doesn't match `snapshot.auto-track`.
+    FileNotAutoTracked,
+    /// The file is larger than `snapshot.max-new-file-size`.
+    FileTooLarge {
+        /// The size of the file in bytes.
+        size: u64,
+        /// The maximum size allowed for new files.
+        max_size: u64,
+    },
+    /// The directory contains a Git repository, such as a submodule that was
+    /// removed from the tree.
+    NestedGitRepository,
+}
+
 struct FsmonitorMatcher {
     matcher: Option<Box<dyn Matcher>>,
     watchman_clock: Option<crate::protos::working_copy::WatchmanClock>,
+    inotify_clock: Option<crate::protos::working_copy::InotifyClock>,
 }
 
 #[derive(Debug, Error)]
@@ -395,6 +473,7 @@ struct DirectoryToVisit {
     dir: RepoPath,
     disk_dir: PathBuf,
     git_ignore: Arc<GitIgnoreFile>,
+    git_attributes: Arc<GitAttributesFile>,
 }
 
 #[derive(Debug, Error)]
@@ -436,12 +515,12 @@ impl TreeState {
         &self.file_states
     }
 
-    pub fn sparse_patterns(&self) -> &Vec<RepoPath> {
+    pub fn sparse_patterns(&self) -> &Vec<SparsePattern> {
         &self.sparse_patterns
     }
 
     fn sparse_matcher(&self) -> Box<dyn Matcher> {
-        Box::new(PrefixMatcher::new(&self.sparse_patterns))
+        Box::new(SparseMatcher::new(&self.sparse_patterns))
     }
 
     pub fn init(
@@ -456,6 +535,10 @@ impl TreeState {
 
     fn empty(store: Arc<Store>, working_copy_path: PathBuf, state_path: PathBuf) -> TreeState {
         let tree_id = store.empty_merged_tree_id();
+        let lfs_store = store
+            .backend_impl()
+            .downcast_ref::<GitBackend>()
+            .map(|git_backend| LfsStore::for_git_dir(&git_backend.git_repo_path()));
         // Canonicalize the working copy path because "repo/." makes libgit2 think that
         // everything should be ignored
         TreeState {
@@ -464,9 +547,11 @@ impl TreeState {
             state_path,
             tree_id,
             file_states: BTreeMap::new(),
-            sparse_patterns: vec![RepoPath::root()],
+            sparse_patterns: vec![SparsePattern::Prefix(RepoPath::root())],
             own_mtime: MillisSinceEpoch(0),
             watchman_clock: None,
+            inotify_clock: None,
+            lfs_store,
         }
     }
 
@@ -529,6 +614,7 @@ impl TreeState {
         self.file_states = file_states_from_proto(&proto);
         self.sparse_patterns = sparse_patterns_from_proto(&proto);
         self.watchman_clock = proto.watchman_clock;
+        self.inotify_clock = proto.inotify_clock;
         Ok(())
     }
 
@@ -550,13 +636,23 @@ impl TreeState {
             );
         }
         let mut sparse_patterns = crate::protos::working_copy::SparsePatterns::default();
-        for path in &self.sparse_patterns {
-            sparse_patterns
-                .prefixes
-                .push(path.to_internal_file_string());
+        for pattern in &self.sparse_patterns {
+            match pattern {
+                SparsePattern::Prefix(path) => sparse_patterns
+                    .prefixes
+                    .push(path.to_internal_file_string()),
+                SparsePattern::Glob(glob) => sparse_patterns.globs.push(glob.clone()),
+                SparsePattern::ExcludePrefix(path) => sparse_patterns
+                    .exclude_prefixes
+                    .push(path.to_internal_file_string()),
+                SparsePattern::ExcludeGlob(glob) => {
+                    sparse_patterns.exclude_globs.push(glob.clone());
+                }
+            }
         }
         proto.sparse_patterns = Some(sparse_patterns);
         proto.watchman_clock = self.watchman_clock.clone();
+        proto.inotify_clock = self.inotify_clock.clone();
 
         let mut temp_file = NamedTempFile::new_in(&self.state_path).unwrap();
         temp_file
@@ -585,16 +681,120 @@ impl TreeState {
         self.store.get_root_tree(&self.tree_id)
     }
 
+    /// Whether any files in the directory `dir` are tracked.
+    fn has_tracked_paths_in(&self, dir: &RepoPath) -> bool {
+        self.file_states
+            .range((Bound::Excluded(dir), Bound::Unbounded))
+            .next()
+            .is_some_and(|(path, _)| dir.contains(path))
+    }
+
+    /// Returns the commit checked out in the submodule at `disk_path` if it's
+    /// not the one in the current tree. Submodules that aren't Git repos
+    /// (e.g. because they haven't been checked out) are left unchanged.
+    fn get_updated_submodule_value(
+        &self,
+        repo_path: &RepoPath,
+        disk_path: &Path,
+        current_tree: &MergedTree,
+    ) -> Option<Merge<Option<TreeValue>>> {
+        let git_repo = git2::Repository::open(disk_path).ok()?;
+        let head_id = git_repo.head().ok()?.target()?;
+        let new_value = TreeValue::GitSubmodule(CommitId::from_bytes(head_id.as_bytes()));
+        let current_value = current_tree.path_value(repo_path);
+        if current_value.as_resolved() == Some(&Some(new_value.clone())) {
+            None
+        } else {
+            Some(Merge::normal(new_value))
+        }
+    }
+
     fn write_file_to_store(
         &self,
         path: &RepoPath,
         disk_path: &Path,
+        current_value: Option<&TreeValue>,
+        git_attributes: &GitAttributesFile,
     ) -> Result<FileId, SnapshotError> {
         let mut file = File::open(disk_path).map_err(|err| SnapshotError::IoError {
             message: format!("Failed to open file {}", disk_path.display()),
             err,
         })?;
-        Ok(self.store.write_file(path, &mut file)?)
+        let path_string = path.to_internal_file_string();
+        if let Some(lfs_store) = &self.lfs_store {
+            if git_lfs::is_lfs_path(git_attributes, &path_string) {
+                return self.write_lfs_file_to_store(lfs_store, path, disk_path, file);
+            }
+        }
+        let eol_conversion = git_attributes.eol_conversion(&path_string);
+        if eol_conversion == EolConversion::None {
+            return Ok(self.store.write_file(path, &mut file)?);
+        }
+        let mut content = vec![];
+        file.read_to_end(&mut content)
+            .map_err(|err| SnapshotError::IoError {
+                message: format!("Failed to read file {}", disk_path.display()),
+                err,
+            })?;
+        // Like Git, don't normalize `text=auto` files that already have CRLF line
+        // endings in the repo, so they don't change just because they were edited.
+        if let (EolConversion::Text { auto: true, .. }, Some(TreeValue::File { id, .. })) =
+            (eol_conversion, current_value)
+        {
+            if gitattributes::has_crlf(&content) && self.stored_file_has_crlf(path, id)? {
+                return Ok(self.store.write_file(path, &mut content.as_slice())?);
+            }
+        }
+        let content = eol_conversion.convert_to_repo(content);
+        Ok(self.store.write_file(path, &mut content.as_slice())?)
+    }
+
+    fn stored_file_has_crlf(&self, path: &RepoPath, id: &FileId) -> Result<bool, SnapshotError> {
+        let mut content = vec![];
+        self.store
+            .read_file(path, id)?
+            .read_to_end(&mut content)
+            .map_err(|err| SnapshotError::IoError {
+                message: format!("Failed to read {}", path.to_internal_file_string()),
+                err,
+            })?;
+        Ok(gitattributes::has_crlf(&content))
+    }
+
+    /// Moves the contents of the file into the LFS store and writes a pointer
+    /// to them to the store instead. Files that are already pointers (because
+    /// the object hasn't been downloaded) are written as they are.
+    fn write_lfs_file_to_store(
+        &self,
+        lfs_store: &LfsStore,
+        path: &RepoPath,
+        disk_path: &Path,
+        mut file: File,
+    ) -> Result<FileId, SnapshotError> {
+        let mut contents = vec![];
+        (&mut file)
+            .take(git_lfs::MAX_POINTER_SIZE)
+            .read_to_end(&mut contents)
+            .map_err(|err| SnapshotError::IoError {
+                message: format!("Failed to read file {}", disk_path.display()),
+                err,
+            })?;
+        let pointer = match LfsPointer::parse(&contents) {
+            Some(pointer) => pointer,
+            None => lfs_store
+                .write_object(&mut contents.as_slice().chain(file))
+                .map_err(|err| SnapshotError::IoError {
+                    message: format!(
+                        "Failed to write {} to the LFS store in {}",
+                        disk_path.display(),
+                        lfs_store.objects_dir().display()
+                    ),
+                    err,
+                })?,
+        };
+        Ok(self
+            .store
+            .write_file(path, &mut pointer.to_bytes().as_slice())?)
     }
 
     fn write_symlink_to_store(
@@ -639,15 +839,32 @@ impl TreeState {
         Ok(changed_files)
     }
 
+    #[cfg(all(feature = "inotify", target_os = "linux"))]
+    #[instrument(skip(self))]
+    pub fn query_inotify(&self) -> Result<(inotify::Clock, Option<Vec<PathBuf>>), TreeStateError> {
+        let previous_clock = self.inotify_clock.clone().map(inotify::Clock::from);
+        inotify::query_changed_files(
+            &self.working_copy_path,
+            &inotify::state_dir(&self.state_path),
+            previous_clock,
+        )
+        .map_err(|err| TreeStateError::Fsmonitor(Box::new(err)))
+    }
+
     /// Look for changes to the working copy. If there are any changes, create
     /// a new tree from it and return it, and also update the dirstate on disk.
     #[instrument(skip_all)]
-    pub fn snapshot(&mut self, options: SnapshotOptions) -> Result<bool, SnapshotError> {
+    pub fn snapshot(
+        &mut self,
+        options: SnapshotOptions,
+    ) -> Result<(bool, SnapshotStats), SnapshotError> {
         let SnapshotOptions {
             base_ignores,
             fsmonitor_kind,
             progress,
+            start_tracking_matcher,
             max_new_file_size,
+            conflict_marker_style,
         } = options;
 
         let sparse_matcher = self.sparse_matcher();
@@ -657,6 +874,7 @@ impl TreeState {
         let FsmonitorMatcher {
             matcher: fsmonitor_matcher,
             watchman_clock,
+            inotify_clock,
         } = self.make_fsmonitor_matcher(fsmonitor_kind)?;
         let fsmonitor_matcher = match fsmonitor_matcher.as_ref() {
             None => &EverythingMatcher,
@@ -666,6 +884,7 @@ impl TreeState {
         let (tree_entries_tx, tree_entries_rx) = channel();
         let (file_states_tx, file_states_rx) = channel();
         let (present_files_tx, present_files_rx) = channel();
+        let (untracked_paths_tx, untracked_paths_rx) = channel();
 
         trace_span!("traverse filesystem").in_scope(|| -> Result<(), SnapshotError> {
             let matcher = IntersectionMatcher::new(sparse_matcher.as_ref(), fsmonitor_matcher);
@@ -674,6 +893,7 @@ impl TreeState {
                 dir: RepoPath::root(),
                 disk_dir: self.working_copy_path.clone(),
                 git_ignore: base_ignores,
+                git_attributes: GitAttributesFile::empty(),
             };
             self.visit_directory(
                 &matcher,
@@ -681,9 +901,12 @@ impl TreeState {
                 tree_entries_tx,
                 file_states_tx,
                 present_files_tx,
+                untracked_paths_tx,
                 directory_to_visit,
                 progress,
+                start_tracking_matcher,
                 max_new_file_size,
+                conflict_marker_style,
             )
         })?;
 
@@ -737,7 +960,11 @@ impl TreeState {
             assert_eq!(state_paths, tree_paths);
         }
         self.watchman_clock = watchman_clock;
-        Ok(is_dirty)
+        self.inotify_clock = inotify_clock;
+        let stats = SnapshotStats {
+            untracked_paths: untracked_paths_rx.into_iter().collect(),
+        };
+        Ok((is_dirty, stats))
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -748,14 +975,18 @@ impl TreeState {
         tree_entries_tx: Sender<(RepoPath, Merge<Option<TreeValue>>)>,
         file_states_tx: Sender<(RepoPath, FileState)>,
         present_files_tx: Sender<RepoPath>,
+        untracked_paths_tx: Sender<(RepoPath, UntrackedReason)>,
         directory_to_visit: DirectoryToVisit,
         progress: Option<&SnapshotProgress>,
+        start_tracking_matcher: &dyn Matcher,
         max_new_file_size: u64,
+        conflict_marker_style: ConflictMarkerStyle,
     ) -> Result<(), SnapshotError> {
         let DirectoryToVisit {
             dir,
             disk_dir,
             git_ignore,
+            git_attributes,
         } = directory_to_visit;
 
         if matcher.visit(&dir).is_nothing() {
@@ -763,6 +994,10 @@ impl TreeState {
         }
         let git_ignore =
             git_ignore.chain_with_file(&dir.to_internal_dir_string(), disk_dir.join(".gitignore"));
+        let git_attributes = git_attributes.chain_with_file(
+            &dir.to_internal_dir_string(),
+            disk_dir.join(".gitattributes"),
+        );
         let dir_entries = disk_dir
             .read_dir()
             .unwrap()
@@ -773,8 +1008,9 @@ impl TreeState {
                 tree_entries_tx.clone(),
                 file_states_tx.clone(),
                 present_files_tx.clone(),
+                untracked_paths_tx.clone(),
             ),
-            |(tree_entries_tx, file_states_tx, present_files_tx),
+            |(tree_entries_tx, file_states_tx, present_files_tx, untracked_paths_tx),
              entry|
              -> Result<(), SnapshotError> {
                 let file_type = entry.file_type().unwrap();
@@ -791,6 +1027,18 @@ impl TreeState {
                 let path = dir.join(&RepoPathComponent::from(name));
                 if let Some(file_state) = self.file_states.get(&path) {
                     if file_state.file_type == FileType::GitSubmodule {
+                        // Only the commit checked out in the submodule is tracked, not its
+                        // files.
+                        if matcher.matches(&path) {
+                            let update = self.get_updated_submodule_value(
+                                &path,
+                                &entry.path(),
+                                current_tree,
+                            );
+                            if let Some(tree_value) = update {
+                                tree_entries_tx.send((path, tree_value)).ok();
+                            }
+                        }
                         return Ok(());
                     }
                 }
@@ -825,14 +1073,16 @@ impl TreeState {
                                     });
                                 }
                             };
-                            if let Some(new_file_state) = file_state(&metadata) {
+                            if let Some(mut new_file_state) = file_state(&metadata) {
                                 present_files_tx.send(tracked_path.clone()).ok();
                                 let update = self.get_updated_tree_value(
                                     &tracked_path,
                                     disk_path,
                                     Some(&current_file_state),
                                     current_tree,
-                                    &new_file_state,
+                                    &mut new_file_state,
+                                    &git_attributes,
+                                    conflict_marker_style,
                                 )?;
                                 if let Some(tree_value) = update {
                                     tree_entries_tx
@@ -844,11 +1094,23 @@ impl TreeState {
                                 }
                             }
                         }
+                    } else if entry.path().join(".git").symlink_metadata().is_ok()
+                        && !self.has_tracked_paths_in(&path)
+                    {
+                        // Like Git, don't track the files of nested repos, such as
+                        // submodules left on disk after they were removed from the
+                        // tree because they weren't empty.
+                        if matcher.matches(&path) {
+                            untracked_paths_tx
+                                .send((path, UntrackedReason::NestedGitRepository))
+                                .ok();
+                        }
                     } else {
                         let directory_to_visit = DirectoryToVisit {
                             dir: path,
                             disk_dir: entry.path(),
                             git_ignore: git_ignore.clone(),
+                            git_attributes: git_attributes.clone(),
                         };
                         self.visit_directory(
                             matcher,
@@ -856,9 +1118,12 @@ impl TreeState {
                             tree_entries_tx.clone(),
                             file_states_tx.clone(),
                             present_files_tx.clone(),
+                            untracked_paths_tx.clone(),
                             directory_to_visit,
                             progress,
+                            start_tracking_matcher,
                             max_new_file_size,
+                            conflict_marker_style,
                         )?;
                     }
                 } else if matcher.matches(&path) {
@@ -872,27 +1137,42 @@ impl TreeState {
                         // If it wasn't already tracked and it matches
                         // the ignored paths, then
                         // ignore it.
+                    } else if maybe_current_file_state.is_none()
+                        && !start_tracking_matcher.matches(&path)
+                    {
+                        untracked_paths_tx
+                            .send((path, UntrackedReason::FileNotAutoTracked))
+                            .ok();
                     } else {
                         let metadata = entry.metadata().map_err(|err| SnapshotError::IoError {
                             message: format!("Failed to stat file {}", entry.path().display()),
                             err,
                         })?;
-                        if maybe_current_file_state.is_none() && metadata.len() > max_new_file_size
+                        // LFS files are stored as small pointers, so they aren't limited
+                        let is_lfs_path = self.lfs_store.is_some()
+                            && git_lfs::is_lfs_path(
+                                &git_attributes,
+                                &path.to_internal_file_string(),
+                            );
+                        if maybe_current_file_state.is_none()
+                            && metadata.len() > max_new_file_size
+                            && !is_lfs_path
                         {
-                            return Err(SnapshotError::NewFileTooLarge {
-                                path: entry.path().clone(),
-                                size: HumanByteSize(metadata.len()),
-                                max_size: HumanByteSize(max_new_file_size),
-                            });
-                        }
-                        if let Some(new_file_state) = file_state(&metadata) {
+                            let reason = UntrackedReason::FileTooLarge {
+                                size: metadata.len(),
+                                max_size: max_new_file_size,
+                            };
+                            untracked_paths_tx.send((path, reason)).ok();
+                        } else if let Some(mut new_file_state) = file_state(&metadata) {
                             present_files_tx.send(path.clone()).ok();
                             let update = self.get_updated_tree_value(
                                 &path,
                                 entry.path(),
                                 maybe_current_file_state,
                                 current_tree,
-                                &new_file_state,
+                                &mut new_file_state,
+                                &git_attributes,
+                                conflict_marker_style,
                             )?;
                             if let Some(tree_value) = update {
                                 tree_entries_tx.send((path.clone(), tree_value)).ok();
@@ -914,6 +1194,8 @@ impl TreeState {
         &mut self,
         fsmonitor_kind: Option<FsmonitorKind>,
     ) -> Result<FsmonitorMatcher, SnapshotError> {
+        #[cfg_attr(not(all(feature = "inotify", target_os = "linux")), allow(unused_mut))]
+        let mut inotify_clock = None;
         let (watchman_clock, changed_files) = match fsmonitor_kind {
             None => (None, None),
             Some(FsmonitorKind::Test { changed_files }) => (None, Some(changed_files)),
@@ -925,6 +1207,26 @@ impl TreeState {
                     (None, None)
                 }
             },
+            #[cfg(all(feature = "inotify", target_os = "linux"))]
+            Some(FsmonitorKind::Inotify) => match self.query_inotify() {
+                Ok((clock, changed_files)) => {
+                    inotify_clock = Some(clock.into());
+                    (None, changed_files)
+                }
+                Err(err) => {
+                    tracing::warn!(?err, "Failed to query filesystem monitor");
+                    (None, None)
+                }
+            },
+            #[cfg(not(all(feature = "inotify", target_os = "linux")))]
+            Some(FsmonitorKind::Inotify) => {
+                return Err(SnapshotError::FsmonitorError(
+                    "Cannot use the built-in filesystem monitor because it's only supported on \
+                     Linux, and jj must be compiled with the `inotify` feature (consider \
+                     disabling `core.fsmonitor`)"
+                        .to_string(),
+                ));
+            }
             #[cfg(not(feature = "watchman"))]
             Some(FsmonitorKind::Watchman) => {
                 return Err(SnapshotError::FsmonitorError(
@@ -959,17 +1261,25 @@ impl TreeState {
         Ok(FsmonitorMatcher {
             matcher,
             watchman_clock,
+            inotify_clock,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn get_updated_tree_value(
         &self,
         repo_path: &RepoPath,
         disk_path: PathBuf,
         maybe_current_file_state: Option<&FileState>,
         current_tree: &MergedTree,
-        new_file_state: &FileState,
+        new_file_state: &mut FileState,
+        git_attributes: &GitAttributesFile,
+        conflict_marker_style: ConflictMarkerStyle,
     ) -> Result<Option<Merge<Option<TreeValue>>>, SnapshotError> {
+        // A conflicted file keeps the markers it was materialized with until the
+        // conflict is resolved.
+        new_file_state.conflict_marker_len =
+            maybe_current_file_state.and_then(|state| state.conflict_marker_len);
         let clean = match maybe_current_file_state {
             None => {
                 // untracked
@@ -978,7 +1288,7 @@ impl TreeState {
             Some(current_file_state) => {
                 // If the file's mtime was set at the same time as this state file's own mtime,
                 // then we don't know if the file was modified before or after this state file.
-                current_file_state == new_file_state && current_file_state.mtime < self.own_mtime
+                current_file_state == &*new_file_state && current_file_state.mtime < self.own_mtime
             }
         };
         if clean {
@@ -991,7 +1301,15 @@ impl TreeState {
                 &disk_path,
                 &current_tree_values,
                 new_file_type,
+                git_attributes,
+                conflict_marker_style,
+                new_file_state
+                    .conflict_marker_len
+                    .unwrap_or(MIN_CONFLICT_MARKER_LEN),
             )?;
+            if new_tree_values.as_resolved().is_some() {
+                new_file_state.conflict_marker_len = None;
+            }
             if new_tree_values != current_tree_values {
                 Ok(Some(new_tree_values))
             } else {
@@ -1000,12 +1318,16 @@ impl TreeState {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn write_path_to_store(
         &self,
         repo_path: &RepoPath,
         disk_path: &Path,
         current_tree_values: &Merge<Option<TreeValue>>,
         file_type: FileType,
+        git_attributes: &GitAttributesFile,
+        conflict_marker_style: ConflictMarkerStyle,
+        conflict_marker_len: usize,
     ) -> Result<Merge<Option<TreeValue>>, SnapshotError> {
         let executable = match file_type {
             FileType::Normal { executable } => executable,
@@ -1019,9 +1341,12 @@ impl TreeState {
         // If the file contained a conflict before and is now a normal file on disk, we
         // try to parse any conflict markers in the file into a conflict.
         if let Some(current_tree_value) = current_tree_values.as_resolved() {
-            #[cfg(unix)]
-            let _ = current_tree_value; // use the variable
-            let id = self.write_file_to_store(repo_path, disk_path)?;
+            let id = self.write_file_to_store(
+                repo_path,
+                disk_path,
+                current_tree_value.as_ref(),
+                git_attributes,
+            )?;
             // On Windows, we preserve the executable bit from the current tree.
             #[cfg(windows)]
             let executable = {
@@ -1043,6 +1368,8 @@ impl TreeState {
                 self.store.as_ref(),
                 repo_path,
                 &content,
+                conflict_marker_style,
+                conflict_marker_len,
             )?;
             match new_file_ids.into_resolved() {
                 Ok(file_id) => {
@@ -1075,6 +1402,7 @@ impl TreeState {
         path: &RepoPath,
         id: &FileId,
         executable: bool,
+        git_attributes: &GitAttributesFile,
     ) -> Result<FileState, CheckoutError> {
         create_parent_dirs(&self.working_copy_path, path)?;
         let mut file = OpenOptions::new()
@@ -1086,6 +1414,25 @@ impl TreeState {
                 err,
             })?;
         let mut contents = self.store.read_file(path, id)?;
+        let path_string = path.to_internal_file_string();
+        let eol_conversion = git_attributes.eol_conversion(&path_string);
+        match &self.lfs_store {
+            Some(lfs_store) if git_lfs::is_lfs_path(git_attributes, &path_string) => {
+                contents = self.read_lfs_file(lfs_store, disk_path, contents)?;
+            }
+            _ if matches!(eol_conversion, EolConversion::Text { crlf: true, .. }) => {
+                let mut content = vec![];
+                contents
+                    .read_to_end(&mut content)
+                    .map_err(|err| CheckoutError::IoError {
+                        message: format!("Failed to read contents of {}", disk_path.display()),
+                        err,
+                    })?;
+                let content = eol_conversion.convert_to_working_copy(content);
+                contents = Box::new(std::io::Cursor::new(content));
+            }
+            _ => {}
+        }
         let size =
             std::io::copy(&mut contents, &mut file).map_err(|err| CheckoutError::IoError {
                 message: format!("Failed to write file {}", disk_path.display()),
@@ -1102,6 +1449,41 @@ impl TreeState {
         Ok(FileState::for_file(executable, size, &metadata))
     }
 
+    /// Replaces the contents of a pointer file by the object it refers to. If
+    /// the object is not in the local LFS store, the pointer itself is
+    /// returned.
+    fn read_lfs_file(
+        &self,
+        lfs_store: &LfsStore,
+        disk_path: &Path,
+        mut contents: Box<dyn Read>,
+    ) -> Result<Box<dyn Read>, CheckoutError> {
+        let mut pointer_contents = vec![];
+        (&mut contents)
+            .take(git_lfs::MAX_POINTER_SIZE)
+            .read_to_end(&mut pointer_contents)
+            .map_err(|err| CheckoutError::IoError {
+                message: format!("Failed to read contents of {}", disk_path.display()),
+                err,
+            })?;
+        let unchanged = Box::new(std::io::Cursor::new(pointer_contents.clone()).chain(contents));
+        let Some(pointer) = LfsPointer::parse(&pointer_contents) else {
+            return Ok(unchanged);
+        };
+        match lfs_store.read_object(&pointer) {
+            Ok(Some(object)) => Ok(Box::new(object)),
+            Ok(None) => Ok(unchanged),
+            Err(err) => Err(CheckoutError::IoError {
+                message: format!(
+                    "Failed to read LFS object {} for {}",
+                    pointer.oid,
+                    disk_path.display()
+                ),
+                err,
+            }),
+        }
+    }
+
     #[cfg_attr(windows, allow(unused_variables))]
     fn write_symlink(
         &self,
@@ -1138,6 +1520,7 @@ impl TreeState {
         disk_path: &Path,
         path: &RepoPath,
         conflict: &Merge<Option<TreeValue>>,
+        conflict_marker_style: ConflictMarkerStyle,
     ) -> Result<FileState, CheckoutError> {
         create_parent_dirs(&self.working_copy_path, path)?;
         let mut file = OpenOptions::new()
@@ -1149,8 +1532,26 @@ impl TreeState {
                 err,
             })?;
         let mut conflict_data = vec![];
-        conflicts::materialize(conflict, self.store.as_ref(), path, &mut conflict_data)
+        let conflict_marker_len = if let Some(file_merge) = conflict.to_file_merge() {
+            // Record the marker length so the markers can be parsed when the
+            // file is snapshotted
+            let content = conflicts::extract_as_single_hunk(&file_merge, &self.store, path);
+            let conflict_marker_len = conflicts::choose_materialized_conflict_marker_len(&content);
+            conflicts::materialize_merge_result_with_marker_len(
+                &content,
+                conflict_marker_style,
+                conflict_marker_len,
+                self.store.merge_hunk_level(),
+                &mut conflict_data,
+            )
             .expect("Failed to materialize conflict to in-memory buffer");
+            Some(conflict_marker_len)
+        } else {
+            conflict
+                .describe(&mut conflict_data)
+                .expect("Failed to materialize conflict to in-memory buffer");
+            None
+        };
         file.write_all(&conflict_data)
             .map_err(|err| CheckoutError::IoError {
                 message: format!("Failed to write conflict to file {}", disk_path.display()),
@@ -1162,7 +1563,10 @@ impl TreeState {
         let metadata = file
             .metadata()
             .map_err(|err| CheckoutError::for_stat_error(err, disk_path))?;
-        Ok(FileState::for_file(false, size, &metadata))
+        Ok(FileState {
+            conflict_marker_len,
+            ..FileState::for_file(false, size, &metadata)
+        })
     }
 
     #[cfg_attr(windows, allow(unused_variables))]
@@ -1176,21 +1580,32 @@ impl TreeState {
         Ok(())
     }
 
-    pub fn check_out(&mut self, new_tree: &MergedTree) -> Result<CheckoutStats, CheckoutError> {
+    pub fn check_out(
+        &mut self,
+        new_tree: &MergedTree,
+        options: &CheckoutOptions,
+    ) -> Result<CheckoutStats, CheckoutError> {
         let old_tree = self.current_tree().map_err(|err| match err {
             err @ BackendError::ObjectNotFound { .. } => CheckoutError::SourceNotFound {
                 source: Box::new(err),
             },
             other => CheckoutError::InternalBackendError(other),
         })?;
-        let stats = self.update(&old_tree, new_tree, self.sparse_matcher().as_ref(), Err)?;
+        let stats = self.update(
+            &old_tree,
+            new_tree,
+            self.sparse_matcher().as_ref(),
+            Err,
+            options,
+        )?;
         self.tree_id = new_tree.id();
         Ok(stats)
     }
 
     pub fn set_sparse_patterns(
         &mut self,
-        sparse_patterns: Vec<RepoPath>,
+        sparse_patterns: Vec<SparsePattern>,
+        options: &CheckoutOptions,
     ) -> Result<CheckoutStats, CheckoutError> {
         let tree = self.current_tree().map_err(|err| match err {
             err @ BackendError::ObjectNotFound { .. } => CheckoutError::SourceNotFound {
@@ -1198,8 +1613,8 @@ impl TreeState {
             },
             other => CheckoutError::InternalBackendError(other),
         })?;
-        let old_matcher = PrefixMatcher::new(&self.sparse_patterns);
-        let new_matcher = PrefixMatcher::new(&sparse_patterns);
+        let old_matcher = SparseMatcher::new(&self.sparse_patterns);
+        let new_matcher = SparseMatcher::new(&sparse_patterns);
         let added_matcher = DifferenceMatcher::new(&new_matcher, &old_matcher);
         let removed_matcher = DifferenceMatcher::new(&old_matcher, &new_matcher);
         let empty_tree = MergedTree::resolved(Tree::null(self.store.clone(), RepoPath::root()));
@@ -1208,8 +1623,9 @@ impl TreeState {
             &tree,
             &added_matcher,
             suppress_file_exists_error, // Keep un-ignored file and mark it as modified
+            options,
         )?;
-        let removed_stats = self.update(&tree, &empty_tree, &removed_matcher, Err)?;
+        let removed_stats = self.update(&tree, &empty_tree, &removed_matcher, Err, options)?;
         self.sparse_patterns = sparse_patterns;
         assert_eq!(added_stats.updated_files, 0);
         assert_eq!(added_stats.removed_files, 0);
@@ -1228,15 +1644,19 @@ impl TreeState {
         new_tree: &MergedTree,
         matcher: &dyn Matcher,
         mut handle_error: impl FnMut(CheckoutError) -> Result<(), CheckoutError>,
+        options: &CheckoutOptions,
     ) -> Result<CheckoutStats, CheckoutError> {
+        let mut new_git_attributes = TreeGitAttributes::new(new_tree);
         let mut apply_diff = |path: RepoPath,
                               before: Merge<Option<TreeValue>>,
                               after: Merge<Option<TreeValue>>|
          -> Result<(), CheckoutError> {
             let disk_path = path.to_fs_path(&self.working_copy_path);
 
-            if before.is_present() {
-                fs::remove_file(&disk_path).ok();
+            if before.is_present() && fs::remove_file(&disk_path).is_err() {
+                // Submodules are directories. Leave them alone unless they're empty,
+                // since they may contain changes that aren't committed in the submodule.
+                fs::remove_dir(&disk_path).ok();
             }
             // TODO: Check that the file has not changed before overwriting/removing it.
             match after.into_resolved() {
@@ -1253,14 +1673,26 @@ impl TreeState {
                 Ok(Some(after)) => {
                     let file_state = match after {
                         TreeValue::File { id, executable } => {
-                            self.write_file(&disk_path, &path, &id, executable)?
+                            let git_attributes = new_git_attributes.for_file(&path)?;
+                            self.write_file(&disk_path, &path, &id, executable, &git_attributes)?
                         }
                         TreeValue::Symlink(id) => self.write_symlink(&disk_path, &path, &id)?,
                         TreeValue::Conflict(_) => {
                             panic!("unexpected conflict entry in diff at {path:?}");
                         }
                         TreeValue::GitSubmodule(_id) => {
-                            println!("ignoring git submodule at {path:?}");
+                            // The submodule's files are checked out separately, by
+                            // `git::check_out_submodules()`.
+                            create_parent_dirs(&self.working_copy_path, &path)?;
+                            fs::create_dir_all(&disk_path).map_err(|err| {
+                                CheckoutError::IoError {
+                                    message: format!(
+                                        "Failed to create submodule directory {}",
+                                        disk_path.display()
+                                    ),
+                                    err,
+                                }
+                            })?;
                             FileState::for_gitsubmodule()
                         }
                         TreeValue::Tree(_id) => {
@@ -1270,7 +1702,12 @@ impl TreeState {
                     self.file_states.insert(path, file_state);
                 }
                 Err(after_conflict) => {
-                    let file_state = self.write_conflict(&disk_path, &path, &after_conflict)?;
+                    let file_state = self.write_conflict(
+                        &disk_path,
+                        &path,
+                        &after_conflict,
+                        options.conflict_marker_style,
+                    )?;
                     self.file_states.insert(path, file_state);
                 }
             }
@@ -1317,10 +1754,7 @@ impl TreeState {
                         TreeValue::Conflict(_id) => {
                             panic!("unexpected conflict entry in diff at {path:?}");
                         }
-                        TreeValue::GitSubmodule(_id) => {
-                            println!("ignoring git submodule at {path:?}");
-                            FileType::GitSubmodule
-                        }
+                        TreeValue::GitSubmodule(_id) => FileType::GitSubmodule,
                         TreeValue::Tree(_id) => {
                             panic!("unexpected tree entry in diff at {path:?}");
                         }
@@ -1336,6 +1770,7 @@ impl TreeState {
                     file_type,
                     mtime: MillisSinceEpoch(0),
                     size: 0,
+                    conflict_marker_len: None,
                 };
                 self.file_states.insert(path.clone(), file_state);
             }
@@ -1476,7 +1911,7 @@ impl WorkingCopy {
         Ok(self.tree_state()?.file_states())
     }
 
-    pub fn sparse_patterns(&self) -> Result<&[RepoPath], TreeStateError> {
+    pub fn sparse_patterns(&self) -> Result<&[SparsePattern], TreeStateError> {
         Ok(self.tree_state()?.sparse_patterns())
     }
 
@@ -1516,6 +1951,7 @@ impl WorkingCopy {
         operation_id: OperationId,
         old_tree_id: Option<&MergedTreeId>,
         new_tree: &MergedTree,
+        options: &CheckoutOptions,
     ) -> Result<CheckoutStats, CheckoutError> {
         let mut locked_wc = self.start_mutation()?;
         // Check if the current working-copy commit has changed on disk compared to what
@@ -1527,7 +1963,7 @@ impl WorkingCopy {
                 return Err(CheckoutError::ConcurrentCheckout);
             }
         }
-        let stats = locked_wc.check_out(new_tree)?;
+        let stats = locked_wc.check_out(new_tree, options)?;
         locked_wc.finish(operation_id)?;
         Ok(stats)
     }
@@ -1538,6 +1974,11 @@ impl WorkingCopy {
     ) -> Result<(watchman::Clock, Option<Vec<PathBuf>>), TreeStateError> {
         self.tree_state()?.query_watchman()
     }
+
+    #[cfg(all(feature = "inotify", target_os = "linux"))]
+    pub fn query_inotify(&self) -> Result<(inotify::Clock, Option<Vec<PathBuf>>), TreeStateError> {
+        self.tree_state()?.query_inotify()
+    }
 }
 
 /// A working copy that's locked on disk. The lock is held until you call
@@ -1572,16 +2013,24 @@ impl LockedWorkingCopy<'_> {
     // The base_ignores are passed in here rather than being set on the TreeState
     // because the TreeState may be long-lived if the library is used in a
     // long-lived process.
-    pub fn snapshot(&mut self, options: SnapshotOptions) -> Result<MergedTreeId, SnapshotError> {
+    pub fn snapshot(
+        &mut self,
+        options: SnapshotOptions,
+    ) -> Result<(MergedTreeId, SnapshotStats), SnapshotError> {
         let tree_state = self.wc.tree_state_mut()?;
-        self.tree_state_dirty |= tree_state.snapshot(options)?;
-        Ok(tree_state.current_tree_id().clone())
+        let (is_dirty, stats) = tree_state.snapshot(options)?;
+        self.tree_state_dirty |= is_dirty;
+        Ok((tree_state.current_tree_id().clone(), stats))
     }
 
-    pub fn check_out(&mut self, new_tree: &MergedTree) -> Result<CheckoutStats, CheckoutError> {
+    pub fn check_out(
+        &mut self,
+        new_tree: &MergedTree,
+        options: &CheckoutOptions,
+    ) -> Result<CheckoutStats, CheckoutError> {
         // TODO: Write a "pending_checkout" file with the new TreeId so we can
         // continue an interrupted update if we find such a file.
-        let stats = self.wc.tree_state_mut()?.check_out(new_tree)?;
+        let stats = self.wc.tree_state_mut()?.check_out(new_tree, options)?;
         self.tree_state_dirty = true;
         Ok(stats)
     }
@@ -1592,20 +2041,21 @@ impl LockedWorkingCopy<'_> {
         Ok(())
     }
 
-    pub fn sparse_patterns(&self) -> Result<&[RepoPath], TreeStateError> {
+    pub fn sparse_patterns(&self) -> Result<&[SparsePattern], TreeStateError> {
         self.wc.sparse_patterns()
     }
 
     pub fn set_sparse_patterns(
         &mut self,
-        new_sparse_patterns: Vec<RepoPath>,
+        new_sparse_patterns: Vec<SparsePattern>,
+        options: &CheckoutOptions,
     ) -> Result<CheckoutStats, CheckoutError> {
         // TODO: Write a "pending_checkout" file with new sparse patterns so we can
         // continue an interrupted update if we find such a file.
         let stats = self
             .wc
             .tree_state_mut()?
-            .set_sparse_patterns(new_sparse_patterns)?;
+            .set_sparse_patterns(new_sparse_patterns, options)?;
         self.tree_state_dirty = true;
         Ok(stats)
     }
@@ -14,11 +14,14 @@
 
 use jj_lib::backend::FileId;
 use jj_lib::conflicts::{
-    extract_as_single_hunk, materialize_merge_result, parse_conflict, update_from_content,
+    choose_materialized_conflict_marker_len, extract_as_single_hunk, materialize_merge_result,
+    parse_conflict, update_from_content, ConflictMarkerStyle, MIN_CONFLICT_MARKER_LEN,
 };
+use jj_lib::files::ContentHunk;
 use jj_lib::merge::Merge;
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::RepoPath;
+use jj_lib::settings::UserSettings;
 use jj_lib::store::Store;
 use testutils::TestRepo;
 
@@ -299,7 +302,7 @@ line 5 right
 
     // The first add should always be from the left side
     insta::assert_debug_snapshot!(
-        parse_conflict(materialized.as_bytes(), conflict.num_sides()),
+        parse_conflict(materialized.as_bytes(), conflict.num_sides(), MIN_CONFLICT_MARKER_LEN),
         @r###"
     Some(
         [
@@ -332,6 +335,229 @@ line 5 right
     "###);
 }
 
+#[test]
+fn test_materialize_conflict_marker_styles() {
+    let test_repo = TestRepo::init(false);
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_id = testutils::write_file(store, &path, "line 1\nline 2\nline 3\n");
+    let left_id = testutils::write_file(store, &path, "line 1\nleft 2\nline 3\n");
+    let right_id = testutils::write_file(store, &path, "line 1\nright 2\nline 3\n");
+    let other_id = testutils::write_file(store, &path, "line 1\nother 2\nline 3\n");
+
+    let conflict = Merge::new(
+        vec![Some(base_id.clone())],
+        vec![Some(left_id.clone()), Some(right_id.clone())],
+    );
+    let materialized = materialize_conflict_string_with_style(
+        store,
+        &path,
+        &conflict,
+        ConflictMarkerStyle::Snapshot,
+    );
+    insta::assert_snapshot!(materialized, @r###"
+    line 1
+    <<<<<<<
+    +++++++
+    left 2
+    -------
+    line 2
+    +++++++
+    right 2
+    >>>>>>>
+    line 3
+    "###);
+    assert_eq!(
+        parse_conflict(
+            materialized.as_bytes(),
+            conflict.num_sides(),
+            MIN_CONFLICT_MARKER_LEN
+        ),
+        parse_conflict(
+            materialize_conflict_string(store, &path, &conflict).as_bytes(),
+            conflict.num_sides(),
+            MIN_CONFLICT_MARKER_LEN
+        )
+    );
+    let materialized =
+        materialize_conflict_string_with_style(store, &path, &conflict, ConflictMarkerStyle::Git);
+    insta::assert_snapshot!(materialized, @r###"
+    line 1
+    <<<<<<<
+    left 2
+    |||||||
+    line 2
+    =======
+    right 2
+    >>>>>>>
+    line 3
+    "###);
+    insta::assert_debug_snapshot!(
+        parse_conflict(materialized.as_bytes(), conflict.num_sides(), MIN_CONFLICT_MARKER_LEN),
+        @r###"
+    Some(
+        [
+            Merge {
+                removes: [],
+                adds: [
+                    "line 1\n",
+                ],
+            },
+            Merge {
+                removes: [
+                    "line 2\n",
+                ],
+                adds: [
+                    "left 2\n",
+                    "right 2\n",
+                ],
+            },
+            Merge {
+                removes: [],
+                adds: [
+                    "line 3\n",
+                ],
+            },
+        ],
+    )
+    "###);
+
+    // Git style markers can't represent more than two sides, so such conflicts
+    // are written like snapshots
+    let conflict = Merge::new(
+        vec![Some(base_id.clone()), Some(base_id.clone())],
+        vec![
+            Some(left_id.clone()),
+            Some(right_id.clone()),
+            Some(other_id.clone()),
+        ],
+    );
+    let materialized =
+        materialize_conflict_string_with_style(store, &path, &conflict, ConflictMarkerStyle::Git);
+    insta::assert_snapshot!(materialized, @r###"
+    line 1
+    <<<<<<<
+    +++++++
+    left 2
+    -------
+    line 2
+    +++++++
+    right 2
+    -------
+    line 2
+    +++++++
+    other 2
+    >>>>>>>
+    line 3
+    "###);
+    assert_eq!(
+        parse_conflict(
+            materialized.as_bytes(),
+            conflict.num_sides(),
+            MIN_CONFLICT_MARKER_LEN
+        ),
+        parse_conflict(
+            materialize_conflict_string(store, &path, &conflict).as_bytes(),
+            conflict.num_sides(),
+            MIN_CONFLICT_MARKER_LEN
+        )
+    );
+}
+
+#[test]
+fn test_materialize_conflict_with_marker_like_lines() {
+    let test_repo = TestRepo::init(false);
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_id = testutils::write_file(store, &path, "line 1\nbase\n");
+    let left_id = testutils::write_file(store, &path, "line 1\nleft\n<<<<<<<\n");
+    let right_id = testutils::write_file(store, &path, "line 1\nright\n=======\nmore\n");
+    let conflict = Merge::new(
+        vec![Some(base_id.clone())],
+        vec![Some(left_id.clone()), Some(right_id.clone())],
+    );
+    let content = extract_as_single_hunk(&conflict, store, &path);
+
+    // The markers are longer than any line that looks like a marker
+    assert_eq!(choose_materialized_conflict_marker_len(&content), 8);
+    for style in [
+        ConflictMarkerStyle::Diff,
+        ConflictMarkerStyle::Snapshot,
+        ConflictMarkerStyle::Git,
+    ] {
+        let materialized = materialize_conflict_string_with_style(store, &path, &conflict, style);
+        let hunks = parse_conflict(materialized.as_bytes(), 2, 8).unwrap();
+        assert_eq!(
+            hunks[1],
+            Merge::new(
+                vec![ContentHunk(b"base\n".to_vec())],
+                vec![
+                    ContentHunk(b"left\n<<<<<<<\n".to_vec()),
+                    ContentHunk(b"right\n=======\nmore\n".to_vec())
+                ]
+            ),
+            "{style:?}"
+        );
+    }
+    let materialized =
+        materialize_conflict_string_with_style(store, &path, &conflict, ConflictMarkerStyle::Git);
+    insta::assert_snapshot!(materialized, @r###"
+    line 1
+    <<<<<<<<
+    left
+    <<<<<<<
+    ||||||||
+    base
+    ========
+    right
+    =======
+    more
+    >>>>>>>>
+    "###);
+    // The lines in the contents would be misread as markers of the minimum
+    // length
+    assert_eq!(
+        parse_conflict(materialized.as_bytes(), 2, MIN_CONFLICT_MARKER_LEN),
+        None
+    );
+
+    // Lines in diffs get a prefix, so a removed "-------" line would become a
+    // marker if the markers weren't longer still
+    let base_id = testutils::write_file(store, &path, "line 1\n-------\n");
+    let left_id = testutils::write_file(store, &path, "line 1\nleft\n");
+    let right_id = testutils::write_file(store, &path, "line 1\nright\n");
+    let conflict = Merge::new(
+        vec![Some(base_id.clone())],
+        vec![Some(left_id.clone()), Some(right_id.clone())],
+    );
+    let content = extract_as_single_hunk(&conflict, store, &path);
+    assert_eq!(choose_materialized_conflict_marker_len(&content), 9);
+    let materialized = materialize_conflict_string(store, &path, &conflict);
+    insta::assert_snapshot!(materialized, @r###"
+    line 1
+    <<<<<<<<<
+    %%%%%%%%%
+    --------
+    +left
+    +++++++++
+    right
+    >>>>>>>>>
+    "###);
+    let hunks = parse_conflict(materialized.as_bytes(), 2, 9).unwrap();
+    assert_eq!(
+        hunks[1],
+        Merge::new(
+            vec![ContentHunk(b"-------\n".to_vec())],
+            vec![
+                ContentHunk(b"left\n".to_vec()),
+                ContentHunk(b"right\n".to_vec())
+            ]
+        )
+    );
+}
+
 #[test]
 fn test_materialize_conflict_modify_delete() {
     let test_repo = TestRepo::init(false);
@@ -426,6 +652,64 @@ line 5
     );
 }
 
+/// Materialize a conflict with `merge.hunk-level = "word"`
+#[test]
+fn test_materialize_conflict_word_level() {
+    let config = testutils::base_config()
+        .add_source(config::File::from_str(
+            r#"merge.hunk-level = "word""#,
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init_with_settings(&settings, false);
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_id = testutils::write_file(
+        store,
+        &path,
+        "| a | 1 | x |
+line 2
+| b | 1 | x |
+",
+    );
+    let left_id = testutils::write_file(
+        store,
+        &path,
+        "| a | 2 | x |
+line 2
+| b | 2 | x |
+",
+    );
+    let right_id = testutils::write_file(
+        store,
+        &path,
+        "| a | 1 | y |
+line 2
+| b | 3 | y |
+",
+    );
+
+    // Only the line where both sides changed the same word gets markers
+    let conflict = Merge::new(vec![Some(base_id)], vec![Some(left_id), Some(right_id)]);
+    insta::assert_snapshot!(
+        &materialize_conflict_string(store, &path, &conflict),
+        @r###"
+    | a | 2 | y |
+    line 2
+    <<<<<<<
+    %%%%%%%
+    -| b | 1 | x |
+    +| b | 2 | x |
+    +++++++
+    | b | 3 | y |
+    >>>>>>>
+    "###
+    );
+}
+
 #[test]
 fn test_parse_conflict_resolved() {
     assert_eq!(
@@ -436,7 +720,8 @@ line 3
 line 4
 line 5
 ",
-            2
+            2,
+            MIN_CONFLICT_MARKER_LEN
         ),
         None
     )
@@ -458,7 +743,8 @@ right
 >>>>>>>
 line 5
 ",
-            2
+            2,
+            MIN_CONFLICT_MARKER_LEN
         ),
         @r###"
     Some(
@@ -511,7 +797,8 @@ right
 >>>>>>>
 line 5
 ",
-            3
+            3,
+            MIN_CONFLICT_MARKER_LEN
         ),
         @r###"
     Some(
@@ -561,7 +848,8 @@ right
 >>>>>>>
 line 5
 ",
-            3
+            3,
+            MIN_CONFLICT_MARKER_LEN
         ),
         None
     )
@@ -583,7 +871,8 @@ right
 >>>>>>>
 line 5
 ",
-            2
+            2,
+            MIN_CONFLICT_MARKER_LEN
         ),
         None
     )
@@ -606,7 +895,8 @@ right
 >>>>>>>
 line 5
 ",
-            2
+            2,
+            MIN_CONFLICT_MARKER_LEN
         ),
         None
     )
@@ -629,12 +919,27 @@ fn test_update_conflict_from_content() {
     // If the content is unchanged compared to the materialized value, we get the
     // old conflict id back.
     let materialized = materialize_conflict_string(store, &path, &conflict);
-    let result = update_from_content(&conflict, store, &path, materialized.as_bytes()).unwrap();
+    let result = update_from_content(
+        &conflict,
+        store,
+        &path,
+        materialized.as_bytes(),
+        ConflictMarkerStyle::Diff,
+        MIN_CONFLICT_MARKER_LEN,
+    )
+    .unwrap();
     assert_eq!(result, conflict);
 
     // If the conflict is resolved, we get None back to indicate that.
-    let result =
-        update_from_content(&conflict, store, &path, b"resolved 1\nline 2\nresolved 3\n").unwrap();
+    let result = update_from_content(
+        &conflict,
+        store,
+        &path,
+        b"resolved 1\nline 2\nresolved 3\n",
+        ConflictMarkerStyle::Diff,
+        MIN_CONFLICT_MARKER_LEN,
+    )
+    .unwrap();
     let expected_file_id = testutils::write_file(store, &path, "resolved 1\nline 2\nresolved 3\n");
     assert_eq!(result, Merge::normal(expected_file_id));
 
@@ -644,6 +949,8 @@ fn test_update_conflict_from_content() {
         store,
         &path,
         b"resolved 1\nline 2\n<<<<<<<\n%%%%%%%\n-line 3\n+left 3\n+++++++\nright 3\n>>>>>>>\n",
+        ConflictMarkerStyle::Diff,
+        MIN_CONFLICT_MARKER_LEN,
     )
     .unwrap();
     assert_ne!(new_conflict, conflict);
@@ -663,6 +970,140 @@ fn test_update_conflict_from_content() {
     );
 }
 
+#[test]
+fn test_update_conflict_from_content_git_style() {
+    let test_repo = TestRepo::init(false);
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("dir/file");
+    let base_file_id = testutils::write_file(store, &path, "line 1\nline 2\nline 3\n");
+    let left_file_id = testutils::write_file(store, &path, "left 1\nline 2\nleft 3\n");
+    let right_file_id = testutils::write_file(store, &path, "right 1\nline 2\nright 3\n");
+    let conflict = Merge::new(
+        vec![Some(base_file_id.clone())],
+        vec![Some(left_file_id.clone()), Some(right_file_id.clone())],
+    );
+
+    // If the content is unchanged compared to the materialized value, we get the
+    // old conflict id back.
+    let materialized =
+        materialize_conflict_string_with_style(store, &path, &conflict, ConflictMarkerStyle::Git);
+    let result = update_from_content(
+        &conflict,
+        store,
+        &path,
+        materialized.as_bytes(),
+        ConflictMarkerStyle::Git,
+        MIN_CONFLICT_MARKER_LEN,
+    )
+    .unwrap();
+    assert_eq!(result, conflict);
+
+    // Markers of a different style than the one the conflict was materialized
+    // with are parsed too
+    let new_conflict = update_from_content(
+        &conflict,
+        store,
+        &path,
+        b"resolved 1\nline 2\n<<<<<<<\n%%%%%%%\n-line 3\n+left 3\n+++++++\nright 3\n>>>>>>>\n",
+        ConflictMarkerStyle::Git,
+        MIN_CONFLICT_MARKER_LEN,
+    )
+    .unwrap();
+    let new_base_file_id = testutils::write_file(store, &path, "resolved 1\nline 2\nline 3\n");
+    let new_left_file_id = testutils::write_file(store, &path, "resolved 1\nline 2\nleft 3\n");
+    let new_right_file_id = testutils::write_file(store, &path, "resolved 1\nline 2\nright 3\n");
+    let expected_conflict = Merge::new(
+        vec![Some(new_base_file_id.clone())],
+        vec![
+            Some(new_left_file_id.clone()),
+            Some(new_right_file_id.clone()),
+        ],
+    );
+    assert_eq!(new_conflict, expected_conflict);
+
+    // If the conflict is partially resolved, we get a new conflict back.
+    let new_conflict = update_from_content(
+        &conflict,
+        store,
+        &path,
+        b"resolved 1\nline 2\n<<<<<<<\nleft 3\n|||||||\nline 3\n=======\nright 3\n>>>>>>>\n",
+        ConflictMarkerStyle::Git,
+        MIN_CONFLICT_MARKER_LEN,
+    )
+    .unwrap();
+    assert_eq!(new_conflict, expected_conflict);
+
+    // Git style markers without a base section aren't parsed
+    let result = update_from_content(
+        &conflict,
+        store,
+        &path,
+        b"resolved 1\nline 2\n<<<<<<<\nleft 3\n=======\nright 3\n>>>>>>>\n",
+        ConflictMarkerStyle::Git,
+        MIN_CONFLICT_MARKER_LEN,
+    )
+    .unwrap();
+    let expected_file_id = testutils::write_file(
+        store,
+        &path,
+        "resolved 1\nline 2\n<<<<<<<\nleft 3\n=======\nright 3\n>>>>>>>\n",
+    );
+    assert_eq!(result, Merge::normal(expected_file_id));
+}
+
+#[test]
+fn test_update_conflict_from_content_with_marker_len() {
+    let test_repo = TestRepo::init(false);
+    let store = test_repo.repo.store();
+
+    let path = RepoPath::from_internal_string("dir/file");
+    let base_file_id = testutils::write_file(store, &path, "line 1\nbase\n");
+    let left_file_id = testutils::write_file(store, &path, "line 1\nleft\n");
+    let right_file_id = testutils::write_file(store, &path, "line 1\nright\n=======\n");
+    let conflict = Merge::new(
+        vec![Some(base_file_id.clone())],
+        vec![Some(left_file_id.clone()), Some(right_file_id.clone())],
+    );
+
+    let materialized =
+        materialize_conflict_string_with_style(store, &path, &conflict, ConflictMarkerStyle::Git);
+    let result = update_from_content(
+        &conflict,
+        store,
+        &path,
+        materialized.as_bytes(),
+        ConflictMarkerStyle::Git,
+        8,
+    )
+    .unwrap();
+    assert_eq!(result, conflict);
+
+    // Edit the part of the file outside the conflict
+    let new_conflict = update_from_content(
+        &conflict,
+        store,
+        &path,
+        materialized.replace("line 1", "edited 1").as_bytes(),
+        ConflictMarkerStyle::Git,
+        8,
+    )
+    .unwrap();
+    let new_base_file_id = testutils::write_file(store, &path, "edited 1\nbase\n");
+    let new_left_file_id = testutils::write_file(store, &path, "edited 1\nleft\n");
+    let new_right_file_id = testutils::write_file(store, &path, "edited 1\nright\n=======\n");
+    assert_eq!(
+        new_conflict,
+        Merge::new(
+            vec![Some(new_base_file_id.clone())],
+            vec![
+                Some(new_left_file_id.clone()),
+                Some(new_right_file_id.clone())
+            ]
+        )
+    );
+}
+
 #[test]
 fn test_update_conflict_from_content_modify_delete() {
     let test_repo = TestRepo::init(false);
@@ -676,11 +1117,27 @@ fn test_update_conflict_from_content_modify_delete() {
     // If the content is unchanged compared to the materialized value, we get the
     // old conflict id back.
     let materialized = materialize_conflict_string(store, &path, &conflict);
-    let result = update_from_content(&conflict, store, &path, materialized.as_bytes()).unwrap();
+    let result = update_from_content(
+        &conflict,
+        store,
+        &path,
+        materialized.as_bytes(),
+        ConflictMarkerStyle::Diff,
+        MIN_CONFLICT_MARKER_LEN,
+    )
+    .unwrap();
     assert_eq!(result, conflict);
 
     // If the conflict is resolved, we get None back to indicate that.
-    let result = update_from_content(&conflict, store, &path, b"resolved\n").unwrap();
+    let result = update_from_content(
+        &conflict,
+        store,
+        &path,
+        b"resolved\n",
+        ConflictMarkerStyle::Diff,
+        MIN_CONFLICT_MARKER_LEN,
+    )
+    .unwrap();
     let expected_file_id = testutils::write_file(store, &path, "resolved\n");
     assert_eq!(result, Merge::normal(expected_file_id));
 
@@ -689,6 +1146,8 @@ fn test_update_conflict_from_content_modify_delete() {
         store,
         &path,
         b"<<<<<<<\n%%%%%%%\n line 1\n-line 2 before\n+line 2 modified after\n line 3\n+++++++\n>>>>>>>\n",
+        ConflictMarkerStyle::Diff,
+        MIN_CONFLICT_MARKER_LEN,
     )
     .unwrap();
     // Calculate expected new FileIds
@@ -709,9 +1168,24 @@ fn materialize_conflict_string(
     store: &Store,
     path: &RepoPath,
     conflict: &Merge<Option<FileId>>,
+) -> String {
+    materialize_conflict_string_with_style(store, path, conflict, ConflictMarkerStyle::Diff)
+}
+
+fn materialize_conflict_string_with_style(
+    store: &Store,
+    path: &RepoPath,
+    conflict: &Merge<Option<FileId>>,
+    conflict_marker_style: ConflictMarkerStyle,
 ) -> String {
     let mut result: Vec<u8> = vec![];
     let contents = extract_as_single_hunk(conflict, store, path);
-    materialize_merge_result(&contents, &mut result).unwrap();
+    materialize_merge_result(
+        &contents,
+        conflict_marker_style,
+        store.merge_hunk_level(),
+        &mut result,
+    )
+    .unwrap();
     String::from_utf8(result).unwrap()
 }
@@ -21,7 +21,8 @@ use assert_matches::assert_matches;
 use git2::Oid;
 use itertools::Itertools;
 use jj_lib::backend::{
-    BackendError, ChangeId, CommitId, MillisSinceEpoch, ObjectId, Signature, Timestamp,
+    Backend, BackendError, ChangeId, CommitId, MergedTreeId, MillisSinceEpoch, ObjectId, Signature,
+    Timestamp, TreeValue,
 };
 use jj_lib::commit::Commit;
 use jj_lib::commit_builder::CommitBuilder;
@@ -31,14 +32,18 @@ use jj_lib::git::{
     GitRefUpdate, SubmoduleConfig,
 };
 use jj_lib::git_backend::GitBackend;
+use jj_lib::matchers::EverythingMatcher;
 use jj_lib::op_store::{BranchTarget, RefTarget};
 use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo};
+use jj_lib::repo_path::RepoPath;
 use jj_lib::settings::{GitSettings, UserSettings};
 use jj_lib::view::RefName;
+use jj_lib::working_copy::CheckoutOptions;
 use maplit::{btreemap, hashset};
 use tempfile::TempDir;
 use testutils::{
     commit_transactions, create_random_commit, load_repo_at_head, write_random_commit, TestRepo,
+    TestWorkspace,
 };
 
 fn empty_git_commit<'r>(
@@ -181,6 +186,70 @@ fn test_import_refs() {
     assert_eq!(view.git_head(), &RefTarget::normal(jj_id(&commit2)));
 }
 
+#[test]
+fn test_import_refs_shallow() {
+    let settings = testutils::user_settings();
+    let git_settings = GitSettings::default();
+    let test_repo = TestRepo::init(true);
+    let repo = &test_repo.repo;
+    let git_repo = get_git_repo(repo);
+
+    let commit1 = empty_git_commit(&git_repo, "refs/heads/main", &[]);
+    let commit2 = empty_git_commit(&git_repo, "refs/heads/main", &[&commit1]);
+    let commit3 = empty_git_commit(&git_repo, "refs/heads/main", &[&commit2]);
+    // Make it look like a shallow clone of depth 2: commit1 is missing and
+    // commit2 is listed in the `shallow` file.
+    let odb = git_repo.odb().unwrap();
+    let commit1_data = odb.read(commit1.id()).unwrap().data().to_vec();
+    let commit1_hex = commit1.id().to_string();
+    fs::remove_file(
+        git_repo
+            .path()
+            .join("objects")
+            .join(&commit1_hex[..2])
+            .join(&commit1_hex[2..]),
+    )
+    .unwrap();
+    fs::write(
+        git_repo.path().join("shallow"),
+        format!("{}\n", commit2.id()),
+    )
+    .unwrap();
+
+    let git_repo = get_git_repo(repo);
+    let mut tx = repo.start_transaction(&settings, "test");
+    git::import_refs(tx.mut_repo(), &git_repo, &git_settings).unwrap();
+    let repo = tx.commit();
+
+    assert_eq!(*repo.view().heads(), hashset! {jj_id(&commit3)});
+    let commit2 = repo.store().get_commit(&jj_id(&commit2)).unwrap();
+    assert_eq!(
+        commit2.parent_ids(),
+        [repo.store().root_commit_id().clone()]
+    );
+    assert!(repo.index().has_id(commit2.id()));
+    assert_eq!(
+        get_git_backend(&repo).shallow_commits().unwrap(),
+        hashset! {commit2.id().clone()}
+    );
+
+    // Once the parent has been fetched, commit2 is read with it after the
+    // shallow commits are invalidated
+    odb.write(git2::ObjectType::Commit, &commit1_data).unwrap();
+    fs::remove_file(git_repo.path().join("shallow")).unwrap();
+    let git_backend = get_git_backend(&repo);
+    assert_eq!(
+        git_backend.read_commit(commit2.id()).unwrap().parents,
+        [repo.store().root_commit_id().clone()]
+    );
+    git_backend.invalidate_shallow_commits();
+    assert_eq!(
+        git_backend.read_commit(commit2.id()).unwrap().parents,
+        [jj_id(&commit1)]
+    );
+    assert!(git_backend.shallow_commits().unwrap().is_empty());
+}
+
 #[test]
 fn test_import_refs_reimport() {
     let settings = testutils::user_settings();
@@ -1259,6 +1328,7 @@ fn test_import_export_no_auto_local_branch() {
     let test_data = GitRepoData::create();
     let git_settings = GitSettings {
         auto_local_branch: false,
+        ..Default::default()
     };
     let git_repo = test_data.git_repo;
     let git_commit = empty_git_commit(&git_repo, "refs/remotes/origin/main", &[]);
@@ -1603,6 +1673,7 @@ fn test_fetch_empty_repo() {
         &test_data.git_repo,
         "origin",
         None,
+        None,
         git::RemoteCallbacks::default(),
         &git_settings,
     )
@@ -1627,6 +1698,7 @@ fn test_fetch_initial_commit() {
         &test_data.git_repo,
         "origin",
         None,
+        None,
         git::RemoteCallbacks::default(),
         &git_settings,
     )
@@ -1671,6 +1743,7 @@ fn test_fetch_success() {
         &test_data.git_repo,
         "origin",
         None,
+        None,
         git::RemoteCallbacks::default(),
         &git_settings,
     )
@@ -1692,6 +1765,7 @@ fn test_fetch_success() {
         &test_data.git_repo,
         "origin",
         None,
+        None,
         git::RemoteCallbacks::default(),
         &git_settings,
     )
@@ -1736,6 +1810,7 @@ fn test_fetch_prune_deleted_ref() {
         &test_data.git_repo,
         "origin",
         None,
+        None,
         git::RemoteCallbacks::default(),
         &git_settings,
     )
@@ -1755,6 +1830,7 @@ fn test_fetch_prune_deleted_ref() {
         &test_data.git_repo,
         "origin",
         None,
+        None,
         git::RemoteCallbacks::default(),
         &git_settings,
     )
@@ -1776,6 +1852,7 @@ fn test_fetch_no_default_branch() {
         &test_data.git_repo,
         "origin",
         None,
+        None,
         git::RemoteCallbacks::default(),
         &git_settings,
     )
@@ -1799,6 +1876,7 @@ fn test_fetch_no_default_branch() {
         &test_data.git_repo,
         "origin",
         None,
+        None,
         git::RemoteCallbacks::default(),
         &git_settings,
     )
@@ -1819,6 +1897,7 @@ fn test_fetch_no_such_remote() {
         &test_data.git_repo,
         "invalid-remote",
         None,
+        None,
         git::RemoteCallbacks::default(),
         &git_settings,
     );
@@ -2403,3 +2482,112 @@ ignoreThisSection = foo
 
     assert_eq!(result, expected);
 }
+
+#[test]
+fn test_check_out_submodules() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings, true);
+    let repo = test_workspace.repo.clone();
+    let store = repo.store().clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    let temp_dir = testutils::new_temp_dir();
+
+    // Create the upstream repo of the submodule
+    let upstream_repo = git2::Repository::init(temp_dir.path().join("sub")).unwrap();
+    let signature = git2::Signature::now("Someone", "someone@example.com").unwrap();
+    let blob_oid = upstream_repo.blob(b"contents\n").unwrap();
+    let mut git_tree_builder = upstream_repo.treebuilder(None).unwrap();
+    git_tree_builder.insert("file", blob_oid, 0o100644).unwrap();
+    let git_tree = upstream_repo
+        .find_tree(git_tree_builder.write().unwrap())
+        .unwrap();
+    let submodule_oid = upstream_repo
+        .commit(
+            Some("refs/heads/main"),
+            &signature,
+            &signature,
+            "initial",
+            &git_tree,
+            &[],
+        )
+        .unwrap();
+
+    // The submodule URL is relative to the parent repo's origin
+    let git_backend = store.backend_impl().downcast_ref::<GitBackend>().unwrap();
+    git_backend
+        .git_repo()
+        .remote("origin", temp_dir.path().join("super").to_str().unwrap())
+        .unwrap();
+
+    let gitmodules_path = RepoPath::from_internal_string(".gitmodules");
+    let submodule_path = RepoPath::from_internal_string("sub");
+    let unconfigured_path = RepoPath::from_internal_string("unconfigured");
+    let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
+    testutils::write_normal_file(
+        &mut tree_builder,
+        &gitmodules_path,
+        "[submodule \"sub\"]\n\tpath = sub\n\turl = ../sub\n",
+    );
+    let submodule_id = CommitId::from_bytes(submodule_oid.as_bytes());
+    tree_builder.set(
+        submodule_path.clone(),
+        TreeValue::GitSubmodule(submodule_id.clone()),
+    );
+    tree_builder.set(
+        unconfigured_path.clone(),
+        TreeValue::GitSubmodule(submodule_id),
+    );
+    let tree_id = MergedTreeId::Legacy(tree_builder.write_tree());
+    let tree = store.get_root_tree(&tree_id).unwrap();
+    let empty_tree = store.get_root_tree(&store.empty_merged_tree_id()).unwrap();
+    let wc = test_workspace.workspace.working_copy_mut();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+
+    let stats = git::check_out_submodules(
+        repo.as_ref(),
+        &workspace_root,
+        &empty_tree,
+        &tree,
+        &EverythingMatcher,
+        git::RemoteCallbacks::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        stats,
+        git::SubmoduleCheckoutStats {
+            checked_out: vec![submodule_path.clone()],
+            unconfigured: vec![unconfigured_path.clone()],
+        }
+    );
+    assert_eq!(
+        fs::read_to_string(workspace_root.join("sub").join("file")).unwrap(),
+        "contents\n"
+    );
+    assert!(repo
+        .submodule_store()
+        .load_submodule("sub")
+        .unwrap()
+        .is_some());
+
+    // The checked-out submodule matches the tree
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(new_tree.id(), tree.id());
+
+    // Checking out the same tree again doesn't do anything
+    let stats = git::check_out_submodules(
+        repo.as_ref(),
+        &workspace_root,
+        &tree,
+        &tree,
+        &EverythingMatcher,
+        git::RemoteCallbacks::default(),
+    )
+    .unwrap();
+    assert_eq!(stats, git::SubmoduleCheckoutStats::default());
+}
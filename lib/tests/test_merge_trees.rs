@@ -642,3 +642,44 @@ fn test_simplify_conflict_after_resolving_parent(use_git: bool) {
 
 // TODO: Add tests for simplification of multi-way conflicts. Both the content
 // and the executable bit need testing.
+
+#[test_case(false ; "local backend")]
+#[test_case(true ; "git backend")]
+fn test_gitattributes_binary(use_git: bool) {
+    // Tests that files with the `-merge` attribute, and files that look binary,
+    // are left conflicted instead of being merged line by line.
+    let test_repo = TestRepo::init(use_git);
+    let repo = &test_repo.repo;
+
+    let gitattributes_path = RepoPath::from_internal_string("dir/.gitattributes");
+    let text_path = RepoPath::from_internal_string("dir/text");
+    let no_merge_path = RepoPath::from_internal_string("dir/no-merge");
+    let other_no_merge_path = RepoPath::from_internal_string("no-merge");
+    let binary_path = RepoPath::from_internal_string("binary");
+    let write_tree = |first: &str, last: &str| {
+        let contents = format!("{first}\nb\n{last}\n");
+        let binary_contents = format!("\0{contents}");
+        create_single_tree(
+            repo,
+            &[
+                (&gitattributes_path, "no-merge -merge\n"),
+                (&text_path, &contents),
+                (&no_merge_path, &contents),
+                (&other_no_merge_path, &contents),
+                (&binary_path, &binary_contents),
+            ],
+        )
+    };
+    let base_tree = write_tree("a", "c");
+    let side1_tree = write_tree("a1", "c");
+    let side2_tree = write_tree("a", "c2");
+
+    let merged_tree = merge_trees(&side1_tree, &base_tree, &side2_tree).unwrap();
+    let is_conflict =
+        |path: &RepoPath| matches!(merged_tree.path_value(path), Some(TreeValue::Conflict(_)));
+    assert!(!is_conflict(&text_path));
+    assert!(is_conflict(&no_merge_path));
+    // The attributes only apply to files in the directory of the .gitattributes
+    assert!(!is_conflict(&other_no_merge_path));
+    assert!(is_conflict(&binary_path));
+}
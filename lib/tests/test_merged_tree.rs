@@ -19,6 +19,7 @@ use jj_lib::merge::{Merge, MergeBuilder};
 use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder, MergedTreeValue};
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::{RepoPath, RepoPathComponent, RepoPathJoin};
+use jj_lib::settings::UserSettings;
 use jj_lib::tree::merge_trees;
 use pretty_assertions::assert_eq;
 use testutils::{create_single_tree, write_file, TestRepo};
@@ -1182,3 +1183,166 @@ fn test_merge_simplify_result() {
     let merged = side1_merged.merge(&base1_merged, &side2_merged).unwrap();
     assert_eq!(merged, expected_merged);
 }
+
+/// Merge conflicting trees with merge drivers configured for some paths
+#[test]
+fn test_merge_with_merge_drivers() {
+    let config = testutils::base_config()
+        .add_source(config::File::from_str(
+            r#"
+            [merge.drivers]
+            "CHANGELOG.md" = "union"
+            "*.lock" = "theirs"
+            "Cargo.lock" = "ours"
+            "/generated/*" = "theirs"
+            "#,
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init_with_settings(&settings, true);
+    let repo = &test_repo.repo;
+
+    let changelog_path = RepoPath::from_internal_string("docs/CHANGELOG.md");
+    let cargo_lock_path = RepoPath::from_internal_string("Cargo.lock");
+    let other_lock_path = RepoPath::from_internal_string("sub/other.lock");
+    let generated_path = RepoPath::from_internal_string("generated/file");
+    let nested_generated_path = RepoPath::from_internal_string("sub/generated/file");
+    let clean_lock_path = RepoPath::from_internal_string("clean.lock");
+    let all_paths = [
+        &changelog_path,
+        &cargo_lock_path,
+        &other_lock_path,
+        &generated_path,
+        &nested_generated_path,
+    ];
+    let tree_with = |contents: &str, clean_lock: &str| {
+        let mut path_contents = all_paths.map(|path| (path, contents)).to_vec();
+        path_contents.push((&clean_lock_path, clean_lock));
+        create_single_tree(repo, &path_contents)
+    };
+    let base = tree_with("a\nbase\nb\n", "1\n2\n3\n");
+    let side1 = tree_with("a\nside1\nb\n", "1 side1\n2\n3\n");
+    let side2 = tree_with("a\nside2\nb\n", "1\n2\n3 side2\n");
+    let merged = MergedTree::resolved(side1)
+        .merge(&MergedTree::resolved(base), &MergedTree::resolved(side2))
+        .unwrap();
+
+    let read_resolved = |path: &RepoPath| {
+        let value = merged.path_value(path);
+        match value.as_resolved() {
+            Some(Some(TreeValue::File {
+                id,
+                executable: false,
+            })) => String::from_utf8(testutils::read_file(repo.store(), path, id)).unwrap(),
+            _ => panic!("unexpected value at {path:?}: {value:?}"),
+        }
+    };
+    // The union driver keeps the lines of both sides
+    assert_eq!(read_resolved(&changelog_path), "a\nside1\nside2\nb\n");
+    // The longest matching pattern wins
+    assert_eq!(read_resolved(&cargo_lock_path), "a\nside1\nb\n");
+    assert_eq!(read_resolved(&other_lock_path), "a\nside2\nb\n");
+    assert_eq!(read_resolved(&generated_path), "a\nside2\nb\n");
+    // Drivers only run if the contents can't be merged line by line
+    assert_eq!(read_resolved(&clean_lock_path), "1 side1\n2\n3 side2\n");
+    // Rooted patterns don't match in subdirectories
+    assert!(!merged.path_value(&nested_generated_path).is_resolved());
+}
+
+/// Merge trees with `merge.hunk-level = "word"`
+#[test]
+fn test_merge_word_level() {
+    let config = testutils::base_config()
+        .add_source(config::File::from_str(
+            r#"merge.hunk-level = "word""#,
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init_with_settings(&settings, true);
+    let repo = &test_repo.repo;
+
+    let resolved_path = RepoPath::from_internal_string("resolved");
+    let conflict_path = RepoPath::from_internal_string("conflict");
+    let base = create_single_tree(
+        repo,
+        &[
+            (&resolved_path, "| a | 1 | x |\n"),
+            (&conflict_path, "| a | 1 | x |\n"),
+        ],
+    );
+    let side1 = create_single_tree(
+        repo,
+        &[
+            (&resolved_path, "| a | 2 | x |\n"),
+            (&conflict_path, "| a | 2 | x |\n"),
+        ],
+    );
+    let side2 = create_single_tree(
+        repo,
+        &[
+            (&resolved_path, "| a | 1 | y |\n"),
+            (&conflict_path, "| a | 3 | y |\n"),
+        ],
+    );
+    let merged = MergedTree::resolved(side1)
+        .merge(&MergedTree::resolved(base), &MergedTree::resolved(side2))
+        .unwrap();
+
+    // Changes to different words of the same line are merged
+    match merged.path_value(&resolved_path).as_resolved() {
+        Some(Some(TreeValue::File { id, .. })) => assert_eq!(
+            testutils::read_file(repo.store(), &resolved_path, id),
+            b"| a | 2 | y |\n"
+        ),
+        value => panic!("unexpected value: {value:?}"),
+    }
+    // Changes to the same word still conflict
+    assert!(!merged.path_value(&conflict_path).is_resolved());
+}
+
+/// Merge conflicting trees with an external merge driver
+#[cfg(unix)]
+#[test]
+fn test_merge_with_external_merge_driver() {
+    let config = testutils::base_config()
+        .add_source(config::File::from_str(
+            r#"
+            [merge.drivers]
+            "merged" = ["sh", "-c", 'cat "$1" "$0" "$2" > "$3"', "$base", "$left", "$right", "$output"]
+            "failing" = ["false"]
+            "#,
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init_with_settings(&settings, true);
+    let repo = &test_repo.repo;
+
+    let merged_path = RepoPath::from_internal_string("merged");
+    let failing_path = RepoPath::from_internal_string("failing");
+    let base = create_single_tree(repo, &[(&merged_path, "base\n"), (&failing_path, "base\n")]);
+    let side1 = create_single_tree(repo, &[(&merged_path, "left\n"), (&failing_path, "left\n")]);
+    let side2 = create_single_tree(
+        repo,
+        &[(&merged_path, "right\n"), (&failing_path, "right\n")],
+    );
+    let merged = MergedTree::resolved(side1)
+        .merge(&MergedTree::resolved(base), &MergedTree::resolved(side2))
+        .unwrap();
+
+    let value = merged.path_value(&merged_path);
+    let Some(Some(TreeValue::File { id, .. })) = value.as_resolved() else {
+        panic!("unexpected value: {value:?}");
+    };
+    assert_eq!(
+        testutils::read_file(repo.store(), &merged_path, id),
+        b"left\nbase\nright\n"
+    );
+    // A driver exiting with non-zero status leaves the conflict
+    assert!(!merged.path_value(&failing_path).is_resolved());
+}
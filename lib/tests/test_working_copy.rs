@@ -25,15 +25,21 @@ use std::os::unix::net::UnixListener;
 use std::sync::Arc;
 
 use itertools::Itertools;
-use jj_lib::backend::{MergedTreeId, ObjectId, TreeId, TreeValue};
+use jj_lib::backend::{CommitId, MergedTreeId, ObjectId, TreeId, TreeValue};
 use jj_lib::fsmonitor::FsmonitorKind;
+use jj_lib::git_backend::GitBackend;
+use jj_lib::git_lfs::{LfsPointer, LfsStore};
+use jj_lib::matchers::PrefixMatcher;
 use jj_lib::merge::Merge;
 use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
 use jj_lib::op_store::{OperationId, WorkspaceId};
 use jj_lib::repo::{ReadonlyRepo, Repo};
 use jj_lib::repo_path::{RepoPath, RepoPathComponent, RepoPathJoin};
 use jj_lib::settings::UserSettings;
-use jj_lib::working_copy::{LockedWorkingCopy, SnapshotError, SnapshotOptions, WorkingCopy};
+use jj_lib::sparse::SparsePattern;
+use jj_lib::working_copy::{
+    CheckoutOptions, LockedWorkingCopy, SnapshotOptions, UntrackedReason, WorkingCopy,
+};
 use test_case::test_case;
 use testutils::{create_tree, write_random_commit, TestWorkspace};
 
@@ -45,7 +51,10 @@ fn test_root(use_git: bool) {
     let mut test_workspace = TestWorkspace::init(&settings, use_git);
 
     let wc = test_workspace.workspace.working_copy();
-    assert_eq!(wc.sparse_patterns().unwrap(), vec![RepoPath::root()]);
+    assert_eq!(
+        wc.sparse_patterns().unwrap(),
+        vec![SparsePattern::Prefix(RepoPath::root())]
+    );
     let new_tree = test_workspace.snapshot().unwrap();
     let repo = &test_workspace.repo;
     let wc_commit_id = repo
@@ -190,10 +199,20 @@ fn test_checkout_file_transitions(use_git: bool) {
     let right_tree = store.get_root_tree(&right_tree_id).unwrap();
 
     let wc = test_workspace.workspace.working_copy_mut();
-    wc.check_out(repo.op_id().clone(), None, &left_tree)
-        .unwrap();
-    wc.check_out(repo.op_id().clone(), None, &right_tree)
-        .unwrap();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &left_tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &right_tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
 
     // Check that the working copy is clean.
     let new_tree = test_workspace.snapshot().unwrap();
@@ -253,8 +272,11 @@ fn test_checkout_file_transitions(use_git: bool) {
                 assert!(metadata.is_dir(), "{path:?} should be a directory");
             }
             Kind::GitSubmodule => {
-                // Not supported for now
-                assert!(maybe_metadata.is_err(), "{path:?} should not exist");
+                // Only the directory is created; its files are checked out by
+                // `git::check_out_submodules()`
+                assert!(maybe_metadata.is_ok(), "{path:?} should exist");
+                let metadata = maybe_metadata.unwrap();
+                assert!(metadata.is_dir(), "{path:?} should be a directory");
             }
         };
     }
@@ -274,9 +296,20 @@ fn test_conflict_subdirectory() {
     let merged_tree = tree1.merge(&empty_tree, &tree2).unwrap();
     let repo = &test_workspace.repo;
     let wc = test_workspace.workspace.working_copy_mut();
-    wc.check_out(repo.op_id().clone(), None, &tree1).unwrap();
-    wc.check_out(repo.op_id().clone(), None, &merged_tree)
-        .unwrap();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree1,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &merged_tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
 }
 
 #[test_case(false ; "local backend")]
@@ -291,8 +324,13 @@ fn test_tree_builder_file_directory_transition(use_git: bool) {
     let mut check_out_tree = |tree_id: &TreeId| {
         let tree = repo.store().get_tree(&RepoPath::root(), tree_id).unwrap();
         let wc = workspace.working_copy_mut();
-        wc.check_out(repo.op_id().clone(), None, &MergedTree::legacy(tree))
-            .unwrap();
+        wc.check_out(
+            repo.op_id().clone(),
+            None,
+            &MergedTree::legacy(tree),
+            &CheckoutOptions::empty_for_test(),
+        )
+        .unwrap();
     };
 
     let parent_path = RepoPath::from_internal_string("foo/bar");
@@ -343,8 +381,13 @@ fn test_reset() {
     );
 
     let wc = test_workspace.workspace.working_copy_mut();
-    wc.check_out(repo.op_id().clone(), None, &tree_with_file)
-        .unwrap();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree_with_file,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
 
     // Test the setup: the file should exist on disk and in the tree state.
     assert!(ignored_path.to_fs_path(&workspace_root).is_file());
@@ -392,7 +435,13 @@ fn test_checkout_discard() {
 
     let wc = test_workspace.workspace.working_copy_mut();
     let state_path = wc.state_path().to_path_buf();
-    wc.check_out(repo.op_id().clone(), None, &tree1).unwrap();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree1,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
 
     // Test the setup: the file should exist on disk and in the tree state.
     assert!(file1_path.to_fs_path(&workspace_root).is_file());
@@ -400,7 +449,9 @@ fn test_checkout_discard() {
 
     // Start a checkout
     let mut locked_wc = wc.start_mutation().unwrap();
-    locked_wc.check_out(&tree2).unwrap();
+    locked_wc
+        .check_out(&tree2, &CheckoutOptions::empty_for_test())
+        .unwrap();
     // The change should be reflected in the working copy but not saved
     assert!(!file1_path.to_fs_path(&workspace_root).is_file());
     assert!(file2_path.to_fs_path(&workspace_root).is_file());
@@ -444,7 +495,7 @@ fn test_snapshot_racy_timestamps(use_git: bool) {
             file.write_all(format!("contents {i}").as_bytes()).unwrap();
         }
         let mut locked_wc = wc.start_mutation().unwrap();
-        let new_tree_id = locked_wc
+        let (new_tree_id, _stats) = locked_wc
             .snapshot(SnapshotOptions::empty_for_test())
             .unwrap();
         assert_ne!(new_tree_id, previous_tree_id);
@@ -477,7 +528,7 @@ fn test_snapshot_special_file() {
     // Snapshot the working copy with the socket file
     let wc = test_workspace.workspace.working_copy_mut();
     let mut locked_wc = wc.start_mutation().unwrap();
-    let tree_id = locked_wc
+    let (tree_id, _stats) = locked_wc
         .snapshot(SnapshotOptions::empty_for_test())
         .unwrap();
     locked_wc.finish(OperationId::from_hex("abc123")).unwrap();
@@ -585,7 +636,13 @@ fn test_gitignores_in_ignored_dir(use_git: bool) {
 
     let tree1 = create_tree(&test_workspace.repo, &[(&gitignore_path, "ignored\n")]);
     let wc = test_workspace.workspace.working_copy_mut();
-    wc.check_out(op_id.clone(), None, &tree1).unwrap();
+    wc.check_out(
+        op_id.clone(),
+        None,
+        &tree1,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
 
     testutils::write_working_copy_file(&workspace_root, &nested_gitignore_path, "!file\n");
     testutils::write_working_copy_file(&workspace_root, &ignored_path, "contents");
@@ -640,7 +697,14 @@ fn test_gitignores_checkout_never_overwrites_ignored(use_git: bool) {
     // "contents". The exiting contents ("garbage") shouldn't be replaced in the
     // working copy.
     let wc = test_workspace.workspace.working_copy_mut();
-    assert!(wc.check_out(repo.op_id().clone(), None, &tree).is_err());
+    assert!(wc
+        .check_out(
+            repo.op_id().clone(),
+            None,
+            &tree,
+            &CheckoutOptions::empty_for_test()
+        )
+        .is_err());
 
     // Check that the old contents are in the working copy
     let path = workspace_root.join("modified");
@@ -675,7 +739,13 @@ fn test_gitignores_ignored_directory_already_tracked(use_git: bool) {
 
     // Check out the tree with the files in `ignored/`
     let wc = test_workspace.workspace.working_copy_mut();
-    wc.check_out(repo.op_id().clone(), None, &tree).unwrap();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
 
     // Make some changes inside the ignored directory and check that they are
     // detected when we snapshot. The files that are still there should not be
@@ -734,7 +804,7 @@ fn test_dotgit_ignored(use_git: bool) {
 
 #[test]
 fn test_gitsubmodule() {
-    // Tests that git submodules are ignored.
+    // Tests that the files in git submodules are ignored.
 
     let settings = testutils::user_settings();
     let mut test_workspace = TestWorkspace::init(&settings, true);
@@ -768,9 +838,16 @@ fn test_gitsubmodule() {
     let tree_id = MergedTreeId::Legacy(tree_builder.write_tree());
     let tree = store.get_root_tree(&tree_id).unwrap();
     let wc = test_workspace.workspace.working_copy_mut();
-    wc.check_out(repo.op_id().clone(), None, &tree).unwrap();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
 
-    std::fs::create_dir(submodule_path.to_fs_path(&workspace_root)).unwrap();
+    // The directory for the submodule is created by the checkout
+    assert!(submodule_path.to_fs_path(&workspace_root).is_dir());
 
     testutils::write_working_copy_file(
         &workspace_root,
@@ -791,6 +868,70 @@ fn test_gitsubmodule() {
     );
 }
 
+#[test]
+fn test_gitsubmodule_head_moved() {
+    // Tests that moving HEAD in a submodule's repo updates the gitlink.
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings, true);
+    let repo = &test_workspace.repo;
+    let store = repo.store().clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let submodule_path = RepoPath::from_internal_string("submodule");
+    let submodule_git_repo =
+        git2::Repository::init(submodule_path.to_fs_path(&workspace_root)).unwrap();
+    let signature = git2::Signature::now("Someone", "someone@example.com").unwrap();
+    let empty_tree_oid = submodule_git_repo
+        .treebuilder(None)
+        .unwrap()
+        .write()
+        .unwrap();
+    let empty_tree = submodule_git_repo.find_tree(empty_tree_oid).unwrap();
+    let commit_oid1 = submodule_git_repo
+        .commit(Some("HEAD"), &signature, &signature, "1", &empty_tree, &[])
+        .unwrap();
+
+    let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
+    tree_builder.set(
+        submodule_path.clone(),
+        TreeValue::GitSubmodule(CommitId::from_bytes(commit_oid1.as_bytes())),
+    );
+    let tree_id = MergedTreeId::Legacy(tree_builder.write_tree());
+    let tree = store.get_root_tree(&tree_id).unwrap();
+    let wc = test_workspace.workspace.working_copy_mut();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+
+    // Nothing changed yet
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(new_tree.id(), tree_id);
+
+    // Commit in the submodule
+    let commit1 = submodule_git_repo.find_commit(commit_oid1).unwrap();
+    let commit_oid2 = submodule_git_repo
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "2",
+            &empty_tree,
+            &[&commit1],
+        )
+        .unwrap();
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(
+        new_tree.path_value(&submodule_path),
+        Merge::normal(TreeValue::GitSubmodule(CommitId::from_bytes(
+            commit_oid2.as_bytes()
+        )))
+    );
+}
+
 #[cfg(unix)]
 #[test_case(false ; "local backend")]
 #[test_case(true ; "git backend")]
@@ -808,7 +949,14 @@ fn test_existing_directory_symlink(use_git: bool) {
 
     // Checkout should fail because "parent" already exists and is a symlink.
     let wc = test_workspace.workspace.working_copy_mut();
-    assert!(wc.check_out(repo.op_id().clone(), None, &tree).is_err());
+    assert!(wc
+        .check_out(
+            repo.op_id().clone(),
+            None,
+            &tree,
+            &CheckoutOptions::empty_for_test()
+        )
+        .is_err());
 
     // Therefore, "../escaped" shouldn't be created.
     assert!(!workspace_root.parent().unwrap().join("escaped").exists());
@@ -822,7 +970,10 @@ fn test_fsmonitor() {
     let workspace_root = test_workspace.workspace.workspace_root().clone();
 
     let wc = test_workspace.workspace.working_copy_mut();
-    assert_eq!(wc.sparse_patterns().unwrap(), vec![RepoPath::root()]);
+    assert_eq!(
+        wc.sparse_patterns().unwrap(),
+        vec![SparsePattern::Prefix(RepoPath::root())]
+    );
 
     let foo_path = RepoPath::from_internal_string("foo");
     let bar_path = RepoPath::from_internal_string("bar");
@@ -849,6 +1000,7 @@ fn test_fsmonitor() {
                 ..SnapshotOptions::empty_for_test()
             })
             .unwrap()
+            .0
     };
 
     {
@@ -930,13 +1082,365 @@ fn test_snapshot_max_new_file_size() {
     test_workspace
         .snapshot()
         .expect("existing files may grow beyond the size limit");
-    // A new file of 1KiB + 1 bytes should fail
+    // A new file of 1KiB + 1 bytes should be left untracked
     std::fs::write(large_path.to_fs_path(&workspace_root), vec![0; 1024 + 1]).unwrap();
-    let err = test_workspace
-        .snapshot()
-        .expect_err("new files beyond the size limit should fail");
-    assert!(
-        matches!(err, SnapshotError::NewFileTooLarge { .. }),
-        "the failure should be attributed to new file size"
+    let (tree, stats) = test_workspace.snapshot_with_stats().unwrap();
+    assert_eq!(tree.path_value(&large_path), Merge::absent());
+    assert_eq!(
+        stats.untracked_paths.into_iter().collect_vec(),
+        vec![(
+            large_path.clone(),
+            UntrackedReason::FileTooLarge {
+                size: 1024 + 1,
+                max_size: 1024
+            }
+        )]
+    );
+    // All new files that are too large are reported
+    let other_large_path = RepoPath::from_internal_string("dir/large");
+    testutils::write_working_copy_file(&workspace_root, &other_large_path, &"a".repeat(2048));
+    let (_tree, stats) = test_workspace.snapshot_with_stats().unwrap();
+    assert_eq!(
+        stats.untracked_paths.keys().collect_vec(),
+        vec![&other_large_path, &large_path]
+    );
+}
+
+#[test]
+fn test_snapshot_start_tracking_matcher() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings, false);
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    let store = test_workspace.repo.store().clone();
+
+    let tracked_path = RepoPath::from_internal_string("src/tracked");
+    let untracked_path = RepoPath::from_internal_string("untracked");
+    let ignored_path = RepoPath::from_internal_string("ignored");
+    let gitignore_path = RepoPath::from_internal_string(".gitignore");
+    testutils::write_working_copy_file(&workspace_root, &tracked_path, "contents");
+    testutils::write_working_copy_file(&workspace_root, &untracked_path, "contents");
+    testutils::write_working_copy_file(&workspace_root, &ignored_path, "contents");
+    testutils::write_working_copy_file(&workspace_root, &gitignore_path, "ignored\n");
+
+    let start_tracking_matcher = PrefixMatcher::new(&[
+        RepoPath::from_internal_string("src"),
+        gitignore_path.clone(),
+    ]);
+    let snapshot = |wc: &mut WorkingCopy| {
+        let mut locked_wc = wc.start_mutation().unwrap();
+        let (tree_id, stats) = locked_wc
+            .snapshot(SnapshotOptions {
+                start_tracking_matcher: &start_tracking_matcher,
+                ..SnapshotOptions::empty_for_test()
+            })
+            .unwrap();
+        locked_wc.finish(OperationId::from_hex("abc123")).unwrap();
+        (store.get_root_tree(&tree_id).unwrap(), stats)
+    };
+
+    // New files not matching the matcher are reported, except for ignored files
+    let (tree, stats) = snapshot(test_workspace.workspace.working_copy_mut());
+    assert_eq!(
+        tree.entries().map(|(path, _value)| path).collect_vec(),
+        vec![gitignore_path.clone(), tracked_path.clone()]
+    );
+    assert_eq!(
+        stats.untracked_paths.into_iter().collect_vec(),
+        vec![(untracked_path.clone(), UntrackedReason::FileNotAutoTracked)]
+    );
+
+    // Files that are already tracked are snapshotted regardless of the matcher
+    let wc = test_workspace.workspace.working_copy_mut();
+    let mut locked_wc = wc.start_mutation().unwrap();
+    locked_wc
+        .snapshot(SnapshotOptions::empty_for_test())
+        .unwrap();
+    locked_wc.finish(OperationId::from_hex("abc123")).unwrap();
+    testutils::write_working_copy_file(&workspace_root, &untracked_path, "modified");
+    let (tree, stats) = snapshot(wc);
+    assert_eq!(
+        tree.path_value(&untracked_path),
+        Merge::normal(TreeValue::File {
+            id: testutils::write_file(&store, &untracked_path, "modified"),
+            executable: false,
+        })
+    );
+    assert!(stats.untracked_paths.is_empty());
+}
+
+#[test]
+fn test_git_lfs() {
+    // Tests that files with the `filter=lfs` attribute are stored as pointers and
+    // checked out from the local LFS object store.
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings, true);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    let git_repo_path = repo
+        .store()
+        .backend_impl()
+        .downcast_ref::<GitBackend>()
+        .unwrap()
+        .git_repo_path();
+    let lfs_store = LfsStore::for_git_dir(&git_repo_path);
+
+    let gitattributes_path = RepoPath::from_internal_string(".gitattributes");
+    let large_path = RepoPath::from_internal_string("assets/large.bin");
+    let text_path = RepoPath::from_internal_string("assets/text.txt");
+    testutils::write_working_copy_file(
+        &workspace_root,
+        &gitattributes_path,
+        "*.bin filter=lfs diff=lfs merge=lfs -text\n",
+    );
+    testutils::write_working_copy_file(&workspace_root, &large_path, "large contents\n");
+    testutils::write_working_copy_file(&workspace_root, &text_path, "text\n");
+
+    // The snapshot stores a pointer and moves the contents to the LFS store
+    let tree = test_workspace.snapshot().unwrap();
+    let read_value = |tree: &MergedTree, path: &RepoPath| match tree.path_value(path).as_resolved()
+    {
+        Some(Some(TreeValue::File { id, .. })) => testutils::read_file(repo.store(), path, id),
+        value => panic!("unexpected value {value:?}"),
+    };
+    let pointer = LfsPointer::parse(&read_value(&tree, &large_path)).unwrap();
+    assert_eq!(pointer.size, 15);
+    assert_eq!(
+        std::fs::read(lfs_store.object_path(&pointer.oid)).unwrap(),
+        b"large contents\n"
+    );
+    assert_eq!(read_value(&tree, &text_path), b"text\n");
+    // Snapshotting again doesn't change anything
+    assert_eq!(test_workspace.snapshot().unwrap().id(), tree.id());
+
+    // Checking out a pointer writes the object's contents to the working copy
+    let other_path = RepoPath::from_internal_string("other.bin");
+    let other_pointer = lfs_store
+        .write_object(&mut b"other contents\n".as_slice())
+        .unwrap();
+    let missing_path = RepoPath::from_internal_string("missing.bin");
+    let missing_pointer = LfsPointer {
+        oid: "0".repeat(64),
+        size: 7,
+    };
+    let new_tree = create_tree(
+        &repo,
+        &[
+            (&gitattributes_path, "*.bin filter=lfs\n"),
+            (
+                &other_path,
+                std::str::from_utf8(&other_pointer.to_bytes()).unwrap(),
+            ),
+            (
+                &missing_path,
+                std::str::from_utf8(&missing_pointer.to_bytes()).unwrap(),
+            ),
+        ],
+    );
+    let wc = test_workspace.workspace.working_copy_mut();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &new_tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read(other_path.to_fs_path(&workspace_root)).unwrap(),
+        b"other contents\n"
+    );
+    // Pointers to objects that aren't in the local store are checked out as they
+    // are
+    assert_eq!(
+        std::fs::read(missing_path.to_fs_path(&workspace_root)).unwrap(),
+        missing_pointer.to_bytes()
+    );
+    assert_eq!(test_workspace.snapshot().unwrap().id(), new_tree.id());
+}
+
+#[test]
+fn test_git_lfs_max_new_file_size() {
+    // Tests that new LFS files aren't limited by snapshot.max-new-file-size since
+    // only their pointers are stored in the repo.
+    let settings = UserSettings::from_config(
+        testutils::base_config()
+            .add_source(config::File::from_str(
+                "snapshot.max-new-file-size = \"1KiB\"",
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap(),
+    );
+    let mut test_workspace = TestWorkspace::init(&settings, true);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    let git_repo_path = repo
+        .store()
+        .backend_impl()
+        .downcast_ref::<GitBackend>()
+        .unwrap()
+        .git_repo_path();
+    let lfs_store = LfsStore::for_git_dir(&git_repo_path);
+
+    let gitattributes_path = RepoPath::from_internal_string(".gitattributes");
+    let lfs_path = RepoPath::from_internal_string("large.bin");
+    let other_path = RepoPath::from_internal_string("large.txt");
+    testutils::write_working_copy_file(&workspace_root, &gitattributes_path, "*.bin filter=lfs\n");
+    testutils::write_working_copy_file(&workspace_root, &lfs_path, &"a".repeat(2048));
+    testutils::write_working_copy_file(&workspace_root, &other_path, &"a".repeat(2048));
+
+    let (tree, stats) = test_workspace.snapshot_with_stats().unwrap();
+    let pointer = match tree.path_value(&lfs_path).as_resolved() {
+        Some(Some(TreeValue::File { id, .. })) => {
+            LfsPointer::parse(&testutils::read_file(repo.store(), &lfs_path, id)).unwrap()
+        }
+        value => panic!("unexpected value {value:?}"),
+    };
+    assert_eq!(pointer.size, 2048);
+    assert!(lfs_store.object_path(&pointer.oid).exists());
+    // Other large files are still left untracked
+    assert_eq!(tree.path_value(&other_path), Merge::absent());
+    assert_eq!(
+        stats.untracked_paths.keys().collect_vec(),
+        vec![&other_path]
+    );
+}
+
+#[test]
+fn test_gitattributes_eol() {
+    // Tests that the `text` and `eol` attributes normalize line endings on
+    // snapshot and convert them back on checkout.
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings, true);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let gitattributes_path = RepoPath::from_internal_string(".gitattributes");
+    let gitattributes = "*.txt text eol=crlf\n*.auto text=auto\n";
+    let text_path = RepoPath::from_internal_string("file.txt");
+    let auto_path = RepoPath::from_internal_string("file.auto");
+    let crlf_auto_path = RepoPath::from_internal_string("crlf.auto");
+    let other_path = RepoPath::from_internal_string("other");
+    let read_value = |tree: &MergedTree, path: &RepoPath| match tree.path_value(path).as_resolved()
+    {
+        Some(Some(TreeValue::File { id, .. })) => testutils::read_file(repo.store(), path, id),
+        value => panic!("unexpected value {value:?}"),
+    };
+
+    // Checking out converts line endings only for files with eol=crlf
+    let tree = create_tree(
+        &repo,
+        &[
+            (&gitattributes_path, gitattributes),
+            (&text_path, "a\nb\n"),
+            (&auto_path, "a\nb\n"),
+            (&crlf_auto_path, "a\r\nb\r\n"),
+            (&other_path, "a\nb\n"),
+        ],
+    );
+    let wc = test_workspace.workspace.working_copy_mut();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+    let read_disk = |path: &RepoPath| std::fs::read(path.to_fs_path(&workspace_root)).unwrap();
+    assert_eq!(read_disk(&text_path), b"a\r\nb\r\n");
+    assert_eq!(read_disk(&auto_path), b"a\nb\n");
+    assert_eq!(read_disk(&crlf_auto_path), b"a\r\nb\r\n");
+    assert_eq!(read_disk(&other_path), b"a\nb\n");
+    assert_eq!(test_workspace.snapshot().unwrap().id(), tree.id());
+
+    // Snapshotting normalizes CRLF to LF, except for files without the `text`
+    // attribute, and for text=auto files that already had CRLF in the repo
+    testutils::write_working_copy_file(&workspace_root, &text_path, "a\r\nb\r\nc\r\n");
+    testutils::write_working_copy_file(&workspace_root, &auto_path, "a\r\nb\r\nc\r\n");
+    testutils::write_working_copy_file(&workspace_root, &crlf_auto_path, "a\r\nb\r\nc\r\n");
+    testutils::write_working_copy_file(&workspace_root, &other_path, "a\r\nb\r\nc\r\n");
+    let tree = test_workspace.snapshot().unwrap();
+    assert_eq!(read_value(&tree, &text_path), b"a\nb\nc\n");
+    assert_eq!(read_value(&tree, &auto_path), b"a\nb\nc\n");
+    assert_eq!(read_value(&tree, &crlf_auto_path), b"a\r\nb\r\nc\r\n");
+    assert_eq!(read_value(&tree, &other_path), b"a\r\nb\r\nc\r\n");
+}
+
+#[test]
+fn test_snapshot_many_directories() {
+    // Tests that the snapshot, which visits directories in parallel, produces
+    // the same tree as writing the files directly, applies ignores from parent
+    // directories, and reports new files that are too large.
+    let settings = UserSettings::from_config(
+        testutils::base_config()
+            .add_source(config::File::from_str(
+                "snapshot.max-new-file-size = \"1KiB\"",
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap(),
+    );
+    let mut test_workspace = TestWorkspace::init(&settings, true);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let mut files = vec![
+        (".gitignore".to_owned(), "*.ignored\n".to_owned()),
+        ("d03/.gitignore".to_owned(), "!keep.ignored\n".to_owned()),
+        ("d05/.gitignore".to_owned(), "s1/\n".to_owned()),
+    ];
+    for dir in 0..16 {
+        for sub_dir in 0..4 {
+            for file in 0..5 {
+                let path = format!("d{dir:02}/s{sub_dir}/f{file}");
+                let contents = format!("contents of {path}\n");
+                files.push((path, contents));
+            }
+        }
+    }
+    let ignored_files = [
+        ("d01/s0/drop.ignored", false),
+        ("d03/s2/keep.ignored", true),
+        ("d03/s2/drop.ignored", false),
+    ];
+    for (path, _) in &ignored_files {
+        files.push((path.to_string(), "ignored\n".to_owned()));
+    }
+    for (path, contents) in &files {
+        testutils::write_working_copy_file(
+            &workspace_root,
+            &RepoPath::from_internal_string(path),
+            contents,
+        );
+    }
+
+    let expected_files = files
+        .iter()
+        .filter(|(path, _)| {
+            !path.starts_with("d05/s1/")
+                && ignored_files
+                    .iter()
+                    .all(|(ignored_path, tracked)| path != ignored_path || *tracked)
+        })
+        .map(|(path, contents)| (RepoPath::from_internal_string(path), contents.as_str()))
+        .collect_vec();
+    let expected_tree = create_tree(
+        &repo,
+        &expected_files
+            .iter()
+            .map(|(path, contents)| (path, *contents))
+            .collect_vec(),
+    );
+    let tree = test_workspace.snapshot().unwrap();
+    assert_eq!(tree.id(), expected_tree.id());
+    assert_eq!(test_workspace.snapshot().unwrap().id(), tree.id());
+
+    // A new file that's too large is reported even if it's deep in the tree
+    let large_path = RepoPath::from_internal_string("d10/s3/large");
+    std::fs::write(large_path.to_fs_path(&workspace_root), vec![0; 1024 + 1]).unwrap();
+    let (new_tree, stats) = test_workspace.snapshot_with_stats().unwrap();
+    assert_eq!(new_tree.id(), tree.id());
+    assert_eq!(
+        stats.untracked_paths.keys().collect_vec(),
+        vec![&large_path]
     );
 }
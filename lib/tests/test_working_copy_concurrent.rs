@@ -18,7 +18,7 @@ use std::thread;
 use assert_matches::assert_matches;
 use jj_lib::repo::{Repo, StoreFactories};
 use jj_lib::repo_path::RepoPath;
-use jj_lib::working_copy::{CheckoutError, SnapshotOptions};
+use jj_lib::working_copy::{CheckoutError, CheckoutOptions, SnapshotOptions};
 use jj_lib::workspace::Workspace;
 use testutils::{create_tree, write_working_copy_file, TestWorkspace};
 
@@ -41,7 +41,13 @@ fn test_concurrent_checkout() {
     // Check out tree1
     let wc1 = test_workspace1.workspace.working_copy_mut();
     // The operation ID is not correct, but that doesn't matter for this test
-    wc1.check_out(repo1.op_id().clone(), None, &tree1).unwrap();
+    wc1.check_out(
+        repo1.op_id().clone(),
+        None,
+        &tree1,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
 
     // Check out tree2 from another process (simulated by another workspace
     // instance)
@@ -49,12 +55,22 @@ fn test_concurrent_checkout() {
         Workspace::load(&settings, &workspace1_root, &StoreFactories::default()).unwrap();
     workspace2
         .working_copy_mut()
-        .check_out(repo1.op_id().clone(), Some(&tree_id1), &tree2)
+        .check_out(
+            repo1.op_id().clone(),
+            Some(&tree_id1),
+            &tree2,
+            &CheckoutOptions::empty_for_test(),
+        )
         .unwrap();
 
     // Checking out another tree (via the first repo instance) should now fail.
     assert_matches!(
-        wc1.check_out(repo1.op_id().clone(), Some(&tree_id1), &tree3),
+        wc1.check_out(
+            repo1.op_id().clone(),
+            Some(&tree_id1),
+            &tree3,
+            &CheckoutOptions::empty_for_test()
+        ),
         Err(CheckoutError::ConcurrentCheckout)
     );
 
@@ -93,7 +109,12 @@ fn test_checkout_parallel() {
     test_workspace
         .workspace
         .working_copy_mut()
-        .check_out(repo.op_id().clone(), None, &tree)
+        .check_out(
+            repo.op_id().clone(),
+            None,
+            &tree,
+            &CheckoutOptions::empty_for_test(),
+        )
         .unwrap();
 
     thread::scope(|s| {
@@ -115,7 +136,7 @@ fn test_checkout_parallel() {
                 // The operation ID is not correct, but that doesn't matter for this test
                 let stats = workspace
                     .working_copy_mut()
-                    .check_out(op_id, None, &tree)
+                    .check_out(op_id, None, &tree, &CheckoutOptions::empty_for_test())
                     .unwrap();
                 assert_eq!(stats.updated_files, 0);
                 assert_eq!(stats.added_files, 1);
@@ -125,7 +146,7 @@ fn test_checkout_parallel() {
                 // write_tree() should take the same lock as check_out(), write_tree()
                 // should never produce a different tree.
                 let mut locked_wc = workspace.working_copy_mut().start_mutation().unwrap();
-                let new_tree_id = locked_wc
+                let (new_tree_id, _stats) = locked_wc
                     .snapshot(SnapshotOptions::empty_for_test())
                     .unwrap();
                 assert!(tree_ids.contains(&new_tree_id));
@@ -148,7 +169,13 @@ fn test_racy_checkout() {
     let mut num_matches = 0;
     for _ in 0..100 {
         let wc = test_workspace.workspace.working_copy_mut();
-        wc.check_out(op_id.clone(), None, &tree).unwrap();
+        wc.check_out(
+            op_id.clone(),
+            None,
+            &tree,
+            &CheckoutOptions::empty_for_test(),
+        )
+        .unwrap();
         assert_eq!(
             std::fs::read(path.to_fs_path(&workspace_root)).unwrap(),
             b"1".to_vec()
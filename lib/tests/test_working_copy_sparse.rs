@@ -16,7 +16,8 @@ use itertools::Itertools;
 use jj_lib::matchers::EverythingMatcher;
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::RepoPath;
-use jj_lib::working_copy::{CheckoutStats, WorkingCopy};
+use jj_lib::sparse::SparsePattern;
+use jj_lib::working_copy::{CheckoutOptions, CheckoutStats, WorkingCopy};
 use testutils::{create_tree, TestWorkspace};
 
 #[test]
@@ -49,13 +50,19 @@ fn test_sparse_checkout() {
     );
 
     let wc = test_workspace.workspace.working_copy_mut();
-    wc.check_out(repo.op_id().clone(), None, &tree).unwrap();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
 
     // Set sparse patterns to only dir1/
     let mut locked_wc = wc.start_mutation().unwrap();
-    let sparse_patterns = vec![dir1_path];
+    let sparse_patterns = vec![SparsePattern::Prefix(dir1_path)];
     let stats = locked_wc
-        .set_sparse_patterns(sparse_patterns.clone())
+        .set_sparse_patterns(sparse_patterns.clone(), &CheckoutOptions::empty_for_test())
         .unwrap();
     assert_eq!(
         stats,
@@ -97,9 +104,13 @@ fn test_sparse_checkout() {
 
     // Set sparse patterns to file2, dir1/subdir1/ and dir2/
     let mut locked_wc = wc.start_mutation().unwrap();
-    let sparse_patterns = vec![root_file1_path.clone(), dir1_subdir1_path, dir2_path];
+    let sparse_patterns = vec![
+        SparsePattern::Prefix(root_file1_path.clone()),
+        SparsePattern::Prefix(dir1_subdir1_path),
+        SparsePattern::Prefix(dir2_path),
+    ];
     let stats = locked_wc
-        .set_sparse_patterns(sparse_patterns.clone())
+        .set_sparse_patterns(sparse_patterns.clone(), &CheckoutOptions::empty_for_test())
         .unwrap();
     assert_eq!(
         stats,
@@ -125,6 +136,103 @@ fn test_sparse_checkout() {
     );
 }
 
+#[test]
+fn test_sparse_checkout_globs_and_excludes() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings, false);
+    let repo = &test_workspace.repo;
+    let working_copy_path = test_workspace.workspace.workspace_root().clone();
+
+    let readme_path = RepoPath::from_internal_string("README.md");
+    let a_src_path = RepoPath::from_internal_string("services/a/src/main.rs");
+    let a_testdata_path = RepoPath::from_internal_string("services/a/testdata/big");
+    let a_readme_path = RepoPath::from_internal_string("services/a/README.md");
+    let b_src_path = RepoPath::from_internal_string("services/b/src/main.rs");
+    let b_test_path = RepoPath::from_internal_string("services/b/test/main.rs");
+    let other_path = RepoPath::from_internal_string("other/file");
+
+    let tree = create_tree(
+        repo,
+        &[
+            (&readme_path, "contents"),
+            (&a_src_path, "contents"),
+            (&a_testdata_path, "contents"),
+            (&a_readme_path, "contents"),
+            (&b_src_path, "contents"),
+            (&b_test_path, "contents"),
+            (&other_path, "contents"),
+        ],
+    );
+
+    let wc = test_workspace.workspace.working_copy_mut();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+
+    // Include everything under services/ except the test data, plus all
+    // Markdown files
+    let mut locked_wc = wc.start_mutation().unwrap();
+    let sparse_patterns = vec![
+        SparsePattern::Prefix(RepoPath::from_internal_string("services")),
+        SparsePattern::Glob("**/*.md".to_owned()),
+        SparsePattern::ExcludeGlob("services/*/testdata".to_owned()),
+    ];
+    let stats = locked_wc
+        .set_sparse_patterns(sparse_patterns.clone(), &CheckoutOptions::empty_for_test())
+        .unwrap();
+    assert_eq!(
+        stats,
+        CheckoutStats {
+            updated_files: 0,
+            added_files: 0,
+            removed_files: 2
+        }
+    );
+    assert!(!a_testdata_path.to_fs_path(&working_copy_path).exists());
+    assert!(!other_path.to_fs_path(&working_copy_path).exists());
+    locked_wc.finish(repo.op_id().clone()).unwrap();
+    assert_eq!(
+        wc.file_states().unwrap().keys().collect_vec(),
+        vec![
+            &readme_path,
+            &a_readme_path,
+            &a_src_path,
+            &b_src_path,
+            &b_test_path
+        ]
+    );
+
+    // Reload the state to check that the patterns were persisted
+    let mut wc = WorkingCopy::load(
+        repo.store().clone(),
+        wc.working_copy_path().to_path_buf(),
+        wc.state_path().to_path_buf(),
+    );
+    assert_eq!(wc.sparse_patterns().unwrap(), sparse_patterns);
+
+    // Only include the sources, except for one of the services
+    let mut locked_wc = wc.start_mutation().unwrap();
+    let sparse_patterns = vec![
+        SparsePattern::Glob("services/*/src".to_owned()),
+        SparsePattern::ExcludePrefix(RepoPath::from_internal_string("services/b")),
+    ];
+    locked_wc
+        .set_sparse_patterns(sparse_patterns, &CheckoutOptions::empty_for_test())
+        .unwrap();
+    locked_wc.finish(repo.op_id().clone()).unwrap();
+    assert_eq!(
+        wc.file_states().unwrap().keys().collect_vec(),
+        vec![&a_src_path]
+    );
+    assert!(a_src_path.to_fs_path(&working_copy_path).exists());
+    assert!(!b_src_path.to_fs_path(&working_copy_path).exists());
+    assert!(!readme_path.to_fs_path(&working_copy_path).exists());
+}
+
 /// Test that sparse patterns are respected on commit
 #[test]
 fn test_sparse_commit() {
@@ -150,12 +258,20 @@ fn test_sparse_commit() {
     );
 
     let wc = test_workspace.workspace.working_copy_mut();
-    wc.check_out(repo.op_id().clone(), None, &tree).unwrap();
+    wc.check_out(
+        repo.op_id().clone(),
+        None,
+        &tree,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
 
     // Set sparse patterns to only dir1/
     let mut locked_wc = wc.start_mutation().unwrap();
-    let sparse_patterns = vec![dir1_path.clone()];
-    locked_wc.set_sparse_patterns(sparse_patterns).unwrap();
+    let sparse_patterns = vec![SparsePattern::Prefix(dir1_path.clone())];
+    locked_wc
+        .set_sparse_patterns(sparse_patterns, &CheckoutOptions::empty_for_test())
+        .unwrap();
     locked_wc.finish(repo.op_id().clone()).unwrap();
 
     // Write modified version of all files, including files that are not in the
@@ -175,8 +291,13 @@ fn test_sparse_commit() {
     // Set sparse patterns to also include dir2/
     let wc = test_workspace.workspace.working_copy_mut();
     let mut locked_wc = wc.start_mutation().unwrap();
-    let sparse_patterns = vec![dir1_path, dir2_path];
-    locked_wc.set_sparse_patterns(sparse_patterns).unwrap();
+    let sparse_patterns = vec![
+        SparsePattern::Prefix(dir1_path),
+        SparsePattern::Prefix(dir2_path),
+    ];
+    locked_wc
+        .set_sparse_patterns(sparse_patterns, &CheckoutOptions::empty_for_test())
+        .unwrap();
     locked_wc.finish(op_id).unwrap();
 
     // Create a tree from the working copy. Only dir1/file1 and dir2/file1 should be
@@ -204,8 +325,10 @@ fn test_sparse_commit_gitignore() {
 
     // Set sparse patterns to only dir1/
     let mut locked_wc = wc.start_mutation().unwrap();
-    let sparse_patterns = vec![dir1_path.clone()];
-    locked_wc.set_sparse_patterns(sparse_patterns).unwrap();
+    let sparse_patterns = vec![SparsePattern::Prefix(dir1_path.clone())];
+    locked_wc
+        .set_sparse_patterns(sparse_patterns, &CheckoutOptions::empty_for_test())
+        .unwrap();
     locked_wc.finish(repo.op_id().clone()).unwrap();
 
     // Write dir1/file1 and dir1/file2 and a .gitignore saying to ignore dir1/file1
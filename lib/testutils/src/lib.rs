@@ -32,7 +32,7 @@ use jj_lib::store::Store;
 use jj_lib::transaction::Transaction;
 use jj_lib::tree::Tree;
 use jj_lib::tree_builder::TreeBuilder;
-use jj_lib::working_copy::{SnapshotError, SnapshotOptions};
+use jj_lib::working_copy::{SnapshotError, SnapshotOptions, SnapshotStats};
 use jj_lib::workspace::Workspace;
 use tempfile::TempDir;
 
@@ -86,7 +86,10 @@ pub struct TestRepo {
 
 impl TestRepo {
     pub fn init(use_git: bool) -> Self {
-        let settings = user_settings();
+        Self::init_with_settings(&user_settings(), use_git)
+    }
+
+    pub fn init_with_settings(settings: &UserSettings, use_git: bool) -> Self {
         let temp_dir = new_temp_dir();
 
         let repo_dir = temp_dir.path().join("repo");
@@ -96,7 +99,7 @@ impl TestRepo {
             let git_path = temp_dir.path().join("git-repo");
             git2::Repository::init(&git_path).unwrap();
             ReadonlyRepo::init(
-                &settings,
+                settings,
                 &repo_dir,
                 |store_path| -> Result<Box<dyn Backend>, BackendInitError> {
                     Ok(Box::new(GitBackend::init_external(store_path, &git_path)?))
@@ -109,7 +112,7 @@ impl TestRepo {
             .unwrap()
         } else {
             ReadonlyRepo::init(
-                &settings,
+                settings,
                 &repo_dir,
                 |store_path| -> Result<Box<dyn Backend>, BackendInitError> {
                     Ok(Box::new(LocalBackend::init(store_path)))
@@ -167,14 +170,19 @@ impl TestWorkspace {
     /// copy state on disk, but does not update the working-copy commit (no
     /// new operation).
     pub fn snapshot(&mut self) -> Result<MergedTree, SnapshotError> {
+        Ok(self.snapshot_with_stats()?.0)
+    }
+
+    /// Like `snapshot()`, but also returns the stats of the snapshot.
+    pub fn snapshot_with_stats(&mut self) -> Result<(MergedTree, SnapshotStats), SnapshotError> {
         let mut locked_wc = self.workspace.working_copy_mut().start_mutation().unwrap();
-        let tree_id = locked_wc.snapshot(SnapshotOptions {
+        let (tree_id, stats) = locked_wc.snapshot(SnapshotOptions {
             max_new_file_size: self.settings.max_new_file_size().unwrap(),
             ..SnapshotOptions::empty_for_test()
         })?;
         // arbitrary operation id
         locked_wc.finish(self.repo.op_id().clone()).unwrap();
-        Ok(self.repo.store().get_root_tree(&tree_id).unwrap())
+        Ok((self.repo.store().get_root_tree(&tree_id).unwrap(), stats))
     }
 }
 